@@ -0,0 +1,38 @@
+//! Build metadata for `ulysses-link version --json`.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Installs from crates.io build outside a git checkout
+    let git_sha = command_output("git", &["rev-parse", "--short", "HEAD"])
+        .unwrap_or_else(|| "unknown".into());
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = command_output(&rustc, &["--version"]).unwrap_or_else(|| "unknown".into());
+    let target = std::env::var("TARGET").expect("cargo sets TARGET for build scripts");
+
+    println!("cargo:rustc-env=ULYSSES_LINK_GIT_SHA={git_sha}");
+    println!("cargo:rustc-env=ULYSSES_LINK_RUSTC={rustc_version}");
+    println!("cargo:rustc-env=ULYSSES_LINK_TARGET={target}");
+
+    // Rebuild when HEAD moves to another commit
+    println!("cargo:rerun-if-changed=build.rs");
+    if Path::new(".git/HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/HEAD");
+        if let Some(head_ref) = std::fs::read_to_string(".git/HEAD")
+            .ok()
+            .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+        {
+            println!("cargo:rerun-if-changed=.git/{head_ref}");
+        }
+    }
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string())
+}
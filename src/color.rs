@@ -0,0 +1,50 @@
+use std::fmt::Display;
+
+use anstyle::{AnsiColor, Style};
+
+/// Things going well: newly created files, a running service
+pub const SUCCESS: Style = AnsiColor::Green.on_default();
+/// Edits on both sides combined by a three-way merge
+pub const MERGED: Style = AnsiColor::Cyan.on_default();
+/// Worth a look but not a failure: skipped or deferred files, a stopped service
+pub const NOTICE: Style = AnsiColor::Yellow.on_default();
+/// Conflicts and errors
+pub const PROBLEM: Style = AnsiColor::Red.on_default().bold();
+
+/// `text` in `style`. Print it with `anstream::println!`, which drops the
+/// styling when stdout isn't a terminal, `NO_COLOR` is set, or `--color
+/// never` was given.
+pub fn paint(style: Style, text: impl Display) -> String {
+    format!("{style}{text}{style:#}")
+}
+
+/// Which output gets styled, from `--color`.
+pub fn set_choice(choice: clap::ColorChoice) {
+    let choice = match choice {
+        clap::ColorChoice::Auto => anstream::ColorChoice::Auto,
+        clap::ColorChoice::Always => anstream::ColorChoice::Always,
+        clap::ColorChoice::Never => anstream::ColorChoice::Never,
+    };
+    choice.write_global();
+}
+
+/// Whether styles written to stdout are kept, for writers that don't go
+/// through anstream such as the log.
+pub fn enabled() -> bool {
+    anstream::AutoStream::choice(&std::io::stdout()) != anstream::ColorChoice::Never
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paint_strips_to_plain_text() {
+        let painted = paint(PROBLEM, "2 conflicts");
+        assert!(painted.contains('\x1b'));
+        assert_eq!(
+            anstream::adapter::strip_str(&painted).to_string(),
+            "2 conflicts"
+        );
+    }
+}
@@ -1,12 +1,19 @@
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::time::Duration;
 
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use globset::{Glob, GlobSetBuilder};
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use regex::Regex;
 use serde::Deserialize;
 use thiserror::Error;
-use tracing::warn;
+use tracing::{debug, info, warn};
+
+use crate::linker::SyncDirection;
+use crate::manifest::{
+    BaseCacheMode, Inbox, KeywordTree, Manifest, ManifestBackend, Normalization, StateOptions,
+};
+use crate::matcher::{has_hidden_segment, IncludeSet, MirrorIgnore, ProtectSet};
 
 // --- Defaults ---
 
@@ -83,14 +90,26 @@ pub const DEFAULT_GLOBAL_INCLUDE: &[&str] = &[
     "TODO",
 ];
 
+/// Mirror files sync never deletes: Ulysses group metadata and other plists.
+pub const DEFAULT_PROTECT: &[&str] = &[".Ulysses-*", "*.plist"];
+
 pub const DEFAULT_DEBOUNCE_SECONDS: f64 = 0.5;
+pub const DEFAULT_MAX_BATCH: usize = 10_000;
 pub const DEFAULT_LOG_LEVEL: &str = "INFO";
+pub const DEFAULT_FLATTEN_SEPARATOR: &str = " - ";
+/// Largest `rescan_jitter`, in percent
+pub const MAX_RESCAN_JITTER: f64 = 50.0;
+/// Gitignore-format file in a repo root whose patterns are added to the
+/// repo's excludes
+pub const REPO_IGNORE_FILE: &str = ".ulyssesignore";
+/// Replaced in an output_dir by the repo's checked-out git branch.
+pub const BRANCH_TOKEN: &str = "{branch}";
 
 // --- Errors ---
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
-    #[error("No config file found at ~/.config/ulysses-link/config.toml. Run 'ulysses-link sync <path> <output-dir>' to get started, or pass --config PATH.")]
+    #[error("No config file found at ~/.config/ulysses-link/config.toml. Run 'ulysses-link sync <path> --output <output-dir>' to get started, or pass --config PATH.")]
     NoConfigFound,
 
     #[error("Config file not found: {0}")]
@@ -121,34 +140,550 @@ struct RawConfig {
     output_dir: Option<String>,
     global_exclude: Option<Vec<String>>,
     global_include: Option<Vec<String>>,
+    global_exclude_from: Option<Vec<String>>,
+    global_include_from: Option<Vec<String>>,
     debounce_seconds: Option<f64>,
+    max_batch: Option<usize>,
     log_level: Option<String>,
     rescan_interval: Option<RawRescanInterval>,
+    rescan_jitter: Option<f64>,
     auto_upgrade: Option<bool>,
+    write_output_gitignore: Option<bool>,
+    bidirectional: Option<bool>,
+    max_depth: Option<usize>,
+    initial_sync_limit: Option<u32>,
+    parallel_hash: Option<bool>,
+    mirror_poll_interval: Option<f64>,
+    include_hidden: Option<bool>,
+    notify_on_conflict: Option<bool>,
+    state_dir: Option<String>,
+    manifest_backend: Option<String>,
+    base_cache: Option<String>,
+    base_cache_compress: Option<bool>,
+    verify_after_write: Option<bool>,
+    skip_if_matches: Option<String>,
+    mirror_binary: Option<bool>,
+    protect: Option<Vec<String>>,
+    mirror_ignore: Option<Vec<String>>,
+    normalize_trailing_newline: Option<bool>,
+    normalize_chars: Option<Vec<(String, String)>>,
+    log_dir: Option<String>,
+    stats_interval: Option<f64>,
+    name_from: Option<String>,
+    disambiguate: Option<String>,
+    repos: Option<Vec<RawRepo>>,
+}
+
+/// Version 2 layout: the same options as v1, grouped into tables.
+/// Converted to the flat `RawConfig` before validation.
+#[derive(Debug, Deserialize)]
+struct RawConfigV2 {
+    version: Option<u64>,
+    output_dir: Option<String>,
+    state_dir: Option<String>,
+    log_level: Option<String>,
+    log_dir: Option<String>,
+    stats_interval: Option<f64>,
+    #[serde(default)]
+    sync: RawSyncV2,
+    #[serde(default)]
+    upgrade: RawUpgradeV2,
     repos: Option<Vec<RawRepo>>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct RawSyncV2 {
+    exclude: Option<Vec<String>>,
+    include: Option<Vec<String>>,
+    exclude_from: Option<Vec<String>>,
+    include_from: Option<Vec<String>>,
+    debounce_seconds: Option<f64>,
+    max_batch: Option<usize>,
+    rescan_interval: Option<RawRescanInterval>,
+    rescan_jitter: Option<f64>,
+    write_output_gitignore: Option<bool>,
+    bidirectional: Option<bool>,
+    max_depth: Option<usize>,
+    initial_sync_limit: Option<u32>,
+    parallel_hash: Option<bool>,
+    mirror_poll_interval: Option<f64>,
+    include_hidden: Option<bool>,
+    notify_on_conflict: Option<bool>,
+    manifest_backend: Option<String>,
+    base_cache: Option<String>,
+    base_cache_compress: Option<bool>,
+    verify_after_write: Option<bool>,
+    skip_if_matches: Option<String>,
+    mirror_binary: Option<bool>,
+    protect: Option<Vec<String>>,
+    mirror_ignore: Option<Vec<String>>,
+    normalize_trailing_newline: Option<bool>,
+    normalize_chars: Option<Vec<(String, String)>>,
+    name_from: Option<String>,
+    disambiguate: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RawUpgradeV2 {
+    auto: Option<bool>,
+}
+
+impl From<RawConfigV2> for RawConfig {
+    fn from(v2: RawConfigV2) -> Self {
+        RawConfig {
+            version: v2.version,
+            output_dir: v2.output_dir,
+            global_exclude: v2.sync.exclude,
+            global_include: v2.sync.include,
+            global_exclude_from: v2.sync.exclude_from,
+            global_include_from: v2.sync.include_from,
+            debounce_seconds: v2.sync.debounce_seconds,
+            max_batch: v2.sync.max_batch,
+            log_level: v2.log_level,
+            rescan_interval: v2.sync.rescan_interval,
+            rescan_jitter: v2.sync.rescan_jitter,
+            auto_upgrade: v2.upgrade.auto,
+            write_output_gitignore: v2.sync.write_output_gitignore,
+            bidirectional: v2.sync.bidirectional,
+            max_depth: v2.sync.max_depth,
+            initial_sync_limit: v2.sync.initial_sync_limit,
+            parallel_hash: v2.sync.parallel_hash,
+            mirror_poll_interval: v2.sync.mirror_poll_interval,
+            include_hidden: v2.sync.include_hidden,
+            notify_on_conflict: v2.sync.notify_on_conflict,
+            state_dir: v2.state_dir,
+            manifest_backend: v2.sync.manifest_backend,
+            base_cache: v2.sync.base_cache,
+            base_cache_compress: v2.sync.base_cache_compress,
+            verify_after_write: v2.sync.verify_after_write,
+            skip_if_matches: v2.sync.skip_if_matches,
+            mirror_binary: v2.sync.mirror_binary,
+            protect: v2.sync.protect,
+            mirror_ignore: v2.sync.mirror_ignore,
+            normalize_trailing_newline: v2.sync.normalize_trailing_newline,
+            normalize_chars: v2.sync.normalize_chars,
+            log_dir: v2.log_dir,
+            stats_interval: v2.stats_interval,
+            name_from: v2.sync.name_from,
+            disambiguate: v2.sync.disambiguate,
+            repos: v2.repos,
+        }
+    }
+}
+
+/// v1 top-level keys that move into a v2 table: (v1 key, v2 table, v2 key).
+const V1_TO_V2_KEYS: &[(&str, &str, &str)] = &[
+    ("global_exclude", "sync", "exclude"),
+    ("global_include", "sync", "include"),
+    ("global_exclude_from", "sync", "exclude_from"),
+    ("global_include_from", "sync", "include_from"),
+    ("debounce_seconds", "sync", "debounce_seconds"),
+    ("max_batch", "sync", "max_batch"),
+    ("rescan_interval", "sync", "rescan_interval"),
+    ("rescan_jitter", "sync", "rescan_jitter"),
+    ("write_output_gitignore", "sync", "write_output_gitignore"),
+    ("bidirectional", "sync", "bidirectional"),
+    ("max_depth", "sync", "max_depth"),
+    ("initial_sync_limit", "sync", "initial_sync_limit"),
+    ("parallel_hash", "sync", "parallel_hash"),
+    ("mirror_poll_interval", "sync", "mirror_poll_interval"),
+    ("include_hidden", "sync", "include_hidden"),
+    ("notify_on_conflict", "sync", "notify_on_conflict"),
+    ("manifest_backend", "sync", "manifest_backend"),
+    ("base_cache", "sync", "base_cache"),
+    ("base_cache_compress", "sync", "base_cache_compress"),
+    ("verify_after_write", "sync", "verify_after_write"),
+    ("skip_if_matches", "sync", "skip_if_matches"),
+    ("mirror_binary", "sync", "mirror_binary"),
+    ("protect", "sync", "protect"),
+    ("mirror_ignore", "sync", "mirror_ignore"),
+    (
+        "normalize_trailing_newline",
+        "sync",
+        "normalize_trailing_newline",
+    ),
+    ("normalize_chars", "sync", "normalize_chars"),
+    ("name_from", "sync", "name_from"),
+    ("disambiguate", "sync", "disambiguate"),
+    ("auto_upgrade", "upgrade", "auto"),
+];
+
+/// A repo include or exclude pattern: a bare string, or a table with the
+/// `pattern`, an optional `enabled = false` to switch it off without
+/// deleting it, and a free-form `comment` saying why it's there, which is
+/// not read.
+#[derive(Debug, Clone)]
+enum RawPattern {
+    Bare(String),
+    Entry(PatternEntry),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PatternEntry {
+    pattern: String,
+    enabled: Option<bool>,
+    #[serde(rename = "comment")]
+    _comment: Option<String>,
+}
+
+// By hand rather than `untagged`, so a misspelled key in a table is named
+// in the error instead of "data did not match any variant"
+impl<'de> Deserialize<'de> for RawPattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PatternVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for PatternVisitor {
+            type Value = RawPattern;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a pattern string or a table with `pattern`, `enabled` and `comment`")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<RawPattern, E> {
+                Ok(RawPattern::Bare(value.to_string()))
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<RawPattern, A::Error> {
+                PatternEntry::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                    .map(RawPattern::Entry)
+            }
+        }
+
+        deserializer.deserialize_any(PatternVisitor)
+    }
+}
+
+/// The patterns of enabled entries, in order.
+fn enabled_patterns(patterns: Option<&[RawPattern]>) -> Vec<String> {
+    patterns
+        .into_iter()
+        .flatten()
+        .filter_map(|p| match p {
+            RawPattern::Bare(pattern) => Some(pattern.clone()),
+            RawPattern::Entry(entry) => {
+                entry.enabled.unwrap_or(true).then(|| entry.pattern.clone())
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug, Deserialize)]
 struct RawRepo {
     path: String,
     name: Option<String>,
-    exclude: Option<Vec<String>>,
-    include: Option<Vec<String>>,
+    mirror_subdir: Option<String>,
+    enabled: Option<bool>,
+    exclude: Option<Vec<RawPattern>>,
+    include: Option<Vec<RawPattern>>,
+    exclude_from: Option<Vec<String>>,
+    include_from: Option<Vec<String>>,
     output_dir: Option<String>,
+    flatten: Option<bool>,
+    flatten_separator: Option<String>,
+    flat_inbox: Option<bool>,
+    root: Option<String>,
+    files: Option<Vec<String>>,
+    priority: Option<i32>,
+    mirror_poll_interval: Option<f64>,
+    follow_symlinks: Option<bool>,
+    mirror_empty_dirs: Option<bool>,
+    output_format: Option<String>,
+    git_tracked_only: Option<bool>,
+    read_only_source: Option<bool>,
+    extra_outputs: Option<Vec<String>>,
 }
 
 // --- Validated config ---
 
 #[derive(Debug, Clone)]
 pub struct RepoConfig {
+    /// Canonical repo path. A symlinked repo root is resolved here, so scans,
+    /// watches and manifest sources all use the real directory.
     pub path: PathBuf,
     pub name: String,
     pub exclude: Gitignore,
-    pub include: GlobSet,
+    pub include: IncludeSet,
+    /// Raw exclude patterns preserved for comparison during config reload
+    pub exclude_patterns: Vec<String>,
     /// Raw include patterns preserved for comparison during config reload
     pub include_patterns: Vec<String>,
+    /// Exact repo-relative paths, `/`-separated, included regardless of the
+    /// include patterns
+    pub files: Vec<String>,
     /// Effective output directory (per-repo override or global fallback)
     pub output_dir: PathBuf,
+    /// Further output directories mirrored one way from source, each under
+    /// `mirror_subdir` and with its own manifest
+    pub extra_outputs: Vec<PathBuf>,
+    /// Git branch substituted for `{branch}` in `output_dir`, when it has one
+    pub branch: Option<String>,
+    /// Path under `output_dir` where this repo is mirrored, `/`-separated.
+    /// Defaults to the repo name; may be nested (e.g. `work/api`). Also the
+    /// prefix of every manifest key owned by this repo.
+    pub mirror_subdir: String,
+    /// Disabled repos keep their config and mirror but are not scanned or watched
+    pub enabled: bool,
+    /// Repos with a higher priority are scanned and watched first
+    pub priority: i32,
+    /// Whether mirror edits flow back to source (from the global
+    /// `bidirectional`, and always `SourceToMirror` with `read_only_source`)
+    pub direction: SyncDirection,
+    /// The source is never written: no merge-back, no conflict files, no
+    /// deletes propagated from the mirror
+    pub read_only_source: bool,
+    /// Deepest directory level a full scan descends to (from the global
+    /// `max_depth`); `None` is unlimited
+    pub max_depth: Option<usize>,
+    /// Whether include patterns alone decide on hidden paths (from the
+    /// global `include_hidden`); already applied to `include`
+    pub include_hidden: bool,
+    /// Files whose first `matcher::CONTENT_MATCH_BYTES` match this (from the global
+    /// `skip_if_matches`) are not mirrored
+    pub skip_if_matches: Option<Regex>,
+    /// Mirror files whose content looks binary (from the global
+    /// `mirror_binary`); otherwise they are skipped like `skip_if_matches`
+    pub mirror_binary: bool,
+    /// Separator joining path segments when the repo is mirrored flat into
+    /// its mirror root; `None` keeps the directory structure
+    pub flatten: Option<String>,
+    /// Flattened mirror files start with a header naming their source path,
+    /// so a single folder of notes can still be traced and synced back
+    pub flat_inbox: bool,
+    /// Subdirectory of the repo, `/`-separated, that is mirrored at the
+    /// mirror root. Only files under it are scanned and watched.
+    pub root: Option<String>,
+    /// Poll the repo's output dir at this interval instead of using native
+    /// file events (per-repo or global `mirror_poll_interval`)
+    pub mirror_poll_interval: Option<Duration>,
+    /// Mirror files reached through symlinks inside the repo. Their manifest
+    /// source is the canonical target.
+    pub follow_symlinks: bool,
+    /// Empty source directories get an empty mirror directory, so Ulysses
+    /// shows the same groups as the repo has folders
+    pub mirror_empty_dirs: bool,
+    /// How mirror files are written
+    pub output_format: OutputFormat,
+    /// Only mirror files git tracks; ignored outside a git work tree
+    pub git_tracked_only: bool,
+    /// Every output dir and the state dir, canonicalized. Scans and watchers
+    /// never descend into them, even when a symlinked path let one end up
+    /// inside this repo.
+    pub guarded_dirs: Vec<PathBuf>,
+}
+
+impl RepoConfig {
+    /// Absolute root of this repo's mirror tree.
+    pub fn mirror_root(&self) -> PathBuf {
+        self.output_dir.join(&self.mirror_subdir)
+    }
+
+    /// The output dir followed by every extra output.
+    pub fn output_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.output_dir).chain(&self.extra_outputs)
+    }
+
+    /// This repo as mirrored into each of its extra outputs: source to
+    /// mirror only, with the source guarded like `read_only_source`.
+    pub fn extra_output_repos(&self) -> Vec<RepoConfig> {
+        self.extra_outputs
+            .iter()
+            .map(|dir| RepoConfig {
+                output_dir: dir.clone(),
+                extra_outputs: Vec::new(),
+                direction: SyncDirection::SourceToMirror,
+                read_only_source: true,
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Whether `path`, absolute under the canonical repo path, is inside an
+    /// output dir or the state dir.
+    pub fn in_guarded_dir(&self, path: &Path) -> bool {
+        self.guarded_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Source file for the repo-relative `rel_path`. With `follow_symlinks`,
+    /// a path through a symlink resolves to its canonical target.
+    pub fn source_path(&self, rel_path: &str) -> PathBuf {
+        let path = self.path.join(rel_path);
+        if self.follow_symlinks {
+            if let Ok(canonical) = path.canonicalize() {
+                return canonical;
+            }
+        }
+        path
+    }
+
+    /// Repo-relative paths of the files git tracks, `/`-separated, for a
+    /// `git_tracked_only` repo. `None` when the option is off or the repo
+    /// isn't in a git work tree, so every file passes.
+    pub fn tracked_files(&self) -> Option<HashSet<String>> {
+        if !self.git_tracked_only {
+            return None;
+        }
+        let output = match std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(["ls-files", "-z"])
+            .stderr(std::process::Stdio::null())
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) => {
+                warn!(
+                    "Failed to run git for {}, mirroring untracked files: {e}",
+                    self.name
+                );
+                return None;
+            }
+        };
+        if !output.status.success() {
+            debug!(
+                "Not a git work tree, mirroring untracked files: {}",
+                self.path.display()
+            );
+            return None;
+        }
+        Some(
+            String::from_utf8_lossy(&output.stdout)
+                .split('\0')
+                .filter(|path| !path.is_empty())
+                .map(String::from)
+                .collect(),
+        )
+    }
+
+    /// Whether the repo-relative `rel_path` lies within `max_depth` of the
+    /// source root, counting the file itself as one level like the scan does.
+    pub fn within_depth(&self, rel_path: &str) -> bool {
+        let Some(max_depth) = self.max_depth else {
+            return true;
+        };
+        let rel_path = match self.root {
+            Some(ref root) => rel_path
+                .strip_prefix(root.as_str())
+                .and_then(|r| r.strip_prefix('/'))
+                .unwrap_or(rel_path),
+            None => rel_path,
+        };
+        rel_path.split('/').filter(|s| !s.is_empty()).count() <= max_depth
+    }
+
+    /// The git index of a `git_tracked_only` repo, whose changes can change
+    /// `tracked_files`. `None` when the option is off or git can't say.
+    pub fn git_index_path(&self) -> Option<PathBuf> {
+        if !self.git_tracked_only {
+            return None;
+        }
+        let output = std::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.path)
+            .args(["rev-parse", "--git-path", "index"])
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+        let index = String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string();
+        // Relative to the directory git ran in
+        Some(self.path.join(index))
+    }
+
+    /// Directory scanned and watched: the repo path, or its `root` subdirectory.
+    pub fn source_root(&self) -> PathBuf {
+        match self.root {
+            Some(ref root) => self.path.join(root),
+            None => self.path.clone(),
+        }
+    }
+
+    /// Manifest key (path under `output_dir`) for the source file at the
+    /// repo-relative `rel_path`. With `root` set, the root prefix is dropped.
+    ///
+    /// Flattened repos reuse the key already recorded for the source, so a
+    /// name picked to dodge a collision sticks. New files that flatten onto a
+    /// name owned by another source get a ` (2)`, ` (3)`, ... suffix.
+    pub fn mirror_key(&self, rel_path: &str, manifest: &Manifest) -> String {
+        let source = self.source_path(rel_path);
+        let rel_path = match self.root {
+            Some(ref root) => rel_path
+                .strip_prefix(root.as_str())
+                .and_then(|r| r.strip_prefix('/'))
+                .unwrap_or(rel_path),
+            None => rel_path,
+        };
+
+        let Some(ref separator) = self.flatten else {
+            return format!("{}/{}", self.mirror_subdir, rel_path);
+        };
+
+        let prefix = format!("{}/", self.mirror_subdir);
+        if let Some(key) = manifest.key_for_source(&source) {
+            // Keys recorded before flattening was turned on are not reused
+            if key
+                .strip_prefix(&prefix)
+                .is_some_and(|name| !name.contains('/'))
+            {
+                return key.to_string();
+            }
+        }
+
+        let flat = rel_path.replace('/', separator);
+        let (stem, extension) = match flat.rfind('.') {
+            Some(i) if i > 0 => flat.split_at(i),
+            _ => (flat.as_str(), ""),
+        };
+        let mut key = format!("{prefix}{flat}");
+        let mut n = 2;
+        while manifest.get(&key).is_some_and(|e| e.source != source) {
+            key = format!("{prefix}{stem} ({n}){extension}");
+            n += 1;
+        }
+        key
+    }
+}
+
+/// Where a configured repo lives and where it is mirrored.
+#[derive(Debug, Clone)]
+pub struct RepoLocation {
+    pub name: String,
+    pub path: PathBuf,
+    pub output_dir: PathBuf,
+    pub mirror_subdir: String,
+}
+
+impl RepoLocation {
+    pub fn mirror_root(&self) -> PathBuf {
+        self.output_dir.join(&self.mirror_subdir)
+    }
+}
+
+impl From<&RepoConfig> for RepoLocation {
+    fn from(repo: &RepoConfig) -> Self {
+        RepoLocation {
+            name: repo.name.clone(),
+            path: repo.path.clone(),
+            output_dir: repo.output_dir.clone(),
+            mirror_subdir: repo.mirror_subdir.clone(),
+        }
+    }
+}
+
+/// How a repo's mirror files are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Exact copies of the source files
+    #[default]
+    Plain,
+    /// Each Markdown file starts with Ulysses keywords named after its
+    /// folders, which are stripped again when mirror edits are written back
+    Ulysses,
 }
 
 #[derive(Debug, Clone)]
@@ -162,27 +697,160 @@ pub enum RescanInterval {
 pub struct Config {
     pub output_dir: PathBuf,
     pub repos: Vec<RepoConfig>,
+    /// Configured repos whose path does not exist. They are not scanned or
+    /// watched, but can still be removed along with their mirrors.
+    pub missing_repos: Vec<RepoLocation>,
     pub debounce_seconds: f64,
+    /// Pending events per watcher that flush a batch without waiting out
+    /// the debounce window
+    pub max_batch: usize,
     pub log_level: String,
     pub rescan_interval: RescanInterval,
+    /// Percentage each periodic rescan interval is randomly stretched or
+    /// shortened by, so rescans don't line up with other periodic work
+    pub rescan_jitter: f64,
     pub auto_upgrade: bool,
+    /// Write a `.gitignore` covering tool metadata into each output directory
+    pub write_output_gitignore: bool,
+    /// Sync mirror edits back to source. When false the mirror is read-only:
+    /// no mirror watcher runs and diverged mirror files are reset from source.
+    pub bidirectional: bool,
+    /// Cap on how many directory levels below each repo root a scan walks
+    pub max_depth: Option<usize>,
+    /// Cap on how many new mirror files one full scan creates; the rest wait
+    /// for later scans
+    pub initial_sync_limit: Option<u32>,
+    /// Hash a repo's source files on several threads before a full scan
+    /// syncs them one by one
+    pub parallel_hash: bool,
+    /// Poll output dirs at this interval instead of using native file events,
+    /// unless a repo overrides it
+    pub mirror_poll_interval: Option<Duration>,
+    /// Let include patterns alone decide on hidden paths, rather than only
+    /// patterns that name a hidden segment
+    pub include_hidden: bool,
+    /// Show a desktop notification when a watcher batch produces conflicts
+    pub notify_on_conflict: bool,
+    /// Content pattern that keeps an otherwise included file out of the mirror
+    pub skip_if_matches: Option<Regex>,
+    /// Mirror included files even when their content looks binary
+    pub mirror_binary: bool,
+    /// Mirror files, like editor lock files, whose events are dropped
+    pub mirror_ignore: MirrorIgnore,
+    /// Where manifests and base caches live and how bases are stored
+    pub state: StateOptions,
+    /// Where the background service writes its logs. Unset keeps the platform
+    /// default: `~/Library/Logs/ulysses-link` on macOS, the journal on Linux.
+    pub log_dir: Option<PathBuf>,
+    /// Log a one-line summary of the running service at this interval
+    pub stats_interval: Option<Duration>,
+    /// Include and exclude patterns that never apply, found while loading.
+    /// The config still loads; callers log these once logging is set up.
+    pub pattern_warnings: Vec<String>,
     pub config_path: Option<PathBuf>,
 }
 
 impl Config {
-    /// Collect unique output directories across all repos.
+    /// Repos that should be scanned and watched.
+    pub fn enabled_repos(&self) -> impl Iterator<Item = &RepoConfig> {
+        self.repos.iter().filter(|r| r.enabled)
+    }
+
+    /// Enabled repos, highest `priority` first; ties keep config order.
+    pub fn repos_by_priority(&self) -> Vec<&RepoConfig> {
+        let mut repos: Vec<&RepoConfig> = self.enabled_repos().collect();
+        repos.sort_by_key(|r| std::cmp::Reverse(r.priority));
+        repos
+    }
+
+    /// How often the mirror watcher on `output_dir` polls, or `None` for
+    /// native file events. The shortest interval among the enabled repos
+    /// mirroring there wins.
+    pub fn mirror_poll_interval(&self, output_dir: &Path) -> Option<Duration> {
+        self.enabled_repos()
+            .filter(|r| r.output_dir == output_dir)
+            .filter_map(|r| r.mirror_poll_interval)
+            .min()
+    }
+
+    /// Collect unique output directories, extra outputs included, across all
+    /// enabled repos.
     pub fn active_output_dirs(&self) -> Vec<PathBuf> {
-        let mut dirs: Vec<PathBuf> = self.repos.iter().map(|r| r.output_dir.clone()).collect();
+        let mut dirs: Vec<PathBuf> = self
+            .enabled_repos()
+            .flat_map(RepoConfig::output_dirs)
+            .cloned()
+            .collect();
         dirs.sort();
         dirs.dedup();
         dirs
     }
+
+    /// Mirror only git-tracked files in every repo, e.g. from
+    /// `sync --exclude-vcs-untracked`. The config file is untouched.
+    pub fn set_git_tracked_only(&mut self) {
+        for repo in &mut self.repos {
+            repo.git_tracked_only = true;
+        }
+    }
+
+    /// Follow symlinks in every repo, e.g. from `sync --follow-symlinks`. The
+    /// config file is untouched.
+    pub fn set_follow_symlinks(&mut self) {
+        for repo in &mut self.repos {
+            repo.follow_symlinks = true;
+        }
+    }
+
+    /// Let include patterns alone decide on hidden paths in every repo, e.g.
+    /// from `sync --include-hidden`. The config file is untouched.
+    pub fn set_include_hidden(&mut self) {
+        self.include_hidden = true;
+        for repo in &mut self.repos {
+            repo.include_hidden = true;
+            repo.include = std::mem::take(&mut repo.include).with_include_hidden(true);
+        }
+    }
+
+    /// Layer extra patterns on top of every repo's global and per-repo
+    /// patterns, e.g. from `sync --include`. The config file is untouched.
+    pub fn add_patterns(
+        &mut self,
+        exclude: &[String],
+        include: &[String],
+    ) -> Result<(), ConfigError> {
+        for repo in &mut self.repos {
+            repo.exclude_patterns.extend_from_slice(exclude);
+            repo.include_patterns.extend_from_slice(include);
+            repo.exclude = compile_exclude(&repo.exclude_patterns, &repo.path)?;
+            repo.include = compile_include(&repo.include_patterns)?
+                .with_files(&repo.files)
+                .with_include_hidden(repo.include_hidden);
+        }
+        Ok(())
+    }
 }
 
 // --- Config search ---
 
+/// Environment variable naming the config file, checked after `--config`.
+pub const CONFIG_ENV_VAR: &str = "ULYSSES_LINK_CONFIG";
+/// `--config` value that reads the config from stdin
+pub const STDIN_CONFIG: &str = "-";
+
+/// Resolve the config file: explicit `--config`, then `$ULYSSES_LINK_CONFIG`,
+/// then the default location.
 pub fn find_config_path(explicit: Option<&Path>) -> Result<PathBuf, ConfigError> {
-    if let Some(p) = explicit {
+    if is_stdin_config(explicit) {
+        return Err(ConfigError::Validation(format!(
+            "--config {STDIN_CONFIG} reads the config from stdin, which this command can't \
+             update. Pass a config file path instead."
+        )));
+    }
+    let from_env = std::env::var_os(CONFIG_ENV_VAR)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    if let Some(p) = explicit.map(Path::to_path_buf).or(from_env) {
         let expanded = expand_path(&p.to_string_lossy())?;
         if expanded.is_file() {
             return Ok(expanded);
@@ -207,35 +875,156 @@ fn expand_path(p: &str) -> Result<PathBuf, ConfigError> {
     Ok(dunce_canonicalize_or_absolute(&path))
 }
 
-/// Canonicalize if path exists, otherwise make absolute without requiring existence.
+/// Like `expand_path`, but a relative path is resolved against `base` rather
+/// than the current directory.
+fn expand_path_from(p: &str, base: &Path) -> Result<PathBuf, ConfigError> {
+    let expanded = shellexpand::full(p)
+        .map_err(|e| ConfigError::Validation(format!("Failed to expand path '{p}': {e}")))?;
+    Ok(dunce_canonicalize_or_absolute(
+        &base.join(expanded.as_ref()),
+    ))
+}
+
+/// Whether a raw config path is absolute once `~` and env vars are expanded.
+fn is_absolute_raw(p: &str) -> bool {
+    shellexpand::full(p).is_ok_and(|e| Path::new(e.as_ref()).is_absolute())
+}
+
+/// The part of a raw output_dir before the segment holding `{branch}`, or
+/// all of it when there's no template.
+fn template_base(p: &str) -> Result<&str, ConfigError> {
+    let Some(at) = p.find(BRANCH_TOKEN) else {
+        return Ok(p);
+    };
+    match p[..at].rfind('/') {
+        Some(0) => Ok("/"),
+        Some(slash) => Ok(&p[..slash]),
+        None => Err(ConfigError::Validation(format!(
+            "'{p}' needs a directory before {BRANCH_TOKEN}"
+        ))),
+    }
+}
+
+/// Canonicalize if path exists, otherwise make absolute without requiring
+/// existence, canonicalizing the nearest ancestor that exists. A directory
+/// that isn't there yet then resolves as it will once created (e.g. macOS
+/// /var -> /private/var).
 fn dunce_canonicalize_or_absolute(path: &Path) -> PathBuf {
     if let Ok(canonical) = std::fs::canonicalize(path) {
-        canonical
-    } else if path.is_absolute() {
+        return canonical;
+    }
+    let absolute = if path.is_absolute() {
         path.to_path_buf()
     } else {
         std::env::current_dir()
             .unwrap_or_else(|_| PathBuf::from("/"))
             .join(path)
+    };
+    absolute
+        .ancestors()
+        .skip(1)
+        .find_map(|ancestor| {
+            let canonical = std::fs::canonicalize(ancestor).ok()?;
+            Some(canonical.join(absolute.strip_prefix(ancestor).ok()?))
+        })
+        .unwrap_or(absolute)
+}
+
+/// Create an output directory when `create` is set, then canonicalize it.
+fn create_output_dir(dir: PathBuf, create: bool) -> Result<PathBuf, ConfigError> {
+    if create {
+        std::fs::create_dir_all(&dir)?;
+    }
+    Ok(dunce_canonicalize_or_absolute(&dir))
+}
+
+/// Canonicalize the longest existing prefix of `path` and re-append the rest.
+pub(crate) fn canonicalize_existing_prefix(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(mut canonical) = std::fs::canonicalize(current) {
+            canonical.extend(missing.iter().rev());
+            return Some(canonical);
+        }
+        missing.push(current.file_name()?);
+        current = current.parent()?;
     }
 }
 
 // --- Loading ---
 
 pub fn load_config(config_path: Option<&Path>) -> Result<Config, ConfigError> {
+    load_config_with(config_path, true)
+}
+
+/// Like `load_config`, but output directories that don't exist are left
+/// missing rather than created, for reloads and dry runs. An output dir that
+/// vanished, e.g. with its drive unmounted, then keeps syncing paused.
+pub fn load_config_without_dirs(config_path: Option<&Path>) -> Result<Config, ConfigError> {
+    load_config_with(config_path, false)
+}
+
+fn load_config_with(config_path: Option<&Path>, create_dirs: bool) -> Result<Config, ConfigError> {
+    if is_stdin_config(config_path) {
+        let contents = read_all(std::io::stdin().lock())?;
+        return parse_config(parse_raw(&contents)?, None, create_dirs);
+    }
     let resolved = find_config_path(config_path)?;
     let contents = std::fs::read_to_string(&resolved)?;
-    let raw: RawConfig = toml::from_str(&contents)?;
-    parse_config(raw, Some(resolved))
+    let raw = parse_raw(&contents)?;
+    parse_config(raw, Some(resolved), create_dirs)
+}
+
+/// Whether `--config` names stdin rather than a file.
+pub fn is_stdin_config(config_path: Option<&Path>) -> bool {
+    config_path == Some(Path::new(STDIN_CONFIG))
+}
+
+/// Read and parse a whole config from `reader`, e.g. stdin.
+pub fn read_config(reader: impl std::io::Read) -> Result<Config, ConfigError> {
+    parse_config_str(&read_all(reader)?)
+}
+
+fn read_all(mut reader: impl std::io::Read) -> Result<String, ConfigError> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Parse a config that has no file behind it. Without a `config_path`, the
+/// engine neither watches nor reloads it.
+pub fn parse_config_str(contents: &str) -> Result<Config, ConfigError> {
+    parse_config(parse_raw(contents)?, None, true)
+}
+
+/// Deserialize either schema version into the flat v1 shape.
+fn parse_raw(contents: &str) -> Result<RawConfig, ConfigError> {
+    #[derive(Deserialize)]
+    struct RawVersion {
+        version: Option<u64>,
+    }
+
+    let RawVersion { version } = toml::from_str(contents)?;
+    match version {
+        Some(2) => Ok(toml::from_str::<RawConfigV2>(contents)?.into()),
+        _ => Ok(toml::from_str(contents)?),
+    }
 }
 
-fn parse_config(raw: RawConfig, config_path: Option<PathBuf>) -> Result<Config, ConfigError> {
+/// Build a `Config` from its raw form. With `create_dirs`, missing output
+/// directories are created.
+fn parse_config(
+    raw: RawConfig,
+    config_path: Option<PathBuf>,
+    create_dirs: bool,
+) -> Result<Config, ConfigError> {
     // Version check
     match raw.version {
-        Some(1) => {}
+        Some(1 | 2) => {}
         other => {
             return Err(ConfigError::Validation(format!(
-                "Config version must be 1, got {other:?}"
+                "Config version must be 1 or 2, got {other:?}"
             )));
         }
     }
@@ -245,10 +1034,10 @@ fn parse_config(raw: RawConfig, config_path: Option<PathBuf>) -> Result<Config,
         .output_dir
         .as_deref()
         .ok_or_else(|| ConfigError::Validation("'output_dir' is required".into()))?;
-    let output_dir = expand_path(output_dir_raw)?;
-    std::fs::create_dir_all(&output_dir)?;
-    // Re-canonicalize now that the directory exists (resolves macOS /var -> /private/var)
-    let output_dir = std::fs::canonicalize(&output_dir).unwrap_or(output_dir);
+    // A `{branch}` template is filled in per repo; the global output_dir is
+    // the directory above it
+    let global_uses_branch = output_dir_raw.contains(BRANCH_TOKEN);
+    let output_dir = create_output_dir(expand_path(template_base(output_dir_raw)?)?, create_dirs)?;
 
     // Debounce
     let debounce = raw.debounce_seconds.unwrap_or(DEFAULT_DEBOUNCE_SECONDS);
@@ -257,6 +1046,12 @@ fn parse_config(raw: RawConfig, config_path: Option<PathBuf>) -> Result<Config,
             "'debounce_seconds' must be between 0.0 and 30.0, got {debounce}"
         )));
     }
+    let max_batch = raw.max_batch.unwrap_or(DEFAULT_MAX_BATCH);
+    if max_batch == 0 {
+        return Err(ConfigError::Validation(
+            "'max_batch' must be at least 1".into(),
+        ));
+    }
 
     // Log level
     let log_level = raw.log_level.unwrap_or_else(|| DEFAULT_LOG_LEVEL.into());
@@ -287,6 +1082,13 @@ fn parse_config(raw: RawConfig, config_path: Option<PathBuf>) -> Result<Config,
         }
     };
 
+    let rescan_jitter = raw.rescan_jitter.unwrap_or(0.0);
+    if !(0.0..=MAX_RESCAN_JITTER).contains(&rescan_jitter) {
+        return Err(ConfigError::Validation(format!(
+            "'rescan_jitter' must be between 0 and {MAX_RESCAN_JITTER} percent, got {rescan_jitter}"
+        )));
+    }
+
     // Global patterns
     let global_exclude: Vec<String> = raw.global_exclude.unwrap_or_else(|| {
         DEFAULT_GLOBAL_EXCLUDE
@@ -308,86 +1110,604 @@ fn parse_config(raw: RawConfig, config_path: Option<PathBuf>) -> Result<Config,
     } else {
         global_include
     };
+    let include_hidden = raw.include_hidden.unwrap_or(false);
+    let global_exclude_from = read_pattern_files(raw.global_exclude_from.as_deref())?;
+    let global_include_from = read_pattern_files(raw.global_include_from.as_deref())?;
+    let global_lints = lint_patterns(
+        &[global_include.as_slice(), &global_include_from].concat(),
+        &[global_exclude.as_slice(), &global_exclude_from].concat(),
+    );
+    let mut pattern_warnings = global_lints.clone();
 
     // Repos
     let repos_raw = raw.repos.unwrap_or_default();
-    let named_repos = resolve_repo_names(&repos_raw)?;
+    let name_from_git = match raw.name_from.as_deref() {
+        None | Some("basename") => false,
+        Some("git") => true,
+        Some(other) => {
+            return Err(ConfigError::Validation(format!(
+                "'name_from' must be \"basename\" or \"git\", got \"{other}\""
+            )));
+        }
+    };
+    let disambiguate_by_path = match raw.disambiguate.as_deref() {
+        None | Some("suffix") => false,
+        Some("path") => true,
+        Some(other) => {
+            return Err(ConfigError::Validation(format!(
+                "'disambiguate' must be \"suffix\" or \"path\", got \"{other}\""
+            )));
+        }
+    };
+    let named_repos = resolve_repo_names(&repos_raw, name_from_git, disambiguate_by_path)?;
 
     let mut repos = Vec::new();
+    // Output dirs repos get without setting their own, which relative
+    // per-repo output dirs and extra outputs may sit inside
+    let mut default_output_dirs = HashSet::new();
+    let mut missing_repos = Vec::new();
     for (repo_raw, path, name) in named_repos {
+        let mirror_subdir = match &repo_raw.mirror_subdir {
+            Some(raw_subdir) => validate_mirror_subdir(raw_subdir, &name)?,
+            None => name.clone(),
+        };
+
         if !path.is_dir() {
             warn!("Repo path does not exist, skipping: {}", path.display());
+            let output_dir = match &repo_raw.output_dir {
+                Some(raw_dir) => expand_path(template_base(raw_dir)?)?,
+                None => output_dir.clone(),
+            };
+            missing_repos.push(RepoLocation {
+                name,
+                path,
+                output_dir,
+                mirror_subdir,
+            });
             continue;
         }
 
-        let repo_output_dir = match &repo_raw.output_dir {
+        // Raw output_dir, and the directory it's relative to when not the
+        // current one. A relative path is under the global output_dir, so a
+        // global template applies to it too.
+        let (raw_repo_output_dir, relative_to) = match &repo_raw.output_dir {
+            Some(raw_dir) if global_uses_branch && !is_absolute_raw(raw_dir) => {
+                (Some(format!("{output_dir_raw}/{raw_dir}")), None)
+            }
+            Some(raw_dir) => (Some(raw_dir.clone()), Some(&output_dir)),
+            None if global_uses_branch => (Some(output_dir_raw.to_string()), None),
+            None => (None, None),
+        };
+        let branch = match &raw_repo_output_dir {
+            Some(raw_dir) if raw_dir.contains(BRANCH_TOKEN) => {
+                Some(git_branch(&path).ok_or_else(|| {
+                    ConfigError::Validation(format!(
+                        "Repo '{name}' has {BRANCH_TOKEN} in its output_dir, but {} is not a git repo",
+                        path.display()
+                    ))
+                })?)
+            }
+            _ => None,
+        };
+        let repo_output_dir = match raw_repo_output_dir {
             Some(raw_dir) => {
-                let expanded = expand_path(raw_dir)?;
-                std::fs::create_dir_all(&expanded)?;
-                std::fs::canonicalize(&expanded).unwrap_or(expanded)
+                let raw_dir = match &branch {
+                    Some(branch) => raw_dir.replace(BRANCH_TOKEN, branch),
+                    None => raw_dir,
+                };
+                let expanded = match relative_to {
+                    Some(base) => expand_path_from(&raw_dir, base)?,
+                    None => expand_path(&raw_dir)?,
+                };
+                create_output_dir(expanded, create_dirs)?
             }
             None => output_dir.clone(),
         };
+        if repo_raw.output_dir.is_none() {
+            default_output_dirs.insert(repo_output_dir.clone());
+        }
+        let mut extra_outputs = Vec::new();
+        for raw_dir in repo_raw.extra_outputs.iter().flatten() {
+            let extra = create_output_dir(expand_path_from(raw_dir, &output_dir)?, create_dirs)?;
+            if extra == repo_output_dir || extra_outputs.contains(&extra) {
+                return Err(ConfigError::Validation(format!(
+                    "'extra_outputs' for repo '{name}' lists {} more than once, or its output_dir",
+                    extra.display()
+                )));
+            }
+            extra_outputs.push(extra);
+        }
 
-        let repo_exclude: Vec<String> = repo_raw.exclude.clone().unwrap_or_default();
-        let repo_include: Vec<String> = repo_raw.include.clone().unwrap_or_default();
+        let repo_exclude = enabled_patterns(repo_raw.exclude.as_deref());
+        let repo_include = enabled_patterns(repo_raw.include.as_deref());
+        let repo_exclude_from = read_pattern_files(repo_raw.exclude_from.as_deref())?;
+        let repo_include_from = read_pattern_files(repo_raw.include_from.as_deref())?;
+        let repo_ignore = read_repo_ignore(&path)?;
 
         let all_exclude: Vec<String> = global_exclude
             .iter()
+            .chain(global_exclude_from.iter())
             .chain(repo_exclude.iter())
+            .chain(repo_exclude_from.iter())
+            .chain(repo_ignore.iter())
             .cloned()
             .collect();
         let all_include: Vec<String> = global_include
             .iter()
+            .chain(global_include_from.iter())
             .chain(repo_include.iter())
+            .chain(repo_include_from.iter())
             .cloned()
             .collect();
+        // Hints about the global patterns alone are only listed once
+        for hint in lint_patterns(&all_include, &all_exclude) {
+            if !global_lints.contains(&hint) {
+                pattern_warnings.push(format!("Repo '{name}': {hint}"));
+            }
+        }
+
+        let files = repo_raw
+            .files
+            .iter()
+            .flatten()
+            .map(|file| relative_segments(file, "files", &name).map(|s| s.join("/")))
+            .collect::<Result<Vec<String>, ConfigError>>()?;
 
         let exclude = compile_exclude(&all_exclude, &path)?;
-        let include = compile_include(&all_include)?;
+        let include = compile_include(&all_include)?
+            .with_files(&files)
+            .with_include_hidden(include_hidden);
+
+        let flat_inbox = repo_raw.flat_inbox.unwrap_or(false);
+        if flat_inbox && repo_raw.flatten == Some(false) {
+            return Err(ConfigError::Validation(format!(
+                "'flat_inbox' for repo '{name}' mirrors flat and cannot be combined with 'flatten = false'"
+            )));
+        }
+        let flatten = match (
+            repo_raw.flatten.or(flat_inbox.then_some(true)),
+            &repo_raw.flatten_separator,
+        ) {
+            (Some(true), separator) => {
+                let separator = separator.as_deref().unwrap_or(DEFAULT_FLATTEN_SEPARATOR);
+                if separator.is_empty() || separator.contains('/') {
+                    return Err(ConfigError::Validation(format!(
+                        "'flatten_separator' for repo '{name}' must be non-empty and cannot contain '/', got '{separator}'"
+                    )));
+                }
+                Some(separator.to_string())
+            }
+            (_, Some(_)) => {
+                return Err(ConfigError::Validation(format!(
+                    "'flatten_separator' for repo '{name}' requires 'flatten = true'"
+                )));
+            }
+            _ => None,
+        };
+
+        let mirror_empty_dirs = repo_raw.mirror_empty_dirs.unwrap_or(false);
+        if mirror_empty_dirs && flatten.is_some() {
+            return Err(ConfigError::Validation(format!(
+                "'mirror_empty_dirs' for repo '{name}' has no folders to mirror when flattened"
+            )));
+        }
+        let output_format = match repo_raw.output_format.as_deref() {
+            None | Some("plain") => OutputFormat::Plain,
+            Some("ulysses") if flatten.is_some() => {
+                return Err(ConfigError::Validation(format!(
+                    "'output_format = \"ulysses\"' for repo '{name}' has no folders to name keywords after when flattened"
+                )));
+            }
+            Some("ulysses") => OutputFormat::Ulysses,
+            Some(other) => {
+                return Err(ConfigError::Validation(format!(
+                    "'output_format' for repo '{name}' must be \"plain\" or \"ulysses\", got \"{other}\""
+                )));
+            }
+        };
+
+        let root = match &repo_raw.root {
+            Some(raw_root) => {
+                let root = relative_segments(raw_root, "root", &name)?.join("/");
+                // A missing repo is reported by the scanner; only check the
+                // root of a repo that is actually there
+                if path.is_dir() && !path.join(&root).is_dir() {
+                    return Err(ConfigError::Validation(format!(
+                        "'root' for repo '{name}' does not exist: {}",
+                        path.join(&root).display()
+                    )));
+                }
+                Some(root)
+            }
+            None => None,
+        };
 
         repos.push(RepoConfig {
             path,
             name,
             exclude,
             include,
+            exclude_patterns: all_exclude,
             include_patterns: all_include,
+            files,
             output_dir: repo_output_dir,
+            extra_outputs,
+            branch,
+            mirror_subdir,
+            enabled: repo_raw.enabled.unwrap_or(true),
+            priority: repo_raw.priority.unwrap_or(0),
+            direction: SyncDirection::default(),
+            read_only_source: repo_raw.read_only_source.unwrap_or(false),
+            max_depth: None,
+            include_hidden,
+            skip_if_matches: None,
+            mirror_binary: true,
+            flatten,
+            flat_inbox,
+            root,
+            mirror_poll_interval: poll_interval(repo_raw.mirror_poll_interval)?,
+            follow_symlinks: repo_raw.follow_symlinks.unwrap_or(false),
+            mirror_empty_dirs,
+            output_format,
+            git_tracked_only: repo_raw.git_tracked_only.unwrap_or(false),
+            guarded_dirs: Vec::new(),
         });
     }
 
-    validate_nesting(&repos)?;
+    validate_nesting(&repos, &default_output_dirs)?;
+
+    let state_dir = match raw.state_dir.as_deref() {
+        Some(raw_dir) => {
+            let dir = expand_path(raw_dir)?;
+            validate_state_dir(&dir, &repos)?;
+            Some(dir)
+        }
+        None => None,
+    };
+
+    let manifest_backend = match raw.manifest_backend.as_deref() {
+        None | Some("toml") => ManifestBackend::Toml,
+        Some("log") => ManifestBackend::Log,
+        Some(other) => {
+            return Err(ConfigError::Validation(format!(
+                "'manifest_backend' must be \"toml\" or \"log\", got \"{other}\""
+            )));
+        }
+    };
+
+    let base_cache = match raw.base_cache.as_deref() {
+        None | Some("path") => BaseCacheMode::Path,
+        Some("content") => BaseCacheMode::Content,
+        Some("off") => BaseCacheMode::Off,
+        Some(other) => {
+            return Err(ConfigError::Validation(format!(
+                "'base_cache' must be \"path\", \"content\", or \"off\", got \"{other}\""
+            )));
+        }
+    };
+
+    let protect = compile_protect(
+        raw.protect
+            .unwrap_or_else(|| DEFAULT_PROTECT.iter().map(|s| s.to_string()).collect()),
+    )?;
+
+    let mirror_ignore = MirrorIgnore::new(raw.mirror_ignore.unwrap_or_default())
+        .map_err(|e| ConfigError::Validation(format!("Invalid 'mirror_ignore' pattern: {e}")))?;
+
+    let log_dir = raw.log_dir.as_deref().map(expand_path).transpose()?;
+    let stats_interval = raw
+        .stats_interval
+        .map(|s| {
+            Duration::try_from_secs_f64(s)
+                .ok()
+                .filter(|interval| !interval.is_zero())
+                .ok_or_else(|| {
+                    ConfigError::Validation(format!(
+                        "'stats_interval' must be a positive number of seconds, got {s}"
+                    ))
+                })
+        })
+        .transpose()?;
+
+    let normalize_chars = raw.normalize_chars.unwrap_or_default();
+    if normalize_chars.iter().any(|(from, _)| from.is_empty()) {
+        return Err(ConfigError::Validation(
+            "'normalize_chars' entries must replace a non-empty string".into(),
+        ));
+    }
+    let normalize = Normalization {
+        trailing_newline: raw.normalize_trailing_newline.unwrap_or(false),
+        chars: normalize_chars,
+    };
 
     let auto_upgrade = raw.auto_upgrade.unwrap_or(true);
+    let write_output_gitignore = raw.write_output_gitignore.unwrap_or(false);
+    let bidirectional = raw.bidirectional.unwrap_or(true);
+    let notify_on_conflict = raw.notify_on_conflict.unwrap_or(false);
+    let mirror_binary = raw.mirror_binary.unwrap_or(true);
+    let direction = if bidirectional {
+        SyncDirection::Bidirectional
+    } else {
+        SyncDirection::SourceToMirror
+    };
+    if raw.max_depth == Some(0) {
+        return Err(ConfigError::Validation(
+            "'max_depth' must be at least 1".into(),
+        ));
+    }
+    if raw.initial_sync_limit == Some(0) {
+        return Err(ConfigError::Validation(
+            "'initial_sync_limit' must be at least 1".into(),
+        ));
+    }
+    let mirror_poll_interval = poll_interval(raw.mirror_poll_interval)?;
+    let skip_if_matches = match &raw.skip_if_matches {
+        Some(pattern) => Some(Regex::new(pattern).map_err(|e| {
+            ConfigError::Validation(format!("Invalid 'skip_if_matches' regex: {e}"))
+        })?),
+        None => None,
+    };
+    let mut guarded_dirs: Vec<PathBuf> = repos
+        .iter()
+        .flat_map(RepoConfig::output_dirs)
+        .cloned()
+        .chain(state_dir.clone())
+        .map(|dir| canonicalize_existing_prefix(&dir).unwrap_or(dir))
+        .collect();
+    guarded_dirs.sort();
+    guarded_dirs.dedup();
+    for repo in &mut repos {
+        repo.guarded_dirs = guarded_dirs.clone();
+        repo.direction = if repo.read_only_source {
+            SyncDirection::SourceToMirror
+        } else {
+            direction
+        };
+        repo.max_depth = raw.max_depth;
+        repo.skip_if_matches = skip_if_matches.clone();
+        repo.mirror_binary = mirror_binary;
+        repo.mirror_poll_interval = repo.mirror_poll_interval.or(mirror_poll_interval);
+    }
+    let inboxes = repos
+        .iter()
+        .filter(|r| r.flat_inbox)
+        .map(|r| Inbox {
+            mirror_root: r.mirror_root(),
+            repo_path: r.path.clone(),
+        })
+        .collect();
+    let keyword_trees = repos
+        .iter()
+        .filter(|r| r.output_format == OutputFormat::Ulysses)
+        .map(|r| KeywordTree {
+            output_dir: r.output_dir.clone(),
+            mirror_root: r.mirror_root(),
+            source_root: r.source_root(),
+        })
+        .collect();
+    let read_only_roots = repos
+        .iter()
+        .filter(|r| r.read_only_source)
+        .map(|r| r.mirror_root())
+        .chain(
+            repos
+                .iter()
+                .flat_map(RepoConfig::extra_output_repos)
+                .map(|r| r.mirror_root()),
+        )
+        .collect();
 
     Ok(Config {
         output_dir,
         repos,
+        missing_repos,
         debounce_seconds: debounce,
+        max_batch,
         log_level,
         rescan_interval,
+        rescan_jitter,
         auto_upgrade,
+        write_output_gitignore,
+        bidirectional,
+        max_depth: raw.max_depth,
+        initial_sync_limit: raw.initial_sync_limit,
+        parallel_hash: raw.parallel_hash.unwrap_or(false),
+        mirror_poll_interval,
+        include_hidden,
+        notify_on_conflict,
+        skip_if_matches,
+        mirror_binary,
+        mirror_ignore,
+        state: StateOptions {
+            state_dir,
+            manifest_backend,
+            base_cache,
+            base_cache_compress: raw.base_cache_compress.unwrap_or(false),
+            verify_after_write: raw.verify_after_write.unwrap_or(false),
+            protect,
+            normalize,
+            inboxes,
+            keyword_trees,
+            read_only_roots,
+        },
+        log_dir,
+        stats_interval,
+        pattern_warnings,
         config_path,
     })
 }
 
-fn resolve_repo_names(repos: &[RawRepo]) -> Result<Vec<(&RawRepo, PathBuf, String)>, ConfigError> {
-    let mut seen: HashMap<String, u32> = HashMap::new();
-    let mut result = Vec::new();
+/// Validate a `mirror_poll_interval` in seconds.
+fn poll_interval(seconds: Option<f64>) -> Result<Option<Duration>, ConfigError> {
+    seconds
+        .map(|s| {
+            Duration::try_from_secs_f64(s)
+                .ok()
+                .filter(|interval| !interval.is_zero())
+                .ok_or_else(|| {
+                    ConfigError::Validation(format!(
+                        "'mirror_poll_interval' must be a positive number of seconds, got {s}"
+                    ))
+                })
+        })
+        .transpose()
+}
 
-    for repo in repos {
-        let path = expand_path(&repo.path)?;
+/// Name for a repo without an explicit `name`: the last component of the
+/// path as written, so a symlinked repo is named after the link rather than
+/// its target. Falls back to the resolved path for paths like `.`.
+fn default_repo_name(raw_path: &str, resolved: &Path) -> String {
+    shellexpand::full(raw_path)
+        .ok()
+        .and_then(|p| Path::new(p.as_ref()).file_name().map(|n| n.to_os_string()))
+        .or_else(|| resolved.file_name().map(|n| n.to_os_string()))
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "unnamed".into())
+}
+
+/// Name for a repo without an explicit `name` under `name_from = "git"`:
+/// `owner-name` from the `origin` remote URL in the repo's `.git/config`.
+/// `None` when the repo has no readable git config or no `origin` remote.
+fn git_repo_name(path: &Path) -> Option<String> {
+    let git_dir = git_dir(path)?;
+    // Worktree dirs share the main repo's config via `commondir`
+    let git_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => git_dir.join(common.trim()),
+        Err(_) => git_dir,
+    };
+    let contents = std::fs::read_to_string(git_dir.join("config")).ok()?;
+    let url = origin_url(&contents)?;
+    name_from_remote_url(&url)
+}
+
+/// The repo's git directory: `.git`, or the directory a worktree's or
+/// submodule's `.git` file points at.
+fn git_dir(path: &Path) -> Option<PathBuf> {
+    let git_dir = path.join(".git");
+    match std::fs::read_to_string(&git_dir) {
+        Ok(contents) => {
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            Some(path.join(target))
+        }
+        Err(_) => git_dir.is_dir().then_some(git_dir),
+    }
+}
+
+/// The branch checked out in the repo at `path`, read from its `HEAD` as
+/// `git rev-parse --abbrev-ref HEAD` would: `"HEAD"` when detached.
+fn git_branch(path: &Path) -> Option<String> {
+    let head = std::fs::read_to_string(git_dir(path)?.join("HEAD")).ok()?;
+    match head.trim().strip_prefix("ref:") {
+        Some(reference) => {
+            let reference = reference.trim();
+            Some(
+                reference
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(reference)
+                    .to_string(),
+            )
+        }
+        None => Some("HEAD".into()),
+    }
+}
+
+/// The `url` of `[remote "origin"]` in a git config file.
+fn origin_url(git_config: &str) -> Option<String> {
+    let mut in_origin = false;
+    for line in git_config.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_origin = line.replace(char::is_whitespace, "") == "[remote\"origin\"]";
+        } else if in_origin {
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() == "url" {
+                    return Some(value.trim().trim_matches('"').to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `owner-name` from a remote URL's last two path segments, for https
+/// (`https://github.com/owner/name.git`), ssh (`ssh://git@host/owner/name`)
+/// and scp-style (`git@github.com:owner/name.git`) URLs.
+fn name_from_remote_url(url: &str) -> Option<String> {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.rsplit_once(':').map_or(url, |(_, path)| path),
+    };
+    let mut segments = path
+        .trim_end_matches('/')
+        .trim_end_matches(".git")
+        .rsplit('/')
+        .filter(|s| !s.is_empty());
+    let name = segments.next()?;
+    match segments.next() {
+        Some(owner) => Some(format!("{owner}-{name}")),
+        None => Some(name.to_string()),
+    }
+}
+
+/// Name every repo, resolving collisions. By default later repos sharing a
+/// name get `-2`, `-3`, ... in config order. With `disambiguate_by_path`
+/// they are instead named after the parent directories that tell them
+/// apart, e.g. `project (work)`. Either way the first keeps its name, so
+/// adding a repo never renames one already mirrored. `-N` remains the
+/// fallback for paths that can't be told apart.
+fn resolve_repo_names(
+    repos: &[RawRepo],
+    name_from_git: bool,
+    disambiguate_by_path: bool,
+) -> Result<Vec<(&RawRepo, PathBuf, String)>, ConfigError> {
+    let mut named = Vec::new();
+    for repo in repos {
+        let path = expand_path(&repo.path)?;
         let base_name = repo.name.clone().unwrap_or_else(|| {
-            path.file_name()
-                .map(|n| n.to_string_lossy().into_owned())
-                .unwrap_or_else(|| "unnamed".into())
+            name_from_git
+                .then(|| git_repo_name(&path))
+                .flatten()
+                .unwrap_or_else(|| default_repo_name(&repo.path, &path))
         });
+        named.push((repo, path, base_name));
+    }
 
-        let count = seen.entry(base_name.clone()).or_insert(0);
-        *count += 1;
+    if disambiguate_by_path {
+        let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, (_, _, name)) in named.iter().enumerate() {
+            groups.entry(name.clone()).or_default().push(i);
+        }
+        for indices in groups.into_values().filter(|g| g.len() > 1) {
+            let paths: Vec<&Path> = indices.iter().map(|&i| named[i].1.as_path()).collect();
+            let labels = parent_labels(&paths);
+            for (&i, label) in indices.iter().zip(labels).skip(1) {
+                if let Some(label) = label {
+                    let name = format!("{} ({label})", named[i].2);
+                    info!("Repo name collision for '{}', using '{name}'", named[i].2);
+                    named[i].2 = name;
+                }
+            }
+        }
+    }
 
-        let name = if *count > 1 {
-            let suffixed = format!("{}-{}", base_name, count);
+    // Names are compared case-folded, as the mirror subdirs named after
+    // them are on a case-insensitive volume. A suffixed name skips every
+    // name a repo already has, so it never takes another repo's.
+    let taken: HashSet<String> = named
+        .iter()
+        .map(|(_, _, name)| name.to_lowercase())
+        .collect();
+    let mut used: HashSet<String> = HashSet::new();
+    let mut result = Vec::new();
+    for (repo, path, base_name) in named {
+        let name = if used.contains(&base_name.to_lowercase()) {
+            let suffixed = (2..)
+                .map(|n| format!("{}-{}", base_name, n))
+                .find(|candidate| {
+                    let folded = candidate.to_lowercase();
+                    !taken.contains(&folded) && !used.contains(&folded)
+                })
+                .expect("unbounded suffixes");
             warn!(
                 "Repo name collision for '{}', using '{}'",
                 base_name, suffixed
@@ -397,18 +1717,149 @@ fn resolve_repo_names(repos: &[RawRepo]) -> Result<Vec<(&RawRepo, PathBuf, Strin
             base_name
         };
 
+        used.insert(name.to_lowercase());
         result.push((repo, path, name));
     }
 
     Ok(result)
 }
 
-fn validate_nesting(repos: &[RepoConfig]) -> Result<(), ConfigError> {
-    let home = dirs::home_dir();
+/// For repos sharing a name, a label per path built from the nearest parent
+/// directories that tell the paths apart, skipping levels where every path
+/// has the same directory. Segments are slugified and joined outermost
+/// first: `~/work/project` and `~/personal/project` get `work` and
+/// `personal`. `None` for a path with no distinguishing parent.
+fn parent_labels(paths: &[&Path]) -> Vec<Option<String>> {
+    let parents: Vec<Vec<String>> = paths
+        .iter()
+        .map(|path| {
+            let mut segments: Vec<String> = path
+                .parent()
+                .into_iter()
+                .flat_map(|p| p.components())
+                .filter_map(|c| match c {
+                    Component::Normal(seg) => Some(slugify(&seg.to_string_lossy())),
+                    _ => None,
+                })
+                .collect();
+            segments.reverse();
+            segments
+        })
+        .collect();
+    let deepest = parents.iter().map(Vec::len).max().unwrap_or(0);
+
+    // Levels (nearest parent first) to label with: the differing ones, up to
+    // the first level where every path's label is unique
+    let mut levels = Vec::new();
+    for level in 0..deepest {
+        let at_level: HashSet<Option<&String>> = parents.iter().map(|p| p.get(level)).collect();
+        if at_level.len() == 1 {
+            continue;
+        }
+        levels.push(level);
+        let labels: HashSet<Vec<Option<&String>>> = parents
+            .iter()
+            .map(|p| levels.iter().map(|&l| p.get(l)).collect())
+            .collect();
+        if labels.len() == parents.len() {
+            break;
+        }
+    }
 
+    parents
+        .iter()
+        .map(|p| {
+            let segments: Vec<&str> = levels
+                .iter()
+                .rev()
+                .filter_map(|&l| p.get(l))
+                .map(String::as_str)
+                .filter(|seg| !seg.is_empty())
+                .collect();
+            (!segments.is_empty()).then(|| segments.join("-"))
+        })
+        .collect()
+}
+
+/// Lowercase `segment`, with each run of characters other than letters and
+/// digits replaced by one `-`.
+fn slugify(segment: &str) -> String {
+    segment
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Split a relative path option into its normal segments, rejecting absolute
+/// paths, `..`, and paths that are empty once `.` segments are dropped.
+fn relative_segments(raw: &str, option: &str, repo_name: &str) -> Result<Vec<String>, ConfigError> {
+    let mut segments = Vec::new();
+    for component in Path::new(raw).components() {
+        match component {
+            Component::Normal(seg) => segments.push(seg.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            _ => {
+                return Err(ConfigError::Validation(format!(
+                    "'{option}' for repo '{repo_name}' must be a relative path without '..', got '{raw}'"
+                )));
+            }
+        }
+    }
+    if segments.is_empty() {
+        return Err(ConfigError::Validation(format!(
+            "'{option}' for repo '{repo_name}' cannot be empty"
+        )));
+    }
+    Ok(segments)
+}
+
+/// Normalize a `mirror_subdir` to a relative, `/`-separated path that stays
+/// inside the output directory.
+fn validate_mirror_subdir(raw: &str, repo_name: &str) -> Result<String, ConfigError> {
+    let segments = relative_segments(raw, "mirror_subdir", repo_name)?;
+    if segments[0].starts_with(".ulysses-link") {
+        return Err(ConfigError::Validation(format!(
+            "'mirror_subdir' for repo '{repo_name}' cannot use the reserved name '{}'",
+            segments[0]
+        )));
+    }
+    Ok(segments.join("/"))
+}
+
+/// The state dir must sit outside every repo and output dir, or its base
+/// cache would itself be scanned or shown in the mirror.
+fn validate_state_dir(state_dir: &Path, repos: &[RepoConfig]) -> Result<(), ConfigError> {
     for repo in repos {
-        let od = &repo.output_dir;
+        if state_dir.starts_with(&repo.path) {
+            return Err(ConfigError::Validation(format!(
+                "state_dir '{}' is inside repo '{}'",
+                state_dir.display(),
+                repo.name,
+            )));
+        }
+        if state_dir.starts_with(&repo.output_dir) {
+            return Err(ConfigError::Validation(format!(
+                "state_dir '{}' is inside output_dir '{}'",
+                state_dir.display(),
+                repo.output_dir.display(),
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_nesting(
+    repos: &[RepoConfig],
+    default_output_dirs: &HashSet<PathBuf>,
+) -> Result<(), ConfigError> {
+    let home = dirs::home_dir();
 
+    for (repo, od) in repos
+        .iter()
+        .flat_map(|r| r.output_dirs().map(move |od| (r, od)))
+    {
         // Output dir cannot be home directory or filesystem root
         if let Some(ref home) = home {
             if od == home {
@@ -461,32 +1912,91 @@ fn validate_nesting(repos: &[RepoConfig]) -> Result<(), ConfigError> {
         }
     }
 
-    // No pair of active output_dirs can be nested inside each other
-    let output_dirs: Vec<&PathBuf> = repos.iter().map(|r| &r.output_dir).collect();
+    // No repo can be inside another repo: its files would be mirrored twice
+    for (i, a) in repos.iter().enumerate() {
+        for b in repos.iter().skip(i + 1) {
+            let (outer, inner) = if b.path.starts_with(&a.path) {
+                (a, b)
+            } else if a.path.starts_with(&b.path) {
+                (b, a)
+            } else {
+                continue;
+            };
+            let relation = if outer.path == inner.path {
+                "is the same directory as"
+            } else {
+                "is inside"
+            };
+            return Err(ConfigError::Validation(format!(
+                "repo '{}' ({}) {} repo '{}' ({}). Its files would be mirrored twice and sync would fight itself; remove one of them from the config.",
+                inner.name,
+                inner.path.display(),
+                relation,
+                outer.name,
+                outer.path.display(),
+            )));
+        }
+    }
+
+    // No pair of active output_dirs can be nested inside each other, except
+    // inside a default one: its watcher ignores files no entry of its own
+    // manifest names, and mirror dirs still can't overlap
+    let output_dirs: Vec<&PathBuf> = repos.iter().flat_map(RepoConfig::output_dirs).collect();
     for (i, a) in output_dirs.iter().enumerate() {
         for b in output_dirs.iter().skip(i + 1) {
             if a == b {
                 continue;
             }
-            if a.starts_with(b.as_path()) || b.starts_with(a.as_path()) {
+            let (outer, inner) = if a.starts_with(b.as_path()) {
+                (b, a)
+            } else if b.starts_with(a.as_path()) {
+                (a, b)
+            } else {
+                continue;
+            };
+            if !default_output_dirs.contains(*outer) {
                 return Err(ConfigError::Validation(format!(
                     "Output directories '{}' and '{}' are nested. Each output_dir must be independent to avoid duplicate watcher events.",
-                    a.display(),
-                    b.display(),
+                    outer.display(),
+                    inner.display(),
                 )));
             }
         }
     }
 
     // No pair of effective mirror dirs can overlap
-    for (i, a) in repos.iter().enumerate() {
-        let a_mirror = a.output_dir.join(&a.name);
-        for b in repos.iter().skip(i + 1) {
-            let b_mirror = b.output_dir.join(&b.name);
-            if a_mirror == b_mirror
-                || a_mirror.starts_with(&b_mirror)
-                || b_mirror.starts_with(&a_mirror)
-            {
+    let mirrors: Vec<(&RepoConfig, PathBuf)> = repos
+        .iter()
+        .flat_map(|r| {
+            r.output_dirs()
+                .map(move |od| (r, od.join(&r.mirror_subdir)))
+        })
+        .collect();
+    // Compared case-folded: macOS volumes, where Ulysses runs, are
+    // case-insensitive by default, so `Docs` and `docs` are one directory
+    let folded: Vec<PathBuf> = mirrors
+        .iter()
+        .map(|(_, mirror)| PathBuf::from(mirror.to_string_lossy().to_lowercase()))
+        .collect();
+    // Checked first for the clearer message. Name dedup can't prevent this:
+    // an explicit mirror_subdir can repeat another repo's.
+    for (i, (a, a_mirror)) in mirrors.iter().enumerate() {
+        for (j, (b, _)) in mirrors.iter().enumerate().skip(i + 1) {
+            if folded[i] == folded[j] {
+                return Err(ConfigError::Validation(format!(
+                    "Repos '{}' ({}) and '{}' ({}) both mirror into '{}'. Their files would overwrite each other; give one a different name or mirror_subdir.",
+                    a.name,
+                    a.path.display(),
+                    b.name,
+                    b.path.display(),
+                    a_mirror.display(),
+                )));
+            }
+        }
+    }
+    for (i, (a, a_mirror)) in mirrors.iter().enumerate() {
+        for (j, (b, b_mirror)) in mirrors.iter().enumerate().skip(i + 1) {
+            if folded[i].starts_with(&folded[j]) || folded[j].starts_with(&folded[i]) {
                 return Err(ConfigError::Validation(format!(
                     "Mirror directories for '{}' and '{}' overlap at '{}' and '{}'. Each repo must have a distinct mirror directory.",
                     a.name,
@@ -501,6 +2011,42 @@ fn validate_nesting(repos: &[RepoConfig]) -> Result<(), ConfigError> {
     Ok(())
 }
 
+/// Read patterns from gitignore-format files: one pattern per line,
+/// blank lines and `#` comments skipped.
+fn read_pattern_files(paths: Option<&[String]>) -> Result<Vec<String>, ConfigError> {
+    let mut patterns = Vec::new();
+    for raw_path in paths.unwrap_or_default() {
+        let path = expand_path(raw_path)?;
+        if !path.is_file() {
+            return Err(ConfigError::Validation(format!(
+                "Pattern file not found: {}",
+                path.display()
+            )));
+        }
+        patterns.extend(read_pattern_file(&path)?);
+    }
+    Ok(patterns)
+}
+
+fn read_pattern_file(path: &Path) -> Result<Vec<String>, ConfigError> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
+
+/// Exclude patterns from the repo's own `.ulyssesignore`, if it has one.
+fn read_repo_ignore(repo_path: &Path) -> Result<Vec<String>, ConfigError> {
+    let path = repo_path.join(REPO_IGNORE_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    read_pattern_file(&path)
+}
+
 fn compile_exclude(patterns: &[String], repo_path: &Path) -> Result<Gitignore, ConfigError> {
     let mut builder = GitignoreBuilder::new(repo_path);
     for pattern in patterns {
@@ -513,24 +2059,81 @@ fn compile_exclude(patterns: &[String], repo_path: &Path) -> Result<Gitignore, C
         .map_err(|e| ConfigError::Validation(format!("Failed to compile exclude patterns: {e}")))
 }
 
-fn compile_include(patterns: &[String]) -> Result<GlobSet, ConfigError> {
+/// Hints about include and exclude patterns that likely don't do what was
+/// meant. They never make a config invalid.
+fn lint_patterns(include: &[String], exclude: &[String]) -> Vec<String> {
+    // Includes and excludes both match bare names at any depth
+    let normalize = |p: &str| p.strip_prefix("**/").unwrap_or(p).to_string();
+    let mut hints = Vec::new();
+    for pattern in include {
+        if pattern.ends_with('/') {
+            hints.push(format!(
+                "Include pattern '{pattern}' matches no files; use '{pattern}**' to include everything under the directory"
+            ));
+        }
+    }
+    for pattern in exclude {
+        let shadowed = include
+            .iter()
+            .filter(|p| !p.starts_with('!'))
+            .find(|p| normalize(p) == normalize(pattern));
+        if let Some(included) = shadowed {
+            hints.push(format!(
+                "Exclude pattern '{pattern}' removes every file include pattern '{included}' matches, so that include never applies"
+            ));
+        }
+    }
+    hints
+}
+
+fn compile_include(patterns: &[String]) -> Result<IncludeSet, ConfigError> {
     let mut builder = GlobSetBuilder::new();
+    let mut negated = Vec::with_capacity(patterns.len());
+    let mut names_hidden = Vec::with_capacity(patterns.len());
     for pattern in patterns {
+        // A leading `!` carves an exception out of earlier includes
+        let (body, is_negated) = match pattern.strip_prefix('!') {
+            Some(rest) => (rest, true),
+            None => (pattern.as_str(), false),
+        };
         // For patterns without path separators, match against filename only
         // by prepending **/ to make them match at any depth
+        let glob_pattern = if !body.contains('/') && !body.starts_with("**/") {
+            format!("**/{body}")
+        } else {
+            body.to_string()
+        };
+        let glob = Glob::new(&glob_pattern).map_err(|e| {
+            ConfigError::Validation(format!("Invalid include pattern '{pattern}': {e}"))
+        })?;
+        builder.add(glob);
+        negated.push(is_negated);
+        names_hidden.push(has_hidden_segment(body));
+    }
+    let globs = builder
+        .build()
+        .map_err(|e| ConfigError::Validation(format!("Failed to compile include patterns: {e}")))?;
+    Ok(IncludeSet::new(globs, negated, names_hidden))
+}
+
+fn compile_protect(patterns: Vec<String>) -> Result<ProtectSet, ConfigError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in &patterns {
+        // Like includes, a pattern without a separator matches at any depth
         let glob_pattern = if !pattern.contains('/') && !pattern.starts_with("**/") {
             format!("**/{pattern}")
         } else {
             pattern.clone()
         };
         let glob = Glob::new(&glob_pattern).map_err(|e| {
-            ConfigError::Validation(format!("Invalid include pattern '{pattern}': {e}"))
+            ConfigError::Validation(format!("Invalid protect pattern '{pattern}': {e}"))
         })?;
         builder.add(glob);
     }
-    builder
+    let globs = builder
         .build()
-        .map_err(|e| ConfigError::Validation(format!("Failed to compile include patterns: {e}")))
+        .map_err(|e| ConfigError::Validation(format!("Failed to compile protect patterns: {e}")))?;
+    Ok(ProtectSet::new(patterns, globs))
 }
 
 // --- Default config generation ---
@@ -539,13 +2142,30 @@ pub fn generate_default_config(path: &Path, output_dir: &Path) -> Result<(), Con
     if let Some(parent) = path.parent() {
         std::fs::create_dir_all(parent)?;
     }
+    // Stored absolute, so the service finds it whatever its working directory
+    let output_dir = expand_path(&output_dir.to_string_lossy())?;
     let content = DEFAULT_CONFIG_TEMPLATE.replace("{{output_dir}}", &output_dir.to_string_lossy());
     std::fs::write(path, content)?;
     Ok(())
 }
 
+/// A starter config for `config --template`: the generated default, or with
+/// `full` every supported option.
+pub fn config_template(full: bool) -> String {
+    let template = if full {
+        FULL_CONFIG_TEMPLATE
+    } else {
+        DEFAULT_CONFIG_TEMPLATE
+    };
+    template.replace("{{output_dir}}", "~/ulysses-link")
+}
+
 pub fn default_config_path() -> PathBuf {
-    dirs::config_dir()
+    // XDG_CONFIG_HOME wins on every platform; dirs only honors it on Linux
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute());
+    xdg.or_else(dirs::config_dir)
         .unwrap_or_else(|| PathBuf::from("~/.config"))
         .join("ulysses-link")
         .join("config.toml")
@@ -556,13 +2176,18 @@ version = 1
 
 # Where the mirror tree is rooted.
 # Files are copied here and kept in bidirectional sync with source repos.
-# Tilde and env vars are expanded.
+# Tilde and env vars are expanded. {branch} is replaced by each repo's
+# checked-out git branch, e.g. "~/Ulysses/{branch}".
 output_dir = "{{output_dir}}"
 
 # Debounce window in seconds for filesystem events.
 # After a burst of events (e.g. git pull), wait this long before syncing.
 debounce_seconds = 0.5
 
+# Once this many events are waiting, sync them without waiting out the
+# debounce window, so a checkout touching 100k files is handled in chunks.
+# max_batch = 10000
+
 # Logging level: TRACE, DEBUG, INFO, WARNING, ERROR
 log_level = "INFO"
 
@@ -571,9 +2196,120 @@ log_level = "INFO"
 # "never" disables periodic rescans. A number sets a fixed interval in seconds.
 # rescan_interval = "auto"
 
+# Randomly lengthen or shorten each periodic rescan by up to this many
+# percent, so rescans don't line up with other periodic work. 0 to 50.
+# rescan_jitter = 10
+
 # Automatically check for and install new versions (default: true).
 # auto_upgrade = true
 
+# Write a .gitignore into each output directory listing the manifest,
+# base cache, and conflict files, so they aren't committed if the mirror
+# tree is version-controlled. An existing .gitignore is never overwritten.
+# write_output_gitignore = false
+
+# Sync edits made in the mirror (e.g. in Ulysses) back to the source repos.
+# Set to false to treat the mirror as read-only: mirror edits are
+# overwritten from source on the next sync and never reach the repo.
+# bidirectional = true
+
+# Show a desktop notification when the service writes conflict files, one
+# per batch of changes, so conflicts don't pile up unnoticed.
+# notify_on_conflict = false
+
+# How many directory levels below each repo root a scan descends.
+# Unset means unlimited; 1 mirrors only files at the repo root.
+# max_depth = 20
+
+# Cap on how many new files one full scan copies into the mirror, so a big
+# first import doesn't swamp Ulysses. The rest follow on later scans.
+# initial_sync_limit = 500
+
+# Hash each repo's files on all CPU cores before a full scan syncs them,
+# which speeds up scans of large repos on fast disks.
+# parallel_hash = false
+
+# Poll each output directory for mirror edits every this many seconds
+# instead of relying on native file events, for Ulysses folders on iCloud
+# Drive, SMB or other volumes that don't report changes reliably.
+# mirror_poll_interval = 2
+
+# Paths with a segment starting with "." (.github/, .notes.md) are skipped
+# unless an include pattern or files entry names them, e.g. ".github/*.md".
+# Set to true to let the patterns alone decide.
+# include_hidden = false
+
+# Skip files whose first few KB match this regex, even when their path is
+# included. Matched files already in the mirror are removed, and come back
+# once the source no longer matches.
+# skip_if_matches = '(?m)^draft:\s*true'
+
+# Set to false to skip included files that look binary (a NUL byte or mostly
+# control characters in the first few KB) with a warning. Their mirrors are
+# removed if already there.
+# mirror_binary = true
+
+# Mirror files that are never deleted, even when tracked: pruning, repo
+# removal and deletes in either direction leave them in place. Matched
+# against paths inside the output directory. Set to [] to protect nothing.
+# protect = [".Ulysses-*", "*.plist"]
+
+# Mirror files whose changes are never synced to source, on top of the
+# lock, swap and temp files editors leave behind (.~lock.*, *.tmp,
+# .goutputstream-*, *~ and similar), which are always ignored.
+# mirror_ignore = ["*.bak"]
+
+# Ignore cosmetic rewrites Ulysses makes on save, so they don't sync back
+# to source as edits. Applied to both sides before comparing.
+# normalize_trailing_newline = true     # one trailing newline is the same as none
+# normalize_chars = [["“", "\""], ["”", "\""], ["’", "'"]]
+
+# Keep the manifest and base cache here instead of inside each output
+# directory, so Ulysses and cloud sync tools only see mirrored files.
+# Each output directory gets its own subdirectory. Must be outside every
+# repo and output directory.
+# state_dir = "~/.local/state/ulysses-link"
+
+# Where the background service writes its logs. Defaults to
+# ~/Library/Logs/ulysses-link on macOS and the systemd journal on Linux.
+# log_dir = "~/.local/state/ulysses-link/logs"
+
+# Seconds between one-line INFO summaries from the background service:
+# repos watched, files tracked, conflicts and the last scan's duration.
+# stats_interval = 3600
+
+# How manifests are stored. "toml" (default) rewrites one file on every
+# save. "log" appends each change to .ulysses-link.log and compacts it now
+# and then, which keeps saves fast with tens of thousands of files. Either
+# converts the other's manifest on the next save.
+# manifest_backend = "toml"
+
+# How last-synced content is kept for three-way merges.
+# "path" (default) stores one copy per mirrored file. "content" stores one
+# copy per distinct content, so identical files (LICENSE, etc.) share it.
+# "off" keeps no copies; edits made on both sides become conflict files.
+# base_cache = "path"
+
+# Gzip base cache copies as they're written, shrinking the base cache
+# several times over for prose. Existing copies are read either way.
+# base_cache_compress = false
+
+# Re-hash every file sync copies or merges right after writing it, and
+# write it once more if it doesn't match, for storage that corrupts
+# writes silently. Costs an extra read of each written file.
+# verify_after_write = false
+
+# How repos without a "name" are named. "basename" (default) uses the
+# directory name; "git" uses owner-name from the origin remote in the
+# repo's .git/config, falling back to the directory name.
+# name_from = "basename"
+
+# How repos that end up with the same name are told apart. "suffix"
+# (default) appends -2, -3, ... in config order; "path" appends the parent
+# directories that differ, e.g. "project (personal)". Either way the first
+# repo in config order keeps its name.
+# disambiguate = "suffix"
+
 # Global exclude patterns applied to ALL repos (gitignore syntax).
 # These are checked BEFORE includes, so node_modules/*.md stays excluded.
 # Uncomment to override defaults (version control dirs, node_modules,
@@ -585,13 +2321,189 @@ log_level = "INFO"
 # *.adoc, *.org, README, LICENSE, CHANGELOG, etc. are included by default).
 # global_include = ["*.md", "*.mdx"]
 
+# Pattern files in gitignore format (one pattern per line, # comments),
+# merged with the inline global patterns above.
+# global_exclude_from = ["~/.config/ulysses-link/ignore"]
+# global_include_from = []
+
 # Per-repo definitions
 # [[repos]]
 # path = "~/code/my-project"
 # name = "my-project"           # optional, defaults to directory basename
-# output_dir = "~/work-docs"    # optional, overrides global output_dir
+# output_dir = "~/work-docs"    # optional, overrides global output_dir;
+#                                # a relative path is under the global one
+# mirror_subdir = "work/my-project" # optional, defaults to name
+# root = "docs"                # optional, mirror only this subdirectory:
+#                                # docs/guide.md -> my-project/guide.md
+# enabled = false               # optional, pause syncing but keep the mirror
+# flatten = true                # optional, mirror all files into one folder:
+#                                # docs/api/auth.md -> "docs - api - auth.md"
+# flatten_separator = " - "     # optional, joins path segments when flattened
+# flat_inbox = true            # optional, flatten and start each file with a
+#                                # <!-- source: docs/api/auth.md --> header
 # exclude = ["docs/generated/"] # merged with global_exclude
 # include = ["*.tex"]           # merged with global_include
+#                                # prefix with ! to carve exceptions, e.g. "!CHANGELOG.md"
+#                                # entries may be tables: { pattern = "*.tex",
+#                                # comment = "why", enabled = false }
+# exclude_from = ["~/code/my-project/.docignore"] # pattern files, merged too
+#                                # a .ulyssesignore in the repo root is merged too
+# files = ["ARCHITECTURE", "docs/NOTES"] # exact paths, mirrored regardless of include
+# priority = 10                # optional, higher-priority repos are synced first
+# mirror_poll_interval = 2      # optional, poll this repo's output_dir for edits
+# follow_symlinks = true        # optional, mirror files reached through symlinks
+# mirror_empty_dirs = true      # optional, mirror empty folders as empty Ulysses groups
+# output_format = "ulysses"     # optional, start each .md file with keywords named
+#                                # after its folders: docs/api/auth.md gets #docs #api
+# git_tracked_only = true       # optional, skip files git doesn't track
+# read_only_source = true       # optional, never write to this repo; mirror
+#                                # edits are reverted and kept as conflict files
+# extra_outputs = ["~/Backups/docs"] # optional, also mirror one way into these
+"#;
+
+/// Every supported option, printed by `config --template full` for users to
+/// copy from. Options without a default are commented out; the rest are set
+/// to their defaults.
+const FULL_CONFIG_TEMPLATE: &str = r#"# ulysses-link configuration: every option, set to its default or
+# commented out with an example value. Copy the parts you need.
+version = 2
+
+# Where the mirror tree is rooted. Tilde and env vars are expanded.
+# {branch} is replaced by each repo's checked-out git branch,
+# e.g. "~/Ulysses/{branch}".
+output_dir = "{{output_dir}}"
+
+# Keep the manifest and base cache here instead of inside each output
+# directory. Must be outside every repo and output directory.
+# state_dir = "~/.local/state/ulysses-link"
+
+# TRACE, DEBUG, INFO, WARNING or ERROR
+log_level = "INFO"
+
+# Where the background service writes its logs. Defaults to
+# ~/Library/Logs/ulysses-link on macOS and the systemd journal on Linux.
+# log_dir = "~/.local/state/ulysses-link/logs"
+
+# Seconds between one-line INFO summaries from the background service:
+# repos watched, files tracked, conflicts and the last scan's duration.
+# stats_interval = 3600
+
+[sync]
+# Seconds to wait after a burst of filesystem events before syncing (0 to 30)
+debounce_seconds = 0.5
+
+# Sync once this many events are waiting, without waiting out the debounce
+max_batch = 10000
+
+# Full rescan interval: "auto" scales with scan speed, "never" turns
+# rescans off, a number is a fixed interval in seconds
+rescan_interval = "auto"
+# rescan_interval = "never"
+# rescan_interval = 3600
+
+# Randomly stretch or shorten each periodic rescan by up to this many percent
+rescan_jitter = 0
+
+# Write a .gitignore for tool metadata into each output directory
+write_output_gitignore = false
+
+# Sync mirror edits back to the source repos; false makes the mirror read-only
+bidirectional = true
+
+# Desktop notification when a batch of changes writes conflict files
+notify_on_conflict = false
+
+# Directory levels below each repo root a scan descends (unset: unlimited)
+# max_depth = 20
+
+# New files one full scan copies at most (unset: unlimited)
+# initial_sync_limit = 500
+
+# Hash each repo's files on all CPU cores during full scans
+parallel_hash = false
+
+# Poll output directories for mirror edits every this many seconds, for
+# volumes that don't report changes (iCloud Drive, SMB)
+# mirror_poll_interval = 2
+
+# Mirror paths with a segment starting with "." without naming them
+include_hidden = false
+
+# Skip files whose first few KB match this regex
+# skip_if_matches = '(?m)^draft:\s*true'
+
+# Mirror included files that look binary instead of skipping them
+mirror_binary = true
+
+# Mirror files that are never deleted
+protect = [".Ulysses-*", "*.plist"]
+
+# Extra mirror files whose changes never sync back, besides editor lock files
+mirror_ignore = []
+
+# Cosmetic rewrites Ulysses makes on save that don't count as edits
+normalize_trailing_newline = false
+# normalize_chars = [["“", "\""], ["”", "\""], ["’", "'"]]
+
+# Manifest storage: "toml", or "log" for very large trees
+manifest_backend = "toml"
+
+# Last-synced copies for three-way merges: "path", "content" or "off"
+base_cache = "path"
+
+# Gzip base cache copies as they're written
+base_cache_compress = false
+
+# Re-hash each written file, rewriting it once on a mismatch
+verify_after_write = false
+
+# Default repo names: "basename" or "git" (owner-name from origin)
+name_from = "basename"
+
+# Telling apart repos with the same name: "suffix" (-2, -3, ...) or
+# "path" (parent directories, e.g. "project (work)")
+disambiguate = "suffix"
+
+# Patterns for every repo, in gitignore syntax. Setting these replaces the
+# defaults: version control, dependency, build and editor directories are
+# excluded, and *.md, *.mdx, *.txt, *.rst, README, LICENSE and the like
+# are included.
+# exclude = [".git/", "node_modules/", "target/"]
+# include = ["*.md", "*.mdx", "*.txt", "README"]
+
+# Pattern files (one pattern per line, # comments) merged with the above
+# exclude_from = ["~/.config/ulysses-link/ignore"]
+# include_from = []
+
+[upgrade]
+# Check for and install new versions
+auto = true
+
+# One [[repos]] section per repo; only path is required.
+#
+# [[repos]]
+# path = "~/code/my-project"
+# name = "my-project"                 # defaults to the directory name
+# output_dir = "~/work-docs"          # overrides the global output_dir
+# mirror_subdir = "work/my-project"   # defaults to name
+# root = "docs"                       # mirror only this subdirectory
+# enabled = true                      # false pauses syncing, keeps the mirror
+# flatten = false                     # mirror every file into one folder
+# flatten_separator = " - "           # joins path segments when flattened
+# flat_inbox = false                  # flatten, with a source header per file
+# exclude = ["docs/generated/"]       # merged with [sync] exclude
+# include = ["*.tex", "!CHANGELOG.md"] # merged with [sync] include
+# exclude_from = ["~/code/my-project/.docignore"]
+# include_from = []
+# files = ["ARCHITECTURE"]            # exact paths, mirrored regardless of include
+# priority = 0                        # higher-priority repos sync first
+# mirror_poll_interval = 2            # poll this repo's output_dir for edits
+# follow_symlinks = false             # mirror files reached through symlinks
+# mirror_empty_dirs = false           # mirror empty folders as Ulysses groups
+# output_format = "plain"             # "ulysses" adds folder keywords, e.g. #docs #api
+# git_tracked_only = false            # skip files git doesn't track
+# read_only_source = false            # never write to the repo, whatever the mirror does
+# extra_outputs = []                  # more output dirs, mirrored source to mirror only
 "#;
 
 // --- Config modification ---
@@ -627,6 +2539,15 @@ pub fn add_repo(config_path: &Path, repo_path: &Path) -> Result<bool, ConfigErro
     if let Some(array) = repos.as_array_of_tables_mut() {
         let mut table = toml_edit::Table::new();
         table.insert("path", toml_edit::value(&repo_str));
+        // The stored path is canonical, so a repo added through a symlink
+        // keeps the link's name explicitly
+        let name = default_repo_name(&repo_path.to_string_lossy(), &canonical);
+        if canonical
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy() != name)
+        {
+            table.insert("name", toml_edit::value(name));
+        }
         array.push(table);
     }
 
@@ -659,11 +2580,7 @@ pub fn remove_repo(config_path: &Path, repo_path: &Path) -> Result<Option<String
                         .get("name")
                         .and_then(|v| v.as_str())
                         .map(|s| s.to_string())
-                        .or_else(|| {
-                            existing.as_ref().and_then(|p| {
-                                p.file_name().map(|n| n.to_string_lossy().to_string())
-                            })
-                        });
+                        .or_else(|| existing.as_ref().map(|p| default_repo_name(path, p)));
                     remove_idx = Some(i);
                     break;
                 }
@@ -681,6 +2598,70 @@ pub fn remove_repo(config_path: &Path, repo_path: &Path) -> Result<Option<String
     Ok(removed_name)
 }
 
+/// Rewrite a version 1 config as version 2, preserving comments and formatting.
+///
+/// Flat global options move into the `[sync]` and `[upgrade]` tables. Input
+/// that is not a parseable v1 config is returned unchanged.
+pub fn migrate_v1_to_v2(contents: &str) -> String {
+    let Ok(mut doc) = contents.parse::<toml_edit::DocumentMut>() else {
+        return contents.to_string();
+    };
+    let Some(version) = doc.get_mut("version").and_then(|v| v.as_value_mut()) else {
+        return contents.to_string();
+    };
+    if version.as_integer() != Some(1) {
+        return contents.to_string();
+    }
+    let decor = version.decor().clone();
+    *version = 2.into();
+    *version.decor_mut() = decor;
+
+    // Place the new tables before [[repos]] so the file keeps reading top-down
+    let first_repo_position = doc
+        .get("repos")
+        .and_then(|v| v.as_array_of_tables())
+        .and_then(|a| a.iter().filter_map(|t| t.position()).min())
+        .unwrap_or(usize::MAX);
+
+    for (old_key, table_name, new_key) in V1_TO_V2_KEYS {
+        let Some((key, item)) = doc.remove_entry(old_key) else {
+            continue;
+        };
+        let table = doc
+            .entry(table_name)
+            .or_insert_with(|| {
+                let mut t = toml_edit::Table::new();
+                t.set_position(first_repo_position.saturating_sub(1));
+                toml_edit::Item::Table(t)
+            })
+            .as_table_mut();
+        if let Some(table) = table {
+            let new_key = toml_edit::Key::new(*new_key).with_leaf_decor(key.leaf_decor().clone());
+            table.insert_formatted(&new_key, item);
+        }
+    }
+
+    doc.to_string()
+}
+
+/// Migrate the config file at `config_path` to version 2 in place.
+/// Returns false if it was already version 2.
+pub fn migrate_config_file(config_path: &Path) -> Result<bool, ConfigError> {
+    let contents = std::fs::read_to_string(config_path)?;
+    let raw = parse_raw(&contents)?;
+    match raw.version {
+        Some(1) => {}
+        Some(2) => return Ok(false),
+        other => {
+            return Err(ConfigError::Validation(format!(
+                "Config version must be 1 or 2, got {other:?}"
+            )));
+        }
+    }
+    std::fs::write(config_path, migrate_v1_to_v2(&contents))?;
+    Ok(true)
+}
+
 /// Ensure a config file exists.
 /// If no config is found and `output_dir` is provided, generates one with that output dir.
 /// If no config is found and `output_dir` is `None`, returns an error.
@@ -699,7 +2680,7 @@ pub fn ensure_config_exists(
                     Ok(dest)
                 }
                 None => Err(ConfigError::Validation(
-                    "No config file found. Run 'ulysses-link sync <path> <output-dir>' to get started.".into(),
+                    "No config file found. Run 'ulysses-link sync <path> --output <output-dir>' to get started.".into(),
                 )),
             }
         }
@@ -707,7 +2688,8 @@ pub fn ensure_config_exists(
     }
 }
 
-/// Update the output_dir value in an existing config file.
+/// Update the output_dir value in an existing config file. A relative path
+/// is resolved against the current directory before it is stored.
 pub fn set_output_dir(config_path: &Path, output_dir: &Path) -> Result<(), ConfigError> {
     let contents = std::fs::read_to_string(config_path)?;
     let mut doc = contents
@@ -720,6 +2702,79 @@ pub fn set_output_dir(config_path: &Path, output_dir: &Path) -> Result<(), Confi
     Ok(())
 }
 
+/// Update the log_dir value in an existing config file.
+pub fn set_log_dir(config_path: &Path, log_dir: &Path) -> Result<(), ConfigError> {
+    let contents = std::fs::read_to_string(config_path)?;
+    let mut doc = contents
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| ConfigError::Validation(format!("Failed to parse config: {e}")))?;
+
+    let expanded = expand_path(&log_dir.to_string_lossy())?;
+    doc["log_dir"] = toml_edit::value(expanded.to_string_lossy().as_ref());
+    std::fs::write(config_path, doc.to_string())?;
+    Ok(())
+}
+
+/// Set the per-repo `output_dir` of the repo whose path matches `repo_path`.
+/// Returns false if no such repo is in the config.
+pub fn set_repo_output_dir(
+    config_path: &Path,
+    repo_path: &Path,
+    output_dir: &Path,
+) -> Result<bool, ConfigError> {
+    let canonical = expand_path(&output_dir.to_string_lossy())?;
+    edit_repo_table(config_path, repo_path, |repo| {
+        repo["output_dir"] = toml_edit::value(canonical.to_string_lossy().as_ref());
+    })
+}
+
+/// Set the `name` of the repo at `repo_path`. Returns false if no repo in the
+/// config has that path.
+pub fn set_repo_name(
+    config_path: &Path,
+    repo_path: &Path,
+    name: &str,
+) -> Result<bool, ConfigError> {
+    edit_repo_table(config_path, repo_path, |repo| {
+        repo["name"] = toml_edit::value(name);
+    })
+}
+
+/// Apply `edit` to the `[[repos]]` table whose path resolves to `repo_path`,
+/// preserving the rest of the file. Returns false if there is no such repo.
+fn edit_repo_table(
+    config_path: &Path,
+    repo_path: &Path,
+    edit: impl FnOnce(&mut toml_edit::Table),
+) -> Result<bool, ConfigError> {
+    let contents = std::fs::read_to_string(config_path)?;
+    let mut doc = contents
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| ConfigError::Validation(format!("Failed to parse config: {e}")))?;
+
+    let target = expand_path(&repo_path.to_string_lossy()).ok();
+
+    let Some(repos) = doc
+        .get_mut("repos")
+        .and_then(|v| v.as_array_of_tables_mut())
+    else {
+        return Ok(false);
+    };
+    let Some(repo) = repos.iter_mut().find(|repo| {
+        let existing = repo
+            .get("path")
+            .and_then(|v| v.as_str())
+            .and_then(|p| expand_path(p).ok());
+        existing.is_some() && existing == target
+    }) else {
+        return Ok(false);
+    };
+    edit(repo);
+
+    std::fs::write(config_path, doc.to_string())?;
+    Ok(true)
+}
+
 /// Open a file in the user's preferred editor.
 pub fn open_in_editor(path: &Path) -> Result<(), ConfigError> {
     let editor = std::env::var("EDITOR").or_else(|_| std::env::var("VISUAL"));
@@ -765,6 +2820,49 @@ pub fn open_in_editor(path: &Path) -> Result<(), ConfigError> {
     Ok(())
 }
 
+/// The command that shows `dir` in the platform's file browser, or `None`
+/// where there is none.
+pub fn file_browser_command(dir: &Path) -> Option<std::process::Command> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "linux") {
+        "xdg-open"
+    } else {
+        return None;
+    };
+    let mut command = std::process::Command::new(program);
+    command.arg(dir);
+    Some(command)
+}
+
+/// Show a directory in Finder, Explorer, or the desktop's file manager.
+pub fn open_in_file_browser(dir: &Path) -> Result<(), ConfigError> {
+    if !dir.is_dir() {
+        return Err(ConfigError::Validation(format!(
+            "{} does not exist yet. Run 'ulysses-link sync' first.",
+            dir.display()
+        )));
+    }
+    let mut command = file_browser_command(dir).ok_or_else(|| {
+        ConfigError::Validation("No file browser is known for this platform".into())
+    })?;
+    let status = command
+        .status()
+        .map_err(|e| ConfigError::Validation(format!("Failed to open {}: {e}", dir.display())))?;
+
+    // explorer exits with 1 even when the window opened
+    if !status.success() && !cfg!(target_os = "windows") {
+        return Err(ConfigError::Validation(format!(
+            "File browser exited with an error opening {}",
+            dir.display()
+        )));
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -812,10 +2910,71 @@ mod tests {
     #[test]
     fn test_wrong_version() {
         let tmp = TempDir::new().unwrap();
-        let config_path = write_config(tmp.path(), "version = 2\noutput_dir = \"/tmp/out\"");
+        let config_path = write_config(tmp.path(), "version = 3\noutput_dir = \"/tmp/out\"");
 
         let err = load_config(Some(&config_path)).unwrap_err();
-        assert!(err.to_string().contains("version must be 1"));
+        assert!(err.to_string().contains("version must be 1 or 2"));
+    }
+
+    #[test]
+    fn test_valid_v2_config() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        fs::create_dir(&repo_dir).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 2\noutput_dir = \"{}\"\n\n[sync]\ndebounce_seconds = 1.5\nrescan_interval = \"never\"\ninclude = [\"*.tex\"]\nwrite_output_gitignore = true\n\n[upgrade]\nauto = false\n\n[[repos]]\npath = \"{}\"",
+                output_dir.display(),
+                repo_dir.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.debounce_seconds, 1.5);
+        assert!(matches!(config.rescan_interval, RescanInterval::Never));
+        assert!(!config.auto_upgrade);
+        assert!(config.write_output_gitignore);
+        assert_eq!(config.repos[0].include_patterns, vec!["*.tex"]);
+    }
+
+    #[test]
+    fn test_migrate_v1_to_v2() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        fs::create_dir(&repo_dir).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let v1 = format!(
+            "# my config\nversion = 1\noutput_dir = \"{}\"\n\n# slower machines need more\ndebounce_seconds = 2.0\nauto_upgrade = false\nglobal_include = [\"*.tex\"]\n\n[[repos]]\npath = \"{}\"\n",
+            output_dir.display(),
+            repo_dir.display()
+        );
+        let v2 = migrate_v1_to_v2(&v1);
+
+        assert!(v2.contains("# my config\nversion = 2"));
+        assert!(v2.contains("[sync]\ninclude = [\"*.tex\"]"));
+        assert!(v2.contains("# slower machines need more\ndebounce_seconds = 2.0"));
+        assert!(v2.contains("[upgrade]\nauto = false"));
+        assert!(!v2.contains("global_include"));
+        assert!(v2.find("[sync]").unwrap() < v2.find("[[repos]]").unwrap());
+        assert_eq!(migrate_v1_to_v2(&v2), v2);
+
+        let config_path = write_config(tmp.path(), &v1);
+        let before = load_config(Some(&config_path)).unwrap();
+        assert!(migrate_config_file(&config_path).unwrap());
+        assert!(!migrate_config_file(&config_path).unwrap());
+        let after = load_config(Some(&config_path)).unwrap();
+
+        assert_eq!(after.debounce_seconds, before.debounce_seconds);
+        assert_eq!(after.auto_upgrade, before.auto_upgrade);
+        assert_eq!(after.output_dir, before.output_dir);
+        assert_eq!(
+            after.repos[0].include_patterns,
+            before.repos[0].include_patterns
+        );
     }
 
     #[test]
@@ -827,6 +2986,526 @@ mod tests {
         assert!(err.to_string().contains("output_dir"));
     }
 
+    #[test]
+    fn test_base_cache_mode() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |mode: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 2\noutput_dir = \"{}\"\n\n[sync]\nbase_cache = \"{mode}\"\nbase_cache_compress = true\n\n[[repos]]\npath = \"{}\"",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("content");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.state.base_cache, BaseCacheMode::Content);
+        assert!(config.state.base_cache_compress);
+
+        write("blobs");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'base_cache' must be"));
+    }
+
+    #[test]
+    fn test_manifest_backend() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |line: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\n{line}\n\n[[repos]]\npath = \"{}\"",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.state.manifest_backend, ManifestBackend::Toml);
+
+        write("manifest_backend = \"log\"");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.state.manifest_backend, ManifestBackend::Log);
+
+        write("manifest_backend = \"sled\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'manifest_backend' must be"));
+    }
+
+    #[test]
+    fn test_name_from_git() {
+        let tmp = TempDir::new().unwrap();
+        let cloned = tmp.path().join("checkout");
+        fs::create_dir_all(cloned.join(".git")).unwrap();
+        fs::write(
+            cloned.join(".git/config"),
+            "[core]\n\tbare = false\n[remote \"upstream\"]\n\turl = https://github.com/other/fork.git\n[remote \"origin\"]\n\turl = git@github.com:LogicWolfe/ulysses-link.git\n\tfetch = +refs/heads/*:refs/remotes/origin/*\n",
+        )
+        .unwrap();
+        let plain = tmp.path().join("plain");
+        fs::create_dir(&plain).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |name_from: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\nname_from = \"{name_from}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                    tmp.path().join("out").display(),
+                    cloned.display(),
+                    plain.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("git");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.repos[0].name, "LogicWolfe-ulysses-link");
+        assert_eq!(config.repos[0].mirror_subdir, "LogicWolfe-ulysses-link");
+        assert_eq!(config.repos[1].name, "plain");
+
+        write("basename");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.repos[0].name, "checkout");
+
+        write("remote");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'name_from' must be"));
+    }
+
+    #[test]
+    fn test_output_dir_branch_template() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/release/2.0\n").unwrap();
+        let plain = tmp.path().join("plain");
+        fs::create_dir(&plain).unwrap();
+        let out = tmp.path().join("out");
+        let config_file = tmp.path().join("config.toml");
+        let write = |repos: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}/{{branch}}\"\n{repos}",
+                    out.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write(&format!(
+            "\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"{}\"",
+            repo.display(),
+            plain.display(),
+            tmp.path().join("plain-out").display()
+        ));
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.output_dir, out.canonicalize().unwrap());
+        assert_eq!(config.repos[0].branch.as_deref(), Some("release/2.0"));
+        assert_eq!(
+            config.repos[0].output_dir,
+            out.join("release/2.0").canonicalize().unwrap()
+        );
+        // A repo with its own absolute output_dir needs no branch
+        assert_eq!(config.repos[1].branch, None);
+
+        // Detached HEAD resolves like `git rev-parse --abbrev-ref HEAD`
+        fs::write(repo.join(".git/HEAD"), "0123456789abcdef\n").unwrap();
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.repos[0].output_dir, out.join("HEAD"));
+
+        write(&format!("\n[[repos]]\npath = \"{}\"", plain.display()));
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("is not a git repo"), "{err}");
+    }
+
+    #[test]
+    fn test_name_from_remote_url() {
+        for url in [
+            "https://github.com/owner/name.git",
+            "https://github.com/owner/name/",
+            "ssh://git@github.com:22/owner/name",
+            "git@github.com:owner/name.git",
+        ] {
+            assert_eq!(
+                name_from_remote_url(url).as_deref(),
+                Some("owner-name"),
+                "{url}"
+            );
+        }
+        assert_eq!(name_from_remote_url("https://example.com/"), None);
+    }
+
+    #[test]
+    fn test_state_dir_inside_output_dir_rejected() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let output = tmp.path().join("out");
+        let config_file = tmp.path().join("config.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\nstate_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                output.display(),
+                output.join("state").display(),
+                repo.display()
+            ),
+        )
+        .unwrap();
+
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("state_dir"));
+        assert!(err.to_string().contains("inside output_dir"));
+    }
+
+    #[test]
+    fn test_state_dir_through_symlink_into_repo_rejected() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        // The state dir doesn't exist yet; it resolves through the link
+        let repo_link = tmp.path().join("repo-link");
+        std::os::unix::fs::symlink(&repo, &repo_link).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\nstate_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                tmp.path().join("out").display(),
+                repo_link.join("sync-state").display(),
+                repo.display()
+            ),
+        )
+        .unwrap();
+
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("is inside repo"), "{err}");
+    }
+
+    #[test]
+    fn test_load_config_without_dirs_leaves_output_dirs_missing() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let output = tmp.path().join("out");
+        let config_file = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"{}\"\nextra_outputs = [\"extra\"]",
+                output.display(),
+                repo.display(),
+                tmp.path().join("repo-out").display()
+            ),
+        );
+
+        let config = load_config_without_dirs(Some(&config_file)).unwrap();
+        assert!(!output.exists());
+        assert!(!tmp.path().join("repo-out").exists());
+        assert!(!output.join("extra").exists());
+
+        // Missing dirs resolve as they will once created
+        let created = load_config(Some(&config_file)).unwrap();
+        assert!(output.join("extra").is_dir());
+        assert_eq!(config.output_dir, created.output_dir);
+        assert_eq!(config.repos[0].output_dir, created.repos[0].output_dir);
+        assert_eq!(
+            config.repos[0].extra_outputs,
+            created.repos[0].extra_outputs
+        );
+    }
+
+    #[test]
+    fn test_flatten_separator() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |extra: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n{extra}",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("flatten = true");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(
+            config.repos[0].flatten.as_deref(),
+            Some(DEFAULT_FLATTEN_SEPARATOR)
+        );
+        assert_eq!(
+            config.repos[0].mirror_key("docs/api/auth.md", &Manifest::empty()),
+            "repo/docs - api - auth.md"
+        );
+
+        write("flatten = true\nflatten_separator = \"_\"");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(
+            config.repos[0].mirror_key("docs/auth.md", &Manifest::empty()),
+            "repo/docs_auth.md"
+        );
+
+        write("flatten = true\nflatten_separator = \"a/b\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("cannot contain '/'"));
+
+        write("flatten_separator = \"_\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("requires 'flatten = true'"));
+
+        write("flat_inbox = true");
+        let config = load_config(Some(&config_file)).unwrap();
+        let repo = &config.repos[0];
+        assert!(repo.flat_inbox);
+        assert_eq!(repo.flatten.as_deref(), Some(DEFAULT_FLATTEN_SEPARATOR));
+        assert_eq!(
+            config.state.inboxes,
+            vec![Inbox {
+                mirror_root: repo.mirror_root(),
+                repo_path: repo.path.clone(),
+            }]
+        );
+
+        write("read_only_source = true");
+        let config = load_config(Some(&config_file)).unwrap();
+        let repo = &config.repos[0];
+        assert!(repo.read_only_source);
+        assert_eq!(repo.direction, SyncDirection::SourceToMirror);
+        assert_eq!(config.state.read_only_roots, vec![repo.mirror_root()]);
+
+        write(&format!(
+            "extra_outputs = [\"{}\"]",
+            tmp.path().join("backup").display()
+        ));
+        let config = load_config(Some(&config_file)).unwrap();
+        let repo = &config.repos[0];
+        let backup = tmp.path().canonicalize().unwrap().join("backup");
+        assert_eq!(repo.extra_outputs, vec![backup.clone()]);
+        assert_eq!(config.active_output_dirs().len(), 2);
+        let extra = &repo.extra_output_repos()[0];
+        assert_eq!(extra.direction, SyncDirection::SourceToMirror);
+        assert_eq!(config.state.read_only_roots, vec![extra.mirror_root()]);
+
+        write(&format!(
+            "extra_outputs = [\"{}\"]",
+            repo.output_dir.display()
+        ));
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'extra_outputs'"));
+
+        write("flat_inbox = true\nflatten = false");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'flat_inbox'"));
+
+        write("flatten = true\nmirror_empty_dirs = true");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'mirror_empty_dirs'"));
+
+        write("output_format = \"ulysses\"");
+        let config = load_config(Some(&config_file)).unwrap();
+        let repo = &config.repos[0];
+        assert_eq!(repo.output_format, OutputFormat::Ulysses);
+        assert_eq!(
+            config.state.keyword_trees,
+            vec![KeywordTree {
+                output_dir: repo.output_dir.clone(),
+                mirror_root: repo.mirror_root(),
+                source_root: repo.source_root(),
+            }]
+        );
+
+        write("flatten = true\noutput_format = \"ulysses\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("'output_format"));
+
+        write("output_format = \"html\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("must be \"plain\" or \"ulysses\""));
+    }
+
+    #[test]
+    fn test_repo_root() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |extra: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n{extra}",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("root = \"./docs/\"");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.repos[0].root.as_deref(), Some("docs"));
+        assert_eq!(config.repos[0].source_root(), repo.join("docs"));
+        assert_eq!(
+            config.repos[0].mirror_key("docs/sub/guide.md", &Manifest::empty()),
+            "repo/sub/guide.md"
+        );
+
+        write("root = \"missing\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        write("root = \"../elsewhere\"");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("without '..'"));
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |depth: u32| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\nmax_depth = {depth}\n\n[[repos]]\npath = \"{}\"",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write(3);
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.max_depth, Some(3));
+        assert_eq!(config.repos[0].max_depth, Some(3));
+
+        write(0);
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("max_depth"));
+    }
+
+    #[test]
+    fn test_stats_interval() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |seconds: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\nstats_interval = {seconds}\n\n[[repos]]\npath = \"{}\"",
+                    tmp.path().join("out").display(),
+                    repo.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("600");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(config.stats_interval, Some(Duration::from_secs(600)));
+
+        for bad in ["0", "-5", "1e300", "inf"] {
+            write(bad);
+            let err = load_config(Some(&config_file)).unwrap_err();
+            assert!(err.to_string().contains("stats_interval"), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_mirror_poll_interval() {
+        let tmp = TempDir::new().unwrap();
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        fs::create_dir(&a).unwrap();
+        fs::create_dir(&b).unwrap();
+        let out = tmp.path().join("out");
+        let config_file = tmp.path().join("config.toml");
+        let write = |global: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\nmirror_poll_interval = {global}\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_poll_interval = 0.5",
+                    out.display(),
+                    a.display(),
+                    b.display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("2");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert_eq!(
+            config.repos[0].mirror_poll_interval,
+            Some(Duration::from_secs(2))
+        );
+        // The shortest interval among repos sharing the output dir wins
+        assert_eq!(
+            config.mirror_poll_interval(&config.output_dir),
+            Some(Duration::from_millis(500))
+        );
+
+        for bad in ["0", "1e300", "1e-300", "inf"] {
+            write(bad);
+            let err = load_config(Some(&config_file)).unwrap_err();
+            assert!(err.to_string().contains("mirror_poll_interval"), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_protect_patterns() {
+        let tmp = TempDir::new().unwrap();
+        let config_file = tmp.path().join("config.toml");
+        let write = |extra: &str| {
+            fs::write(
+                &config_file,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\n{extra}",
+                    tmp.path().join("out").display()
+                ),
+            )
+            .unwrap();
+        };
+
+        write("");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert!(config.state.protect.is_match("repo/.Ulysses-Group.plist"));
+        assert!(config.state.protect.is_match("repo/docs/Info.plist"));
+        assert!(!config.state.protect.is_match("repo/README.md"));
+
+        write("protect = []");
+        let config = load_config(Some(&config_file)).unwrap();
+        assert!(!config.state.protect.is_match("repo/.Ulysses-Group.plist"));
+
+        write("protect = [\"notes/[\"]");
+        let err = load_config(Some(&config_file)).unwrap_err();
+        assert!(err.to_string().contains("protect"));
+    }
+
     #[test]
     fn test_debounce_out_of_range() {
         let tmp = TempDir::new().unwrap();
@@ -862,26 +3541,178 @@ mod tests {
     #[test]
     fn test_repo_name_deduplication() {
         let tmp = TempDir::new().unwrap();
-        let repo1 = tmp.path().join("repos").join("project");
-        let repo2 = tmp.path().join("other").join("project");
-        fs::create_dir_all(&repo1).unwrap();
-        fs::create_dir_all(&repo2).unwrap();
+        let repo1 = tmp.path().join("repos").join("project");
+        let repo2 = tmp.path().join("other").join("project");
+        fs::create_dir_all(&repo1).unwrap();
+        fs::create_dir_all(&repo2).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                output_dir.display(),
+                repo1.display(),
+                repo2.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.repos.len(), 2);
+        assert_eq!(config.repos[0].name, "project");
+        assert_eq!(config.repos[1].name, "project-2");
+    }
+
+    #[test]
+    fn test_repo_name_disambiguate_by_path() {
+        let tmp = TempDir::new().unwrap();
+        let work = tmp.path().join("code").join("Work").join("project");
+        let personal = tmp.path().join("code").join("personal").join("project");
+        let nested = tmp
+            .path()
+            .join("code")
+            .join("personal")
+            .join("old")
+            .join("project");
+        for dir in [&work, &personal, &nested] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        let output_dir = tmp.path().join("output");
+
+        let load = |disambiguate: &str, repos: &[&PathBuf]| {
+            let mut contents = format!(
+                "version = 1\noutput_dir = \"{}\"\ndisambiguate = \"{disambiguate}\"",
+                output_dir.display()
+            );
+            for repo in repos {
+                contents.push_str(&format!("\n\n[[repos]]\npath = \"{}\"", repo.display()));
+            }
+            let config_path = write_config(tmp.path(), &contents);
+            let config = load_config(Some(&config_path))?;
+            Ok::<_, ConfigError>(config.repos.into_iter().map(|r| r.name).collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            load("path", &[&work, &personal]).unwrap(),
+            ["project", "project (personal)"]
+        );
+        // The first repo keeps its name when a colliding one is added
+        assert_eq!(load("path", &[&personal]).unwrap(), ["project"]);
+        assert_eq!(
+            load("path", &[&personal, &work]).unwrap(),
+            ["project", "project (work)"]
+        );
+        assert_eq!(
+            load("path", &[&work, &personal, &nested]).unwrap(),
+            ["project", "project (personal)", "project (old)"]
+        );
+        assert_eq!(
+            load("suffix", &[&work, &personal]).unwrap(),
+            ["project", "project-2"]
+        );
+        let err = load("order", &[&work]).unwrap_err();
+        assert!(err.to_string().contains("'disambiguate' must be"));
+    }
+
+    #[test]
+    fn test_disabled_repo_not_active() {
+        let tmp = TempDir::new().unwrap();
+        let repo1 = tmp.path().join("repo1");
+        let repo2 = tmp.path().join("repo2");
+        fs::create_dir(&repo1).unwrap();
+        fs::create_dir(&repo2).unwrap();
+        let output_dir = tmp.path().join("output");
+        let parked_dir = tmp.path().join("parked");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"{}\"\nenabled = false",
+                output_dir.display(),
+                repo1.display(),
+                repo2.display(),
+                parked_dir.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert!(config.repos[0].enabled);
+        assert!(!config.repos[1].enabled);
+        assert_eq!(config.enabled_repos().count(), 1);
+        assert_eq!(config.active_output_dirs(), vec![config.output_dir.clone()]);
+    }
+
+    #[test]
+    fn test_mirror_subdir() {
+        let tmp = TempDir::new().unwrap();
+        let repo1 = tmp.path().join("api");
+        let repo2 = tmp.path().join("blog");
+        fs::create_dir(&repo1).unwrap();
+        fs::create_dir(&repo2).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"./work/api/\"\n\n[[repos]]\npath = \"{}\"",
+                output_dir.display(),
+                repo1.display(),
+                repo2.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.repos[0].mirror_subdir, "work/api");
+        assert_eq!(
+            config.repos[0].mirror_root(),
+            config.output_dir.join("work").join("api")
+        );
+        assert_eq!(config.repos[1].mirror_subdir, "blog");
+    }
+
+    #[test]
+    fn test_mirror_subdir_escaping_output_dir() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        fs::create_dir(&repo_dir).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        for bad in ["../elsewhere", "/abs/path", ".ulysses-link.d/x", ""] {
+            let config_path = write_config(
+                tmp.path(),
+                &format!(
+                    "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"{}\"",
+                    output_dir.display(),
+                    repo_dir.display(),
+                    bad
+                ),
+            );
+            let err = load_config(Some(&config_path)).unwrap_err();
+            assert!(err.to_string().contains("mirror_subdir"), "{bad}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_mirror_subdirs_overlap() {
+        let tmp = TempDir::new().unwrap();
+        let repo1 = tmp.path().join("repo1");
+        let repo2 = tmp.path().join("repo2");
+        fs::create_dir(&repo1).unwrap();
+        fs::create_dir(&repo2).unwrap();
         let output_dir = tmp.path().join("output");
 
         let config_path = write_config(
             tmp.path(),
             &format!(
-                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"work\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"work/api\"",
                 output_dir.display(),
                 repo1.display(),
                 repo2.display()
             ),
         );
 
-        let config = load_config(Some(&config_path)).unwrap();
-        assert_eq!(config.repos.len(), 2);
-        assert_eq!(config.repos[0].name, "project");
-        assert_eq!(config.repos[1].name, "project-2");
+        let err = load_config(Some(&config_path)).unwrap_err();
+        assert!(err.to_string().contains("overlap"));
     }
 
     #[test]
@@ -967,6 +3798,135 @@ mod tests {
             .contains(&"*.rst".to_string()));
     }
 
+    #[test]
+    fn test_pattern_tables() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        fs::create_dir(&repo_dir).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                r#"version = 1
+output_dir = "{}"
+global_include = ["*.md"]
+
+[[repos]]
+path = "{}"
+
+[[repos.include]]
+pattern = "*.tex"
+comment = "Thesis chapters"
+
+[[repos.include]]
+pattern = "*.rst"
+comment = "Old Sphinx docs, moved to markdown"
+enabled = false
+
+[[repos.exclude]]
+pattern = "drafts/"
+enabled = true
+"#,
+                output_dir.display(),
+                repo_dir.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        let repo = &config.repos[0];
+        assert_eq!(repo.include_patterns, vec!["*.md", "*.tex"]);
+        assert!(repo.exclude_patterns.contains(&"drafts/".to_string()));
+        assert!(crate::matcher::should_mirror(
+            "thesis.tex",
+            &repo.exclude,
+            &repo.include
+        ));
+        assert!(!crate::matcher::should_mirror(
+            "old.rst",
+            &repo.exclude,
+            &repo.include
+        ));
+        assert!(!crate::matcher::should_mirror(
+            "drafts/idea.md",
+            &repo.exclude,
+            &repo.include
+        ));
+
+        // Bare strings and tables can be mixed in one array
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\nglobal_include = [\"*.md\"]\n\n[[repos]]\npath = \"{}\"\ninclude = [\"*.tex\", {{ pattern = \"*.rst\", enabled = false }}]",
+                output_dir.display(),
+                repo_dir.display()
+            ),
+        );
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.repos[0].include_patterns, vec!["*.md", "*.tex"]);
+
+        // Misspelled keys are named rather than ignored or reported as a
+        // mismatch with every form
+        for (table, key) in [
+            ("{ patern = \"*.rst\" }", "patern"),
+            ("{ pattern = \"*.rst\", enable = false }", "enable"),
+        ] {
+            let config_path = write_config(
+                tmp.path(),
+                &format!(
+                    "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\ninclude = [{table}]",
+                    output_dir.display(),
+                    repo_dir.display()
+                ),
+            );
+            let err = load_config(Some(&config_path)).unwrap_err().to_string();
+            assert!(err.contains(&format!("unknown field `{key}`")), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_pattern_files_merged() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        fs::create_dir(&repo_dir).unwrap();
+        let output_dir = tmp.path().join("output");
+        let include_file = tmp.path().join("includes");
+        fs::write(&include_file, "# extra formats\n*.tex\n\n").unwrap();
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\ninclude_from = [\"{}\"]",
+                output_dir.display(),
+                repo_dir.display(),
+                include_file.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        let patterns = &config.repos[0].include_patterns;
+        assert!(patterns.contains(&"*.tex".to_string()));
+        assert!(!patterns.iter().any(|p| p.starts_with('#') || p.is_empty()));
+    }
+
+    #[test]
+    fn test_missing_pattern_file() {
+        let tmp = TempDir::new().unwrap();
+        let output_dir = tmp.path().join("output");
+        let missing = tmp.path().join("no-such-ignore");
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\nglobal_exclude_from = [\"{}\"]",
+                output_dir.display(),
+                missing.display()
+            ),
+        );
+
+        let err = load_config(Some(&config_path)).unwrap_err();
+        assert!(err.to_string().contains("no-such-ignore"));
+    }
+
     #[test]
     fn test_generate_default_config() {
         let tmp = TempDir::new().unwrap();
@@ -981,27 +3941,162 @@ mod tests {
         assert!(content.contains(&output_dir.to_string_lossy().to_string()));
     }
 
+    #[test]
+    fn test_full_config_template_parses() {
+        let tmp = TempDir::new().unwrap();
+        let output_dir = tmp.path().join("output");
+        let template = config_template(true);
+        let config_path = write_config(
+            tmp.path(),
+            &template.replace("~/ulysses-link", &output_dir.to_string_lossy()),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.output_dir, fs::canonicalize(&output_dir).unwrap());
+        assert_eq!(config.debounce_seconds, DEFAULT_DEBOUNCE_SECONDS);
+        assert_eq!(config.max_batch, DEFAULT_MAX_BATCH);
+        // Options set in the template are set to their defaults
+        fs::write(
+            &config_path,
+            format!("version = 2\noutput_dir = \"{}\"\n", output_dir.display()),
+        )
+        .unwrap();
+        let defaults = load_config(Some(&config_path)).unwrap();
+        assert_eq!(format!("{config:?}"), format!("{defaults:?}"));
+        // Every option appears, set or commented out
+        for (_, _, key) in V1_TO_V2_KEYS {
+            assert!(
+                template.contains(&format!("\n{key} = "))
+                    || template.contains(&format!("\n# {key} = ")),
+                "{key} missing from the full template"
+            );
+        }
+        assert!(config_template(false).contains("version = 1"));
+    }
+
+    #[test]
+    fn test_read_config_from_stdin() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let toml = format!(
+            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+            tmp.path().join("output").display(),
+            repo.display()
+        );
+
+        let config = read_config(toml.as_bytes()).unwrap();
+        assert_eq!(config.repos.len(), 1);
+        assert_eq!(config.repos[0].name, "repo");
+        assert!(config.config_path.is_none());
+
+        // Commands that edit the config need a real file
+        let err = find_config_path(Some(Path::new(STDIN_CONFIG))).unwrap_err();
+        assert!(err.to_string().contains("stdin"));
+    }
+
     #[test]
     fn test_explicit_config_not_found() {
         let err = find_config_path(Some(Path::new("/nonexistent/config.toml"))).unwrap_err();
         assert!(matches!(err, ConfigError::FileNotFound(_)));
     }
 
+    /// Serializes tests that modify process environment variables.
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
     #[test]
     fn test_no_config_found() {
+        let _guard = ENV_LOCK.lock().unwrap();
         let tmp = TempDir::new().unwrap();
         // Override HOME so default_config_path won't find a real config
         let orig_home = std::env::var("HOME").ok();
+        let orig_xdg = std::env::var_os("XDG_CONFIG_HOME");
         std::env::set_var("HOME", tmp.path());
+        std::env::remove_var("XDG_CONFIG_HOME");
 
         let err = find_config_path(None);
 
         if let Some(h) = orig_home {
             std::env::set_var("HOME", h);
         }
+        if let Some(x) = orig_xdg {
+            std::env::set_var("XDG_CONFIG_HOME", x);
+        }
         assert!(matches!(err, Err(ConfigError::NoConfigFound)));
     }
 
+    #[test]
+    fn test_config_env_var() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let tmp = TempDir::new().unwrap();
+        let config_path = write_config(tmp.path(), "version = 1");
+        let explicit = tmp.path().join("explicit.toml");
+        fs::write(&explicit, "version = 1").unwrap();
+
+        std::env::set_var(CONFIG_ENV_VAR, &config_path);
+        let found = find_config_path(None);
+        let explicit_found = find_config_path(Some(&explicit));
+        std::env::set_var(CONFIG_ENV_VAR, tmp.path().join("missing.toml"));
+        let missing = find_config_path(None);
+        std::env::remove_var(CONFIG_ENV_VAR);
+
+        assert_eq!(found.unwrap(), fs::canonicalize(&config_path).unwrap());
+        assert_eq!(
+            explicit_found.unwrap(),
+            fs::canonicalize(&explicit).unwrap()
+        );
+        assert!(matches!(missing, Err(ConfigError::FileNotFound(_))));
+    }
+
+    #[test]
+    fn test_xdg_config_home() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let tmp = TempDir::new().unwrap();
+        let orig_xdg = std::env::var_os("XDG_CONFIG_HOME");
+        std::env::set_var("XDG_CONFIG_HOME", tmp.path());
+
+        let path = default_config_path();
+
+        match orig_xdg {
+            Some(x) => std::env::set_var("XDG_CONFIG_HOME", x),
+            None => std::env::remove_var("XDG_CONFIG_HOME"),
+        }
+        assert_eq!(path, tmp.path().join("ulysses-link").join("config.toml"));
+    }
+
+    #[test]
+    fn test_set_repo_output_dir() {
+        let tmp = TempDir::new().unwrap();
+        let repo1 = tmp.path().join("repo1");
+        let repo2 = tmp.path().join("repo2");
+        fs::create_dir(&repo1).unwrap();
+        fs::create_dir(&repo2).unwrap();
+        let output_dir = tmp.path().join("output");
+        let work_dir = tmp.path().join("work");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n# keep me\n[[repos]]\npath = \"{}\"",
+                output_dir.display(),
+                repo1.display(),
+                repo2.display()
+            ),
+        );
+
+        assert!(set_repo_output_dir(&config_path, &repo2, &work_dir).unwrap());
+        assert!(!set_repo_output_dir(&config_path, &tmp.path().join("nope"), &work_dir).unwrap());
+
+        let content = fs::read_to_string(&config_path).unwrap();
+        assert!(content.contains("# keep me"));
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.repos[0].output_dir, config.output_dir);
+        assert_eq!(
+            config.repos[1].output_dir,
+            fs::canonicalize(&work_dir).unwrap()
+        );
+    }
+
     #[test]
     fn test_add_repo() {
         let tmp = TempDir::new().unwrap();
@@ -1074,6 +4169,30 @@ mod tests {
         assert_eq!(config.repos.len(), 0);
     }
 
+    #[test]
+    fn test_add_repo_through_symlink_keeps_link_name() {
+        let tmp = TempDir::new().unwrap();
+        let real = tmp.path().join("real-notes");
+        fs::create_dir(&real).unwrap();
+        let link = tmp.path().join("notes");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"",
+                tmp.path().join("output").display()
+            ),
+        );
+
+        assert!(add_repo(&config_path, &link).unwrap());
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.repos[0].name, "notes");
+        assert_eq!(config.repos[0].path, fs::canonicalize(&real).unwrap());
+
+        // Already configured under its real path
+        assert!(!add_repo(&config_path, &real).unwrap());
+    }
+
     #[test]
     fn test_remove_nonexistent_repo() {
         let tmp = TempDir::new().unwrap();
@@ -1280,6 +4399,88 @@ mod tests {
             PathBuf::from(&stored).is_absolute(),
             "Stored output_dir should be absolute, got: {stored}"
         );
+
+        // A relative --output is anchored to the current directory
+        set_output_dir(&config_path, Path::new("relative-output")).unwrap();
+        let content = fs::read_to_string(&config_path).unwrap();
+        let doc: toml_edit::DocumentMut = content.parse().unwrap();
+        let stored = PathBuf::from(doc["output_dir"].as_str().unwrap());
+        assert!(stored.is_absolute());
+        assert_eq!(
+            stored,
+            std::env::current_dir().unwrap().join("relative-output")
+        );
+    }
+
+    #[test]
+    fn test_lint_patterns() {
+        let strings =
+            |patterns: &[&str]| -> Vec<String> { patterns.iter().map(|p| p.to_string()).collect() };
+
+        let hints = lint_patterns(&strings(&["*.md", "docs/"]), &strings(&["target/"]));
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("'docs/**'"));
+
+        assert!(lint_patterns(&strings(&["*.md", "docs/**"]), &strings(&["target/"])).is_empty());
+
+        let hints = lint_patterns(&strings(&["*.md", "*.txt"]), &strings(&["**/*.txt"]));
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("'*.txt'"));
+    }
+
+    #[test]
+    fn test_pattern_warnings_returned_with_config() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir(&repo).unwrap();
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\ninclude = [\"docs/\"]",
+                tmp.path().join("out").display(),
+                repo.display()
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.pattern_warnings.len(), 1);
+        assert!(config.pattern_warnings[0].starts_with("Repo 'repo': "));
+        assert!(config.pattern_warnings[0].contains("'docs/**'"));
+    }
+
+    #[test]
+    fn test_file_browser_command() {
+        let dir = Path::new("/tmp/mirror");
+        let command = file_browser_command(dir).unwrap();
+        let expected = if cfg!(target_os = "macos") {
+            "open"
+        } else if cfg!(target_os = "windows") {
+            "explorer"
+        } else {
+            "xdg-open"
+        };
+        assert_eq!(command.get_program(), expected);
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec![dir.as_os_str()]
+        );
+    }
+
+    #[test]
+    fn test_set_log_dir_round_trips() {
+        let tmp = TempDir::new().unwrap();
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"",
+                tmp.path().join("output").display()
+            ),
+        );
+        assert_eq!(load_config(Some(&config_path)).unwrap().log_dir, None);
+
+        set_log_dir(&config_path, &tmp.path().join("logs")).unwrap();
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(config.log_dir, Some(tmp.path().join("logs")));
     }
 
     #[test]
@@ -1306,6 +4507,47 @@ mod tests {
         assert_eq!(config.repos[0].output_dir, expected);
     }
 
+    #[test]
+    fn test_relative_per_repo_output_dir_resolves_under_global() {
+        let tmp = TempDir::new().unwrap();
+        let repo_dir = tmp.path().join("my-repo");
+        let other_dir = tmp.path().join("other");
+        fs::create_dir(&repo_dir).unwrap();
+        fs::create_dir(&other_dir).unwrap();
+        let global_output = tmp.path().join("global-output");
+
+        // Another repo still mirrors into the global output_dir around it
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"archive/old-stuff\"\n\n[[repos]]\npath = \"{}\"",
+                global_output.display(),
+                repo_dir.display(),
+                other_dir.display(),
+            ),
+        );
+
+        let config = load_config(Some(&config_path)).unwrap();
+        assert_eq!(
+            config.repos[0].output_dir,
+            config.output_dir.join("archive/old-stuff")
+        );
+        assert_eq!(config.repos[1].output_dir, config.output_dir);
+
+        // Its mirror still can't overlap another repo's
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"other\"\n\n[[repos]]\npath = \"{}\"",
+                global_output.display(),
+                repo_dir.display(),
+                other_dir.display(),
+            ),
+        );
+        let err = load_config(Some(&config_path)).unwrap_err();
+        assert!(err.to_string().contains("overlap"), "{err}");
+    }
+
     #[test]
     fn test_per_repo_output_dir_falls_back_to_global() {
         let tmp = TempDir::new().unwrap();
@@ -1377,6 +4619,30 @@ mod tests {
         assert!(err.to_string().contains("inside output_dir"));
     }
 
+    #[test]
+    fn test_nesting_repo_inside_repo() {
+        let tmp = TempDir::new().unwrap();
+        let outer = tmp.path().join("code");
+        let inner = outer.join("project");
+        fs::create_dir_all(&inner).unwrap();
+        let output_dir = tmp.path().join("output");
+
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}/../code/project\"",
+                output_dir.display(),
+                outer.display(),
+                outer.display()
+            ),
+        );
+
+        let err = load_config(Some(&config_path)).unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("repo 'project'"), "{msg}");
+        assert!(msg.contains("is inside repo 'code'"), "{msg}");
+    }
+
     #[test]
     fn test_nesting_output_dir_inside_repo() {
         let tmp = TempDir::new().unwrap();
@@ -1406,8 +4672,9 @@ mod tests {
         let repo2 = tmp.path().join("repo2");
         fs::create_dir(&repo1).unwrap();
         fs::create_dir(&repo2).unwrap();
-        let output1 = tmp.path().join("output");
-        let output2 = output1.join("nested");
+        // A repo's own output dir may sit inside the global one, not around it
+        let output2 = tmp.path().join("output");
+        let output1 = output2.join("nested");
 
         let config_path = write_config(
             tmp.path(),
@@ -1425,19 +4692,59 @@ mod tests {
     }
 
     #[test]
-    fn test_nesting_overlapping_mirror_dirs() {
+    fn test_nesting_same_mirror_dir() {
         let tmp = TempDir::new().unwrap();
-        let repo1 = tmp.path().join("repo1");
-        let repo2 = tmp.path().join("repo2");
-        fs::create_dir(&repo1).unwrap();
-        fs::create_dir(&repo2).unwrap();
+        let output_dir = tmp.path().join("output");
+        let repos: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|n| {
+                let repo = tmp.path().join(n);
+                fs::create_dir(&repo).unwrap();
+                repo
+            })
+            .collect();
+
+        // The second `docs` skips `docs-2`, which the third asked for, and
+        // `Docs` collides with `docs` case-insensitively
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nname = \"docs\"\n\n[[repos]]\npath = \"{}\"\nname = \"Docs\"\n\n[[repos]]\npath = \"{}\"\nname = \"docs-2\"",
+                output_dir.display(),
+                repos[0].display(),
+                repos[1].display(),
+                repos[2].display()
+            ),
+        );
+        let config = load_config(Some(&config_path)).unwrap();
+        let names: Vec<&str> = config.repos.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["docs", "Docs-3", "docs-2"]);
+
+        // Same per-repo output_dir and mirror_subdir
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"shared\"\nmirror_subdir = \"notes\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"shared\"\nmirror_subdir = \"notes\"",
+                output_dir.display(),
+                repos[0].display(),
+                repos[1].display()
+            ),
+        );
+        let err = load_config(Some(&config_path)).unwrap_err().to_string();
+        assert!(err.contains("both mirror into"), "{err}");
+        assert!(err.contains("'a'") && err.contains("'b'"), "{err}");
 
-        // repo1 goes to output/repo1, repo2 with name "repo1" suffix would overlap
-        // We need repos with the same name going to different output_dirs that produce
-        // overlapping mirror paths. Since names are deduped, the overlap would be
-        // output_dir_A/name == output_dir_B/name. That requires same name + same output_dir,
-        // which is the same path and thus not overlapping in the nesting sense.
-        // The realistic case: output_dir_A == output_dir_B/reponame
-        // Already covered by nested output_dirs test.
+        // Subdirs differing only in case are one directory on macOS
+        let config_path = write_config(
+            tmp.path(),
+            &format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"Notes\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"notes\"",
+                output_dir.display(),
+                repos[0].display(),
+                repos[1].display()
+            ),
+        );
+        let err = load_config(Some(&config_path)).unwrap_err().to_string();
+        assert!(err.contains("both mirror into"), "{err}");
     }
 }
@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use diffy::{DiffOptions, Line};
+
+use crate::config::RepoConfig;
+use crate::linker;
+use crate::manifest::Manifest;
+
+/// How a tracked file's source and mirror differ.
+#[derive(Debug, Clone)]
+pub struct FileDiff {
+    /// Manifest key, relative to the output dir
+    pub rel_path: String,
+    pub source: PathBuf,
+    pub mirror: PathBuf,
+    /// Lines only in the mirror
+    pub insertions: usize,
+    /// Lines only in the source
+    pub deletions: usize,
+    /// Unified diff from source to mirror; `None` when either side isn't UTF-8
+    pub patch: Option<String>,
+}
+
+/// Diff every tracked file of `repo` whose source and mirror content differ,
+/// sorted by manifest key. `only` narrows it to one file, given as its
+/// repo-relative source path or its manifest key. Files missing a side are
+/// left to `verify`. Only reads files.
+pub fn diff_repo(
+    repo: &RepoConfig,
+    manifest: &Manifest,
+    only: Option<&str>,
+) -> Result<Vec<FileDiff>> {
+    let mut entries = manifest.entries_for_repo(&repo.mirror_subdir);
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut diffs = Vec::new();
+    for (rel_path, entry) in entries {
+        if let Some(only) = only {
+            let source_rel = entry.source.strip_prefix(&repo.path).ok();
+            if rel_path != only && source_rel != Some(Path::new(only)) {
+                continue;
+            }
+        }
+        let mirror = repo.output_dir.join(rel_path);
+        if !entry.source.is_file() || !mirror.is_file() {
+            continue;
+        }
+        if linker::content_hash(manifest, &entry.source)?
+            == linker::content_hash(manifest, &mirror)?
+        {
+            continue;
+        }
+        diffs.push(diff_file(manifest, rel_path, &entry.source, &mirror)?);
+    }
+    Ok(diffs)
+}
+
+fn diff_file(
+    manifest: &Manifest,
+    rel_path: &str,
+    source: &Path,
+    mirror: &Path,
+) -> Result<FileDiff> {
+    let mut diff = FileDiff {
+        rel_path: rel_path.to_string(),
+        source: source.to_path_buf(),
+        mirror: mirror.to_path_buf(),
+        insertions: 0,
+        deletions: 0,
+        patch: None,
+    };
+    let (Ok(source_text), Ok(mirror_text)) = (
+        std::fs::read_to_string(source),
+        linker::read_side_text(manifest, mirror),
+    ) else {
+        return Ok(diff);
+    };
+
+    let patch = DiffOptions::new()
+        .set_original_filename(source.display().to_string())
+        .set_modified_filename(mirror.display().to_string())
+        .create_patch(&source_text, &mirror_text);
+    for line in patch.hunks().iter().flat_map(|h| h.lines()) {
+        match line {
+            Line::Insert(_) => diff.insertions += 1,
+            Line::Delete(_) => diff.deletions += 1,
+            Line::Context(_) => {}
+        }
+    }
+    diff.patch = Some(patch.to_string());
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linker::SyncDirection;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_diff_repo_shows_both_versions() {
+        let tmp = TempDir::new().unwrap();
+        let repo_path = tmp.path().join("wiki");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo_path.join("docs")).unwrap();
+        fs::write(repo_path.join("docs/guide.md"), "# Guide\n\nSteps\n").unwrap();
+        fs::write(repo_path.join("README.md"), "hello\n").unwrap();
+        let config_file = tmp.path().join("config.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                output.display(),
+                repo_path.display()
+            ),
+        )
+        .unwrap();
+        let config = crate::config::load_config(Some(&config_file)).unwrap();
+        let repo = &config.repos[0];
+
+        let mut manifest = Manifest::load(&output).unwrap();
+        for rel in ["docs/guide.md", "README.md"] {
+            let key = repo.mirror_key(rel, &manifest);
+            linker::sync_file(
+                &repo.source_path(rel),
+                &output.join(&key),
+                &mut manifest,
+                &key,
+                &output,
+                SyncDirection::Bidirectional,
+            )
+            .unwrap();
+        }
+        assert!(diff_repo(repo, &manifest, None).unwrap().is_empty());
+
+        // Diverge the guide on both sides
+        fs::write(repo_path.join("docs/guide.md"), "# Guide\n\nSource steps\n").unwrap();
+        fs::write(
+            output.join("wiki/docs/guide.md"),
+            "# Guide\n\nMirror steps\n",
+        )
+        .unwrap();
+
+        let diffs = diff_repo(repo, &manifest, None).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].rel_path, "wiki/docs/guide.md");
+        assert_eq!((diffs[0].insertions, diffs[0].deletions), (1, 1));
+        let patch = diffs[0].patch.as_deref().unwrap();
+        assert!(patch.contains("-Source steps"));
+        assert!(patch.contains("+Mirror steps"));
+        assert!(patch.contains(" # Guide"));
+
+        // Filtering by source path or manifest key
+        assert_eq!(
+            diff_repo(repo, &manifest, Some("docs/guide.md"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            diff_repo(repo, &manifest, Some("wiki/docs/guide.md"))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(diff_repo(repo, &manifest, Some("README.md"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -8,14 +8,48 @@ use std::time::{Duration, Instant};
 use anyhow::Result;
 use tracing::{debug, error, info, warn};
 
-use crate::config::{load_config, Config, RepoConfig, RescanInterval};
+use crate::config::{load_config_without_dirs, Config, OutputFormat, RepoConfig, RescanInterval};
 use crate::linker;
 use crate::manifest::Manifest;
-use crate::scanner::{full_scan, scan_repo};
+use crate::notifier::ConflictNotifier;
+use crate::pool::SyncPool;
+use crate::scanner::{self, full_scan, plan_scan, scan_repo, ScanResult};
+use crate::syncer;
 use crate::upgrade::{self, VersionCheck};
-use crate::watcher::{self, ConfigWatcher, MirrorWatcher, RepoWatcher};
+use crate::watcher::{
+    self, ConfigWatcher, ExtraOutput, MirrorWatcher, MirroredRepo, RepoWatcher, SelfWrites,
+};
 
 const UPGRADE_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+/// How often a config that fails to parse on reload is retried before the
+/// error is logged, for editors that save in more than one write
+const RELOAD_RETRIES: u32 = 5;
+/// Wait before the first reload retry; doubled for each one after
+const RELOAD_RETRY_DELAY: Duration = Duration::from_millis(300);
+const RELOAD_RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+/// Percent each reload retry delay is randomly lengthened or shortened by
+const RELOAD_RETRY_JITTER: f64 = 20.0;
+/// How long the main loop sleeps between ticks
+const TICK: Duration = Duration::from_secs(1);
+
+/// Random multiplier within `±jitter_percent` of 1.0.
+fn jitter_factor(jitter_percent: f64, rng: &mut fastrand::Rng) -> f64 {
+    let band = jitter_percent / 100.0;
+    1.0 + band * (rng.f64() * 2.0 - 1.0)
+}
+
+/// Snapshot of what a running engine is doing, for `watch`'s status line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineStatus {
+    pub repos_watched: usize,
+    pub files_mirrored: usize,
+    /// Source and mirror events waiting out the debounce window
+    pub pending_events: usize,
+    /// Manifest key and time of the most recent conflict since startup
+    pub last_conflict: Option<(String, chrono::DateTime<chrono::Local>)>,
+    /// Conflicts resolved since the manifests were loaded
+    pub conflicts: usize,
+}
 
 pub struct MirrorEngine {
     config: Config,
@@ -23,43 +57,193 @@ pub struct MirrorEngine {
     mirror_watchers: HashMap<PathBuf, MirrorWatcher>,
     config_watcher: Option<ConfigWatcher>,
     manifests: HashMap<PathBuf, Arc<Mutex<Manifest>>>,
+    /// Output dirs that disappeared (e.g. an unmounted drive); their watchers
+    /// are stopped until the directory comes back
+    missing_output_dirs: HashSet<PathBuf>,
+    /// Files the watchers just synced, shared so neither side reacts to the
+    /// other's writes
+    self_writes: Arc<SelfWrites>,
+    /// Desktop notifications for conflicts, shared by every watcher
+    conflicts: Arc<ConflictNotifier>,
+    /// Threads every watcher's debounced flushes run on
+    pool: SyncPool,
     running: Arc<AtomicBool>,
+    /// Set by SIGUSR2 to run a full rescan on the next tick of the main loop
+    rescan_requested: Arc<AtomicBool>,
     last_scan_at: Instant,
     last_scan_duration: Duration,
+    /// Multiplier applied to the next periodic rescan interval, re-rolled
+    /// after each periodic rescan when `rescan_jitter` is set
+    rescan_factor: f64,
+    rng: fastrand::Rng,
     last_upgrade_check: Instant,
+    /// When the last `stats_interval` summary was logged
+    last_stats_at: Instant,
+    /// `--stats-interval`, which takes precedence over the config's
+    stats_interval_override: Option<Duration>,
     last_etag: Option<String>,
+    /// Next attempt at a config reload that failed to parse, and how many
+    /// attempts failed so far
+    reload_retry: Option<(Instant, u32)>,
+    /// Log what each scan and source change would sync instead of syncing
+    dry_run: bool,
 }
 
 impl MirrorEngine {
     pub fn new(config: Config) -> Self {
+        let conflicts = Arc::new(ConflictNotifier::new(config.notify_on_conflict));
         Self {
             config,
             watchers: HashMap::new(),
             mirror_watchers: HashMap::new(),
             config_watcher: None,
             manifests: HashMap::new(),
+            missing_output_dirs: HashSet::new(),
+            self_writes: Arc::new(SelfWrites::default()),
+            conflicts,
+            pool: SyncPool::default(),
             running: Arc::new(AtomicBool::new(false)),
+            rescan_requested: Arc::new(AtomicBool::new(false)),
             last_scan_at: Instant::now(),
             last_scan_duration: Duration::ZERO,
+            rescan_factor: 1.0,
+            rng: fastrand::Rng::new(),
             last_upgrade_check: Instant::now(),
+            last_stats_at: Instant::now(),
+            stats_interval_override: None,
             last_etag: None,
+            reload_retry: None,
+            dry_run: false,
         }
     }
 
+    /// An engine that watches the config and repos like `new`, but only logs
+    /// the changes it would make. Nothing is written to the repos, the output
+    /// dirs, or the manifests; a config change replans from scratch.
+    pub fn new_dry_run(config: Config) -> Self {
+        Self {
+            dry_run: true,
+            ..Self::new(config)
+        }
+    }
+
+    /// Log summaries at `interval` whatever the config's `stats_interval`.
+    pub fn override_stats_interval(&mut self, interval: Duration) {
+        self.stats_interval_override = Some(interval);
+    }
+
     /// Start the engine: load manifests, full scan, start watchers, enter main loop.
     pub fn start(&mut self) -> Result<()> {
+        self.start_with_status(&mut |_| {})
+    }
+
+    /// `start`, calling `report` with the engine's status about once a second
+    /// until shutdown.
+    pub fn start_with_status(&mut self, report: &mut dyn FnMut(&EngineStatus)) -> Result<()> {
         info!("Starting ulysses-link engine");
+        if self.dry_run {
+            info!("Dry run: changes are logged, not written");
+        }
+
+        let result = self.start_watching()?;
+
+        self.running.store(true, Ordering::SeqCst);
+
+        // Register signal handlers
+        let running = Arc::clone(&self.running);
+        ctrlc::set_handler(move || {
+            info!("Received shutdown signal");
+            running.store(false, Ordering::SeqCst);
+        })?;
+
+        info!(
+            "Started watching {} repos, {} files mirrored",
+            self.watchers.len(),
+            result.created + result.already_existed,
+        );
+
+        self.main_loop(report);
+        Ok(())
+    }
+
+    /// Current counters across all watchers and manifests.
+    pub fn status(&self) -> EngineStatus {
+        let mut files_mirrored = 0;
+        let mut conflicts = 0;
+        let mut last_conflict: Option<(String, chrono::DateTime<chrono::Local>)> = None;
+        for manifest in self.manifests.values() {
+            let manifest = manifest.lock().unwrap();
+            files_mirrored += manifest.len();
+            conflicts += manifest.conflict_count();
+            if let Some((rel_path, at)) = manifest.last_conflict() {
+                if last_conflict
+                    .as_ref()
+                    .is_none_or(|(_, latest)| at > *latest)
+                {
+                    last_conflict = Some((rel_path.to_string(), at));
+                }
+            }
+        }
+        let pending_events = self
+            .watchers
+            .values()
+            .map(RepoWatcher::pending_count)
+            .chain(
+                self.mirror_watchers
+                    .values()
+                    .map(MirrorWatcher::pending_count),
+            )
+            .sum();
+        EngineStatus {
+            repos_watched: self.watchers.len(),
+            files_mirrored,
+            pending_events,
+            last_conflict,
+            conflicts,
+        }
+    }
+
+    /// Log a one-line summary once `stats_interval` has passed since the
+    /// last one. `now` is passed in so tests can move the clock. Returns
+    /// whether a summary was logged.
+    fn log_stats_if_due(&mut self, now: Instant) -> bool {
+        let Some(interval) = self.stats_interval_override.or(self.config.stats_interval) else {
+            return false;
+        };
+        if now.saturating_duration_since(self.last_stats_at) < interval {
+            return false;
+        }
+        self.last_stats_at = now;
+        info!("{}", self.stats_summary());
+        true
+    }
 
+    fn stats_summary(&self) -> String {
+        let status = self.status();
+        format!(
+            "Stats: {} repos watched, {} files tracked, {} conflicts, last scan took {:?}",
+            status.repos_watched, status.files_mirrored, status.conflicts, self.last_scan_duration,
+        )
+    }
+
+    /// Load manifests, run the initial full scan, and start all watchers.
+    fn start_watching(&mut self) -> Result<ScanResult> {
         // Load one manifest per unique output_dir
-        for output_dir in self.config.active_output_dirs() {
-            let loaded = Manifest::load(&output_dir)?;
+        for (output_dir, loaded) in syncer::load_manifests(&self.config)? {
             self.manifests
                 .insert(output_dir, Arc::new(Mutex::new(loaded)));
         }
 
         // Initial full scan
         let scan_start = Instant::now();
-        let result = {
+        let result = if self.dry_run {
+            let unlocked: HashMap<PathBuf, Manifest> = self
+                .manifests
+                .iter()
+                .map(|(k, v)| (k.clone(), v.lock().unwrap().clone()))
+                .collect();
+            plan_scan(&self.config, &unlocked)
+        } else {
             let mut unlocked: HashMap<PathBuf, Manifest> = self
                 .manifests
                 .iter()
@@ -77,6 +261,7 @@ impl MirrorEngine {
         };
         self.last_scan_duration = scan_start.elapsed();
         self.last_scan_at = Instant::now();
+        self.rescan_factor = jitter_factor(self.config.rescan_jitter, &mut self.rng);
         info!(
             "Initial scan: {} created, {} existed, {} pruned, {} errors in {:?}",
             result.created,
@@ -87,14 +272,22 @@ impl MirrorEngine {
         );
 
         // Start per-repo source watchers
-        let repos: Vec<RepoConfig> = self.config.repos.clone();
+        let repos: Vec<RepoConfig> = self
+            .config
+            .repos_by_priority()
+            .into_iter()
+            .cloned()
+            .collect();
         for repo_config in &repos {
             self.start_repo_watcher(repo_config);
         }
 
-        // Start one mirror watcher per unique output_dir
-        for output_dir in self.config.active_output_dirs() {
-            self.start_mirror_watcher(&output_dir);
+        // Start one mirror watcher per unique output_dir, unless the mirror is
+        // read-only or nothing is being synced
+        if self.config.bidirectional && !self.dry_run {
+            for output_dir in self.config.active_output_dirs() {
+                self.start_mirror_watcher(&output_dir);
+            }
         }
 
         // Start config file watcher
@@ -109,24 +302,53 @@ impl MirrorEngine {
                 }
             }
         }
+        self.watch_ignore_files();
 
-        self.running.store(true, Ordering::SeqCst);
-
-        // Register signal handlers
-        let running = Arc::clone(&self.running);
-        ctrlc::set_handler(move || {
-            info!("Received shutdown signal");
-            running.store(false, Ordering::SeqCst);
-        })?;
+        Ok(result)
+    }
 
-        info!(
-            "Started watching {} repos, {} files mirrored",
-            self.watchers.len(),
-            result.created + result.already_existed,
-        );
+    /// Tear down every watcher and manifest, then start over with `new_config`.
+    fn restart_watching(&mut self, new_config: Config) {
+        for (_, mut watcher) in self.watchers.drain() {
+            watcher.cancel();
+        }
+        for (_, mut watcher) in self.mirror_watchers.drain() {
+            watcher.cancel();
+        }
+        for (dir, manifest) in self.manifests.drain() {
+            if self.dry_run {
+                continue;
+            }
+            let mut manifest = manifest.lock().unwrap();
+            // Repos leaving flat-inbox mode or the Ulysses output format lose
+            // their source headers and folder keywords before the new
+            // config's scan compares their mirrors with source
+            for repo in self.config.repos.iter().filter(|r| {
+                let kept = |n: &RepoConfig| {
+                    n.name == r.name
+                        && n.flat_inbox == r.flat_inbox
+                        && n.output_format == r.output_format
+                };
+                r.output_dir == dir
+                    && (r.flat_inbox || r.output_format == OutputFormat::Ulysses)
+                    && !new_config.repos.iter().any(kept)
+            }) {
+                if let Err(e) = linker::strip_decorations(&manifest, &dir, &repo.mirror_subdir) {
+                    error!(
+                        "Failed to strip source headers or keywords for '{}': {}",
+                        repo.name, e
+                    );
+                }
+            }
+            if let Err(e) = manifest.save(&dir) {
+                error!("Failed to save manifest for {}: {}", dir.display(), e);
+            }
+        }
 
-        self.main_loop();
-        Ok(())
+        self.config = new_config;
+        if let Err(e) = self.start_watching() {
+            error!("Failed to restart after config change: {}", e);
+        }
     }
 
     /// Stop all watchers and clean up.
@@ -147,11 +369,56 @@ impl MirrorEngine {
         self.mirror_watchers.clear();
         self.config_watcher = None;
 
+        // Watcher batches defer saves that follow closely on another
+        for (dir, manifest) in &self.manifests {
+            if let Err(e) = manifest.lock().unwrap().save_pending(dir) {
+                error!("Failed to save manifest for {}: {}", dir.display(), e);
+            }
+        }
+
         info!("Engine stopped");
     }
 
+    /// Point the config watcher at the `.ulyssesignore` of every enabled repo.
+    fn watch_ignore_files(&mut self) {
+        if let Some(ref mut cw) = self.config_watcher {
+            cw.watch_ignore_files(self.config.enabled_repos().map(|r| r.path.as_path()));
+        }
+    }
+
     /// Reload config: diff repos, add/remove/update watchers.
+    ///
+    /// A config that fails to parse is retried a few times with growing
+    /// delays, in case the editor was still writing it; the current config
+    /// stays active meanwhile.
     pub fn reload_config(&mut self) {
+        self.reload_retry = None;
+        self.try_reload_config(0);
+    }
+
+    /// Retry a failed config reload once its delay is up. Returns whether a
+    /// reload was attempted.
+    fn retry_reload_if_due(&mut self) -> bool {
+        let Some((due, failures)) = self.reload_retry else {
+            return false;
+        };
+        if Instant::now() < due {
+            return false;
+        }
+        self.reload_retry = None;
+        self.try_reload_config(failures);
+        true
+    }
+
+    /// Delay before the reload retry that follows `failures` failed attempts.
+    fn reload_retry_delay(&mut self, failures: u32) -> Duration {
+        RELOAD_RETRY_DELAY
+            .saturating_mul(1 << failures.saturating_sub(1).min(16))
+            .mul_f64(jitter_factor(RELOAD_RETRY_JITTER, &mut self.rng))
+            .min(RELOAD_RETRY_MAX_DELAY)
+    }
+
+    fn try_reload_config(&mut self, failures: u32) {
         let config_path = match &self.config.config_path {
             Some(p) => p.clone(),
             None => {
@@ -162,13 +429,54 @@ impl MirrorEngine {
 
         info!("Reloading config from {}", config_path.display());
 
-        let new_config = match load_config(Some(&config_path)) {
+        let new_config = match load_config_without_dirs(Some(&config_path)) {
             Ok(c) => c,
             Err(e) => {
-                error!("Failed to reload config: {}", e);
+                let failures = failures + 1;
+                if failures > RELOAD_RETRIES {
+                    error!(
+                        "Failed to reload config after {} attempts, keeping the current config: {}",
+                        failures, e
+                    );
+                    return;
+                }
+                let delay = self.reload_retry_delay(failures);
+                debug!("Config failed to parse, retrying in {:?}: {}", delay, e);
+                self.reload_retry = Some((Instant::now() + delay, failures));
                 return;
             }
         };
+        self.conflicts.set_enabled(new_config.notify_on_conflict);
+        for warning in &new_config.pattern_warnings {
+            warn!("{warning}");
+        }
+
+        // Nothing was synced, so there is no mirror to move or prune
+        if self.dry_run {
+            self.restart_watching(new_config);
+            return;
+        }
+
+        // Output dirs new to the config are created; one already in use that
+        // went missing, e.g. on an unmounted drive, stays paused
+        let old_dirs: HashSet<PathBuf> = self.config.active_output_dirs().into_iter().collect();
+        for dir in new_config.active_output_dirs() {
+            if !old_dirs.contains(&dir) {
+                if let Err(e) = std::fs::create_dir_all(&dir) {
+                    error!("Failed to create output dir {}: {}", dir.display(), e);
+                }
+            }
+        }
+
+        // Manifests and bases live under the state options, so changing them
+        // means reloading every manifest from scratch
+        if new_config.state != self.config.state {
+            info!(
+                "state_dir, base_cache, protect, normalization, flat_inbox, output_format, read_only_source or extra_outputs changed, restarting all watchers"
+            );
+            self.restart_watching(new_config);
+            return;
+        }
 
         let old_active = self.config.active_output_dirs();
         let new_active = new_config.active_output_dirs();
@@ -178,8 +486,20 @@ impl MirrorEngine {
 
         // Determine if this is a simple global move:
         // ALL old repos shared one output_dir and ALL new repos share one (different) output_dir.
-        let is_simple_global_move =
-            old_active.len() == 1 && new_active.len() == 1 && old_active[0] != new_active[0];
+        // A branch switch under a `{branch}` output_dir isn't a move: each
+        // branch keeps its own mirror.
+        let enabled_names =
+            |c: &Config| -> HashSet<String> { c.enabled_repos().map(|r| r.name.clone()).collect() };
+        let branches = |c: &Config| -> HashMap<String, Option<String>> {
+            c.enabled_repos()
+                .map(|r| (r.name.clone(), r.branch.clone()))
+                .collect()
+        };
+        let is_simple_global_move = old_active.len() == 1
+            && new_active.len() == 1
+            && old_active[0] != new_active[0]
+            && enabled_names(&self.config) == enabled_names(&new_config)
+            && branches(&self.config) == branches(&new_config);
 
         if is_simple_global_move {
             let old_dir = &old_active[0];
@@ -195,9 +515,21 @@ impl MirrorEngine {
                 mw.cancel();
             }
 
+            // Source watchers share this manifest and take its lock before
+            // each flush, so holding it keeps them from writing into either
+            // directory until they have been pointed at the new one
+            let manifest_arc = self
+                .manifests
+                .remove(old_dir)
+                .unwrap_or_else(|| Arc::new(Mutex::new(Manifest::empty())));
+            let mut manifest = manifest_arc.lock().unwrap();
+            if let Err(e) = manifest.save_pending(old_dir) {
+                error!("Failed to save manifest for {}: {}", old_dir.display(), e);
+            }
+
             // Try to move the old output_dir to the new location
             let mut moved = false;
-            match linker::move_output_dir(old_dir, new_dir) {
+            match linker::move_output_dir(old_dir, new_dir, new_config.state.state_dir.as_deref()) {
                 Ok(true) => {
                     moved = true;
                     info!("Output directory moved successfully");
@@ -209,17 +541,20 @@ impl MirrorEngine {
             }
 
             // Load manifest from new location
-            match Manifest::load(new_dir) {
-                Ok(m) => {
-                    self.manifests.remove(old_dir);
-                    self.manifests
-                        .insert(new_dir.clone(), Arc::new(Mutex::new(m)));
-                }
+            match Manifest::load_in(new_dir, &new_config.state) {
+                Ok(m) => *manifest = m,
                 Err(e) => {
                     error!("Failed to load manifest from new output_dir: {}", e);
+                    drop(manifest);
+                    self.manifests.insert(old_dir.clone(), manifest_arc);
                     return;
                 }
             }
+            for watcher in self.watchers.values() {
+                watcher.set_output_dir(new_dir);
+            }
+            drop(manifest);
+            self.manifests.insert(new_dir.clone(), manifest_arc);
 
             if moved {
                 info!("Running reconciliation scan after move");
@@ -229,8 +564,10 @@ impl MirrorEngine {
         }
 
         // Build repo name maps for diffing
-        let old_names: HashSet<String> = self.config.repos.iter().map(|r| r.name.clone()).collect();
-        let new_names: HashSet<String> = new_config.repos.iter().map(|r| r.name.clone()).collect();
+        let mut old_names: HashSet<String> =
+            self.config.repos.iter().map(|r| r.name.clone()).collect();
+        let mut new_names: HashSet<String> =
+            new_config.repos.iter().map(|r| r.name.clone()).collect();
 
         let new_repos_by_name: HashMap<String, RepoConfig> = new_config
             .repos
@@ -244,6 +581,32 @@ impl MirrorEngine {
             .map(|r| (r.name.clone(), r.clone()))
             .collect();
 
+        let mut repos_changed = false;
+
+        // Renamed repos: the same path and output_dir under a new name. Move
+        // the mirror in place instead of deleting and re-copying it.
+        let renamed: Vec<(String, String)> = old_names
+            .difference(&new_names)
+            .filter_map(|old_name| {
+                let old_rc = &old_repos_by_name[old_name];
+                new_names
+                    .difference(&old_names)
+                    .find(|new_name| {
+                        let new_rc = &new_repos_by_name[*new_name];
+                        new_rc.path == old_rc.path && new_rc.output_dir == old_rc.output_dir
+                    })
+                    .map(|new_name| (old_name.clone(), new_name.clone()))
+            })
+            .collect();
+        for (old_name, new_name) in &renamed {
+            info!("Repo renamed: {} -> {}", old_name, new_name);
+            self.stop_repo_watcher(old_name);
+            self.rename_repo(&old_repos_by_name[old_name], &new_repos_by_name[new_name]);
+            old_names.remove(old_name);
+            new_names.remove(new_name);
+            repos_changed = true;
+        }
+
         // Removed repos: prune mirrors from their old output_dir
         for name in old_names.difference(&new_names) {
             info!("Repo removed from config: {}", name);
@@ -251,13 +614,30 @@ impl MirrorEngine {
             let old_rc = &old_repos_by_name[name];
             if let Some(manifest_arc) = self.manifests.get(&old_rc.output_dir) {
                 let mut manifest = manifest_arc.lock().unwrap();
-                let _ = linker::remove_repo_mirror(name, &old_rc.output_dir, &mut manifest);
+                let _ = linker::remove_repo_mirror(
+                    &old_rc.mirror_subdir,
+                    &old_rc.output_dir,
+                    &mut manifest,
+                );
+            } else if let Ok(mut manifest) =
+                Manifest::load_in(&old_rc.output_dir, &self.config.state)
+            {
+                // A disabled repo's output_dir may have no loaded manifest
+                if linker::remove_repo_mirror(
+                    &old_rc.mirror_subdir,
+                    &old_rc.output_dir,
+                    &mut manifest,
+                )
+                .is_ok()
+                {
+                    let _ = manifest.save(&old_rc.output_dir);
+                }
             }
         }
 
         // Load manifests for newly active output_dirs
         for dir in new_active_set.difference(&old_active_set) {
-            match Manifest::load(dir) {
+            match Manifest::load_in(dir, &new_config.state) {
                 Ok(m) => {
                     self.manifests.insert(dir.clone(), Arc::new(Mutex::new(m)));
                 }
@@ -271,60 +651,99 @@ impl MirrorEngine {
             }
         }
 
-        let mut repos_changed = false;
-
         // Added repos
         for name in new_names.difference(&old_names) {
             info!("New repo in config: {}", name);
-            if let Some(repo_config) = new_repos_by_name.get(name) {
-                if let Some(manifest_arc) = self.manifests.get(&repo_config.output_dir) {
-                    let mut manifest = manifest_arc.lock().unwrap();
-                    scan_repo(repo_config, &repo_config.output_dir, &mut manifest);
-                }
+            if let Some(repo_config) = new_repos_by_name.get(name).filter(|rc| rc.enabled) {
+                self.scan_repo_outputs(&new_config, repo_config);
                 self.start_repo_watcher(repo_config);
                 repos_changed = true;
             }
         }
 
-        // Changed repos (includes output_dir changes)
+        // Changed repos (includes output_dir and mirror_subdir changes)
         for name in old_names.intersection(&new_names) {
             let old_rc = &old_repos_by_name[name];
             let new_rc = &new_repos_by_name[name];
 
+            // Disabling keeps the mirror in place so re-enabling is instant
+            match (old_rc.enabled, new_rc.enabled) {
+                (true, false) => {
+                    info!("Repo disabled, pausing sync: {}", name);
+                    self.stop_repo_watcher(name);
+                    repos_changed = true;
+                    continue;
+                }
+                (false, true) => {
+                    info!("Repo enabled, re-scanning: {}", name);
+                    self.scan_repo_outputs(&new_config, new_rc);
+                    self.start_repo_watcher(new_rc);
+                    repos_changed = true;
+                    continue;
+                }
+                (false, false) => continue,
+                (true, true) => {}
+            }
+
             let output_dir_changed = old_rc.output_dir != new_rc.output_dir;
-            let patterns_changed =
-                old_rc.include_patterns != new_rc.include_patterns || old_rc.path != new_rc.path;
+            let switched_branch = old_rc.branch != new_rc.branch;
+            // Already retargeted above and covered by the scan that follows
+            let moved_globally = is_simple_global_move && output_dir_changed;
+            let patterns_changed = old_rc.include_patterns != new_rc.include_patterns
+                || old_rc.files != new_rc.files
+                || old_rc.exclude_patterns != new_rc.exclude_patterns
+                || old_rc.path != new_rc.path
+                || old_rc.direction != new_rc.direction
+                || old_rc.max_depth != new_rc.max_depth
+                || old_rc.include_hidden != new_rc.include_hidden
+                || old_rc.git_tracked_only != new_rc.git_tracked_only
+                || old_rc.skip_if_matches.as_ref().map(|r| r.as_str())
+                    != new_rc.skip_if_matches.as_ref().map(|r| r.as_str());
+
+            let layout_changed = old_rc.mirror_subdir != new_rc.mirror_subdir
+                || old_rc.flatten != new_rc.flatten
+                || old_rc.root != new_rc.root;
+
+            if moved_globally && !layout_changed && !patterns_changed {
+                continue;
+            }
 
-            if output_dir_changed {
+            if output_dir_changed || layout_changed {
                 info!(
-                    "Repo '{}' output_dir changed: {} -> {}, re-scanning",
+                    "Repo '{}' mirror location changed: {} -> {}, re-scanning",
                     name,
-                    old_rc.output_dir.display(),
-                    new_rc.output_dir.display()
+                    old_rc.mirror_root().display(),
+                    new_rc.mirror_root().display()
                 );
                 self.stop_repo_watcher(name);
 
-                // Prune old mirror (don't move — could share output_dir with other repos)
-                if let Some(manifest_arc) = self.manifests.get(&old_rc.output_dir) {
-                    let mut manifest = manifest_arc.lock().unwrap();
-                    let _ = linker::remove_repo_mirror(name, &old_rc.output_dir, &mut manifest);
+                // Move just this repo's subtree when only output_dir changed;
+                // otherwise prune the old mirror and let the scan rebuild it.
+                // The old branch's mirror stays for when it's checked out again.
+                let moved = output_dir_changed
+                    && !layout_changed
+                    && !switched_branch
+                    && self.move_repo_mirror(old_rc, &new_rc.output_dir);
+                if !moved && !switched_branch {
+                    if let Some(manifest_arc) = self.manifests.get(&old_rc.output_dir) {
+                        let mut manifest = manifest_arc.lock().unwrap();
+                        let _ = linker::remove_repo_mirror(
+                            &old_rc.mirror_subdir,
+                            &old_rc.output_dir,
+                            &mut manifest,
+                        );
+                    }
                 }
 
                 // Scan into new output_dir
-                if let Some(manifest_arc) = self.manifests.get(&new_rc.output_dir) {
-                    let mut manifest = manifest_arc.lock().unwrap();
-                    scan_repo(new_rc, &new_rc.output_dir, &mut manifest);
-                }
+                self.scan_repo_outputs(&new_config, new_rc);
 
                 self.start_repo_watcher(new_rc);
                 repos_changed = true;
             } else if patterns_changed {
                 info!("Repo config changed, re-scanning: {}", name);
                 self.stop_repo_watcher(name);
-                if let Some(manifest_arc) = self.manifests.get(&new_rc.output_dir) {
-                    let mut manifest = manifest_arc.lock().unwrap();
-                    scan_repo(new_rc, &new_rc.output_dir, &mut manifest);
-                }
+                self.scan_repo_outputs(&new_config, new_rc);
                 self.start_repo_watcher(new_rc);
                 repos_changed = true;
             }
@@ -355,33 +774,44 @@ impl MirrorEngine {
                 "Scan after output_dir change: {} created, {} existed, {} pruned in {:?}",
                 result.created, result.already_existed, result.pruned, self.last_scan_duration,
             );
-
-            // Restart all repo watchers with new output_dir
-            let repo_names: Vec<String> = self.watchers.keys().cloned().collect();
-            for name in &repo_names {
-                self.stop_repo_watcher(name);
-            }
-            let repos: Vec<RepoConfig> = self.config.repos.clone();
-            for repo_config in &repos {
-                self.start_repo_watcher(repo_config);
-            }
         } else if repos_changed {
             self.last_scan_at = Instant::now();
         }
 
         // Reconcile mirror watchers: stop removed, start added
         let final_active: HashSet<PathBuf> = self.config.active_output_dirs().into_iter().collect();
+        let want_watched: HashSet<PathBuf> = if self.config.bidirectional {
+            final_active.clone()
+        } else {
+            HashSet::new()
+        };
         let current_watched: HashSet<PathBuf> = self.mirror_watchers.keys().cloned().collect();
 
-        for dir in current_watched.difference(&final_active) {
+        for dir in current_watched.difference(&want_watched) {
             if let Some(mut mw) = self.mirror_watchers.remove(dir) {
                 debug!("Stopping mirror watcher on {}", dir.display());
                 mw.cancel();
             }
         }
-        for dir in final_active.difference(&current_watched) {
+        for dir in want_watched.difference(&current_watched) {
             self.start_mirror_watcher(dir);
         }
+        // A changed mirror_poll_interval needs a watcher on the other
+        // backend, a changed mirror_ignore one that filters with it, and
+        // changed repos one that passes edits on to their extra outputs
+        for dir in want_watched.intersection(&current_watched) {
+            let interval = self.config.mirror_poll_interval(dir);
+            let watcher = &self.mirror_watchers[dir];
+            if watcher.poll_interval() != interval
+                || *watcher.ignore() != self.config.mirror_ignore
+                || !watcher.serves(&self.mirrored_repos(dir))
+            {
+                if let Some(mut mw) = self.mirror_watchers.remove(dir) {
+                    mw.cancel();
+                }
+                self.start_mirror_watcher(dir);
+            }
+        }
 
         // Drop manifests for output_dirs no longer in use
         let stale_dirs: Vec<PathBuf> = self
@@ -391,8 +821,55 @@ impl MirrorEngine {
             .cloned()
             .collect();
         for dir in stale_dirs {
-            self.manifests.remove(&dir);
+            if let Some(manifest) = self.manifests.remove(&dir) {
+                if let Err(e) = manifest.lock().unwrap().save_pending(&dir) {
+                    error!("Failed to save manifest for {}: {}", dir.display(), e);
+                }
+            }
+        }
+    }
+
+    /// Move a repo's mirror subtree into `new_output_dir`. Returns false if
+    /// the move could not be done and the caller should prune and re-scan.
+    fn move_repo_mirror(&self, old_rc: &RepoConfig, new_output_dir: &Path) -> bool {
+        let (Some(old_arc), Some(new_arc)) = (
+            self.manifests.get(&old_rc.output_dir),
+            self.manifests.get(new_output_dir),
+        ) else {
+            return false;
+        };
+        let mut old_manifest = old_arc.lock().unwrap();
+        let mut new_manifest = new_arc.lock().unwrap();
+        if let Err(e) = linker::move_repo_mirror(
+            &old_rc.mirror_subdir,
+            &old_rc.output_dir,
+            &mut old_manifest,
+            new_output_dir,
+            &mut new_manifest,
+        ) {
+            warn!(
+                "Failed to move mirror for {}, will re-scan: {}",
+                old_rc.name, e
+            );
+            return false;
+        }
+        for (manifest, dir) in [
+            (&mut *old_manifest, old_rc.output_dir.as_path()),
+            (&mut *new_manifest, new_output_dir),
+        ] {
+            if let Err(e) = manifest.save(dir) {
+                error!("Failed to save manifest: {}", e);
+            }
         }
+        true
+    }
+
+    /// Scan `repo_config` into its output dir and each of its extra outputs
+    /// as a startup scan under `config` would, `initial_sync_limit` included.
+    fn scan_repo_outputs(&self, config: &Config, repo_config: &RepoConfig) {
+        scanner::scan_repo_outputs(config, repo_config, |dir| {
+            self.manifests.get(dir).map(|m| m.lock().unwrap())
+        });
     }
 
     fn start_repo_watcher(&mut self, repo_config: &RepoConfig) {
@@ -408,11 +885,19 @@ impl MirrorEngine {
             }
         };
 
+        let extra_outputs = self.extra_outputs(repo_config);
+
         match watcher::create_watcher(
             repo_config,
             &repo_config.output_dir,
             self.config.debounce_seconds,
+            self.config.max_batch,
             manifest_arc,
+            extra_outputs,
+            Arc::clone(&self.self_writes),
+            Arc::clone(&self.conflicts),
+            &self.pool,
+            self.dry_run,
         ) {
             Ok(w) => {
                 debug!("Started watcher for {}", repo_config.name);
@@ -434,6 +919,81 @@ impl MirrorEngine {
         }
     }
 
+    /// Move a renamed repo's mirror and manifest entries to its new mirror
+    /// subdir, then watch it under the new name. Falls back to a fresh copy
+    /// if the mirror can't be moved.
+    fn rename_repo(&mut self, old_rc: &RepoConfig, new_rc: &RepoConfig) {
+        let output_dir = &new_rc.output_dir;
+        let loaded = self.manifests.get(output_dir).map(Arc::clone);
+        let manifest_arc = match loaded {
+            Some(m) => m,
+            // A disabled repo's output_dir may have no loaded manifest
+            None => match Manifest::load_in(output_dir, &self.config.state) {
+                Ok(m) => Arc::new(Mutex::new(m)),
+                Err(e) => {
+                    error!(
+                        "Failed to load manifest for {}: {}",
+                        output_dir.display(),
+                        e
+                    );
+                    return;
+                }
+            },
+        };
+        let mut manifest = manifest_arc.lock().unwrap();
+        if let Err(e) = linker::rename_repo_mirror(
+            &old_rc.mirror_subdir,
+            &new_rc.mirror_subdir,
+            output_dir,
+            &mut manifest,
+        ) {
+            warn!(
+                "Failed to move mirror for {}, re-copying: {}",
+                new_rc.name, e
+            );
+            let _ = linker::remove_repo_mirror(&old_rc.mirror_subdir, output_dir, &mut manifest);
+        }
+        manifest.rename_repo(&old_rc.name, &new_rc.name);
+        if new_rc.enabled {
+            scan_repo(new_rc, output_dir, &mut manifest);
+        }
+        if let Err(e) = manifest.save(output_dir) {
+            error!(
+                "Failed to save manifest for {}: {}",
+                output_dir.display(),
+                e
+            );
+        }
+        drop(manifest);
+        if new_rc.enabled {
+            self.start_repo_watcher(new_rc);
+        }
+    }
+
+    /// The repo's extra outputs, each with the manifest of its output dir.
+    fn extra_outputs(&self, repo_config: &RepoConfig) -> Vec<ExtraOutput> {
+        repo_config
+            .extra_output_repos()
+            .into_iter()
+            .filter_map(|repo| {
+                let manifest = Arc::clone(self.manifests.get(&repo.output_dir)?);
+                Some(ExtraOutput { repo, manifest })
+            })
+            .collect()
+    }
+
+    /// The enabled repos mirrored into `output_dir`, for its mirror watcher.
+    fn mirrored_repos(&self, output_dir: &Path) -> Vec<MirroredRepo> {
+        self.config
+            .enabled_repos()
+            .filter(|r| r.output_dir == output_dir)
+            .map(|repo| MirroredRepo {
+                repo: repo.clone(),
+                extra_outputs: self.extra_outputs(repo),
+            })
+            .collect()
+    }
+
     fn start_mirror_watcher(&mut self, output_dir: &Path) {
         let manifest_arc = match self.manifests.get(output_dir) {
             Some(m) => Arc::clone(m),
@@ -446,8 +1006,18 @@ impl MirrorEngine {
             }
         };
 
-        match watcher::create_mirror_watcher(output_dir, self.config.debounce_seconds, manifest_arc)
-        {
+        match watcher::create_mirror_watcher(
+            output_dir,
+            self.config.debounce_seconds,
+            self.config.max_batch,
+            manifest_arc,
+            self.mirrored_repos(output_dir),
+            Arc::clone(&self.self_writes),
+            Arc::clone(&self.conflicts),
+            &self.pool,
+            self.config.mirror_poll_interval(output_dir),
+            self.config.mirror_ignore.clone(),
+        ) {
             Ok(w) => {
                 debug!("Started mirror watcher on {}", output_dir.display());
                 self.mirror_watchers.insert(output_dir.to_path_buf(), w);
@@ -468,6 +1038,72 @@ impl MirrorEngine {
         }
     }
 
+    /// Pause syncing into output dirs that vanished, and resume with a scan
+    /// once they are back. While paused, nothing is written there and no
+    /// mirror file counts as deleted.
+    fn check_output_dirs(&mut self) {
+        if self.dry_run {
+            return;
+        }
+        let active = self.config.active_output_dirs();
+        self.missing_output_dirs.retain(|dir| active.contains(dir));
+        for output_dir in active {
+            let missing = !output_dir.is_dir();
+            if missing == self.missing_output_dirs.contains(&output_dir) {
+                continue;
+            }
+            let repos: Vec<RepoConfig> = self
+                .config
+                .repos_by_priority()
+                .into_iter()
+                .filter(|r| r.output_dir == output_dir)
+                .cloned()
+                .collect();
+
+            if missing {
+                warn!(
+                    "Output directory {} is gone, pausing sync until it returns",
+                    output_dir.display()
+                );
+                if let Some(mut mw) = self.mirror_watchers.remove(&output_dir) {
+                    mw.cancel();
+                }
+                for repo_config in &repos {
+                    self.stop_repo_watcher(&repo_config.name);
+                }
+                self.missing_output_dirs.insert(output_dir);
+                continue;
+            }
+
+            info!(
+                "Output directory {} is back, resuming sync",
+                output_dir.display()
+            );
+            self.missing_output_dirs.remove(&output_dir);
+            // Repos using it as an extra output only need the rescan: their
+            // watchers kept running against their own output dirs
+            let extras: Vec<RepoConfig> = self
+                .config
+                .repos_by_priority()
+                .into_iter()
+                .flat_map(RepoConfig::extra_output_repos)
+                .filter(|r| r.output_dir == output_dir)
+                .collect();
+            if let Some(manifest_arc) = self.manifests.get(&output_dir) {
+                let mut manifest = manifest_arc.lock().unwrap();
+                for repo_config in repos.iter().chain(&extras) {
+                    scan_repo(repo_config, &output_dir, &mut manifest);
+                }
+            }
+            for repo_config in &repos {
+                self.start_repo_watcher(repo_config);
+            }
+            if self.config.bidirectional {
+                self.start_mirror_watcher(&output_dir);
+            }
+        }
+    }
+
     fn check_for_upgrade(&mut self) {
         match upgrade::check_latest_version(self.last_etag.as_deref()) {
             Ok(VersionCheck::NotModified) => {
@@ -506,32 +1142,91 @@ impl MirrorEngine {
     }
 
     fn rescan_interval(&self) -> Option<Duration> {
-        match &self.config.rescan_interval {
-            RescanInterval::Never => None,
+        let base = match &self.config.rescan_interval {
+            RescanInterval::Never => return None,
             RescanInterval::Auto => {
                 let computed = self.last_scan_duration * 1000;
-                Some(computed.max(Duration::from_secs(60)))
+                computed.max(Duration::from_secs(60))
             }
-            RescanInterval::Fixed(d) => Some(*d),
+            RescanInterval::Fixed(d) => *d,
+        };
+        Some(base.mul_f64(self.rescan_factor))
+    }
+
+    /// Run a full rescan once the periodic interval has elapsed, or right
+    /// away when one was requested with SIGUSR2.
+    fn rescan_if_due(&mut self) {
+        let requested = self.rescan_requested.swap(false, Ordering::SeqCst);
+        let due = self
+            .rescan_interval()
+            .is_some_and(|interval| self.last_scan_at.elapsed() >= interval);
+        if self.dry_run || !(requested || due) {
+            return;
         }
+
+        info!(
+            "{}",
+            if requested {
+                "Requested rescan"
+            } else {
+                "Periodic rescan"
+            }
+        );
+        let scan_start = Instant::now();
+        let result = {
+            let mut unlocked: HashMap<PathBuf, Manifest> = self
+                .manifests
+                .iter()
+                .map(|(k, v)| (k.clone(), v.lock().unwrap().clone()))
+                .collect();
+            let r = full_scan(&self.config, &mut unlocked);
+            for (k, v) in unlocked {
+                if let Some(arc) = self.manifests.get(&k) {
+                    *arc.lock().unwrap() = v;
+                }
+            }
+            r
+        };
+        self.last_scan_duration = scan_start.elapsed();
+        self.last_scan_at = Instant::now();
+        self.rescan_factor = jitter_factor(self.config.rescan_jitter, &mut self.rng);
+        info!(
+            "Rescan: {} created, {} pruned in {:?}",
+            result.created, result.pruned, self.last_scan_duration,
+        );
     }
 
-    fn main_loop(&mut self) {
+    fn main_loop(&mut self, report: &mut dyn FnMut(&EngineStatus)) {
         #[cfg(unix)]
-        let mut sighup_signals = {
+        let mut signals = {
+            use signal_hook::consts::{SIGHUP, SIGUSR2};
             use signal_hook::iterator::Signals;
-            Signals::new([signal_hook::consts::SIGHUP]).ok()
+            Signals::new([SIGHUP, SIGUSR2]).ok()
         };
 
+        report(&self.status());
         while self.running.load(Ordering::SeqCst) {
-            thread::sleep(Duration::from_secs(1));
+            // Wake early for a pending reload retry
+            let tick = match self.reload_retry {
+                Some((due, _)) => due.saturating_duration_since(Instant::now()).min(TICK),
+                None => TICK,
+            };
+            thread::sleep(tick);
 
             #[cfg(unix)]
-            if let Some(ref mut signals) = sighup_signals {
+            if let Some(ref mut signals) = signals {
                 for sig in signals.pending() {
-                    if sig == signal_hook::consts::SIGHUP {
-                        info!("Received SIGHUP, reloading config");
-                        self.reload_config();
+                    match sig {
+                        signal_hook::consts::SIGHUP => {
+                            info!("Received SIGHUP, reloading config");
+                            self.reload_config();
+                            self.watch_ignore_files();
+                        }
+                        signal_hook::consts::SIGUSR2 => {
+                            info!("Received SIGUSR2, requesting a rescan");
+                            self.rescan_requested.store(true, Ordering::SeqCst);
+                        }
+                        _ => {}
                     }
                 }
             }
@@ -540,44 +1235,427 @@ impl MirrorEngine {
                 if cw.has_changed() {
                     info!("Config file changed, reloading");
                     self.reload_config();
+                    self.watch_ignore_files();
                 }
             }
-
-            if let Some(interval) = self.rescan_interval() {
-                if self.last_scan_at.elapsed() >= interval {
-                    info!("Periodic rescan");
-                    let scan_start = Instant::now();
-                    let result = {
-                        let mut unlocked: HashMap<PathBuf, Manifest> = self
-                            .manifests
-                            .iter()
-                            .map(|(k, v)| (k.clone(), v.lock().unwrap().clone()))
-                            .collect();
-                        let r = full_scan(&self.config, &mut unlocked);
-                        for (k, v) in unlocked {
-                            if let Some(arc) = self.manifests.get(&k) {
-                                *arc.lock().unwrap() = v;
-                            }
-                        }
-                        r
-                    };
-                    self.last_scan_duration = scan_start.elapsed();
-                    self.last_scan_at = Instant::now();
-                    info!(
-                        "Rescan: {} created, {} pruned in {:?}",
-                        result.created, result.pruned, self.last_scan_duration,
-                    );
-                }
+            if self.retry_reload_if_due() {
+                self.watch_ignore_files();
             }
 
+            self.check_output_dirs();
+
+            self.rescan_if_due();
+
             if self.config.auto_upgrade
                 && self.last_upgrade_check.elapsed() >= UPGRADE_CHECK_INTERVAL
             {
                 self.last_upgrade_check = Instant::now();
                 self.check_for_upgrade();
             }
+
+            self.log_stats_if_due(Instant::now());
+
+            report(&self.status());
         }
 
         self.stop();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::load_config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_config(config_path: &Path, repo: &Path, output: &Path, enabled: bool) {
+        fs::write(
+            config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nenabled = {}",
+                output.display(),
+                repo.display(),
+                enabled
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_reload_toggles_enabled() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+        assert!(engine.watchers.contains_key("my-repo"));
+        let mirror = engine.config.repos[0].mirror_root().join("README.md");
+        assert!(mirror.exists());
+
+        // Disabling stops the watcher but keeps the mirror
+        write_config(&config_path, &repo, &output, false);
+        engine.reload_config();
+        assert!(engine.watchers.is_empty());
+        assert!(mirror.exists());
+
+        // Changes while disabled are not synced
+        fs::write(repo.join("NOTES.md"), "notes").unwrap();
+        let notes = engine.config.repos[0].mirror_root().join("NOTES.md");
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(!notes.exists());
+
+        // Re-enabling rescans and restarts the watcher
+        write_config(&config_path, &repo, &output, true);
+        engine.reload_config();
+        assert!(engine.watchers.contains_key("my-repo"));
+        assert!(notes.exists());
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_repo_added_on_reload_honors_initial_sync_limit() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let added = tmp.path().join("added");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::create_dir(&added).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        for i in 0..5 {
+            fs::write(added.join(format!("doc{i}.md")), format!("doc {i}")).unwrap();
+        }
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+
+        fs::write(
+            &config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\ninitial_sync_limit = 2\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                output.display(),
+                repo.display(),
+                added.display()
+            ),
+        )
+        .unwrap();
+        engine.reload_config();
+        assert!(engine.watchers.contains_key("added"));
+        assert_eq!(fs::read_dir(output.join("added")).unwrap().count(), 2);
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_reload_retries_config_that_fails_to_parse() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+
+        // A half-written save keeps the old config and schedules a retry
+        fs::write(&config_path, "version = 1\noutput_dir = \"").unwrap();
+        engine.reload_config();
+        assert!(engine.watchers.contains_key("my-repo"));
+        let (due, failures) = engine.reload_retry.unwrap();
+        assert_eq!(failures, 1);
+        assert!(due <= Instant::now() + RELOAD_RETRY_DELAY.mul_f64(1.5));
+        assert!(!engine.retry_reload_if_due());
+
+        // Still broken on the next attempt: the delay grows
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        assert!(engine.retry_reload_if_due());
+        assert_eq!(engine.reload_retry.unwrap().1, 2);
+
+        // The editor finishes the write and the retry adopts it
+        write_config(&config_path, &repo, &output, false);
+        let (due, _) = engine.reload_retry.unwrap();
+        std::thread::sleep(due.saturating_duration_since(Instant::now()));
+        assert!(engine.retry_reload_if_due());
+        assert!(engine.reload_retry.is_none());
+        assert!(!engine.config.repos[0].enabled);
+        assert!(engine.watchers.is_empty());
+
+        // Retries are capped
+        fs::write(&config_path, "version = ").unwrap();
+        engine.reload_config();
+        for _ in 0..RELOAD_RETRIES {
+            engine.reload_retry.as_mut().unwrap().0 = Instant::now();
+            assert!(engine.retry_reload_if_due());
+        }
+        assert!(engine.reload_retry.is_none());
+        assert!(engine.reload_retry_delay(20) <= RELOAD_RETRY_MAX_DELAY);
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_rescan_request_runs_scan() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+        // Stop watching so only a scan can pick up the new file
+        for (_, mut w) in engine.watchers.drain() {
+            w.cancel();
+        }
+        fs::write(repo.join("NOTES.md"), "notes").unwrap();
+        let notes = engine.config.repos[0].mirror_root().join("NOTES.md");
+
+        // Not due yet: the auto interval is at least a minute
+        engine.rescan_if_due();
+        assert!(!notes.exists());
+
+        engine.rescan_requested.store(true, Ordering::SeqCst);
+        engine.rescan_if_due();
+        assert!(notes.exists());
+        assert!(!engine.rescan_requested.load(Ordering::SeqCst));
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_stats_logged_at_interval() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        let start = engine.last_stats_at;
+        assert!(!engine.log_stats_if_due(start + Duration::from_secs(3600)));
+
+        engine.config.stats_interval = Some(Duration::from_secs(60));
+        assert!(!engine.log_stats_if_due(start + Duration::from_secs(30)));
+        assert!(engine.log_stats_if_due(start + Duration::from_secs(61)));
+        assert!(!engine.log_stats_if_due(start + Duration::from_secs(62)));
+        assert!(engine.log_stats_if_due(start + Duration::from_secs(122)));
+
+        // --stats-interval wins over the config
+        engine.override_stats_interval(Duration::from_secs(10));
+        assert!(engine.log_stats_if_due(start + Duration::from_secs(132)));
+        assert!(engine
+            .stats_summary()
+            .contains("0 repos watched, 0 files tracked, 0 conflicts"));
+    }
+
+    #[test]
+    fn test_rescan_jitter_stays_in_band() {
+        let tmp = TempDir::new().unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\nrescan_interval = 600\nrescan_jitter = 10",
+                tmp.path().join("output").display()
+            ),
+        )
+        .unwrap();
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.rng = fastrand::Rng::with_seed(7);
+
+        let base = Duration::from_secs(600);
+        let mut seen_short = false;
+        let mut seen_long = false;
+        for _ in 0..200 {
+            engine.rescan_factor = jitter_factor(engine.config.rescan_jitter, &mut engine.rng);
+            let interval = engine.rescan_interval().unwrap();
+            assert!(interval >= base.mul_f64(0.9) && interval <= base.mul_f64(1.1));
+            seen_short |= interval < base;
+            seen_long |= interval > base;
+        }
+        assert!(seen_short && seen_long);
+
+        // Without jitter the interval is exact
+        assert_eq!(jitter_factor(0.0, &mut engine.rng), 1.0);
+    }
+
+    #[test]
+    fn test_global_move_keeps_unchanged_watchers() {
+        let tmp = TempDir::new().unwrap();
+        let repo1 = tmp.path().join("repo1");
+        let repo2 = tmp.path().join("repo2");
+        fs::create_dir(&repo1).unwrap();
+        fs::create_dir(&repo2).unwrap();
+        fs::write(repo1.join("README.md"), "one").unwrap();
+        fs::write(repo2.join("README.md"), "two").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        let write = |output: &Path, repo2_include: &str| {
+            fs::write(
+                &config_path,
+                format!(
+                    "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"\n{repo2_include}",
+                    output.display(),
+                    repo1.display(),
+                    repo2.display(),
+                ),
+            )
+            .unwrap();
+        };
+
+        write(&tmp.path().join("output"), "");
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+        let watcher_id = |engine: &MirrorEngine, name: &str| engine.watchers[name].id();
+        let repo1_watcher = watcher_id(&engine, "repo1");
+        let repo2_watcher = watcher_id(&engine, "repo2");
+
+        // Move the output dir; repo2 also gains a pattern
+        let new_output = tmp.path().join("moved");
+        write(&new_output, "include = [\"*.tex\"]");
+        engine.reload_config();
+
+        assert_eq!(watcher_id(&engine, "repo1"), repo1_watcher);
+        assert_ne!(watcher_id(&engine, "repo2"), repo2_watcher);
+        let new_output = engine.config.output_dir.clone();
+        assert!(new_output.join("repo1").join("README.md").exists());
+
+        // The kept watcher now mirrors into the new output dir
+        fs::write(repo1.join("NOTES.md"), "notes").unwrap();
+        let notes = new_output.join("repo1").join("NOTES.md");
+        for _ in 0..50 {
+            if notes.exists() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "notes");
+        assert!(!tmp.path().join("output").join("repo1").exists());
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_branch_switch_keeps_old_branch_mirror() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(repo.join(".git")).unwrap();
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let output = tmp.path().join("output");
+        let config_path = tmp.path().join("ulysses-link.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}/{{branch}}\"\n\n[[repos]]\npath = \"{}\"",
+                output.display(),
+                repo.display()
+            ),
+        )
+        .unwrap();
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+        let main_mirror = output.join("main").join("repo").join("README.md");
+        assert!(main_mirror.exists());
+
+        fs::write(repo.join(".git/HEAD"), "ref: refs/heads/next\n").unwrap();
+        engine.reload_config();
+        assert!(output.join("next").join("repo").join("README.md").exists());
+        assert!(main_mirror.exists());
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_missing_output_dir_pauses_without_pruning() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("NOTES.md"), "notes").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new(load_config(Some(&config_path)).unwrap());
+        engine.start_watching().unwrap();
+        let output = engine.config.output_dir.clone();
+        let tracked = |engine: &MirrorEngine| engine.manifests[&output].lock().unwrap().len();
+        assert_eq!(tracked(&engine), 2);
+
+        // The drive holding the output dir goes away
+        fs::remove_dir_all(&output).unwrap();
+        engine.check_output_dirs();
+        assert!(engine.watchers.is_empty());
+        assert!(engine.mirror_watchers.is_empty());
+
+        // A rescan neither prunes the manifest nor recreates the directory
+        let mut unlocked: HashMap<PathBuf, Manifest> = engine
+            .manifests
+            .iter()
+            .map(|(k, v)| (k.clone(), v.lock().unwrap().clone()))
+            .collect();
+        let result = full_scan(&engine.config, &mut unlocked);
+        assert_eq!(result.pruned, 0);
+        assert_eq!(unlocked[&output].len(), 2);
+        assert!(!output.exists());
+        assert_eq!(tracked(&engine), 2);
+
+        // Nor does a config reload
+        engine.reload_config();
+        assert!(!output.exists());
+        assert_eq!(tracked(&engine), 2);
+
+        // Once it is back, watching resumes and the mirror is restored
+        fs::create_dir(&output).unwrap();
+        engine.check_output_dirs();
+        assert!(engine.watchers.contains_key("my-repo"));
+        assert!(engine.mirror_watchers.contains_key(&output));
+        assert!(output.join("my-repo").join("README.md").exists());
+        assert_eq!(tracked(&engine), 2);
+
+        engine.stop();
+    }
+
+    #[test]
+    fn test_dry_run_writes_nothing() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let config_path = tmp.path().join("ulysses-link.toml");
+        write_config(&config_path, &repo, &output, true);
+
+        let mut engine = MirrorEngine::new_dry_run(load_config(Some(&config_path)).unwrap());
+        let result = engine.start_watching().unwrap();
+        let output = engine.config.output_dir.clone();
+        assert_eq!(result.created, 1);
+        assert!(engine.watchers.contains_key("my-repo"));
+        assert!(engine.mirror_watchers.is_empty());
+
+        // Source edits and config reloads are planned, never synced
+        fs::write(repo.join("NOTES.md"), "notes").unwrap();
+        thread::sleep(Duration::from_millis(1500));
+        engine.reload_config();
+        engine.check_output_dirs();
+        assert!(engine.watchers.contains_key("my-repo"));
+
+        engine.stop();
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 0);
+        assert!(engine.manifests[&output].lock().unwrap().is_empty());
+    }
+}
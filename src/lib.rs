@@ -1,9 +1,17 @@
+pub mod color;
 pub mod config;
+pub mod diff;
 pub mod engine;
 pub mod linker;
 pub mod manifest;
 pub mod matcher;
+pub mod notifier;
+pub mod pool;
+pub mod portable;
 pub mod scanner;
 pub mod service;
+pub mod stats;
+pub mod syncer;
 pub mod upgrade;
+pub mod verify;
 pub mod watcher;
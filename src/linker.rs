@@ -1,16 +1,36 @@
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 
 use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
 use tracing::{debug, error, info, warn};
 use walkdir::WalkDir;
 
-use crate::manifest::{hash_bytes, hash_file, Manifest, ManifestEntry};
+use crate::manifest::{
+    hash_bytes, hash_file, state_root_for, BaseCacheMode, ConflictInfo, ConflictWinner, Manifest,
+    ManifestEntry, MANIFEST_LOG_FILENAME,
+};
 
 const BASE_CACHE_DIR: &str = ".ulysses-link.d";
+/// Subdirectory of the base cache holding content-addressed blobs
+const BLOB_DIR: &str = ".blobs";
+/// First bytes of every gzip stream, marking a compressed base cache file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
 const MANIFEST_FILENAME: &str = ".ulysses-link";
-
-#[derive(Debug, PartialEq)]
+const GITIGNORE_FILENAME: &str = ".gitignore";
+/// Suffix of the temp files that merge and conflict writes go through before
+/// being renamed over the live file. Watchers ignore paths ending in it.
+pub const TEMP_SUFFIX: &str = ".ulysses-link.tmp";
+/// Start and end of the first line of each flat-inbox mirror file
+const SOURCE_HEADER_PREFIX: &str = "<!-- source: ";
+const SOURCE_HEADER_SUFFIX: &str = " -->";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SyncOutcome {
     Copied,
     AlreadyInSync,
@@ -20,18 +40,65 @@ pub enum SyncOutcome {
     Conflict,
 }
 
+/// Which way changes may flow between a source file and its mirror.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// Edits on either side propagate; divergent edits are three-way merged
+    #[default]
+    Bidirectional,
+    /// Source always wins: the source file is never written
+    SourceToMirror,
+}
+
 /// Sync a single file between source and mirror using three-way algorithm.
 ///
 /// The `rel_path` is relative to `output_dir` (e.g. "repo-name/docs/guide.md").
+/// With `SyncDirection::SourceToMirror`, a mirror that differs from source is
+/// overwritten instead of merged; that is always the case for a mirror file
+/// of a `read_only_source` repo, whose mirror edits are kept as a conflict
+/// file instead. When both sides changed and one is blank
+/// (empty or whitespace-only), the side with content wins; after a blank base,
+/// sides that differ only in whitespace keep the source version.
 pub fn sync_file(
     source: &Path,
     mirror: &Path,
     manifest: &mut Manifest,
     rel_path: &str,
     output_dir: &Path,
+    direction: SyncDirection,
+) -> Result<SyncOutcome> {
+    sync_file_with_source_hash(
+        source, mirror, manifest, rel_path, output_dir, direction, None,
+    )
+}
+
+/// `sync_file` with the source's `content_hash` already worked out, so a
+/// scan can hash sources in parallel and only make the decisions serially.
+/// With `None` the source is hashed here when needed.
+pub fn sync_file_with_source_hash(
+    source: &Path,
+    mirror: &Path,
+    manifest: &mut Manifest,
+    rel_path: &str,
+    output_dir: &Path,
+    direction: SyncDirection,
+    source_hash: Option<String>,
 ) -> Result<SyncOutcome> {
     let source_exists = source.exists();
     let mirror_exists = mirror.exists() && !mirror.is_symlink();
+    let read_only = manifest.is_read_only(mirror);
+    let direction = if read_only {
+        SyncDirection::SourceToMirror
+    } else {
+        direction
+    };
+
+    // Fifos, sockets, and device files are never synced: hashing or reading
+    // them can block forever or fail in confusing ways
+    if (source_exists && !source.is_file()) || (mirror_exists && !mirror.is_file()) {
+        debug!("Skipping non-regular file: {}", rel_path);
+        return Ok(SyncOutcome::Skipped);
+    }
 
     // New file: source exists, mirror doesn't
     if source_exists && !mirror_exists {
@@ -39,15 +106,19 @@ pub fn sync_file(
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create dirs for {}", mirror.display()))?;
         }
-        fs::copy(source, mirror).with_context(|| {
-            format!(
-                "Failed to copy {} -> {}",
-                source.display(),
-                mirror.display()
-            )
+        let mut hash = if is_decorated(manifest, mirror) {
+            copy_side(manifest, source, mirror)?;
+            hash_file(source)?
+        } else {
+            copy_and_hash(source, mirror)?
+        };
+        if !manifest.normalization().is_none() {
+            hash = content_hash(manifest, source)?;
+        }
+        verify_written(manifest, mirror, &hash, || {
+            copy_side(manifest, source, mirror)
         })?;
-        let hash = hash_file(source)?;
-        write_base(output_dir, rel_path, &fs::read_to_string(source)?)?;
+        write_base_from(manifest, output_dir, rel_path, source)?;
         manifest.insert(
             rel_path.to_string(),
             ManifestEntry {
@@ -65,10 +136,10 @@ pub fn sync_file(
 
     // Mirror exists but not in manifest — try to claim ownership
     if source_exists && mirror_exists && manifest.get(rel_path).is_none() {
-        let source_hash = hash_file(source)?;
-        let mirror_hash = hash_file(mirror)?;
+        let source_hash = source_hash.map_or_else(|| content_hash(manifest, source), Ok)?;
+        let mirror_hash = content_hash(manifest, mirror)?;
         if source_hash == mirror_hash {
-            write_base(output_dir, rel_path, &fs::read_to_string(source)?)?;
+            write_base_from(manifest, output_dir, rel_path, source)?;
             manifest.insert(
                 rel_path.to_string(),
                 ManifestEntry {
@@ -76,6 +147,7 @@ pub fn sync_file(
                     hash: source_hash,
                 },
             );
+            ensure_decoration(manifest, source, mirror)?;
             debug!("Claimed existing file: {}", rel_path);
             return Ok(SyncOutcome::Claimed);
         }
@@ -88,8 +160,8 @@ pub fn sync_file(
         let entry = manifest.get(rel_path).unwrap();
         let manifest_hash = entry.hash.clone();
 
-        let source_hash = hash_file(source)?;
-        let mirror_hash = hash_file(mirror)?;
+        let source_hash = source_hash.map_or_else(|| content_hash(manifest, source), Ok)?;
+        let mirror_hash = content_hash(manifest, mirror)?;
 
         if source_hash == mirror_hash {
             // In sync — update manifest hash if needed
@@ -101,23 +173,46 @@ pub fn sync_file(
                         hash: source_hash.clone(),
                     },
                 );
-                let content = fs::read_to_string(source)?;
-                write_base(output_dir, rel_path, &content)?;
+                write_base_from(manifest, output_dir, rel_path, source)?;
             }
+            ensure_decoration(manifest, source, mirror)?;
             return Ok(SyncOutcome::AlreadyInSync);
         }
 
+        if direction == SyncDirection::SourceToMirror || mirror_hash == manifest_hash {
+            // Mirror unchanged (or read-only), source changed → copy source → mirror.
+            // A read-only source keeps mirror edits as a conflict file beside it.
+            let kept_edit = if read_only && mirror_hash != manifest_hash {
+                Some(save_conflict(mirror, &fs::read(mirror)?)?)
+            } else {
+                None
+            };
+            copy_verified(manifest, source, mirror, &source_hash)?;
+            write_base_from(manifest, output_dir, rel_path, source)?;
+            manifest.insert(
+                rel_path.to_string(),
+                ManifestEntry {
+                    source: source.to_path_buf(),
+                    hash: source_hash,
+                },
+            );
+            if let Some(conflict_file) = kept_edit {
+                manifest.note_conflict(ConflictInfo {
+                    rel_path: rel_path.to_string(),
+                    winner: ConflictWinner::Source,
+                    conflict_file,
+                });
+                warn!("Reverted mirror edit to read-only source: {}", rel_path);
+                return Ok(SyncOutcome::Conflict);
+            }
+            debug!("Synced source change to mirror: {}", rel_path);
+            return Ok(SyncOutcome::Copied);
+        }
+
         if source_hash == manifest_hash {
             // Source unchanged, mirror changed → copy mirror → source
-            fs::copy(mirror, source).with_context(|| {
-                format!(
-                    "Failed to copy {} -> {}",
-                    mirror.display(),
-                    source.display()
-                )
-            })?;
-            let content = fs::read_to_string(mirror)?;
-            write_base(output_dir, rel_path, &content)?;
+            copy_verified(manifest, mirror, source, &mirror_hash)?;
+            write_base_from(manifest, output_dir, rel_path, mirror)?;
             manifest.insert(
                 rel_path.to_string(),
                 ManifestEntry {
@@ -129,45 +224,72 @@ pub fn sync_file(
             return Ok(SyncOutcome::Copied);
         }
 
-        if mirror_hash == manifest_hash {
-            // Mirror unchanged, source changed → copy source → mirror
-            fs::copy(source, mirror).with_context(|| {
-                format!(
-                    "Failed to copy {} -> {}",
-                    source.display(),
-                    mirror.display()
-                )
-            })?;
-            let content = fs::read_to_string(source)?;
-            write_base(output_dir, rel_path, &content)?;
+        // Both changed. Blank files (empty or whitespace-only) get no merge:
+        // a side that was blanked loses to a side with content, and when the
+        // base was blank, sides differing only in whitespace are in sync.
+        let base_content = read_base(manifest, output_dir, rel_path)?;
+        let source_text = fs::read_to_string(source)?;
+        let mirror_text = read_side_text(manifest, mirror)?;
+        let base_blank = match base_content {
+            Some(ref base) => is_blank(base),
+            None => manifest_hash == hash_bytes(b""),
+        };
+        let keep_source = if is_blank(&mirror_text) && !is_blank(&source_text) {
+            Some(true)
+        } else if is_blank(&source_text) && !is_blank(&mirror_text) {
+            Some(false)
+        } else if base_blank && source_text.trim() == mirror_text.trim() {
+            Some(true)
+        } else {
+            None
+        };
+        if let Some(keep_source) = keep_source {
+            let (from, to, hash) = if keep_source {
+                (source, mirror, source_hash)
+            } else {
+                (mirror, source, mirror_hash)
+            };
+            copy_verified(manifest, from, to, &hash)?;
+            let content = if keep_source {
+                source_text
+            } else {
+                mirror_text
+            };
+            write_base(manifest, output_dir, rel_path, &content)?;
             manifest.insert(
                 rel_path.to_string(),
                 ManifestEntry {
                     source: source.to_path_buf(),
-                    hash: source_hash,
+                    hash,
                 },
             );
-            debug!("Synced source change to mirror: {}", rel_path);
+            debug!("Synced {} over blank side: {}", from.display(), rel_path);
             return Ok(SyncOutcome::Copied);
         }
 
-        // Both changed — attempt three-way merge
-        let base_content = read_base(output_dir, rel_path)?;
+        // Attempt a three-way merge of the content as written; normalization
+        // only decided that both sides changed
         if let Some(base) = base_content {
-            let source_content = fs::read_to_string(source)?;
-            let mirror_content = fs::read_to_string(mirror)?;
-
-            let merge_result = diffy::merge(&base, &source_content, &mirror_content);
+            let merge_result = diffy::merge(&base, &source_text, &mirror_text);
             match merge_result {
                 Ok(merged) => {
-                    fs::write(source, &merged).with_context(|| {
-                        format!("Failed to write merged result to {}", source.display())
-                    })?;
-                    fs::write(mirror, &merged).with_context(|| {
-                        format!("Failed to write merged result to {}", mirror.display())
-                    })?;
-                    let merged_hash = hash_bytes(merged.as_bytes());
-                    write_base(output_dir, rel_path, &merged)?;
+                    let merged_hash =
+                        hash_bytes(manifest.normalization().apply(&merged).as_bytes());
+                    let write_source = || {
+                        write_atomic(source, merged.as_bytes()).with_context(|| {
+                            format!("Failed to write merged result to {}", source.display())
+                        })
+                    };
+                    write_source()?;
+                    verify_written(manifest, source, &merged_hash, write_source)?;
+                    let write_mirror = || {
+                        write_side(manifest, mirror, source, merged.as_bytes()).with_context(|| {
+                            format!("Failed to write merged result to {}", mirror.display())
+                        })
+                    };
+                    write_mirror()?;
+                    verify_written(manifest, mirror, &merged_hash, write_mirror)?;
+                    write_base(manifest, output_dir, rel_path, &merged)?;
                     manifest.insert(
                         rel_path.to_string(),
                         ManifestEntry {
@@ -193,6 +315,480 @@ pub fn sync_file(
     Ok(SyncOutcome::Skipped)
 }
 
+/// Empty or whitespace-only content, which three-way merges handle poorly.
+fn is_blank(content: &str) -> bool {
+    content.trim().is_empty()
+}
+
+/// What `sync_file` would do to this pair, without writing anything: no
+/// copies, no base cache updates, no manifest changes.
+pub fn plan_sync(
+    source: &Path,
+    mirror: &Path,
+    manifest: &Manifest,
+    rel_path: &str,
+    output_dir: &Path,
+    direction: SyncDirection,
+) -> Result<SyncOutcome> {
+    let source_exists = source.exists();
+    let mirror_exists = mirror.exists() && !mirror.is_symlink();
+
+    if (source_exists && !source.is_file()) || (mirror_exists && !mirror.is_file()) {
+        return Ok(SyncOutcome::Skipped);
+    }
+    if !source_exists {
+        return Ok(SyncOutcome::Skipped);
+    }
+    if !mirror_exists {
+        return Ok(SyncOutcome::Copied);
+    }
+
+    let source_hash = content_hash(manifest, source)?;
+    let mirror_hash = content_hash(manifest, mirror)?;
+    let Some(entry) = manifest.get(rel_path) else {
+        return Ok(if source_hash == mirror_hash {
+            SyncOutcome::Claimed
+        } else {
+            SyncOutcome::Skipped
+        });
+    };
+    if source_hash == mirror_hash {
+        return Ok(SyncOutcome::AlreadyInSync);
+    }
+    if manifest.is_read_only(mirror) {
+        return Ok(if mirror_hash == entry.hash {
+            SyncOutcome::Copied
+        } else {
+            SyncOutcome::Conflict
+        });
+    }
+    if direction == SyncDirection::SourceToMirror
+        || mirror_hash == entry.hash
+        || source_hash == entry.hash
+    {
+        return Ok(SyncOutcome::Copied);
+    }
+
+    // Both changed: blank sides are copied over, anything else is merged
+    let base_content = read_base(manifest, output_dir, rel_path)?;
+    let source_text = fs::read_to_string(source)?;
+    let mirror_text = read_side_text(manifest, mirror)?;
+    let base_blank = match base_content {
+        Some(ref base) => is_blank(base),
+        None => entry.hash == hash_bytes(b""),
+    };
+    if is_blank(&source_text) != is_blank(&mirror_text)
+        || (base_blank && source_text.trim() == mirror_text.trim())
+    {
+        return Ok(SyncOutcome::Copied);
+    }
+    let Some(base) = base_content else {
+        return Ok(SyncOutcome::Conflict);
+    };
+    Ok(match diffy::merge(&base, &source_text, &mirror_text) {
+        Ok(_) => SyncOutcome::Merged,
+        Err(_) => SyncOutcome::Conflict,
+    })
+}
+
+/// Log what a planned sync of `rel_path` would do, for dry runs.
+pub fn log_planned(rel_path: &str, outcome: SyncOutcome) {
+    match outcome {
+        SyncOutcome::Copied => info!("Would sync {}", rel_path),
+        SyncOutcome::Merged => info!("Would merge {}", rel_path),
+        SyncOutcome::Claimed => info!("Would claim existing mirror {}", rel_path),
+        SyncOutcome::Conflict => info!("Would write a conflict file for {}", rel_path),
+        SyncOutcome::Skipped => debug!("Would skip {}", rel_path),
+        SyncOutcome::AlreadyInSync => debug!("Already in sync: {}", rel_path),
+    }
+}
+
+/// Overwrite `mirror` with `source` regardless of mirror-side edits, then
+/// refresh the base cache and manifest.
+///
+/// Returns `Conflict` when the mirror held edits that never reached source;
+/// with `keep_conflict` those are saved as a conflict file first. Mirror files
+/// not in the manifest are only claimed when identical, as in `sync_file`.
+pub fn force_source(
+    source: &Path,
+    mirror: &Path,
+    manifest: &mut Manifest,
+    rel_path: &str,
+    output_dir: &Path,
+    keep_conflict: bool,
+) -> Result<SyncOutcome> {
+    let mirror_exists = mirror.exists() && !mirror.is_symlink();
+    let Some(entry) = manifest.get(rel_path).cloned() else {
+        return sync_file(
+            source,
+            mirror,
+            manifest,
+            rel_path,
+            output_dir,
+            SyncDirection::SourceToMirror,
+        );
+    };
+    if !source.is_file() || (mirror_exists && !mirror.is_file()) {
+        debug!("Skipping non-regular file: {}", rel_path);
+        return Ok(SyncOutcome::Skipped);
+    }
+
+    let source_hash = content_hash(manifest, source)?;
+    let mirror_hash = if mirror_exists {
+        Some(content_hash(manifest, mirror)?)
+    } else {
+        None
+    };
+
+    let mut outcome = SyncOutcome::AlreadyInSync;
+    if mirror_hash.as_deref() != Some(source_hash.as_str()) {
+        let mirror_edited = mirror_hash.is_some_and(|h| h != entry.hash);
+        if mirror_edited {
+            if keep_conflict {
+                let conflict_file = save_conflict(mirror, &fs::read(mirror)?)?;
+                manifest.note_conflict(ConflictInfo {
+                    rel_path: rel_path.to_string(),
+                    winner: ConflictWinner::Source,
+                    conflict_file,
+                });
+            }
+            outcome = SyncOutcome::Conflict;
+        } else {
+            outcome = SyncOutcome::Copied;
+        }
+        if let Some(parent) = mirror.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create dirs for {}", mirror.display()))?;
+        }
+        copy_side(manifest, source, mirror)?;
+        debug!("Reset mirror to source: {}", rel_path);
+    }
+
+    write_base_from(manifest, output_dir, rel_path, source)?;
+    manifest.insert(
+        rel_path.to_string(),
+        ManifestEntry {
+            source: source.to_path_buf(),
+            hash: source_hash,
+        },
+    );
+    Ok(outcome)
+}
+
+/// Hash of a file's content as compared during sync: the raw bytes, or the
+/// normalized text when the manifest has `Normalization` set. Files that
+/// aren't UTF-8 are hashed raw. A decorated mirror file is hashed without
+/// its source header and folder keywords.
+pub(crate) fn content_hash(manifest: &Manifest, path: &Path) -> Result<String> {
+    let normalize = manifest.normalization();
+    if normalize.is_none() && !is_decorated(manifest, path) {
+        return hash_file(path);
+    }
+    let content = read_side(manifest, path)?;
+    match std::str::from_utf8(&content) {
+        Ok(text) => Ok(hash_bytes(normalize.apply(text).as_bytes())),
+        Err(_) => Ok(hash_bytes(&content)),
+    }
+}
+
+/// Whether the file at `path` is a mirror file carrying lines its source
+/// doesn't have: a flat-inbox source header or folder keywords.
+fn is_decorated(manifest: &Manifest, path: &Path) -> bool {
+    manifest.inbox_for(path).is_some()
+        || manifest.keyword_tree_for(path).is_some() && takes_keywords(path)
+}
+
+/// Whether folder keywords go on the file at `path`: only Markdown, where
+/// Ulysses reads them. In other text files they would just be a stray line.
+fn takes_keywords(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md") || ext.eq_ignore_ascii_case("markdown"))
+}
+
+/// The header line starting each flat-inbox mirror file, e.g.
+/// `<!-- source: docs/api/auth.md -->`, naming `source` relative to its
+/// repo. `None` when `mirror` isn't in a flat inbox.
+fn source_header(manifest: &Manifest, mirror: &Path, source: &Path) -> Option<String> {
+    let inbox = manifest.inbox_for(mirror)?;
+    let path = source.strip_prefix(&inbox.repo_path).unwrap_or(source);
+    Some(format!(
+        "{SOURCE_HEADER_PREFIX}{}{SOURCE_HEADER_SUFFIX}\n",
+        path.to_string_lossy()
+    ))
+}
+
+/// Split a leading source header off `content`: the source path it names and
+/// the content after it.
+pub fn split_source_header(content: &[u8]) -> Option<(&str, &[u8])> {
+    let end = content.iter().position(|&b| b == b'\n')?;
+    let line = std::str::from_utf8(&content[..end]).ok()?;
+    let path = line
+        .trim_end_matches('\r')
+        .strip_prefix(SOURCE_HEADER_PREFIX)?
+        .strip_suffix(SOURCE_HEADER_SUFFIX)?;
+    Some((path, &content[end + 1..]))
+}
+
+/// The Ulysses keywords for the mirror file at `mirror`, one per folder
+/// between its keyword tree's root and the file: `#docs #api` for
+/// `docs/api/auth.md`. Whitespace in a folder name becomes `-`, as a keyword
+/// ends at whitespace. Empty outside keyword trees, at a tree's root, and for
+/// files other than Markdown.
+fn folder_keywords(manifest: &Manifest, mirror: &Path) -> Vec<String> {
+    match manifest.keyword_tree_for(mirror) {
+        Some(tree) if takes_keywords(mirror) => keywords_under(&tree.mirror_root, mirror),
+        _ => Vec::new(),
+    }
+}
+
+/// The folder keywords of `mirror` in a keyword tree rooted at `mirror_root`.
+fn keywords_under(mirror_root: &Path, mirror: &Path) -> Vec<String> {
+    let Some(dir) = mirror.strip_prefix(mirror_root).ok().and_then(Path::parent) else {
+        return Vec::new();
+    };
+    dir.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => {
+                let name = name.to_string_lossy();
+                Some(format!(
+                    "#{}",
+                    name.split_whitespace().collect::<Vec<_>>().join("-")
+                ))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split `content` at its first line break: the first line without it, and
+/// what follows, `None` when there is no line break.
+fn split_first_line(content: &[u8]) -> (&[u8], Option<&[u8]>) {
+    match content.iter().position(|&b| b == b'\n') {
+        Some(end) => (&content[..end], Some(&content[end + 1..])),
+        None => (content, None),
+    }
+}
+
+/// The keywords on a line holding nothing but keywords, e.g. `#draft #api`.
+fn line_keywords(line: &[u8]) -> Option<Vec<&str>> {
+    let line = std::str::from_utf8(line).ok()?;
+    let keywords: Vec<&str> = line.split_whitespace().collect();
+    let is_keyword =
+        |word: &&str| word.len() > 1 && word.starts_with('#') && !word[1..].starts_with('#');
+    (!keywords.is_empty() && keywords.iter().all(is_keyword)).then_some(keywords)
+}
+
+/// `content` starting with `keywords`: added to the end of a first line that
+/// already holds keywords, skipping the ones it has, or else on a new first
+/// line. Content that isn't UTF-8 is left as is.
+fn add_keywords(content: &[u8], keywords: &[String]) -> Vec<u8> {
+    if keywords.is_empty() || std::str::from_utf8(content).is_err() {
+        return content.to_vec();
+    }
+    let (line, rest) = split_first_line(content);
+    let Some(present) = line_keywords(line) else {
+        return [keywords.join(" ").as_bytes(), b"\n", content].concat();
+    };
+    let missing: Vec<&str> = keywords
+        .iter()
+        .map(String::as_str)
+        .filter(|k| !present.contains(k))
+        .collect();
+    if missing.is_empty() {
+        return content.to_vec();
+    }
+    let (line, cr) = match line.strip_suffix(b"\r") {
+        Some(line) => (line, "\r"),
+        None => (line, ""),
+    };
+    let line = String::from_utf8_lossy(line);
+    let line = format!("{} {}{cr}", line.trim_end(), missing.join(" "));
+    match rest {
+        Some(rest) => [line.as_bytes(), b"\n", rest].concat(),
+        None => line.into_bytes(),
+    }
+}
+
+/// Undo `add_keywords`: drop `keywords` from `content`'s first line, except
+/// the ones the first line of `source`, the content they were added to, has
+/// itself. A line left with no keywords goes entirely; one left with exactly
+/// the source's keywords is restored to the source's line as written.
+fn remove_keywords(content: &[u8], keywords: &[String], source: &[u8]) -> Vec<u8> {
+    let (line, rest) = split_first_line(content);
+    let Some(present) = line_keywords(line) else {
+        return content.to_vec();
+    };
+    let (source_line, _) = split_first_line(source);
+    let source_keywords = line_keywords(source_line).unwrap_or_default();
+    let kept: Vec<&str> = present
+        .iter()
+        .copied()
+        .filter(|k| source_keywords.contains(k) || !keywords.iter().any(|w| w == k))
+        .collect();
+    if kept.len() == present.len() {
+        return content.to_vec();
+    }
+    if kept.is_empty() {
+        return rest.unwrap_or_default().to_vec();
+    }
+    let line = if kept == source_keywords {
+        source_line.to_vec()
+    } else {
+        let cr = if line.ends_with(b"\r") { "\r" } else { "" };
+        format!("{}{cr}", kept.join(" ")).into_bytes()
+    };
+    match rest {
+        Some(rest) => [line.as_slice(), b"\n", rest].concat(),
+        None => line,
+    }
+}
+
+/// The first line of the file at `path`, empty when it doesn't exist.
+fn read_first_line(path: &Path) -> std::io::Result<Vec<u8>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut line = Vec::new();
+    BufReader::new(file).read_until(b'\n', &mut line)?;
+    Ok(line)
+}
+
+/// `content` of the file at `path` as synced to the other side: a decorated
+/// mirror file without its source header and folder keywords, anything else
+/// as is. Keywords on the first line of the content last synced, its base,
+/// are kept; without a base, those on the source file's first line are.
+fn undecorate(manifest: &Manifest, path: &Path, content: Vec<u8>) -> Result<Vec<u8>> {
+    let mut content = content;
+    if manifest.inbox_for(path).is_some() {
+        if let Some((_, rest)) = split_source_header(&content) {
+            content = rest.to_vec();
+        }
+    }
+    if let Some(tree) = manifest.keyword_tree_for(path) {
+        let keywords = folder_keywords(manifest, path);
+        if !keywords.is_empty() {
+            let rel_path = path
+                .strip_prefix(&tree.output_dir)
+                .unwrap_or(path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let synced = match read_base(manifest, &tree.output_dir, &rel_path)? {
+                Some(base) => base.into_bytes(),
+                None => {
+                    let rel = path.strip_prefix(&tree.mirror_root).unwrap_or(path);
+                    read_first_line(&tree.source_root.join(rel))?
+                }
+            };
+            content = remove_keywords(&content, &keywords, &synced);
+        }
+    }
+    Ok(content)
+}
+
+/// Synced `content` as written to `path`: behind the source header when
+/// `path` is a flat-inbox mirror file of `source`, and starting with its
+/// folder keywords when it's in a keyword tree.
+fn decorate(manifest: &Manifest, path: &Path, source: &Path, content: &[u8]) -> Vec<u8> {
+    let content = add_keywords(content, &folder_keywords(manifest, path));
+    match source_header(manifest, path, source) {
+        Some(header) => [header.as_bytes(), &content].concat(),
+        None => content,
+    }
+}
+
+/// A file's content as synced to the other side; see `undecorate`.
+fn read_side(manifest: &Manifest, path: &Path) -> Result<Vec<u8>> {
+    let content = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if !is_decorated(manifest, path) {
+        return Ok(content);
+    }
+    undecorate(manifest, path, content)
+}
+
+/// `read_side` as text. Content that isn't UTF-8 is an `InvalidData` error,
+/// as from `fs::read_to_string`.
+pub(crate) fn read_side_text(manifest: &Manifest, path: &Path) -> std::io::Result<String> {
+    if !is_decorated(manifest, path) {
+        return fs::read_to_string(path);
+    }
+    let content = undecorate(manifest, path, fs::read(path)?).map_err(std::io::Error::other)?;
+    String::from_utf8(content).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Atomically write synced `content` to `path`, decorated when `path` is a
+/// decorated mirror file of `source`.
+fn write_side(manifest: &Manifest, path: &Path, source: &Path, content: &[u8]) -> Result<()> {
+    write_atomic(path, &decorate(manifest, path, source, content))
+}
+
+/// Copy one side of a pair over the other. A decorated mirror file loses its
+/// decoration when copied from and gains it when copied to.
+fn copy_side(manifest: &Manifest, from: &Path, to: &Path) -> Result<()> {
+    if !is_decorated(manifest, from) && !is_decorated(manifest, to) {
+        fs::copy(from, to)
+            .with_context(|| format!("Failed to copy {} -> {}", from.display(), to.display()))?;
+        return Ok(());
+    }
+    copy_atomic(manifest, from, to)
+}
+
+/// `copy_side`, then `verify_written` against `expected`, the content hash
+/// of `from`.
+fn copy_verified(manifest: &Manifest, from: &Path, to: &Path, expected: &str) -> Result<()> {
+    copy_side(manifest, from, to)?;
+    verify_written(manifest, to, expected, || copy_side(manifest, from, to))
+}
+
+/// With `verify_after_write` on, re-hash `path` just written and compare it
+/// with `expected`, its content hash as meant to be written. A mismatch is
+/// written again with `rewrite` and checked once more before it's an error.
+fn verify_written(
+    manifest: &Manifest,
+    path: &Path,
+    expected: &str,
+    rewrite: impl Fn() -> Result<()>,
+) -> Result<()> {
+    if !manifest.verify_after_write() || content_hash(manifest, path)? == expected {
+        return Ok(());
+    }
+    warn!(
+        "Written file doesn't match what was copied, writing it again: {}",
+        path.display()
+    );
+    rewrite()?;
+    let actual = content_hash(manifest, path)?;
+    if actual != expected {
+        anyhow::bail!(
+            "{} still doesn't match what was written after a retry (hash {actual}, expected {expected})",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Put back the source header or folder keywords of a decorated mirror file
+/// in sync with `source` that lacks them or has stale ones, e.g. after
+/// `flat_inbox` was turned on or the header was deleted in the editor.
+fn ensure_decoration(manifest: &Manifest, source: &Path, mirror: &Path) -> Result<()> {
+    if !is_decorated(manifest, mirror) {
+        return Ok(());
+    }
+    let content =
+        fs::read(mirror).with_context(|| format!("Failed to read {}", mirror.display()))?;
+    let body = read_side(manifest, mirror)?;
+    let decorated = decorate(manifest, mirror, source, &body);
+    if decorated == content {
+        return Ok(());
+    }
+    debug!("Restoring mirror decoration: {}", mirror.display());
+    write_atomic(mirror, &decorated)
+}
+
 /// Resolve a conflict by keeping the newest version and saving the older as .conflict_<timestamp>.
 fn resolve_conflict(
     source: &Path,
@@ -208,14 +804,13 @@ fn resolve_conflict(
         .and_then(|m| m.modified())
         .unwrap_or(std::time::UNIX_EPOCH);
 
-    if source_mtime >= mirror_mtime {
+    let (winner, conflict_file) = if source_mtime >= mirror_mtime {
         // Keep source, save mirror as conflict (in mirror dir)
-        let mirror_content = fs::read_to_string(mirror)?;
-        save_conflict(mirror, &mirror_content)?;
-        fs::copy(source, mirror)?;
-        let hash = hash_file(source)?;
+        let conflict_file = save_conflict(mirror, &fs::read(mirror)?)?;
+        copy_atomic(manifest, source, mirror)?;
         let content = fs::read_to_string(source)?;
-        write_base(output_dir, rel_path, &content)?;
+        let hash = hash_bytes(manifest.normalization().apply(&content).as_bytes());
+        write_base(manifest, output_dir, rel_path, &content)?;
         manifest.insert(
             rel_path.to_string(),
             ManifestEntry {
@@ -223,14 +818,14 @@ fn resolve_conflict(
                 hash,
             },
         );
+        (ConflictWinner::Source, conflict_file)
     } else {
         // Keep mirror, save source as conflict (in source dir)
-        let source_content = fs::read_to_string(source)?;
-        save_conflict(source, &source_content)?;
-        fs::copy(mirror, source)?;
-        let hash = hash_file(mirror)?;
-        let content = fs::read_to_string(mirror)?;
-        write_base(output_dir, rel_path, &content)?;
+        let conflict_file = save_conflict(source, &fs::read(source)?)?;
+        copy_atomic(manifest, mirror, source)?;
+        let content = read_side_text(manifest, mirror)?;
+        let hash = hash_bytes(manifest.normalization().apply(&content).as_bytes());
+        write_base(manifest, output_dir, rel_path, &content)?;
         manifest.insert(
             rel_path.to_string(),
             ManifestEntry {
@@ -238,14 +833,31 @@ fn resolve_conflict(
                 hash,
             },
         );
-    }
+        (ConflictWinner::Mirror, conflict_file)
+    };
 
+    manifest.note_conflict(ConflictInfo {
+        rel_path: rel_path.to_string(),
+        winner,
+        conflict_file,
+    });
     warn!("Conflict resolved for {}: kept newest version", rel_path);
     Ok(SyncOutcome::Conflict)
 }
 
+/// Whether a tracked mirror file should be deleted along with its entry.
+/// Protected files stay on disk and simply stop being tracked.
+fn removable_mirror(mirror: &Path, rel_path: &str, manifest: &Manifest) -> bool {
+    if manifest.is_protected(rel_path) {
+        debug!("Keeping protected mirror file: {}", mirror.display());
+        return false;
+    }
+    mirror.exists() && !mirror.is_symlink()
+}
+
 /// Called when a source file is deleted: removes mirror + base cache + manifest entry.
 pub fn propagate_delete(
+    mirror_subdir: &str,
     rel_path: &str,
     manifest: &mut Manifest,
     output_dir: &Path,
@@ -255,26 +867,212 @@ pub fn propagate_delete(
     }
 
     let mirror = output_dir.join(rel_path);
-    if mirror.exists() && !mirror.is_symlink() {
+    if removable_mirror(&mirror, rel_path, manifest) {
         fs::remove_file(&mirror)
             .with_context(|| format!("Failed to remove mirror {}", mirror.display()))?;
         debug!("Removed mirror file: {}", mirror.display());
     }
 
-    remove_base(output_dir, rel_path)?;
+    remove_base(manifest, output_dir, rel_path)?;
     manifest.remove(rel_path);
 
-    // Prune empty parent dirs up to the repo name dir
+    // Prune empty parent dirs up to the repo's mirror root
     if let Some(parent) = mirror.parent() {
-        let repo_name = rel_path.split('/').next().unwrap_or("");
-        let stop_at = output_dir.join(repo_name);
+        let stop_at = output_dir.join(mirror_subdir);
         prune_empty_parents(parent, &stop_at);
     }
 
     Ok(true)
 }
 
+/// Remove the mirror of a source file that is still present but should no
+/// longer be mirrored. A mirror edited since the last sync is first saved as
+/// a conflict file, so the edits aren't lost. Returns false if the source
+/// was not tracked.
+pub fn untrack_source(
+    mirror_subdir: &str,
+    source: &Path,
+    manifest: &mut Manifest,
+    output_dir: &Path,
+) -> Result<bool> {
+    let Some(rel_path) = manifest.key_for_source(source).map(str::to_string) else {
+        return Ok(false);
+    };
+    let mirror = output_dir.join(&rel_path);
+    if removable_mirror(&mirror, &rel_path, manifest)
+        && mirror.is_file()
+        && manifest.get(&rel_path).map(|e| &e.hash) != Some(&content_hash(manifest, &mirror)?)
+    {
+        let conflict_file = save_conflict(&mirror, &fs::read(&mirror)?)?;
+        warn!(
+            "Kept unsynced edits to untracked {} in {}",
+            rel_path,
+            conflict_file.display()
+        );
+    }
+    propagate_delete(mirror_subdir, &rel_path, manifest, output_dir)
+}
+
+/// Take over the entry of a flat-inbox mirror file renamed in the editor:
+/// an untracked file at `rel_path` whose source header names a tracked source
+/// with no mirror file left at its key. The entry and its base move to
+/// `rel_path`. Returns whether the file was adopted.
+pub fn adopt_renamed_mirror(
+    rel_path: &str,
+    manifest: &mut Manifest,
+    output_dir: &Path,
+) -> Result<bool> {
+    let mirror = output_dir.join(rel_path);
+    if manifest.get(rel_path).is_some() || !mirror.is_file() {
+        return Ok(false);
+    }
+    let Some(inbox) = manifest.inbox_for(&mirror) else {
+        return Ok(false);
+    };
+    let content =
+        fs::read(&mirror).with_context(|| format!("Failed to read {}", mirror.display()))?;
+    let Some((source_rel, _)) = split_source_header(&content) else {
+        return Ok(false);
+    };
+    let source = inbox.repo_path.join(source_rel);
+    let Some(old_key) = manifest.key_for_source(&source).map(str::to_string) else {
+        return Ok(false);
+    };
+    if output_dir.join(&old_key).exists() {
+        return Ok(false);
+    }
+
+    let base = read_base(manifest, output_dir, &old_key)?;
+    remove_base(manifest, output_dir, &old_key)?;
+    if let Some(base) = base {
+        write_base(manifest, output_dir, rel_path, &base)?;
+    }
+    let entry = manifest.remove(&old_key).unwrap();
+    manifest.insert(rel_path.to_string(), entry);
+    debug!("Adopted renamed mirror file: {} -> {}", old_key, rel_path);
+    Ok(true)
+}
+
+/// Drop the source header and folder keywords from every tracked mirror
+/// file of the repo at `mirror_subdir`, while `manifest` still knows it as
+/// decorated.
+pub fn strip_decorations(
+    manifest: &Manifest,
+    output_dir: &Path,
+    mirror_subdir: &str,
+) -> Result<()> {
+    for (rel_path, _) in manifest.entries_for_repo(mirror_subdir) {
+        let mirror = output_dir.join(rel_path);
+        if !is_decorated(manifest, &mirror) || !mirror.is_file() {
+            continue;
+        }
+        let content =
+            fs::read(&mirror).with_context(|| format!("Failed to read {}", mirror.display()))?;
+        let body = read_side(manifest, &mirror)?;
+        if body != content {
+            write_atomic(&mirror, &body)?;
+        }
+    }
+    Ok(())
+}
+
+/// Drop the decorations an earlier config left on the tracked mirror files
+/// of the repo at `mirror_subdir`, which the config no longer decorates, so a
+/// scan doesn't read them as mirror edits and write them into source: a
+/// flat-inbox source header naming the file's own source relative to
+/// `repo_path`, and a first line holding all of the file's folder keywords.
+/// Keywords the last synced content starts with are kept. Returns how many
+/// files were rewritten.
+pub fn strip_stale_decorations(
+    manifest: &Manifest,
+    output_dir: &Path,
+    mirror_subdir: &str,
+    repo_path: &Path,
+) -> Result<u32> {
+    let mut stripped = 0;
+    for (rel_path, entry) in manifest.entries_for_repo(mirror_subdir) {
+        let mirror = output_dir.join(rel_path);
+        if !mirror.is_file() {
+            continue;
+        }
+        let line = read_first_line(&mirror)
+            .with_context(|| format!("Failed to read {}", mirror.display()))?;
+        let source_line = read_first_line(&entry.source).unwrap_or_default();
+        let source_rel = entry
+            .source
+            .strip_prefix(repo_path)
+            .unwrap_or(&entry.source);
+        let stale_header = manifest.inbox_for(&mirror).is_none()
+            && line != source_line
+            && split_source_header(&line)
+                .is_some_and(|(header_source, _)| header_source == source_rel.to_string_lossy());
+        // Files other than Markdown lose keywords added before they were
+        // limited to Markdown
+        let keywords = if is_decorated(manifest, &mirror) {
+            Vec::new()
+        } else {
+            keywords_under(&output_dir.join(mirror_subdir), &mirror)
+        };
+        let stale_keywords = !keywords.is_empty()
+            && line_keywords(&line)
+                .is_some_and(|present| keywords.iter().all(|k| present.contains(&k.as_str())));
+        if !stale_header && !stale_keywords {
+            continue;
+        }
+
+        let original =
+            fs::read(&mirror).with_context(|| format!("Failed to read {}", mirror.display()))?;
+        let mut content = original.clone();
+        if stale_header {
+            content.drain(..line.len());
+        }
+        if stale_keywords {
+            let synced = match read_base(manifest, output_dir, rel_path)? {
+                Some(base) => base.into_bytes(),
+                None => source_line,
+            };
+            content = remove_keywords(&content, &keywords, &synced);
+        }
+        if content == original {
+            continue;
+        }
+        write_atomic(&mirror, &content)?;
+        debug!("Dropped stale decorations: {}", rel_path);
+        stripped += 1;
+    }
+    Ok(stripped)
+}
+
+/// Set the modification time of every tracked mirror file of the repo at
+/// `mirror_subdir` to now, leaving content alone, so Ulysses picks up changes
+/// it missed. Returns how many files were touched. A running service's
+/// mirror watcher may see the touch, but the content still matches the
+/// manifest, so nothing syncs back to source.
+pub fn touch_repo_mirror(
+    manifest: &Manifest,
+    output_dir: &Path,
+    mirror_subdir: &str,
+) -> Result<usize> {
+    let now = SystemTime::now();
+    let mut touched = 0;
+    for (rel_path, _) in manifest.entries_for_repo(mirror_subdir) {
+        let mirror = output_dir.join(rel_path);
+        if !mirror.is_file() {
+            continue;
+        }
+        fs::File::options()
+            .write(true)
+            .open(&mirror)
+            .and_then(|file| file.set_modified(now))
+            .with_context(|| format!("Failed to touch {}", mirror.display()))?;
+        touched += 1;
+    }
+    Ok(touched)
+}
+
 /// Called when a mirror file is deleted: removes source + base cache + manifest entry.
+/// A protected mirror file, or one of a `read_only_source` repo, is left
+/// tracked, so the next scan restores it.
 pub fn propagate_mirror_delete(
     rel_path: &str,
     manifest: &mut Manifest,
@@ -284,6 +1082,20 @@ pub fn propagate_mirror_delete(
         Some(e) => e.clone(),
         None => return Ok(false),
     };
+    if manifest.is_protected(rel_path) {
+        debug!(
+            "Not propagating delete of protected mirror file: {}",
+            rel_path
+        );
+        return Ok(false);
+    }
+    if manifest.is_read_only(&output_dir.join(rel_path)) {
+        debug!(
+            "Not propagating delete to read-only source: {}",
+            entry.source.display()
+        );
+        return Ok(false);
+    }
 
     if entry.source.exists() {
         fs::remove_file(&entry.source)
@@ -291,58 +1103,56 @@ pub fn propagate_mirror_delete(
         debug!("Removed source file: {}", entry.source.display());
     }
 
-    remove_base(output_dir, rel_path)?;
+    remove_base(manifest, output_dir, rel_path)?;
     manifest.remove(rel_path);
     Ok(true)
 }
 
 /// Remove all mirror files for a repo (only those in manifest), plus base cache entries.
 pub fn remove_repo_mirror(
-    repo_name: &str,
+    mirror_subdir: &str,
     output_dir: &Path,
     manifest: &mut Manifest,
 ) -> Result<()> {
     let entries: Vec<String> = manifest
-        .entries_for_repo(repo_name)
+        .entries_for_repo(mirror_subdir)
         .iter()
         .map(|(k, _)| (*k).clone())
         .collect();
 
     for rel_path in &entries {
         let mirror = output_dir.join(rel_path);
-        if mirror.exists() && !mirror.is_symlink() {
+        if removable_mirror(&mirror, rel_path, manifest) {
             let _ = fs::remove_file(&mirror);
         }
-        let _ = remove_base(output_dir, rel_path);
+        let _ = remove_base(manifest, output_dir, rel_path);
         manifest.remove(rel_path);
     }
 
-    // Clean up empty directories
-    let mirror_root = output_dir.join(repo_name);
+    // Clean up empty directories, including intermediate dirs of a nested subdir
+    let mirror_root = output_dir.join(mirror_subdir);
     if mirror_root.exists() {
         prune_empty_dirs(&mirror_root);
-        if mirror_root.exists() && is_dir_empty(&mirror_root) {
-            let _ = fs::remove_dir(&mirror_root);
-        }
+        prune_empty_parents(&mirror_root, output_dir);
     }
 
     // Clean up base cache directory
-    let base_root = base_cache_dir(output_dir).join(repo_name);
+    let base_cache = base_cache_dir(manifest.state_root(output_dir));
+    let base_root = base_cache.join(mirror_subdir);
     if base_root.exists() {
         prune_empty_dirs(&base_root);
-        if base_root.exists() && is_dir_empty(&base_root) {
-            let _ = fs::remove_dir(&base_root);
-        }
+        prune_empty_parents(&base_root, &base_cache);
     }
 
     Ok(())
 }
 
-/// Iterate manifest entries for a repo, remove entries where source is gone.
+/// Iterate manifest entries for a repo, remove entries whose source is gone
+/// or is now mirrored under a different key.
 /// Deletes corresponding mirror files + base cache entries.
-pub fn prune_stale(repo_name: &str, output_dir: &Path, manifest: &mut Manifest) -> Result<u32> {
+pub fn prune_stale(mirror_subdir: &str, output_dir: &Path, manifest: &mut Manifest) -> Result<u32> {
     let entries: Vec<(String, ManifestEntry)> = manifest
-        .entries_for_repo(repo_name)
+        .entries_for_repo(mirror_subdir)
         .iter()
         .map(|(k, v)| ((*k).clone(), (*v).clone()))
         .collect();
@@ -350,15 +1160,18 @@ pub fn prune_stale(repo_name: &str, output_dir: &Path, manifest: &mut Manifest)
     let mut pruned = 0u32;
 
     for (rel_path, entry) in &entries {
-        if !entry.source.exists() {
+        // Also prune entries superseded by a new key for the same source,
+        // e.g. after `flatten` is toggled
+        let superseded = manifest.key_for_source(&entry.source) != Some(rel_path.as_str());
+        if !entry.source.exists() || superseded {
             let mirror = output_dir.join(rel_path);
-            if mirror.exists() && !mirror.is_symlink() {
+            if removable_mirror(&mirror, rel_path, manifest) {
                 if let Err(e) = fs::remove_file(&mirror) {
                     error!("Failed to prune mirror {}: {}", mirror.display(), e);
                     continue;
                 }
             }
-            let _ = remove_base(output_dir, rel_path);
+            let _ = remove_base(manifest, output_dir, rel_path);
             manifest.remove(rel_path);
             debug!("Pruned stale entry: {}", rel_path);
             pruned += 1;
@@ -366,7 +1179,7 @@ pub fn prune_stale(repo_name: &str, output_dir: &Path, manifest: &mut Manifest)
     }
 
     if pruned > 0 {
-        let mirror_root = output_dir.join(repo_name);
+        let mirror_root = output_dir.join(mirror_subdir);
         if mirror_root.exists() {
             prune_empty_dirs(&mirror_root);
         }
@@ -375,69 +1188,156 @@ pub fn prune_stale(repo_name: &str, output_dir: &Path, manifest: &mut Manifest)
     Ok(pruned)
 }
 
-/// Remove manifest entries + mirror files + base cache entries under a directory prefix.
+/// Keys of the entries `prune_stale` would remove, for dry runs. Only reads.
+pub fn plan_prune_stale(mirror_subdir: &str, manifest: &Manifest) -> Vec<String> {
+    let mut keys: Vec<String> = manifest
+        .entries_for_repo(mirror_subdir)
+        .into_iter()
+        .filter(|(key, entry)| {
+            !entry.source.exists() || manifest.key_for_source(&entry.source) != Some(key.as_str())
+        })
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// Keys of the entries `remove_dir_mirrors` would remove, for dry runs.
+pub fn plan_remove_dir_mirrors(
+    mirror_subdir: &str,
+    source_dir: &Path,
+    manifest: &Manifest,
+) -> Vec<String> {
+    let mut keys: Vec<String> = manifest
+        .entries_for_repo(mirror_subdir)
+        .into_iter()
+        .filter(|(_, e)| e.source.starts_with(source_dir))
+        .map(|(key, _)| key.clone())
+        .collect();
+    keys.sort();
+    keys
+}
+
+/// The mirror file that removing the entry at `rel_path` would delete, for
+/// dry runs: `None` when the file is protected or already gone.
+pub fn planned_removal(rel_path: &str, manifest: &Manifest, output_dir: &Path) -> Option<PathBuf> {
+    let mirror = output_dir.join(rel_path);
+    (!manifest.is_protected(rel_path) && mirror.exists() && !mirror.is_symlink()).then_some(mirror)
+}
+
+/// Log what removing the entry at `rel_path` would do, for dry runs, and
+/// return the mirror file it would delete.
+pub fn log_planned_removal(
+    rel_path: &str,
+    manifest: &Manifest,
+    output_dir: &Path,
+) -> Option<PathBuf> {
+    let removal = planned_removal(rel_path, manifest, output_dir);
+    match removal {
+        Some(_) => info!("Would delete mirror {}", rel_path),
+        None => info!("Would stop tracking {}", rel_path),
+    }
+    removal
+}
+
+/// Remove manifest entries + mirror files + base cache entries under a directory prefix.
 pub fn remove_dir_mirrors(
-    repo_name: &str,
-    dir_rel_path: &str,
+    mirror_subdir: &str,
+    source_dir: &Path,
     output_dir: &Path,
     manifest: &mut Manifest,
 ) -> Result<u32> {
-    let prefix = format!("{repo_name}/{dir_rel_path}");
+    // Match on source paths rather than keys so flattened mirrors are found too
     let entries: Vec<String> = manifest
-        .entries_for_repo(repo_name)
+        .entries_for_repo(mirror_subdir)
         .iter()
-        .filter(|(k, _)| k.starts_with(&prefix))
+        .filter(|(_, e)| e.source.starts_with(source_dir))
         .map(|(k, _)| (*k).clone())
         .collect();
 
+    let mirror_root = output_dir.join(mirror_subdir);
     let mut removed = 0u32;
     for rel_path in &entries {
         let mirror = output_dir.join(rel_path);
-        if mirror.exists() && !mirror.is_symlink() {
+        if removable_mirror(&mirror, rel_path, manifest) {
             let _ = fs::remove_file(&mirror);
             removed += 1;
         }
-        let _ = remove_base(output_dir, rel_path);
+        let _ = remove_base(manifest, output_dir, rel_path);
         manifest.remove(rel_path);
-    }
-
-    let mirror_dir = output_dir.join(repo_name).join(dir_rel_path);
-    if mirror_dir.exists() {
-        prune_empty_dirs(&mirror_dir);
-        if mirror_dir.exists() && is_dir_empty(&mirror_dir) {
-            let _ = fs::remove_dir(&mirror_dir);
+        if let Some(parent) = mirror.parent() {
+            prune_empty_parents(parent, &mirror_root);
         }
     }
 
-    let stop_at = output_dir.join(repo_name);
-    if let Some(parent) = mirror_dir.parent() {
-        prune_empty_parents(parent, &stop_at);
-    }
-
     Ok(removed)
 }
 
-/// Save content as a conflict file: `path.conflict_YYYYMMDD_HHMMSS`.
-pub fn save_conflict(path: &Path, content: &str) -> Result<PathBuf> {
+/// Save content as a conflict file: `path.conflict_YYYYMMDD_HHMMSS`. The
+/// bytes are kept as they are, so files that aren't UTF-8 can be saved too.
+pub fn save_conflict(path: &Path, content: &[u8]) -> Result<PathBuf> {
     let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
     let file_name = path
         .file_name()
         .map(|n| n.to_string_lossy().to_string())
         .unwrap_or_else(|| "file".into());
-    let conflict_name = format!("{file_name}.conflict_{timestamp}");
-    let conflict_path = path.with_file_name(conflict_name);
 
-    fs::write(&conflict_path, content)
-        .with_context(|| format!("Failed to write conflict file {}", conflict_path.display()))?;
-    debug!("Saved conflict file: {}", conflict_path.display());
-    Ok(conflict_path)
+    // Conflicts within the same second get `-2`, `-3`, ... rather than
+    // overwriting an earlier conflict file
+    let mut n = 1u32;
+    loop {
+        let conflict_name = match n {
+            1 => format!("{file_name}.conflict_{timestamp}"),
+            _ => format!("{file_name}.conflict_{timestamp}-{n}"),
+        };
+        let conflict_path = path.with_file_name(conflict_name);
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&conflict_path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                n += 1;
+                continue;
+            }
+            Err(e) => {
+                return Err(e).with_context(|| {
+                    format!("Failed to write conflict file {}", conflict_path.display())
+                })
+            }
+        };
+        file.write_all(content).with_context(|| {
+            format!("Failed to write conflict file {}", conflict_path.display())
+        })?;
+        debug!("Saved conflict file: {}", conflict_path.display());
+        return Ok(conflict_path);
+    }
 }
 
-/// Attempt to move the output directory from old to new via rename.
+/// Attempt to move the output directory from old to new via rename, along
+/// with its state root when a `state_dir` is configured. Across filesystems
+/// the tree is copied and the original removed, keeping the manifest and
+/// base cache.
 /// Returns `true` if the move succeeded, `false` if the caller should fall back to re-scan.
-pub fn move_output_dir(old: &Path, new: &Path) -> Result<bool> {
-    // Old dir must exist and contain our manifest
-    if !old.is_dir() || !old.join(MANIFEST_FILENAME).exists() {
+pub fn move_output_dir(old: &Path, new: &Path, state_dir: Option<&Path>) -> Result<bool> {
+    move_output_dir_with(old, new, state_dir, |from, to| fs::rename(from, to))
+}
+
+fn move_output_dir_with(
+    old: &Path,
+    new: &Path,
+    state_dir: Option<&Path>,
+    rename: impl Fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<bool> {
+    let old_state = state_dir.map(|d| state_root_for(d, old));
+
+    // Old dir must exist and have our manifest
+    let manifest_dir = old_state.as_deref().unwrap_or(old);
+    let has_manifest = [MANIFEST_FILENAME, MANIFEST_LOG_FILENAME]
+        .iter()
+        .any(|name| manifest_dir.join(name).exists());
+    if !old.is_dir() || !has_manifest {
         return Ok(false);
     }
 
@@ -458,67 +1358,506 @@ pub fn move_output_dir(old: &Path, new: &Path) -> Result<bool> {
             .with_context(|| format!("Failed to remove empty dir {}", new.display()))?;
     }
 
-    match fs::rename(old, new) {
+    let moved = match rename(old, new) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            info!(
+                "Output directory is moving across filesystems, copying {} -> {}",
+                old.display(),
+                new.display()
+            );
+            copy_then_remove_dir(old, new)
+        }
+        Err(e) => Err(e)
+            .with_context(|| format!("Failed to rename {} -> {}", old.display(), new.display())),
+    };
+
+    match moved {
         Ok(()) => {
             info!(
                 "Moved output directory: {} -> {}",
                 old.display(),
                 new.display()
             );
+            if let (Some(old_state), Some(state_dir)) = (old_state, state_dir) {
+                let new_state = state_root_for(state_dir, new);
+                fs::rename(&old_state, &new_state).with_context(|| {
+                    format!(
+                        "Failed to rename state {} -> {}",
+                        old_state.display(),
+                        new_state.display()
+                    )
+                })?;
+            }
             Ok(true)
         }
-        Err(e) => {
-            // Cross-device rename fails — fall back to re-scan
-            if e.raw_os_error() == Some(libc::EXDEV) {
-                warn!(
-                    "Cannot move output_dir across filesystems ({} -> {}), will re-scan",
-                    old.display(),
-                    new.display()
-                );
-                Ok(false)
-            } else {
-                Err(e).with_context(|| {
-                    format!("Failed to rename {} -> {}", old.display(), new.display())
-                })
+        Err(e) => Err(e),
+    }
+}
+
+/// Copy the tree at `from` to `to`, then delete `from`. A failed copy
+/// removes the partial destination and leaves `from` untouched.
+fn copy_then_remove_dir(from: &Path, to: &Path) -> Result<()> {
+    if let Err(e) = copy_dir_all(from, to) {
+        let _ = fs::remove_dir_all(to);
+        return Err(e);
+    }
+    fs::remove_dir_all(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+fn copy_dir_all(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from).follow_links(false) {
+        let entry = entry.with_context(|| format!("Failed to read {}", from.display()))?;
+        let rel = entry.path().strip_prefix(from).unwrap_or(entry.path());
+        let target = to.join(rel);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)
+                .with_context(|| format!("Failed to create {}", target.display()))?;
+        } else if entry.path_is_symlink() {
+            let link = fs::read_link(entry.path())
+                .with_context(|| format!("Failed to read link {}", entry.path().display()))?;
+            std::os::unix::fs::symlink(&link, &target)
+                .with_context(|| format!("Failed to create link {}", target.display()))?;
+        } else {
+            fs::copy(entry.path(), &target).with_context(|| {
+                format!(
+                    "Failed to copy {} -> {}",
+                    entry.path().display(),
+                    target.display()
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`.
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("Invalid path {}", path.display()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("Failed to read free space for {}", path.display()));
+    }
+    // Field widths differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Move one repo's mirror files and base cache entries to another output directory.
+///
+/// Manifest entries move from `old_manifest` to `new_manifest` as each file is
+/// moved, so a mirror watcher flushing concurrently never sees a moved file as
+/// a user deletion. Fails before touching anything if the destination already
+/// has a file at any of the repo's paths. Returns the number of files moved.
+pub fn move_repo_mirror(
+    mirror_subdir: &str,
+    old_output_dir: &Path,
+    old_manifest: &mut Manifest,
+    new_output_dir: &Path,
+    new_manifest: &mut Manifest,
+) -> Result<u32> {
+    let entries: Vec<(String, ManifestEntry)> = old_manifest
+        .entries_for_repo(mirror_subdir)
+        .iter()
+        .map(|(k, v)| ((*k).clone(), (*v).clone()))
+        .collect();
+
+    for (rel_path, _) in &entries {
+        let dest = new_output_dir.join(rel_path);
+        if dest.exists() || new_manifest.get(rel_path).is_some() {
+            anyhow::bail!("Destination already contains {}", dest.display());
+        }
+    }
+
+    let mut moved = 0u32;
+    for (rel_path, entry) in entries {
+        let mirror = old_output_dir.join(&rel_path);
+        if mirror.is_file() && !mirror.is_symlink() {
+            move_file(&mirror, &new_output_dir.join(&rel_path))?;
+            moved += 1;
+        }
+        if let Some(base) = read_base(old_manifest, old_output_dir, &rel_path)? {
+            write_base(new_manifest, new_output_dir, &rel_path, &base)?;
+            remove_base(old_manifest, old_output_dir, &rel_path)?;
+        }
+        old_manifest.remove(&rel_path);
+        new_manifest.insert(rel_path, entry);
+    }
+
+    let mirror_root = old_output_dir.join(mirror_subdir);
+    if mirror_root.exists() {
+        prune_empty_dirs(&mirror_root);
+        prune_empty_parents(&mirror_root, old_output_dir);
+    }
+    let old_base_cache = base_cache_dir(old_manifest.state_root(old_output_dir));
+    let base_root = old_base_cache.join(mirror_subdir);
+    if base_root.exists() {
+        prune_empty_dirs(&base_root);
+        prune_empty_parents(&base_root, &old_base_cache);
+    }
+
+    info!(
+        "Moved {} mirrored files for {}: {} -> {}",
+        moved,
+        mirror_subdir,
+        old_output_dir.display(),
+        new_output_dir.display()
+    );
+    Ok(moved)
+}
+
+/// Rename one repo's mirror within its output directory, from `old_subdir` to
+/// `new_subdir`, rekeying its manifest and base cache entries to match.
+///
+/// The whole subtree is renamed, so untracked files such as Ulysses group
+/// metadata come along. Fails before touching anything if something already
+/// exists at `new_subdir`. Returns the number of entries rekeyed.
+pub fn rename_repo_mirror(
+    old_subdir: &str,
+    new_subdir: &str,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+) -> Result<u32> {
+    if old_subdir == new_subdir {
+        return Ok(0);
+    }
+    if new_subdir.starts_with(&format!("{old_subdir}/"))
+        || old_subdir.starts_with(&format!("{new_subdir}/"))
+    {
+        anyhow::bail!("Cannot rename mirror {old_subdir} to {new_subdir}: one contains the other");
+    }
+    let old_root = output_dir.join(old_subdir);
+    let new_root = output_dir.join(new_subdir);
+    if new_root.exists() || !manifest.entries_for_repo(new_subdir).is_empty() {
+        anyhow::bail!("Destination already exists: {}", new_root.display());
+    }
+
+    let entries: Vec<(String, ManifestEntry)> = manifest
+        .entries_for_repo(old_subdir)
+        .iter()
+        .map(|(k, v)| ((*k).clone(), (*v).clone()))
+        .collect();
+
+    if old_root.exists() {
+        if let Some(parent) = new_root.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::rename(&old_root, &new_root).with_context(|| {
+            format!(
+                "Failed to rename {} -> {}",
+                old_root.display(),
+                new_root.display()
+            )
+        })?;
+        prune_empty_parents(&old_root, output_dir);
+    }
+
+    let mut rekeyed = 0u32;
+    for (old_key, entry) in entries {
+        let new_key = format!("{new_subdir}{}", &old_key[old_subdir.len()..]);
+        if let Some(base) = read_base(manifest, output_dir, &old_key)? {
+            write_base(manifest, output_dir, &new_key, &base)?;
+            remove_base(manifest, output_dir, &old_key)?;
+        }
+        manifest.remove(&old_key);
+        manifest.insert(new_key, entry);
+        rekeyed += 1;
+    }
+
+    let base_cache = base_cache_dir(manifest.state_root(output_dir));
+    let base_root = base_cache.join(old_subdir);
+    if base_root.exists() {
+        prune_empty_dirs(&base_root);
+        prune_empty_parents(&base_root, &base_cache);
+    }
+
+    info!(
+        "Renamed mirror {} -> {} ({} files)",
+        old_subdir, new_subdir, rekeyed
+    );
+    Ok(rekeyed)
+}
+
+/// Copy `from` to `to` and return the SHA-256 of the copied content.
+///
+/// On macOS `fs::copy` clones the file on APFS, which costs no I/O, so the
+/// hash is the only read. Elsewhere the copy and hash share one streaming
+/// pass instead of reading the file twice.
+pub fn copy_and_hash(from: &Path, to: &Path) -> Result<String> {
+    let copy_context = || format!("Failed to copy {} -> {}", from.display(), to.display());
+
+    if cfg!(target_os = "macos") {
+        fs::copy(from, to).with_context(copy_context)?;
+        return hash_file(to);
+    }
+
+    let mut reader = fs::File::open(from).with_context(copy_context)?;
+    let mut writer = fs::File::create(to).with_context(copy_context)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buffer).with_context(copy_context)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        writer.write_all(&buffer[..n]).with_context(copy_context)?;
+    }
+    // Match fs::copy, which carries permissions over
+    let permissions = reader.metadata().with_context(copy_context)?.permissions();
+    writer
+        .set_permissions(permissions)
+        .with_context(copy_context)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether `path` is one of the temp files `write_atomic` renames into place.
+pub fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(TEMP_SUFFIX))
+}
+
+/// Replace `path` with `content` by writing a temp file beside it and
+/// renaming it over `path`, so the live file is never partially written.
+/// An existing file's permissions carry over.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("No file name in {}", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}{TEMP_SUFFIX}", file_name.to_string_lossy()));
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(content)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        anyhow::Error::new(e).context(format!("Failed to write {}", path.display()))
+    })
+}
+
+/// `fs::copy` through `write_atomic`.
+fn copy_atomic(manifest: &Manifest, from: &Path, to: &Path) -> Result<()> {
+    let content = read_side(manifest, from)?;
+    write_side(manifest, to, from, &content)
+}
+
+/// Move a single file, creating parent dirs. Falls back to copy + delete when
+/// the destination is on another filesystem.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create parent dirs for {}", to.display()))?;
+    }
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            fs::copy(from, to).with_context(|| {
+                format!("Failed to copy {} -> {}", from.display(), to.display())
+            })?;
+            fs::remove_file(from)
+                .with_context(|| format!("Failed to remove {}", from.display()))?;
+            Ok(())
+        }
+        Err(e) => Err(e)
+            .with_context(|| format!("Failed to rename {} -> {}", from.display(), to.display())),
+    }
+}
+
+/// Write a `.gitignore` into the output directory that keeps the manifest,
+/// base cache, and conflict files out of version control.
+/// Returns `false` without touching anything if a `.gitignore` already exists,
+/// so a user-authored file is never clobbered.
+pub fn write_output_gitignore(output_dir: &Path) -> Result<bool> {
+    let path = output_dir.join(GITIGNORE_FILENAME);
+    if path.exists() {
+        return Ok(false);
+    }
+    let content =
+        format!("{MANIFEST_FILENAME}\n{MANIFEST_LOG_FILENAME}\n{BASE_CACHE_DIR}/\n*.conflict_*\n");
+    fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    debug!("Wrote {}", path.display());
+    Ok(true)
+}
+
+/// Delete the base cache and/or manifest kept for `output_dir`, in its
+/// subdirectory of `state_dir` when one is set. Mirrored files stay, so the
+/// next scan claims them again. Returns the paths removed.
+pub fn clean_state(
+    output_dir: &Path,
+    state_dir: Option<&Path>,
+    cache: bool,
+    manifest: bool,
+) -> Result<Vec<PathBuf>> {
+    let state_root = state_dir.map(|d| state_root_for(d, output_dir));
+    let root = state_root.as_deref().unwrap_or(output_dir);
+    let mut removed = Vec::new();
+    if cache {
+        let dir = base_cache_dir(root);
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir)
+                .with_context(|| format!("Failed to remove {}", dir.display()))?;
+            removed.push(dir);
+        }
+    }
+    if manifest {
+        for name in [MANIFEST_FILENAME, MANIFEST_LOG_FILENAME] {
+            let path = root.join(name);
+            if path.is_file() {
+                fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                removed.push(path);
             }
         }
     }
+    Ok(removed)
 }
 
 // --- Base cache helpers ---
 
-fn base_cache_dir(output_dir: &Path) -> PathBuf {
-    output_dir.join(BASE_CACHE_DIR)
+/// Base cache location under a state root (see `Manifest::state_root`).
+pub(crate) fn base_cache_dir(state_root: &Path) -> PathBuf {
+    state_root.join(BASE_CACHE_DIR)
 }
 
-fn base_cache_path(output_dir: &Path, rel_path: &str) -> PathBuf {
-    base_cache_dir(output_dir).join(rel_path)
+fn base_cache_path(state_root: &Path, rel_path: &str) -> PathBuf {
+    base_cache_dir(state_root).join(rel_path)
 }
 
-pub fn write_base(output_dir: &Path, rel_path: &str, content: &str) -> Result<()> {
-    let path = base_cache_path(output_dir, rel_path);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent)?;
+/// Content-mode blob for `hash`, fanned out by its first two hex digits.
+fn blob_path(state_root: &Path, hash: &str) -> PathBuf {
+    base_cache_dir(state_root)
+        .join(BLOB_DIR)
+        .join(&hash[..2])
+        .join(hash)
+}
+
+/// Record `content` as the last-synced base of `rel_path`. It's kept as
+/// written, not normalized, so merges against it keep each side's text.
+pub fn write_base(
+    manifest: &mut Manifest,
+    output_dir: &Path,
+    rel_path: &str,
+    content: &str,
+) -> Result<()> {
+    let state_root = manifest.state_root(output_dir).to_path_buf();
+    let compress = manifest.base_cache_compress();
+    match manifest.base_cache() {
+        BaseCacheMode::Path => {
+            write_cache_file(&base_cache_path(&state_root, rel_path), content, compress)
+        }
+        BaseCacheMode::Content => {
+            let hash = hash_bytes(content.as_bytes());
+            let blob = blob_path(&state_root, &hash);
+            if !blob.exists() {
+                write_cache_file(&blob, content, compress)?;
+            }
+            if let Some(released) = manifest.set_base_blob(rel_path, hash) {
+                remove_cache_file(&state_root, &blob_path(&state_root, &released))?;
+            }
+            Ok(())
+        }
+        BaseCacheMode::Off => Ok(()),
+    }
+}
+
+/// Cache the content of the file at `path` as the base of `rel_path`. A
+/// file that isn't UTF-8 has no text to merge from, so its old base is
+/// dropped instead and edits on both sides can't be merged.
+fn write_base_from(
+    manifest: &mut Manifest,
+    output_dir: &Path,
+    rel_path: &str,
+    path: &Path,
+) -> Result<()> {
+    match read_side_text(manifest, path) {
+        Ok(content) => write_base(manifest, output_dir, rel_path, &content),
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+            remove_base(manifest, output_dir, rel_path)
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
     }
-    fs::write(&path, content)?;
-    Ok(())
 }
 
-pub fn read_base(output_dir: &Path, rel_path: &str) -> Result<Option<String>> {
-    let path = base_cache_path(output_dir, rel_path);
+pub fn read_base(manifest: &Manifest, output_dir: &Path, rel_path: &str) -> Result<Option<String>> {
+    let state_root = manifest.state_root(output_dir);
+    let path = match manifest.base_cache() {
+        BaseCacheMode::Path => base_cache_path(state_root, rel_path),
+        BaseCacheMode::Content => match manifest.base_blob(rel_path) {
+            Some(hash) => blob_path(state_root, hash),
+            None => return Ok(None),
+        },
+        BaseCacheMode::Off => return Ok(None),
+    };
     if !path.exists() {
         return Ok(None);
     }
-    Ok(Some(fs::read_to_string(&path)?))
+    Ok(Some(read_cache_file(&path)?))
+}
+
+/// Forget `rel_path`'s base. In content mode the blob is deleted once no
+/// other file references it.
+pub fn remove_base(manifest: &mut Manifest, output_dir: &Path, rel_path: &str) -> Result<()> {
+    let state_root = manifest.state_root(output_dir).to_path_buf();
+    match manifest.base_cache() {
+        BaseCacheMode::Path => {
+            remove_cache_file(&state_root, &base_cache_path(&state_root, rel_path))
+        }
+        BaseCacheMode::Content => match manifest.clear_base_blob(rel_path) {
+            Some(released) => remove_cache_file(&state_root, &blob_path(&state_root, &released)),
+            None => Ok(()),
+        },
+        BaseCacheMode::Off => Ok(()),
+    }
+}
+
+/// Write a base cache file through `write_atomic`, so a crash mid-write
+/// never leaves a truncated base behind.
+fn write_cache_file(path: &Path, content: &str, compress: bool) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if compress {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content.as_bytes())?;
+        write_atomic(path, &encoder.finish()?)
+    } else {
+        write_atomic(path, content.as_bytes())
+    }
+}
+
+/// Read a base cache file, gzipped or plain. Bases are UTF-8 text, which
+/// never starts with the gzip magic bytes, so files written before
+/// `base_cache_compress` was turned on (or after it was turned off) still
+/// read back.
+fn read_cache_file(path: &Path) -> Result<String> {
+    let bytes = fs::read(path)?;
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut content = String::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut content)
+            .with_context(|| format!("Failed to decompress {}", path.display()))?;
+        Ok(content)
+    } else {
+        Ok(String::from_utf8(bytes).with_context(|| format!("{} is not UTF-8", path.display()))?)
+    }
 }
 
-pub fn remove_base(output_dir: &Path, rel_path: &str) -> Result<()> {
-    let path = base_cache_path(output_dir, rel_path);
+fn remove_cache_file(state_root: &Path, path: &Path) -> Result<()> {
     if path.exists() {
-        fs::remove_file(&path)?;
+        fs::remove_file(path)?;
         // Prune empty parent dirs in base cache
         if let Some(parent) = path.parent() {
-            let stop = base_cache_dir(output_dir);
+            let stop = base_cache_dir(state_root);
             prune_empty_parents(parent, &stop);
         }
     }
@@ -570,6 +1909,8 @@ pub fn is_dir_empty(path: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::manifest::{Inbox, KeywordTree, Normalization, StateOptions};
+    use crate::matcher::ProtectSet;
     use tempfile::TempDir;
 
     fn setup() -> (TempDir, TempDir) {
@@ -593,6 +1934,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -619,6 +1961,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -629,6 +1972,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -650,6 +1994,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -662,6 +2007,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -684,6 +2030,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -696,6 +2043,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -718,6 +2066,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -732,6 +2081,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -742,6 +2092,44 @@ mod tests {
         assert_eq!(fs::read_to_string(&mirror).unwrap(), result);
     }
 
+    #[test]
+    fn test_merge_writes_leave_no_temp_files() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "line1\nline2\nline3\n").unwrap();
+
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        sync(&mut manifest);
+
+        fs::write(&source, "LINE1\nline2\nline3\n").unwrap();
+        fs::write(&mirror, "line1\nline2\nLINE3\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Merged);
+
+        let merged = "LINE1\nline2\nLINE3\n";
+        assert_eq!(fs::read_to_string(&source).unwrap(), merged);
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), merged);
+        for dir in [repo.path(), mirror.parent().unwrap()] {
+            let temp_files: Vec<_> = fs::read_dir(dir)
+                .unwrap()
+                .filter_map(|e| e.ok())
+                .filter(|e| is_temp_file(&e.path()))
+                .collect();
+            assert!(temp_files.is_empty(), "{temp_files:?}");
+        }
+    }
+
     #[test]
     fn test_sync_file_both_changed_conflict() {
         let (repo, output) = setup();
@@ -757,6 +2145,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -770,6 +2159,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -792,6 +2182,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sync_file_blank_source_loses_to_mirror_edit() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "original\n").unwrap();
+
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        sync(&mut manifest);
+
+        // Source emptied while the mirror was edited
+        fs::write(&source, "").unwrap();
+        fs::write(&mirror, "edited in ulysses\n").unwrap();
+
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "edited in ulysses\n");
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+    }
+
+    #[test]
+    fn test_sync_file_blank_base_whitespace_difference_in_sync() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "").unwrap();
+
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        sync(&mut manifest);
+
+        // Both sides add the same line, one without a trailing newline
+        fs::write(&source, "same line\n").unwrap();
+        fs::write(&mirror, "same line").unwrap();
+
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "same line\n");
+        let conflicts = fs::read_dir(output.path().join("my-repo"))
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().contains(".conflict_"))
+            .count();
+        assert_eq!(conflicts, 0);
+    }
+
     #[test]
     fn test_sync_file_no_base_falls_back_to_conflict() {
         let (repo, output) = setup();
@@ -818,6 +2273,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -843,6 +2299,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -869,6 +2326,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -893,6 +2351,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
         assert!(mirror.exists());
@@ -900,19 +2359,56 @@ mod tests {
         // Delete source
         fs::remove_file(&source).unwrap();
 
-        let deleted = propagate_delete("my-repo/doc.md", &mut manifest, output.path()).unwrap();
+        let deleted =
+            propagate_delete("my-repo", "my-repo/doc.md", &mut manifest, output.path()).unwrap();
         assert!(deleted);
         assert!(!mirror.exists());
         assert!(manifest.get("my-repo/doc.md").is_none());
     }
 
+    #[test]
+    fn test_untrack_source_keeps_unsynced_mirror_edits() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "hello").unwrap();
+
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        sync_file(
+            &source,
+            &mirror,
+            &mut manifest,
+            "my-repo/doc.md",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        fs::write(&mirror, "hello, edited").unwrap();
+
+        assert!(untrack_source("my-repo", &source, &mut manifest, output.path()).unwrap());
+        assert!(!mirror.exists());
+        assert!(manifest.get("my-repo/doc.md").is_none());
+        let conflicts: Vec<_> = fs::read_dir(output.path().join("my-repo"))
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(fs::read_to_string(&conflicts[0]).unwrap(), "hello, edited");
+        assert_eq!(fs::read_to_string(&source).unwrap(), "hello");
+    }
+
     #[test]
     fn test_propagate_delete_not_in_manifest() {
         let output = TempDir::new().unwrap();
         let mut manifest = Manifest::load(output.path()).unwrap();
 
-        let deleted =
-            propagate_delete("my-repo/nonexistent.md", &mut manifest, output.path()).unwrap();
+        let deleted = propagate_delete(
+            "my-repo",
+            "my-repo/nonexistent.md",
+            &mut manifest,
+            output.path(),
+        )
+        .unwrap();
         assert!(!deleted);
     }
 
@@ -931,6 +2427,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -944,6 +2441,168 @@ mod tests {
         assert!(manifest.get("my-repo/doc.md").is_none());
     }
 
+    #[test]
+    fn test_verify_after_write_catches_corruption() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "line one\n").unwrap();
+        let options = StateOptions {
+            verify_after_write: true,
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        fs::write(&mirror, "line one\nmirror edit\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "line one\nmirror edit\n"
+        );
+
+        // A write corrupted once is caught and written again
+        let expected = hash_bytes(b"line one\nmirror edit\n");
+        fs::write(&mirror, "line one\nmirror edjt\n").unwrap();
+        let rewrites = std::cell::Cell::new(0);
+        let rewrite = || {
+            rewrites.set(rewrites.get() + 1);
+            copy_side(&manifest, &source, &mirror)
+        };
+        verify_written(&manifest, &mirror, &expected, rewrite).unwrap();
+        assert_eq!(rewrites.get(), 1);
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            "line one\nmirror edit\n"
+        );
+
+        // Storage that keeps corrupting it is an error
+        let corrupt = || fs::write(&mirror, "line one\nmirror edjt\n").map_err(Into::into);
+        corrupt().unwrap();
+        let err = verify_written(&manifest, &mirror, &expected, corrupt).unwrap_err();
+        assert!(err.to_string().contains("after a retry"), "{err}");
+
+        // Off by default: nothing is re-read
+        let unverified = Manifest::load(output.path()).unwrap();
+        verify_written(&unverified, &mirror, &expected, || unreachable!()).unwrap();
+    }
+
+    #[test]
+    fn test_read_only_source_never_written() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        fs::write(&source, "line one\nline two\n").unwrap();
+        let options = StateOptions {
+            read_only_roots: vec![output.path().join("my-repo")],
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let mirror = output.path().join("my-repo").join("doc.md");
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+
+        // A mirror edit is reverted and kept as a mirror-side conflict file
+        fs::write(&mirror, "line one\nline two\nmirror edit\n").unwrap();
+        assert_eq!(
+            plan_sync(
+                &source,
+                &mirror,
+                &manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap(),
+            SyncOutcome::Conflict
+        );
+        assert_eq!(sync(&mut manifest), SyncOutcome::Conflict);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "line one\nline two\n");
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "line one\nline two\n");
+        let conflict = manifest.last_conflict_info().unwrap();
+        assert_eq!(conflict.winner, ConflictWinner::Source);
+        assert!(conflict.conflict_file.starts_with(output.path()));
+        assert_eq!(
+            fs::read_to_string(&conflict.conflict_file).unwrap(),
+            "line one\nline two\nmirror edit\n"
+        );
+
+        // Edits on both sides are not merged back either
+        fs::write(&source, "source edit\nline one\nline two\n").unwrap();
+        fs::write(&mirror, "line one\nline two\nmirror edit\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Conflict);
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "source edit\nline one\nline two\n"
+        );
+
+        // A mirror delete leaves the source, tracked for the next scan
+        fs::remove_file(&mirror).unwrap();
+        let deleted =
+            propagate_mirror_delete("my-repo/doc.md", &mut manifest, output.path()).unwrap();
+        assert!(!deleted);
+        assert!(source.exists());
+        assert!(manifest.get("my-repo/doc.md").is_some());
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert!(mirror.exists());
+        assert!(!repo.path().read_dir().unwrap().any(|e| e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .contains("conflict")));
+    }
+
+    #[test]
+    fn test_read_only_source_keeps_binary_mirror_edit() {
+        let (repo, output) = setup();
+        let source = repo.path().join("image.png");
+        fs::write(&source, [0x89, b'P', b'N', b'G', 0xff, 0x00]).unwrap();
+        let options = StateOptions {
+            read_only_roots: vec![output.path().join("my-repo")],
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let mirror = output.path().join("my-repo").join("image.png");
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/image.png",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+
+        let edited = [0x89, b'P', b'N', b'G', 0xfe, 0x01];
+        fs::write(&mirror, edited).unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Conflict);
+        assert_eq!(fs::read(&mirror).unwrap(), fs::read(&source).unwrap());
+        let conflict = manifest.last_conflict_info().unwrap();
+        assert_eq!(fs::read(&conflict.conflict_file).unwrap(), edited);
+    }
+
     #[test]
     fn test_prune_stale_via_manifest() {
         let (repo, output) = setup();
@@ -959,6 +2618,7 @@ mod tests {
             &mut manifest,
             "my-repo/doc.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -971,6 +2631,72 @@ mod tests {
         assert!(manifest.get("my-repo/doc.md").is_none());
     }
 
+    fn protecting_manifest(output_dir: &Path) -> Manifest {
+        let mut builder = globset::GlobSetBuilder::new();
+        builder.add(globset::Glob::new("**/*.plist").unwrap());
+        let options = StateOptions {
+            protect: ProtectSet::new(vec!["*.plist".into()], builder.build().unwrap()),
+            ..Default::default()
+        };
+        Manifest::load_in(output_dir, &options).unwrap()
+    }
+
+    #[test]
+    fn test_prune_stale_keeps_protected() {
+        let (repo, output) = setup();
+        let source = repo.path().join("Info.plist");
+        fs::write(&source, "<plist/>").unwrap();
+
+        let mirror = output.path().join("my-repo").join("Info.plist");
+        let mut manifest = protecting_manifest(output.path());
+        sync_file(
+            &source,
+            &mirror,
+            &mut manifest,
+            "my-repo/Info.plist",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+
+        fs::remove_file(&source).unwrap();
+
+        let pruned = prune_stale("my-repo", output.path(), &mut manifest).unwrap();
+        assert_eq!(pruned, 1);
+        assert!(mirror.exists());
+        assert!(manifest.get("my-repo/Info.plist").is_none());
+
+        // Once untracked, it is left alone by later removals too
+        remove_repo_mirror("my-repo", output.path(), &mut manifest).unwrap();
+        assert!(mirror.exists());
+    }
+
+    #[test]
+    fn test_protected_mirror_delete_keeps_source() {
+        let (repo, output) = setup();
+        let source = repo.path().join("Info.plist");
+        fs::write(&source, "<plist/>").unwrap();
+
+        let mirror = output.path().join("my-repo").join("Info.plist");
+        let mut manifest = protecting_manifest(output.path());
+        sync_file(
+            &source,
+            &mirror,
+            &mut manifest,
+            "my-repo/Info.plist",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+
+        fs::remove_file(&mirror).unwrap();
+        let propagated =
+            propagate_mirror_delete("my-repo/Info.plist", &mut manifest, output.path()).unwrap();
+        assert!(!propagated);
+        assert!(source.exists());
+        assert!(manifest.get("my-repo/Info.plist").is_some());
+    }
+
     #[test]
     fn test_remove_repo_mirror_with_manifest() {
         let (repo, output) = setup();
@@ -986,6 +2712,7 @@ mod tests {
             &mut manifest,
             "my-repo/a.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
         sync_file(
@@ -994,6 +2721,7 @@ mod tests {
             &mut manifest,
             "my-repo/sub/b.md",
             output.path(),
+            SyncDirection::Bidirectional,
         )
         .unwrap();
 
@@ -1003,19 +2731,491 @@ mod tests {
         assert!(manifest.entries_for_repo("my-repo").is_empty());
     }
 
+    #[test]
+    fn test_touch_repo_mirror_keeps_content() {
+        let (repo, output) = setup();
+        fs::write(repo.path().join("a.md"), "a").unwrap();
+        let mirror = output.path().join("my-repo").join("a.md");
+
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        sync_file(
+            &repo.path().join("a.md"),
+            &mirror,
+            &mut manifest,
+            "my-repo/a.md",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        let hash = manifest.get("my-repo/a.md").unwrap().hash.clone();
+        let old = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&mirror)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        assert_eq!(
+            touch_repo_mirror(&manifest, output.path(), "my-repo").unwrap(),
+            1
+        );
+        assert!(fs::metadata(&mirror).unwrap().modified().unwrap() > old);
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "a");
+        assert_eq!(hash_file(&mirror).unwrap(), hash);
+        assert_eq!(manifest.get("my-repo/a.md").unwrap().hash, hash);
+    }
+
+    #[test]
+    fn test_copy_and_hash_matches_hash_file() {
+        let tmp = TempDir::new().unwrap();
+        let from = tmp.path().join("big.md");
+        let to = tmp.path().join("copy.md");
+        // Several buffers' worth, with a partial final chunk
+        let content: String = (0..20_000).map(|i| format!("line {i}\n")).collect();
+        fs::write(&from, &content).unwrap();
+
+        let hash = copy_and_hash(&from, &to).unwrap();
+
+        assert_eq!(hash, hash_file(&to).unwrap());
+        assert_eq!(hash, hash_file(&from).unwrap());
+        assert_eq!(fs::read_to_string(&to).unwrap(), content);
+    }
+
     #[test]
     fn test_base_cache_read_write_remove() {
         let output = TempDir::new().unwrap();
+        let mut manifest = Manifest::empty();
 
-        write_base(output.path(), "repo/doc.md", "base content").unwrap();
-        let content = read_base(output.path(), "repo/doc.md").unwrap();
+        write_base(&mut manifest, output.path(), "repo/doc.md", "base content").unwrap();
+        let content = read_base(&manifest, output.path(), "repo/doc.md").unwrap();
         assert_eq!(content, Some("base content".into()));
 
-        remove_base(output.path(), "repo/doc.md").unwrap();
-        let content = read_base(output.path(), "repo/doc.md").unwrap();
+        remove_base(&mut manifest, output.path(), "repo/doc.md").unwrap();
+        let content = read_base(&manifest, output.path(), "repo/doc.md").unwrap();
         assert_eq!(content, None);
     }
 
+    #[test]
+    fn test_flat_inbox_source_header() {
+        let repo = TempDir::new().unwrap();
+        let output = TempDir::new().unwrap();
+        let options = StateOptions {
+            inboxes: vec![Inbox {
+                mirror_root: output.path().join("repo"),
+                repo_path: repo.path().to_path_buf(),
+            }],
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        fs::create_dir_all(repo.path().join("docs/api")).unwrap();
+        let source = repo.path().join("docs/api/auth.md");
+        fs::write(&source, "# Auth\n").unwrap();
+        let rel_path = "repo/docs - api - auth.md";
+        let mirror = output.path().join(rel_path);
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                rel_path,
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        let header = "<!-- source: docs/api/auth.md -->\n";
+
+        // The header is injected on copy and isn't part of the content hash
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            format!("{header}# Auth\n")
+        );
+        assert_eq!(
+            manifest.get(rel_path).unwrap().hash,
+            hash_file(&source).unwrap()
+        );
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+
+        // Mirror edits reach source without the header
+        fs::write(&mirror, format!("{header}# Auth\n\nEdited\n")).unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "# Auth\n\nEdited\n");
+
+        // A deleted header comes back without touching source
+        fs::write(&mirror, "# Auth\n\nEdited\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            format!("{header}# Auth\n\nEdited\n")
+        );
+
+        // Edits on both sides merge without the header
+        fs::write(&source, "# Auth\n\nEdited\nSource line\n").unwrap();
+        fs::write(&mirror, format!("{header}# Auth title\n\nEdited\n")).unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Merged);
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "# Auth title\n\nEdited\nSource line\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            format!("{header}# Auth title\n\nEdited\nSource line\n")
+        );
+
+        // A rename in the editor is routed back to the entry by its header
+        let renamed = "repo/Auth.md";
+        fs::rename(&mirror, output.path().join(renamed)).unwrap();
+        assert!(adopt_renamed_mirror(renamed, &mut manifest, output.path()).unwrap());
+        assert!(manifest.get(rel_path).is_none());
+        assert_eq!(manifest.get(renamed).unwrap().source, source);
+        assert!(read_base(&manifest, output.path(), renamed)
+            .unwrap()
+            .is_some());
+        assert!(!propagate_mirror_delete(rel_path, &mut manifest, output.path()).unwrap());
+        assert!(source.exists());
+
+        // Turning the inbox off strips the header again
+        strip_decorations(&manifest, output.path(), "repo").unwrap();
+        assert_eq!(
+            fs::read_to_string(output.path().join(renamed)).unwrap(),
+            "# Auth title\n\nEdited\nSource line\n"
+        );
+    }
+
+    #[test]
+    fn test_folder_keywords_round_trip() {
+        let repo = TempDir::new().unwrap();
+        let output = TempDir::new().unwrap();
+        let options = StateOptions {
+            keyword_trees: vec![KeywordTree {
+                output_dir: output.path().to_path_buf(),
+                mirror_root: output.path().join("repo"),
+                source_root: repo.path().to_path_buf(),
+            }],
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        fs::create_dir_all(repo.path().join("docs/api")).unwrap();
+        let source = repo.path().join("docs/api/auth.md");
+        fs::write(&source, "# Auth\n").unwrap();
+        let rel_path = "repo/docs/api/auth.md";
+        let mirror = output.path().join(rel_path);
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                rel_path,
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+
+        // Keywords are injected on copy and aren't part of the content hash
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "#docs #api\n# Auth\n");
+        assert_eq!(
+            manifest.get(rel_path).unwrap().hash,
+            hash_file(&source).unwrap()
+        );
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+
+        // Mirror edits, including new keywords, reach source without the
+        // folder keywords
+        fs::write(&mirror, "#docs #api #draft\n# Auth\n\nEdited\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "#draft\n# Auth\n\nEdited\n"
+        );
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+
+        // Keywords the source has are merged, not duplicated, and survive
+        // the round trip as written
+        fs::write(&source, "#api  #draft\n# Auth\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            "#api  #draft #docs\n# Auth\n"
+        );
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+        fs::write(&mirror, "#api  #draft #docs\n# Auth\n\nMore\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(
+            fs::read_to_string(&source).unwrap(),
+            "#api  #draft\n# Auth\n\nMore\n"
+        );
+
+        // Files at the mirror root get no keywords
+        let readme = repo.path().join("README.md");
+        fs::write(&readme, "hello\n").unwrap();
+        let readme_mirror = output.path().join("repo/README.md");
+        sync_file(
+            &readme,
+            &readme_mirror,
+            &mut manifest,
+            "repo/README.md",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&readme_mirror).unwrap(), "hello\n");
+
+        // Switching the format off strips the keywords again
+        strip_decorations(&manifest, output.path(), "repo").unwrap();
+        assert_eq!(
+            fs::read_to_string(&mirror).unwrap(),
+            "#api  #draft\n# Auth\n\nMore\n"
+        );
+    }
+
+    #[test]
+    fn test_add_and_remove_keywords() {
+        let keywords = vec!["#docs".to_string(), "#api".to_string()];
+        assert_eq!(
+            add_keywords(b"# Title\n", &keywords),
+            b"#docs #api\n# Title\n"
+        );
+        assert_eq!(add_keywords(b"", &keywords), b"#docs #api\n");
+        assert_eq!(
+            add_keywords(b"#api\r\nbody", &keywords),
+            b"#api #docs\r\nbody"
+        );
+        assert_eq!(add_keywords(b"#docs #api", &keywords), b"#docs #api");
+        assert_eq!(add_keywords(b"\xff\n", &keywords), b"\xff\n");
+
+        assert_eq!(
+            remove_keywords(b"#docs #api\n# Title\n", &keywords, b"# Title\n"),
+            b"# Title\n"
+        );
+        assert_eq!(remove_keywords(b"#docs #api\n", &keywords, b""), b"");
+        assert_eq!(
+            remove_keywords(b"#api #docs\r\nbody", &keywords, b"#api\r\nbody"),
+            b"#api\r\nbody"
+        );
+        assert_eq!(
+            remove_keywords(b"#docs #new\nbody", &keywords, b"body"),
+            b"#new\nbody"
+        );
+        // Headings aren't keyword lines
+        assert_eq!(
+            remove_keywords(b"# docs\nbody", &keywords, b""),
+            b"# docs\nbody"
+        );
+    }
+
+    #[test]
+    fn test_split_source_header() {
+        assert_eq!(
+            split_source_header(b"<!-- source: a/b.md -->\r\nbody"),
+            Some(("a/b.md", b"body".as_slice()))
+        );
+        assert_eq!(split_source_header(b"<!-- note -->\nbody"), None);
+        assert_eq!(split_source_header(b"<!-- source: a.md -->"), None);
+    }
+
+    #[test]
+    fn test_compressed_base_cache_roundtrip() {
+        let output = TempDir::new().unwrap();
+        write_base(
+            &mut Manifest::empty(),
+            output.path(),
+            "repo/plain.md",
+            "written before compression",
+        )
+        .unwrap();
+
+        let options = StateOptions {
+            base_cache_compress: true,
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let content = "# Notes\n\n".to_string() + &"A line of prose.\n".repeat(200);
+        write_base(&mut manifest, output.path(), "repo/doc.md", &content).unwrap();
+
+        let cached = base_cache_path(output.path(), "repo/doc.md");
+        let stored = fs::read(&cached).unwrap();
+        assert!(stored.starts_with(&GZIP_MAGIC));
+        assert!(stored.len() < content.len() / 4);
+        assert_eq!(
+            read_base(&manifest, output.path(), "repo/doc.md").unwrap(),
+            Some(content.clone())
+        );
+        // Bases written uncompressed still read back
+        assert_eq!(
+            read_base(&manifest, output.path(), "repo/plain.md").unwrap(),
+            Some("written before compression".into())
+        );
+
+        // A write that fails partway leaves the previous base whole
+        let name = cached.file_name().unwrap().to_string_lossy();
+        let tmp = cached.with_file_name(format!(".{name}{TEMP_SUFFIX}"));
+        fs::create_dir(&tmp).unwrap();
+        assert!(write_base(&mut manifest, output.path(), "repo/doc.md", "newer").is_err());
+        assert_eq!(
+            read_base(&manifest, output.path(), "repo/doc.md").unwrap(),
+            Some(content)
+        );
+    }
+
+    #[test]
+    fn test_normalized_trailing_newline_is_in_sync() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        let mirror = output.path().join("my-repo").join("doc.md");
+        fs::write(&source, "Say “hi”").unwrap();
+
+        let options = StateOptions {
+            normalize: Normalization {
+                trailing_newline: true,
+                chars: vec![("“".into(), "\"".into()), ("”".into(), "\"".into())],
+            },
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+
+        // Ulysses adds a trailing newline and straightens the quotes on save
+        fs::write(&mirror, "Say \"hi\"\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "Say “hi”");
+        assert_eq!(
+            read_base(&manifest, output.path(), "my-repo/doc.md").unwrap(),
+            Some("Say “hi”".into())
+        );
+
+        // A real edit still syncs back
+        fs::write(&mirror, "Say \"hello\"\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "Say \"hello\"\n");
+    }
+
+    #[test]
+    fn test_merge_keeps_unnormalized_text() {
+        let (repo, output) = setup();
+        let source = repo.path().join("doc.md");
+        let mirror = output.path().join("my-repo").join("doc.md");
+        fs::write(&source, "It’s one\nmiddle\nIt’s two\n").unwrap();
+
+        let options = StateOptions {
+            normalize: Normalization {
+                trailing_newline: false,
+                chars: vec![("’".into(), "'".into())],
+            },
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(output.path(), &options).unwrap();
+        let sync = |manifest: &mut Manifest| {
+            sync_file(
+                &source,
+                &mirror,
+                manifest,
+                "my-repo/doc.md",
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap()
+        };
+        assert_eq!(sync(&mut manifest), SyncOutcome::Copied);
+
+        // Normalization decides both sides changed; the merge keeps the
+        // apostrophes both sides still have
+        fs::write(&source, "It’s ONE\nmiddle\nIt’s two\n").unwrap();
+        fs::write(&mirror, "It’s one\nmiddle\nIt’s TWO\n").unwrap();
+        assert_eq!(sync(&mut manifest), SyncOutcome::Merged);
+        let merged = "It’s ONE\nmiddle\nIt’s TWO\n";
+        assert_eq!(fs::read_to_string(&source).unwrap(), merged);
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), merged);
+        assert_eq!(sync(&mut manifest), SyncOutcome::AlreadyInSync);
+    }
+
+    fn content_mode_manifest(output_dir: &Path) -> Manifest {
+        let options = StateOptions {
+            state_dir: None,
+            base_cache: BaseCacheMode::Content,
+            ..Default::default()
+        };
+        Manifest::load_in(output_dir, &options).unwrap()
+    }
+
+    fn blob_count(output_dir: &Path) -> usize {
+        WalkDir::new(base_cache_dir(output_dir))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .count()
+    }
+
+    #[test]
+    fn test_content_base_cache_dedups_identical_files() {
+        let output = TempDir::new().unwrap();
+        let mut manifest = content_mode_manifest(output.path());
+
+        write_base(&mut manifest, output.path(), "a/LICENSE", "MIT").unwrap();
+        write_base(&mut manifest, output.path(), "b/LICENSE", "MIT").unwrap();
+        write_base(&mut manifest, output.path(), "b/README.md", "hello").unwrap();
+        assert_eq!(blob_count(output.path()), 2);
+        assert_eq!(
+            read_base(&manifest, output.path(), "b/LICENSE").unwrap(),
+            Some("MIT".into())
+        );
+
+        // The index survives a save/load round trip
+        manifest.save(output.path()).unwrap();
+        let manifest = content_mode_manifest(output.path());
+        assert_eq!(
+            manifest.base_blob("a/LICENSE"),
+            manifest.base_blob("b/LICENSE")
+        );
+        assert_eq!(
+            read_base(&manifest, output.path(), "a/LICENSE").unwrap(),
+            Some("MIT".into())
+        );
+    }
+
+    #[test]
+    fn test_content_base_cache_refcount_on_delete() {
+        let output = TempDir::new().unwrap();
+        let mut manifest = content_mode_manifest(output.path());
+
+        write_base(&mut manifest, output.path(), "a/LICENSE", "MIT").unwrap();
+        write_base(&mut manifest, output.path(), "b/LICENSE", "MIT").unwrap();
+
+        // Still referenced by b/LICENSE
+        remove_base(&mut manifest, output.path(), "a/LICENSE").unwrap();
+        assert_eq!(blob_count(output.path()), 1);
+        assert_eq!(
+            read_base(&manifest, output.path(), "a/LICENSE").unwrap(),
+            None
+        );
+
+        // Rewriting the last reference releases the old blob
+        write_base(&mut manifest, output.path(), "b/LICENSE", "Apache").unwrap();
+        assert_eq!(blob_count(output.path()), 1);
+        assert_eq!(
+            read_base(&manifest, output.path(), "b/LICENSE").unwrap(),
+            Some("Apache".into())
+        );
+
+        remove_base(&mut manifest, output.path(), "b/LICENSE").unwrap();
+        assert_eq!(blob_count(output.path()), 0);
+        assert!(!base_cache_dir(output.path()).join(BLOB_DIR).exists());
+    }
+
     #[test]
     fn test_empty_dir_cleanup() {
         let output = TempDir::new().unwrap();
@@ -1048,7 +3248,7 @@ mod tests {
         fs::create_dir_all(old.join("repo").join("sub")).unwrap();
         fs::write(old.join("repo").join("sub").join("doc.md"), "content").unwrap();
 
-        let moved = move_output_dir(&old, &new).unwrap();
+        let moved = move_output_dir(&old, &new, None).unwrap();
         assert!(moved);
         assert!(!old.exists());
         assert!(new.join(MANIFEST_FILENAME).exists());
@@ -1058,6 +3258,135 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_move_output_dir_across_filesystems() {
+        let (repo, old) = setup();
+        let tmp = TempDir::new().unwrap();
+        let new = tmp.path().join("external").join("mirror");
+        fs::write(repo.path().join("doc.md"), "content").unwrap();
+
+        let mut manifest = Manifest::load(old.path()).unwrap();
+        sync_file(
+            &repo.path().join("doc.md"),
+            &old.path().join("my-repo").join("doc.md"),
+            &mut manifest,
+            "my-repo/doc.md",
+            old.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        manifest.save(old.path()).unwrap();
+
+        let cross_device = |_: &Path, _: &Path| Err(std::io::Error::from_raw_os_error(libc::EXDEV));
+        let moved = move_output_dir_with(old.path(), &new, None, cross_device).unwrap();
+
+        assert!(moved);
+        assert!(!old.path().exists());
+        assert_eq!(
+            fs::read_to_string(new.join("my-repo").join("doc.md")).unwrap(),
+            "content"
+        );
+        let manifest = Manifest::load(&new).unwrap();
+        assert!(manifest.get("my-repo/doc.md").is_some());
+        assert_eq!(
+            read_base(&manifest, &new, "my-repo/doc.md").unwrap(),
+            Some("content".into())
+        );
+    }
+
+    #[test]
+    fn test_move_repo_mirror_destination_occupied() {
+        let (repo, output) = setup();
+        let new_output = TempDir::new().unwrap();
+        fs::write(repo.path().join("a.md"), "a").unwrap();
+
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        let mirror = output.path().join("my-repo").join("a.md");
+        sync_file(
+            &repo.path().join("a.md"),
+            &mirror,
+            &mut manifest,
+            "my-repo/a.md",
+            output.path(),
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+
+        fs::create_dir_all(new_output.path().join("my-repo")).unwrap();
+        fs::write(new_output.path().join("my-repo").join("a.md"), "theirs").unwrap();
+
+        let mut new_manifest = Manifest::load(new_output.path()).unwrap();
+        let result = move_repo_mirror(
+            "my-repo",
+            output.path(),
+            &mut manifest,
+            new_output.path(),
+            &mut new_manifest,
+        );
+        assert!(result.is_err());
+        assert!(mirror.exists());
+        assert!(manifest.get("my-repo/a.md").is_some());
+        assert_eq!(
+            fs::read_to_string(new_output.path().join("my-repo").join("a.md")).unwrap(),
+            "theirs"
+        );
+    }
+
+    #[test]
+    fn test_rename_repo_mirror() {
+        let (repo, output) = setup();
+        let other = TempDir::new().unwrap();
+        fs::create_dir_all(repo.path().join("docs")).unwrap();
+        fs::write(repo.path().join("docs").join("a.md"), "a").unwrap();
+        fs::write(other.path().join("b.md"), "b").unwrap();
+
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        for (source, key) in [
+            (repo.path().join("docs").join("a.md"), "my-repo/docs/a.md"),
+            (other.path().join("b.md"), "other/b.md"),
+        ] {
+            sync_file(
+                &source,
+                &output.path().join(key),
+                &mut manifest,
+                key,
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap();
+        }
+        // Untracked Ulysses metadata travels with the subtree
+        fs::write(
+            output.path().join("my-repo").join(".Ulysses-Group.plist"),
+            "meta",
+        )
+        .unwrap();
+
+        let rekeyed =
+            rename_repo_mirror("my-repo", "nicer-name", output.path(), &mut manifest).unwrap();
+        assert_eq!(rekeyed, 1);
+
+        let renamed = output.path().join("nicer-name");
+        assert_eq!(
+            fs::read_to_string(renamed.join("docs").join("a.md")).unwrap(),
+            "a"
+        );
+        assert!(renamed.join(".Ulysses-Group.plist").exists());
+        assert!(!output.path().join("my-repo").exists());
+        assert!(manifest.get("my-repo/docs/a.md").is_none());
+        assert!(manifest.get("nicer-name/docs/a.md").is_some());
+        assert_eq!(
+            read_base(&manifest, output.path(), "nicer-name/docs/a.md").unwrap(),
+            Some("a".into())
+        );
+        assert!(manifest.get("other/b.md").is_some());
+        assert!(output.path().join("other").join("b.md").exists());
+
+        // Renaming onto another repo's mirror is refused
+        assert!(rename_repo_mirror("nicer-name", "other", output.path(), &mut manifest).is_err());
+        assert!(manifest.get("nicer-name/docs/a.md").is_some());
+    }
+
     #[test]
     fn test_move_output_dir_no_manifest() {
         let tmp = TempDir::new().unwrap();
@@ -1066,7 +3395,7 @@ mod tests {
         fs::create_dir_all(&old).unwrap();
         // No manifest file — should fall back
 
-        let moved = move_output_dir(&old, &new).unwrap();
+        let moved = move_output_dir(&old, &new, None).unwrap();
         assert!(!moved);
         assert!(old.exists());
     }
@@ -1081,7 +3410,7 @@ mod tests {
         fs::create_dir_all(&new).unwrap();
         fs::write(new.join("existing.txt"), "something").unwrap();
 
-        let moved = move_output_dir(&old, &new).unwrap();
+        let moved = move_output_dir(&old, &new, None).unwrap();
         assert!(!moved);
         // Both dirs still exist
         assert!(old.exists());
@@ -1098,7 +3427,7 @@ mod tests {
         fs::create_dir_all(&new).unwrap();
         // new exists but is empty — should succeed
 
-        let moved = move_output_dir(&old, &new).unwrap();
+        let moved = move_output_dir(&old, &new, None).unwrap();
         assert!(moved);
         assert!(!old.exists());
         assert!(new.join(MANIFEST_FILENAME).exists());
@@ -1112,19 +3441,54 @@ mod tests {
         fs::create_dir_all(&old).unwrap();
         fs::write(old.join(MANIFEST_FILENAME), "manifest data").unwrap();
 
-        let moved = move_output_dir(&old, &new).unwrap();
+        let moved = move_output_dir(&old, &new, None).unwrap();
         assert!(moved);
         assert!(!old.exists());
         assert!(new.join(MANIFEST_FILENAME).exists());
     }
 
+    #[test]
+    fn test_write_output_gitignore() {
+        let output = TempDir::new().unwrap();
+
+        let written = write_output_gitignore(output.path()).unwrap();
+        assert!(written);
+        let content = fs::read_to_string(output.path().join(".gitignore")).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                ".ulysses-link",
+                ".ulysses-link.log",
+                ".ulysses-link.d/",
+                "*.conflict_*"
+            ]
+        );
+
+        // Second call is a no-op
+        assert!(!write_output_gitignore(output.path()).unwrap());
+    }
+
+    #[test]
+    fn test_write_output_gitignore_keeps_existing() {
+        let output = TempDir::new().unwrap();
+        fs::write(output.path().join(".gitignore"), "my-notes/\n").unwrap();
+
+        let written = write_output_gitignore(output.path()).unwrap();
+        assert!(!written);
+        assert_eq!(
+            fs::read_to_string(output.path().join(".gitignore")).unwrap(),
+            "my-notes/\n"
+        );
+    }
+
     #[test]
     fn test_save_conflict() {
         let tmp = TempDir::new().unwrap();
         let file = tmp.path().join("doc.md");
         fs::write(&file, "current").unwrap();
 
-        let conflict_path = save_conflict(&file, "old content").unwrap();
+        let conflict_path = save_conflict(&file, b"old content").unwrap();
         assert!(conflict_path.exists());
         assert!(conflict_path
             .file_name()
@@ -1132,5 +3496,11 @@ mod tests {
             .to_string_lossy()
             .contains("doc.md.conflict_"));
         assert_eq!(fs::read_to_string(&conflict_path).unwrap(), "old content");
+
+        // A second conflict in the same second gets its own file
+        let second = save_conflict(&file, b"older content").unwrap();
+        assert_ne!(second, conflict_path);
+        assert_eq!(fs::read_to_string(&conflict_path).unwrap(), "old content");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "older content");
     }
 }
@@ -1,8 +1,14 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use clap::{Parser, Subcommand};
-use ulysses_link::{config, engine, linker, manifest, scanner, service, upgrade};
+use ulysses_link::color::{self, paint};
+use ulysses_link::{
+    config, diff, engine, linker, manifest, portable, scanner, service, stats, syncer, upgrade,
+    verify,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -15,61 +21,367 @@ const VERSION: &str = env!("CARGO_PKG_VERSION");
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Log more for this run, overriding log_level (-v debug, -vv trace)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Log only errors for this run, overriding log_level, and print only
+    /// each command's result line
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Color sync summaries, logs and status: auto colors only a terminal, and
+    /// not when NO_COLOR is set
+    #[arg(long, value_enum, global = true, default_value_t = clap::ColorChoice::Auto)]
+    color: clap::ColorChoice,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Sync a directory (or all configured repos) to the link tree
     Sync {
-        /// Directory to add and sync. Omit to sync all configured repos.
-        path: Option<PathBuf>,
+        /// Directories to add and sync. Omit to sync all configured repos.
+        paths: Vec<PathBuf>,
 
         /// Output directory for the mirror tree.
         /// Required when no config file exists.
+        #[arg(long)]
         output: Option<PathBuf>,
 
+        /// Extra exclude pattern for this run only (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+
+        /// Extra include pattern for this run only (repeatable)
+        #[arg(long)]
+        include: Vec<String>,
+
+        /// Let include patterns match hidden paths for this run only
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// Mirror files reached through symlinks in every repo for this run only
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Only mirror files git tracks in every repo for this run only
+        #[arg(long)]
+        exclude_vcs_untracked: bool,
+
+        /// Only remove mirrors of deleted sources; don't copy or re-check files
+        #[arg(long)]
+        prune_only: bool,
+
+        /// Only sync source files modified within this long, e.g. 30m, 2h, 1d.
+        /// Stale mirrors are still pruned.
+        #[arg(long, value_parser = parse_since, conflicts_with = "prune_only")]
+        since: Option<Duration>,
+
+        /// Copy at most this many new files, leaving the rest for later syncs.
+        /// Overrides initial_sync_limit.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "prune_only")]
+        limit: Option<u32>,
+
+        /// Hash each repo's files on all CPU cores before syncing them.
+        /// Overrides parallel_hash.
+        #[arg(long, conflicts_with = "prune_only")]
+        parallel_hash: bool,
+
+        /// Re-hash each file after writing it, rewriting it once on a
+        /// mismatch. Overrides verify_after_write.
+        #[arg(long)]
+        verify_after: bool,
+
+        /// Sync even if the output directory looks too full for the files to copy
+        #[arg(long)]
+        no_space_check: bool,
+
+        /// List what would be copied, merged and deleted without writing
+        /// anything. Only for the configured repos.
+        #[arg(long, conflicts_with_all = ["paths", "since", "limit"])]
+        dry_run: bool,
+
         /// Path to config file
         #[arg(long)]
         config: Option<PathBuf>,
     },
     /// Remove a directory from the synced repos
+    #[command(group(clap::ArgGroup::new("target").required(true).args(["path", "name", "all"])))]
     Remove {
         /// Directory path to remove
-        path: PathBuf,
+        path: Option<PathBuf>,
+
+        /// Remove the repo with this configured name instead of by path
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Remove every configured repo
+        #[arg(long)]
+        all: bool,
+
+        /// Don't ask for confirmation, keeping the mirrored files unless
+        /// --delete-mirrors is given. Required when not run from a terminal.
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Also delete the mirrored files without asking
+        #[arg(long)]
+        delete_mirrors: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Move a repo's mirror to a different output directory
+    Move {
+        /// Name of the repo to move
+        name: String,
+
+        /// New output directory for this repo's mirror
+        new_output: PathBuf,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Rename a repo, moving its mirror to match the new name
+    Rename {
+        /// Current name or path of the repo
+        repo: String,
+
+        /// New name for the repo
+        new_name: String,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Reset mirrors to match source, discarding edits made in the mirror
+    Reset {
+        /// Only reset the repo with this name
+        name: Option<String>,
+
+        /// Save discarded mirror edits as conflict files
+        #[arg(long)]
+        keep_conflicts: bool,
+
+        /// Don't ask for confirmation. Required when not run from a terminal.
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Delete the manifest and/or base cache, keeping mirrored files so the
+    /// next sync claims them again
+    #[command(group(clap::ArgGroup::new("state").required(true).args(["cache", "manifest", "all"])))]
+    Clean {
+        /// Delete the base cache kept for three-way merges
+        #[arg(long)]
+        cache: bool,
+
+        /// Delete the manifest of tracked files
+        #[arg(long)]
+        manifest: bool,
+
+        /// Delete both
+        #[arg(long)]
+        all: bool,
+
+        /// Don't ask for confirmation. Required when not run from a terminal.
+        #[arg(long, short)]
+        yes: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Write the sync state of every output directory to a JSON file, to
+    /// carry it to another machine
+    ExportState {
+        /// File to write
+        file: PathBuf,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Load sync state written by export-state into the output directories'
+    /// manifests, so existing files are tracked instead of copied or skipped
+    ImportState {
+        /// File written by export-state
+        file: PathBuf,
+
+        /// Rewrite paths starting with OLD to start with NEW, e.g. an old
+        /// home directory (repeatable)
+        #[arg(long, value_name = "OLD=NEW", value_parser = portable::Remap::parse)]
+        remap: Vec<portable::Remap>,
 
         /// Path to config file
         #[arg(long)]
         config: Option<PathBuf>,
     },
     /// Open the config file in your editor
-    Config,
+    Config {
+        /// Rewrite a version 1 config file as version 2 instead of opening it
+        #[arg(long)]
+        migrate: bool,
+
+        /// Validate the config and print the resolved repos instead of opening it
+        #[arg(long, conflicts_with = "migrate")]
+        check: bool,
+
+        /// Print a starter config instead of opening it: "minimal" is the
+        /// generated default, "full" shows every supported option
+        #[arg(long, value_parser = ["minimal", "full"], conflicts_with_all = ["migrate", "check", "config"])]
+        template: Option<String>,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Show the output directory in Finder, Explorer, or the file manager
+    Open {
+        /// Open this repo's mirror folder instead
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
     /// Install as an OS background service
     Install {
+        /// Write service logs to this directory instead of the platform
+        /// default. Saved to the config as `log_dir`.
+        #[arg(long)]
+        log_dir: Option<PathBuf>,
+
         /// Path to config file
         #[arg(long)]
         config: Option<PathBuf>,
     },
+    /// Ask the running service for a full rescan now
+    Rescan,
     /// Remove the OS background service
-    Uninstall,
+    Uninstall {
+        /// Don't ask for confirmation. Required when not run from a terminal.
+        #[arg(long, short)]
+        yes: bool,
+    },
     /// Check service status
-    Status,
+    Status {
+        /// Show only this repo's last sync and log lines
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Summarize the mirror tree in each output directory
+    Stats {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Check each manifest entry against the files on disk
+    Verify {
+        /// Re-sync drifted files whose source still exists
+        #[arg(long)]
+        fix: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Show how tracked files differ between source and mirror
+    Diff {
+        /// Only this file, as its path in the repo or in the output dir
+        path: Option<String>,
+
+        /// Only files of the repo with this name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Print changed line counts per file instead of the diffs
+        #[arg(long)]
+        stat: bool,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Bump the modification time of mirrored files so Ulysses re-imports
+    /// them, without changing their content
+    Reindex {
+        /// Only files of the repo with this name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
     /// Show service logs
-    Logs,
+    Logs {
+        /// Read logs from this directory instead of the configured `log_dir`
+        #[arg(long)]
+        log_dir: Option<PathBuf>,
+
+        /// Show only log lines naming this repo
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+    },
+    /// Watch repos in the foreground with a live status line
+    Watch {
+        /// Path to config file
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Log what would sync on each change instead of syncing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Log a summary line at this interval, e.g. 10m, overriding stats_interval
+        #[arg(long, value_parser = parse_interval)]
+        stats_interval: Option<Duration>,
+    },
     /// Start watching repos in the foreground
     #[command(hide = true)]
     Run {
         /// Path to config file
         #[arg(long)]
         config: Option<PathBuf>,
+
+        /// Log what would sync on each change instead of syncing
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Log a summary line at this interval, e.g. 10m, overriding stats_interval
+        #[arg(long, value_parser = parse_interval)]
+        stats_interval: Option<Duration>,
     },
     /// Upgrade to the latest version
     Upgrade,
     /// Print version and exit
-    Version,
+    Version {
+        /// Print the version and build details as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 fn main() {
     let cli = Cli::parse();
+    let log_level = level_override(cli.verbose, cli.quiet);
+    color::set_choice(cli.color);
 
     match cli.command {
         None => {
@@ -78,29 +390,139 @@ fn main() {
             println!();
             std::process::exit(1);
         }
-        Some(Commands::Version) => {
-            println!("ulysses-link {VERSION}");
-        }
+        Some(Commands::Version { json }) => cmd_version(json),
         Some(Commands::Sync {
-            path,
+            paths,
+            output,
+            exclude,
+            include,
+            include_hidden,
+            follow_symlinks,
+            exclude_vcs_untracked,
+            prune_only,
+            since,
+            limit,
+            parallel_hash,
+            verify_after,
+            no_space_check,
+            dry_run,
+            config,
+        }) => cmd_sync(
+            paths,
             output,
+            &RunPatterns {
+                exclude,
+                include,
+                include_hidden,
+                follow_symlinks,
+                exclude_vcs_untracked,
+            },
+            prune_only,
+            since,
+            limit,
+            parallel_hash,
+            verify_after,
+            !no_space_check,
+            dry_run,
+            config,
+            log_level,
+            cli.quiet,
+        ),
+        Some(Commands::Remove {
+            path,
+            name,
+            all,
+            yes,
+            delete_mirrors,
+            config,
+        }) => cmd_remove(path, name, all, yes, delete_mirrors, config),
+        Some(Commands::Move {
+            name,
+            new_output,
+            config,
+        }) => cmd_move(name, new_output, config, cli.quiet),
+        Some(Commands::Rename {
+            repo,
+            new_name,
+            config,
+        }) => cmd_rename(repo, new_name, config, cli.quiet),
+        Some(Commands::Reset {
+            name,
+            keep_conflicts,
+            yes,
+            config,
+        }) => cmd_reset(name, keep_conflicts, yes, config, log_level),
+        Some(Commands::Clean {
+            cache,
+            manifest,
+            all,
+            yes,
+            config,
+        }) => cmd_clean(cache || all, manifest || all, yes, config, log_level),
+        Some(Commands::ExportState { file, config }) => cmd_export_state(file, config),
+        Some(Commands::ImportState {
+            file,
+            remap,
             config,
-        }) => cmd_sync(path, output, config),
-        Some(Commands::Remove { path, config }) => cmd_remove(path, config),
-        Some(Commands::Config) => cmd_config(),
-        Some(Commands::Run { config }) => cmd_run(config),
-        Some(Commands::Install { config }) => cmd_install(config),
-        Some(Commands::Uninstall) => cmd_uninstall(),
+        }) => cmd_import_state(file, remap, config),
+        Some(Commands::Config {
+            migrate,
+            check,
+            template,
+            config,
+        }) => cmd_config(migrate, check, template, config),
+        Some(Commands::Open { name, config }) => cmd_open(name, config),
+        Some(Commands::Run {
+            config,
+            dry_run,
+            stats_interval,
+        }) => cmd_run(config, dry_run, stats_interval, log_level),
+        Some(Commands::Watch {
+            config,
+            dry_run,
+            stats_interval,
+        }) => cmd_watch(config, dry_run, stats_interval, log_level),
+        Some(Commands::Install { log_dir, config }) => cmd_install(log_dir, config, log_level),
+        Some(Commands::Rescan) => cmd_rescan(),
+        Some(Commands::Uninstall { yes }) => cmd_uninstall(yes, log_level),
         Some(Commands::Upgrade) => cmd_upgrade(),
-        Some(Commands::Status) => cmd_status(),
-        Some(Commands::Logs) => cmd_logs(),
+        Some(Commands::Status { name }) => cmd_status(name),
+        Some(Commands::Stats { json, config }) => cmd_stats(json, config),
+        Some(Commands::Verify { fix, config }) => cmd_verify(fix, config),
+        Some(Commands::Diff {
+            path,
+            name,
+            stat,
+            config,
+        }) => cmd_diff(path, name, stat, config),
+        Some(Commands::Reindex { name, config }) => cmd_reindex(name, config, cli.quiet),
+        Some(Commands::Logs {
+            log_dir,
+            name,
+            config,
+        }) => cmd_logs(log_dir, name, config),
+    }
+}
+
+/// Log level forced by `-v`/`-q` for this run, if any.
+fn level_override(verbose: u8, quiet: bool) -> Option<&'static str> {
+    match verbose {
+        0 if quiet => Some("ERROR"),
+        0 => None,
+        1 => Some("DEBUG"),
+        _ => Some("TRACE"),
     }
 }
 
-fn setup_logging(log_level: &str) {
+/// The level to log at: the command-line override, else the config's `log_level`.
+fn resolve_log_level<'a>(configured: &'a str, level_override: Option<&'a str>) -> &'a str {
+    level_override.unwrap_or(configured)
+}
+
+fn setup_logging(configured: &str, level_override: Option<&str>) {
     use tracing_subscriber::EnvFilter;
 
-    let level = match log_level {
+    let level = match resolve_log_level(configured, level_override) {
         "TRACE" => "trace",
         "DEBUG" => "debug",
         "INFO" => "info",
@@ -115,28 +537,45 @@ fn setup_logging(log_level: &str) {
     tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(true)
+        .with_ansi(color::enabled())
         .init();
 }
 
-fn load_manifests(cfg: &config::Config) -> HashMap<PathBuf, manifest::Manifest> {
-    let mut manifests = HashMap::new();
-    for output_dir in cfg.active_output_dirs() {
-        match manifest::Manifest::load(&output_dir) {
-            Ok(m) => {
-                manifests.insert(output_dir, m);
-            }
-            Err(e) => {
-                eprintln!("Failed to load manifest from {}: {e}", output_dir.display());
-                std::process::exit(1);
-            }
-        }
+/// Set up logging at the config's level, then log the warnings found while
+/// loading it, before any subscriber was listening.
+fn setup_config_logging(cfg: &config::Config, level_override: Option<&str>) {
+    setup_logging(&cfg.log_level, level_override);
+    for warning in &cfg.pattern_warnings {
+        tracing::warn!("{warning}");
     }
-    manifests
 }
 
-fn cmd_sync(path: Option<PathBuf>, output: Option<PathBuf>, config_arg: Option<PathBuf>) {
-    if let Some(ref repo_path) = path {
-        // Sync a specific directory: ensure config exists, add repo, scan
+fn load_manifests(cfg: &config::Config) -> HashMap<PathBuf, manifest::Manifest> {
+    syncer::load_manifests(cfg).unwrap_or_else(|e| {
+        eprintln!("Error: {e:#}");
+        std::process::exit(1);
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_sync(
+    paths: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    patterns: &RunPatterns,
+    prune_only: bool,
+    since: Option<Duration>,
+    limit: Option<u32>,
+    parallel_hash: bool,
+    verify_after: bool,
+    space_check: bool,
+    dry_run: bool,
+    config_arg: Option<PathBuf>,
+    log_level: Option<&str>,
+    quiet: bool,
+) {
+    // Every path is a repo; the output dir is only ever named by --output
+    if !paths.is_empty() {
+        // Sync specific directories: ensure config exists, add repos, scan
         let config_path =
             match config::ensure_config_exists(config_arg.as_deref(), output.as_deref()) {
                 Ok(p) => p,
@@ -154,36 +593,45 @@ fn cmd_sync(path: Option<PathBuf>, output: Option<PathBuf>, config_arg: Option<P
             }
         }
 
-        match config::add_repo(&config_path, repo_path) {
-            Ok(true) => println!("Added {} to config", repo_path.display()),
-            Ok(false) => println!("{} is already configured", repo_path.display()),
-            Err(e) => {
-                eprintln!("Failed to add repo: {e}");
-                std::process::exit(1);
+        for repo_path in &paths {
+            match config::add_repo(&config_path, repo_path) {
+                Ok(_) if quiet => {}
+                Ok(true) => println!("Added {} to config", repo_path.display()),
+                Ok(false) => println!("{} is already configured", repo_path.display()),
+                Err(e) => {
+                    eprintln!("Failed to add repo {}: {e}", repo_path.display());
+                    std::process::exit(1);
+                }
             }
         }
 
-        let cfg = match config::load_config(Some(&config_path)) {
+        let mut cfg = match config::load_config(Some(&config_path)) {
             Ok(c) => c,
             Err(e) => {
                 eprintln!("Error: {e}");
                 std::process::exit(1);
             }
         };
-        setup_logging(&cfg.log_level);
+        setup_config_logging(&cfg, log_level);
+        add_patterns_or_exit(&mut cfg, patterns);
+        apply_run_overrides(&mut cfg, limit, parallel_hash, verify_after);
 
-        let mut manifests = load_manifests(&cfg);
-        let result = scanner::full_scan(&cfg, &mut manifests);
-        print_sync_summary(&result);
+        run_sync(cfg, prune_only, since, space_check, false);
 
-        notify_or_warn_service();
+        notify_or_warn_service(show_hints(quiet));
     } else {
-        // Bare sync: sync all repos in config
-        let cfg = match config::load_config(config_arg.as_deref()) {
+        // Bare sync: sync all repos in config. A dry run creates nothing,
+        // output dirs included
+        let load = if dry_run {
+            config::load_config_without_dirs
+        } else {
+            config::load_config
+        };
+        let mut cfg = match load(config_arg.as_deref()) {
             Ok(c) => c,
             Err(config::ConfigError::NoConfigFound) => {
                 eprintln!(
-                    "No config file found. Run 'ulysses-link sync <path> <output-dir>' to get started."
+                    "No config file found. Run 'ulysses-link sync <path> --output <output-dir>' to get started."
                 );
                 std::process::exit(1);
             }
@@ -192,118 +640,531 @@ fn cmd_sync(path: Option<PathBuf>, output: Option<PathBuf>, config_arg: Option<P
                 std::process::exit(1);
             }
         };
-        setup_logging(&cfg.log_level);
+        setup_config_logging(&cfg, log_level);
+        add_patterns_or_exit(&mut cfg, patterns);
+        apply_run_overrides(&mut cfg, limit, parallel_hash, verify_after);
+
+        run_sync(cfg, prune_only, since, space_check, dry_run);
+    }
+}
+
+/// `sync` options choosing which files are mirrored, for this run only.
+struct RunPatterns {
+    exclude: Vec<String>,
+    include: Vec<String>,
+    include_hidden: bool,
+    follow_symlinks: bool,
+    exclude_vcs_untracked: bool,
+}
+
+/// Apply `sync --exclude/--include/--include-hidden/--follow-symlinks/
+/// --exclude-vcs-untracked` for this run only.
+fn add_patterns_or_exit(cfg: &mut config::Config, patterns: &RunPatterns) {
+    if patterns.include_hidden {
+        cfg.set_include_hidden();
+    }
+    if patterns.follow_symlinks {
+        cfg.set_follow_symlinks();
+    }
+    if patterns.exclude_vcs_untracked {
+        cfg.set_git_tracked_only();
+    }
+    if patterns.exclude.is_empty() && patterns.include.is_empty() {
+        return;
+    }
+    if let Err(e) = cfg.add_patterns(&patterns.exclude, &patterns.include) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+/// Apply `sync --limit/--parallel-hash/--verify-after` for this run only.
+fn apply_run_overrides(
+    cfg: &mut config::Config,
+    limit: Option<u32>,
+    parallel_hash: bool,
+    verify_after: bool,
+) {
+    if limit.is_some() {
+        cfg.initial_sync_limit = limit;
+    }
+    if parallel_hash {
+        cfg.parallel_hash = true;
+    }
+    if verify_after {
+        cfg.state.verify_after_write = true;
+    }
+}
 
-        let mut manifests = load_manifests(&cfg);
-        let result = scanner::full_scan(&cfg, &mut manifests);
+fn run_sync(
+    cfg: config::Config,
+    prune_only: bool,
+    since: Option<Duration>,
+    space_check: bool,
+    dry_run: bool,
+) {
+    if dry_run {
+        let syncer = syncer::Syncer::new(cfg).unwrap_or_else(|e| {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1);
+        });
+        let result = if prune_only {
+            syncer.plan_prune_all()
+        } else {
+            syncer.plan_all()
+        };
+        print_dry_run(&result, prune_only);
+        return;
+    }
+    if space_check && !prune_only {
+        if let Err(e) = scanner::check_disk_space(&cfg) {
+            eprintln!("Error: {e:#}. Pass --no-space-check to sync anyway.");
+            std::process::exit(1);
+        }
+    }
+    let mut syncer = syncer::Syncer::new(cfg).unwrap_or_else(|e| {
+        eprintln!("Error: {e:#}");
+        std::process::exit(1);
+    });
+    if prune_only {
+        let result = syncer.prune_all();
+        if result.errors > 0 {
+            println!(
+                "Prune complete: {} pruned, {} errors",
+                result.pruned, result.errors
+            );
+        } else {
+            println!("Prune complete: {} pruned", result.pruned);
+        }
+    } else {
+        let result = match since {
+            Some(window) => syncer.scan_since(
+                SystemTime::now()
+                    .checked_sub(window)
+                    .unwrap_or(SystemTime::UNIX_EPOCH),
+            ),
+            None => syncer.scan_all(),
+        };
         print_sync_summary(&result);
     }
 }
 
+/// Parse a `--since` window: a whole number followed by `s`, `m`, `h` or `d`.
+fn parse_since(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a number and a unit (s, m, h, d), got '{s}'");
+    let unit = s.chars().last().ok_or_else(invalid)?;
+    let seconds = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = s[..s.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| invalid())?;
+    let window = count
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .filter(|&window| SystemTime::now().checked_sub(window).is_some())
+        .ok_or_else(|| format!("'{s}' reaches back further than the clock can count"))?;
+    Ok(window)
+}
+
+/// Parse a `--stats-interval`: a `--since` style duration, which can't be zero.
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    let interval = parse_since(s)?;
+    if interval.is_zero() {
+        return Err(format!("the interval must be longer than zero, got '{s}'"));
+    }
+    Ok(interval)
+}
+
 fn print_sync_summary(result: &scanner::ScanResult) {
+    let created = format!("{} created", result.created);
     let mut parts = vec![
-        format!("{} created", result.created),
+        if result.created > 0 {
+            paint(color::SUCCESS, created)
+        } else {
+            created
+        },
         format!("{} existed", result.already_existed),
     ];
     if result.skipped > 0 {
-        parts.push(format!("{} skipped", result.skipped));
+        parts.push(paint(color::NOTICE, format!("{} skipped", result.skipped)));
     }
     if result.merged > 0 {
-        parts.push(format!("{} merged", result.merged));
+        parts.push(paint(color::MERGED, format!("{} merged", result.merged)));
     }
     if result.conflicts > 0 {
-        parts.push(format!("{} conflicts", result.conflicts));
+        parts.push(paint(
+            color::PROBLEM,
+            format!("{} conflicts", result.conflicts),
+        ));
     }
     parts.push(format!("{} pruned", result.pruned));
+    if result.deferred > 0 {
+        parts.push(paint(
+            color::NOTICE,
+            format!("{} deferred", result.deferred),
+        ));
+    }
     if result.errors > 0 {
-        parts.push(format!("{} errors", result.errors));
+        parts.push(paint(color::PROBLEM, format!("{} errors", result.errors)));
     }
-    println!("Sync complete: {}", parts.join(", "));
+    anstream::println!("Sync complete: {}", parts.join(", "));
 }
 
-fn cmd_remove(repo_path: PathBuf, config_arg: Option<PathBuf>) {
-    let config_path = match config::find_config_path(config_arg.as_deref()) {
-        Ok(p) => p,
-        Err(e) => {
-            eprintln!("Error: {e}");
-            std::process::exit(1);
-        }
-    };
+/// List the mirror files a dry run would delete, then the planned totals.
+/// Deletes are listed one by one since they're the changes hardest to undo.
+fn print_dry_run(result: &scanner::ScanResult, prune_only: bool) {
+    for path in &result.planned_deletes {
+        println!("Would delete {}", path.display());
+    }
+    if prune_only {
+        println!("Dry run: {} would be pruned", result.pruned);
+        return;
+    }
+    let created = format!("{} would be created", result.created);
+    let mut parts = vec![
+        if result.created > 0 {
+            paint(color::SUCCESS, created)
+        } else {
+            created
+        },
+        format!("{} existed", result.already_existed),
+    ];
+    if result.skipped > 0 {
+        parts.push(paint(color::NOTICE, format!("{} skipped", result.skipped)));
+    }
+    if result.merged > 0 {
+        parts.push(paint(
+            color::MERGED,
+            format!("{} would be merged", result.merged),
+        ));
+    }
+    if result.conflicts > 0 {
+        parts.push(paint(
+            color::PROBLEM,
+            format!("{} would conflict", result.conflicts),
+        ));
+    }
+    parts.push(format!("{} would be pruned", result.pruned));
+    if result.errors > 0 {
+        parts.push(paint(color::PROBLEM, format!("{} errors", result.errors)));
+    }
+    anstream::println!("Dry run: {}", parts.join(", "));
+}
 
-    // Load config to find the repo name and output dir
-    let cfg = match config::load_config(Some(&config_path)) {
+fn cmd_reset(
+    name: Option<String>,
+    keep_conflicts: bool,
+    yes: bool,
+    config_arg: Option<PathBuf>,
+    log_level: Option<&str>,
+) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Error: {e}");
             std::process::exit(1);
         }
     };
+    setup_config_logging(&cfg, log_level);
 
-    // Find the matching repo
-    let canonical = std::fs::canonicalize(&repo_path).unwrap_or_else(|_| repo_path.clone());
-    let matched_repo = cfg.repos.iter().find(|r| r.path == canonical);
-
-    if matched_repo.is_none() {
-        eprintln!("{} is not in the config", repo_path.display());
+    let repos: Vec<&config::RepoConfig> = cfg
+        .enabled_repos()
+        .filter(|r| name.as_ref().is_none_or(|n| &r.name == n))
+        .collect();
+    if repos.is_empty() {
+        match name {
+            Some(n) => eprintln!("No enabled repo named '{n}' in the config"),
+            None => eprintln!("No enabled repos in the config"),
+        }
         std::process::exit(1);
     }
-    let matched_repo = matched_repo.unwrap();
-    let repo_name = matched_repo.name.clone();
-    let repo_output_dir = matched_repo.output_dir.clone();
 
-    // Confirm removal
-    let confirm = dialoguer::Confirm::new()
-        .with_prompt(format!("Remove {} from synced repos?", repo_path.display()))
-        .default(false)
-        .interact()
-        .unwrap_or(false);
-
-    if !confirm {
-        println!("Cancelled.");
+    let fate = if keep_conflicts {
+        "saved as conflict files"
+    } else {
+        "discarded"
+    };
+    let prompt = format!(
+        "Reset {} mirror(s) to match source? Edits made in the mirror will be {fate}.",
+        repos.len()
+    );
+    if !confirm_or_exit(&prompt, false, yes) {
+        println!("Cancelled.");
         return;
     }
 
-    // Remove from config
-    match config::remove_repo(&config_path, &repo_path) {
-        Ok(Some(_)) => println!("Removed from config"),
-        Ok(None) => {
-            eprintln!("{} is not in the config", repo_path.display());
+    let mut manifests = load_manifests(&cfg);
+    let mut errors = 0;
+    for repo in repos {
+        let Some(manifest) = manifests.get_mut(&repo.output_dir) else {
+            continue;
+        };
+        let result = scanner::reset_repo(repo, &repo.output_dir, manifest, keep_conflicts);
+        println!(
+            "{}: {} reset, {} unchanged, {} mirror edits {fate}",
+            repo.name,
+            result.created + result.conflicts,
+            result.already_existed,
+            result.conflicts
+        );
+        errors += result.errors;
+    }
+    if errors > 0 {
+        eprintln!("{errors} errors, see log output above");
+        std::process::exit(1);
+    }
+}
+
+fn cmd_clean(
+    cache: bool,
+    manifest: bool,
+    yes: bool,
+    config_arg: Option<PathBuf>,
+    log_level: Option<&str>,
+) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    setup_config_logging(&cfg, log_level);
+
+    let what = match (cache, manifest) {
+        (true, true) => "the manifest and base cache",
+        (true, false) => "the base cache",
+        _ => "the manifest",
+    };
+    let output_dirs = cfg.active_output_dirs();
+    let prompt = format!(
+        "Delete {what} for {} output director{}? Mirrored files are kept and claimed again on the next sync.",
+        output_dirs.len(),
+        if output_dirs.len() == 1 { "y" } else { "ies" }
+    );
+    if !confirm_or_exit(&prompt, false, yes) {
+        println!("Cancelled.");
+        return;
+    }
+
+    let state_dir = cfg.state.state_dir.as_deref();
+    let failed = while_service_stopped(|| {
+        for output_dir in &output_dirs {
+            match linker::clean_state(output_dir, state_dir, cache, manifest) {
+                Ok(removed) => {
+                    for path in removed {
+                        println!("Removed {}", path.display());
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Error: {e:#}");
+                    return true;
+                }
+            }
+        }
+        false
+    });
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn cmd_export_state(file: PathBuf, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
             std::process::exit(1);
         }
+    };
+    let state = match portable::export_state(&cfg) {
+        Ok(s) => s,
         Err(e) => {
-            eprintln!("Failed to remove repo: {e}");
+            eprintln!("Error: {e:#}");
             std::process::exit(1);
         }
+    };
+    let json = match serde_json::to_string_pretty(&state) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Failed to serialize state: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = std::fs::write(&file, json) {
+        eprintln!("Failed to write {}: {e}", file.display());
+        std::process::exit(1);
+    }
+    let files: usize = state.output_dirs.values().map(|m| m.files.len()).sum();
+    println!(
+        "Exported {files} tracked files from {} output director{} to {}",
+        state.output_dirs.len(),
+        if state.output_dirs.len() == 1 {
+            "y"
+        } else {
+            "ies"
+        },
+        file.display()
+    );
+}
+
+fn cmd_import_state(file: PathBuf, remaps: Vec<portable::Remap>, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let json = match std::fs::read_to_string(&file) {
+        Ok(j) => j,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", file.display());
+            std::process::exit(1);
+        }
+    };
+    let state = match serde_json::from_str(&json) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to parse {}: {e}", file.display());
+            std::process::exit(1);
+        }
+    };
+    match while_service_stopped(|| portable::import_state(&cfg, state, &remaps)) {
+        Ok(imported) => {
+            for (output_dir, count) in imported {
+                println!(
+                    "Imported {count} tracked files into {}",
+                    output_dir.display()
+                );
+            }
+        }
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_remove(
+    repo_path: Option<PathBuf>,
+    name: Option<String>,
+    all: bool,
+    yes: bool,
+    delete_mirrors: bool,
+    config_arg: Option<PathBuf>,
+) {
+    let config_path = match config::find_config_path(config_arg.as_deref()) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Load config to find the repo name and output dir
+    let cfg = match config::load_config(Some(&config_path)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // Find the matching repos, including ones whose directory no longer
+    // exists. Names are unique after `-2`-style dedup.
+    let mut candidates: Vec<config::RepoLocation> =
+        cfg.repos.iter().map(config::RepoLocation::from).collect();
+    candidates.extend(cfg.missing_repos.iter().cloned());
+    let targets: Vec<config::RepoLocation> = if all {
+        candidates
+    } else if let Some(ref name) = name {
+        let Some(repo) = candidates.into_iter().find(|r| &r.name == name) else {
+            eprintln!("No repo named '{name}' in the config");
+            std::process::exit(1);
+        };
+        vec![repo]
+    } else {
+        let repo_path = repo_path.unwrap_or_default();
+        let canonical = std::fs::canonicalize(&repo_path).unwrap_or_else(|_| repo_path.clone());
+        let Some(repo) = candidates.into_iter().find(|r| r.path == canonical) else {
+            eprintln!("{} is not in the config", repo_path.display());
+            std::process::exit(1);
+        };
+        vec![repo]
+    };
+
+    if targets.is_empty() {
+        println!("No repos in the config.");
+        return;
+    }
+
+    // Confirm removal
+    let prompt = if all {
+        format!("Remove all {} repos from synced repos?", targets.len())
+    } else {
+        format!("Remove {} from synced repos?", targets[0].path.display())
+    };
+    if !confirm_or_exit(&prompt, false, yes) {
+        println!("Cancelled.");
+        return;
+    }
+
+    // Remove from config
+    for repo in &targets {
+        match config::remove_repo(&config_path, &repo.path) {
+            Ok(Some(_)) => println!("Removed {} from config", repo.name),
+            Ok(None) => {
+                eprintln!("{} is not in the config", repo.path.display());
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Failed to remove repo: {e}");
+                std::process::exit(1);
+            }
+        }
     }
 
     // Ask about removing linked files
-    let mirror_path = repo_output_dir.join(&repo_name);
-    if mirror_path.exists() {
-        let remove_links = dialoguer::Confirm::new()
-            .with_prompt(format!(
+    let mirrored: Vec<&config::RepoLocation> = targets
+        .iter()
+        .filter(|r| r.mirror_root().exists())
+        .collect();
+    if !mirrored.is_empty() {
+        let prompt = if all {
+            format!("Also remove linked files for all {} repos?", mirrored.len())
+        } else {
+            format!(
                 "Also remove linked files from {}?",
-                mirror_path.display()
-            ))
-            .default(true)
-            .interact()
-            .unwrap_or(true);
-
-        if remove_links {
-            let mut manifest = match manifest::Manifest::load(&repo_output_dir) {
-                Ok(m) => m,
-                Err(e) => {
-                    eprintln!("Failed to load manifest: {e}");
-                    std::process::exit(1);
-                }
-            };
-            if let Err(e) = linker::remove_repo_mirror(&repo_name, &repo_output_dir, &mut manifest)
-            {
-                eprintln!("Failed to remove mirrored files: {e}");
-            } else {
-                if let Err(e) = manifest.save(&repo_output_dir) {
-                    eprintln!("Failed to save manifest: {e}");
+                mirrored[0].mirror_root().display()
+            )
+        };
+        // --yes only answers the removal prompt; deleting mirrors, which may
+        // hold edits not yet synced back, takes --delete-mirrors
+        if delete_mirrors || (!yes && confirm_or_exit(&prompt, true, false)) {
+            for repo in mirrored {
+                let mut manifest = match manifest::Manifest::load_in(&repo.output_dir, &cfg.state) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("Failed to load manifest: {e}");
+                        std::process::exit(1);
+                    }
+                };
+                if let Err(e) =
+                    linker::remove_repo_mirror(&repo.mirror_subdir, &repo.output_dir, &mut manifest)
+                {
+                    eprintln!("Failed to remove mirrored files: {e}");
+                } else {
+                    if let Err(e) = manifest.save(&repo.output_dir) {
+                        eprintln!("Failed to save manifest: {e}");
+                    }
+                    println!("Removed {}", repo.mirror_root().display());
                 }
-                println!("Removed {}", mirror_path.display());
             }
         }
     }
@@ -318,45 +1179,433 @@ fn cmd_remove(repo_path: PathBuf, config_arg: Option<PathBuf>) {
     }
 }
 
-fn cmd_config() {
-    let config_path = match config::ensure_config_exists(None, None) {
+fn cmd_move(name: String, new_output: PathBuf, config_arg: Option<PathBuf>, quiet: bool) {
+    let config_path = match config::find_config_path(config_arg.as_deref()) {
         Ok(p) => p,
         Err(e) => {
             eprintln!("Error: {e}");
             std::process::exit(1);
         }
     };
+    let cfg = match config::load_config(Some(&config_path)) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let Some(repo) = cfg.repos.iter().find(|r| r.name == name) else {
+        eprintln!("No repo named '{name}' in the config");
+        std::process::exit(1);
+    };
 
-    if let Err(e) = config::open_in_editor(&config_path) {
-        eprintln!("Error: {e}");
+    if let Err(e) = std::fs::create_dir_all(&new_output) {
+        eprintln!("Failed to create {}: {e}", new_output.display());
         std::process::exit(1);
     }
+    let new_output = std::fs::canonicalize(&new_output).unwrap_or(new_output);
+    if new_output == repo.output_dir {
+        println!("{name} already mirrors to {}", new_output.display());
+        return;
+    }
+
+    // A running service holds the manifests in memory, so let it do the move
+    // on reload instead of racing its watchers.
+    let service_running = service::is_running();
+    if !service_running {
+        let mut old_manifest = load_manifest_or_exit(&repo.output_dir, &cfg.state);
+        let mut new_manifest = load_manifest_or_exit(&new_output, &cfg.state);
+        match linker::move_repo_mirror(
+            &repo.mirror_subdir,
+            &repo.output_dir,
+            &mut old_manifest,
+            &new_output,
+            &mut new_manifest,
+        ) {
+            Ok(n) => println!(
+                "Moved {n} files to {}",
+                new_output.join(&repo.mirror_subdir).display()
+            ),
+            Err(e) => {
+                eprintln!("Failed to move mirror: {e}");
+                std::process::exit(1);
+            }
+        }
+        for (manifest, dir) in [
+            (&mut old_manifest, &repo.output_dir),
+            (&mut new_manifest, &new_output),
+        ] {
+            if let Err(e) = manifest.save(dir) {
+                eprintln!("Failed to save manifest in {}: {e}", dir.display());
+                std::process::exit(1);
+            }
+        }
+    }
+
+    match config::set_repo_output_dir(&config_path, &repo.path, &new_output) {
+        Ok(true) => println!("Updated output_dir for {name}"),
+        Ok(false) => {
+            eprintln!("{} is not in the config", repo.path.display());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to update config: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    if service_running {
+        notify_or_warn_service(show_hints(quiet));
+    }
 }
 
-fn cmd_run(config_arg: Option<PathBuf>) {
-    let cfg = match config::load_config(config_arg.as_deref()) {
+fn cmd_rename(target: String, new_name: String, config_arg: Option<PathBuf>, quiet: bool) {
+    let config_path = match config::find_config_path(config_arg.as_deref()) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let cfg = match config::load_config(Some(&config_path)) {
         Ok(c) => c,
-        Err(config::ConfigError::NoConfigFound) => {
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    let repo = cfg.repos.iter().find(|r| r.name == target).or_else(|| {
+        let path = std::fs::canonicalize(&target).ok()?;
+        cfg.repos.iter().find(|r| r.path == path)
+    });
+    let Some(repo) = repo else {
+        eprintln!("No repo named or at '{target}' in the config");
+        std::process::exit(1);
+    };
+
+    if new_name.is_empty() || new_name.contains('/') || new_name == "." || new_name == ".." {
+        eprintln!("Invalid repo name '{new_name}'");
+        std::process::exit(1);
+    }
+    if new_name == repo.name {
+        println!("{} is already named {new_name}", repo.path.display());
+        return;
+    }
+    let taken = cfg.repos.iter().any(|r| r.name == new_name)
+        || cfg.missing_repos.iter().any(|r| r.name == new_name);
+    if taken {
+        eprintln!("A repo named '{new_name}' already exists");
+        std::process::exit(1);
+    }
+
+    let original = match std::fs::read_to_string(&config_path) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Failed to read config: {e}");
+            std::process::exit(1);
+        }
+    };
+    // Put the config back if the new name turns out to be unusable
+    let restore_and_exit = |message: String| -> ! {
+        eprintln!("{message}");
+        if let Err(e) = std::fs::write(&config_path, &original) {
+            eprintln!("Failed to restore config: {e}");
+        }
+        std::process::exit(1);
+    };
+
+    match config::set_repo_name(&config_path, &repo.path, &new_name) {
+        Ok(true) => {}
+        Ok(false) => {
+            eprintln!("{} is not in the config", repo.path.display());
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Failed to update config: {e}");
+            std::process::exit(1);
+        }
+    }
+    let new_cfg = match config::load_config(Some(&config_path)) {
+        Ok(c) => c,
+        Err(e) => restore_and_exit(format!("Error: {e}")),
+    };
+    let Some(renamed) = new_cfg.repos.iter().find(|r| r.path == repo.path) else {
+        restore_and_exit(format!("{} is not in the config", repo.path.display()));
+    };
+
+    // A running service holds the manifests in memory, so let it do the
+    // rename on reload instead of racing its watchers.
+    let service_running = service::is_running();
+    if !service_running {
+        let mut manifest = load_manifest_or_exit(&repo.output_dir, &cfg.state);
+        match linker::rename_repo_mirror(
+            &repo.mirror_subdir,
+            &renamed.mirror_subdir,
+            &repo.output_dir,
+            &mut manifest,
+        ) {
+            Ok(_) if repo.mirror_subdir == renamed.mirror_subdir => {}
+            Ok(n) => println!("Moved {n} files to {}", renamed.mirror_root().display()),
+            Err(e) => restore_and_exit(format!("Failed to rename mirror: {e}")),
+        }
+        manifest.rename_repo(&repo.name, &new_name);
+        if let Err(e) = manifest.save(&repo.output_dir) {
             eprintln!(
-                "No config file found. Run 'ulysses-link sync <path> <output-dir>' to get started."
+                "Failed to save manifest in {}: {e}",
+                repo.output_dir.display()
             );
             std::process::exit(1);
         }
+    }
+    println!("Renamed {} to {new_name}", repo.name);
+
+    if service_running {
+        notify_or_warn_service(show_hints(quiet));
+    }
+}
+
+fn load_manifest_or_exit(output_dir: &Path, state: &manifest::StateOptions) -> manifest::Manifest {
+    match manifest::Manifest::load_in(output_dir, state) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Failed to load manifest from {}: {e}", output_dir.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn cmd_config(migrate: bool, check: bool, template: Option<String>, config_arg: Option<PathBuf>) {
+    if let Some(template) = template {
+        print!("{}", config::config_template(template == "full"));
+        return;
+    }
+    if check {
+        cmd_config_check(config_arg);
+        return;
+    }
+
+    let config_path = match config::ensure_config_exists(config_arg.as_deref(), None) {
+        Ok(p) => p,
         Err(e) => {
             eprintln!("Error: {e}");
             std::process::exit(1);
         }
     };
-    setup_logging(&cfg.log_level);
 
-    let mut engine = engine::MirrorEngine::new(cfg);
+    if migrate {
+        match config::migrate_config_file(&config_path) {
+            Ok(true) => println!("Migrated {} to version 2", config_path.display()),
+            Ok(false) => println!("{} is already version 2", config_path.display()),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = config::open_in_editor(&config_path) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn cmd_open(name: Option<String>, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let dir = match name {
+        Some(name) => match cfg.repos.iter().find(|r| r.name == name) {
+            Some(repo) => repo.output_dir.join(&repo.mirror_subdir),
+            None => {
+                eprintln!("No repo named '{name}' in config");
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let dirs = cfg.active_output_dirs();
+            match dirs.as_slice() {
+                [] => cfg.output_dir.clone(),
+                [dir] => dir.clone(),
+                _ => {
+                    eprintln!("Repos mirror into several output dirs:");
+                    for dir in &dirs {
+                        eprintln!("  {}", dir.display());
+                    }
+                    eprintln!("Pass --name <repo> to open one repo's mirror folder.");
+                    std::process::exit(1);
+                }
+            }
+        }
+    };
+
+    if let Err(e) = config::open_in_file_browser(&dir) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn cmd_config_check(config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Invalid config: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(ref path) = cfg.config_path {
+        println!("Config OK: {}", path.display());
+    }
+    for warning in &cfg.pattern_warnings {
+        println!("Warning: {warning}");
+    }
+    println!("Output dir: {}", cfg.output_dir.display());
+    println!("Repos:");
+    for repo in &cfg.repos {
+        let mut notes = vec![
+            format!("{} include", repo.include_patterns.len()),
+            format!("{} exclude patterns", repo.exclude.num_ignores()),
+        ];
+        if !repo.enabled {
+            notes.push("disabled".into());
+        }
+        if !repo.path.is_dir() {
+            notes.push("path missing".into());
+        }
+        println!(
+            "  {} {} -> {} ({})",
+            repo.name,
+            repo.path.display(),
+            repo.mirror_root().display(),
+            notes.join(", ")
+        );
+    }
+    for repo in &cfg.missing_repos {
+        println!(
+            "  {} {} -> {} (path missing, skipped)",
+            repo.name,
+            repo.path.display(),
+            repo.mirror_root().display()
+        );
+    }
+}
+
+fn cmd_run(
+    config_arg: Option<PathBuf>,
+    dry_run: bool,
+    stats_interval: Option<Duration>,
+    log_level: Option<&str>,
+) {
+    let mut engine = new_engine(
+        load_engine_config(config_arg, log_level),
+        dry_run,
+        stats_interval,
+    );
     if let Err(e) = engine.start() {
         tracing::error!("Engine error: {e}");
         std::process::exit(1);
     }
 }
 
-fn cmd_install(config_arg: Option<PathBuf>) {
+/// Run the engine like `run`, printing a summary line whenever it changes.
+fn cmd_watch(
+    config_arg: Option<PathBuf>,
+    dry_run: bool,
+    stats_interval: Option<Duration>,
+    log_level: Option<&str>,
+) {
+    let mut engine = new_engine(
+        load_engine_config(config_arg, log_level),
+        dry_run,
+        stats_interval,
+    );
+    let mut last_line = String::new();
+    let result = engine.start_with_status(&mut |status| {
+        let line = format_watch_status(status);
+        if line != last_line {
+            println!("{line}");
+            last_line = line;
+        }
+    });
+    if let Err(e) = result {
+        tracing::error!("Engine error: {e}");
+        std::process::exit(1);
+    }
+}
+
+fn format_watch_status(status: &engine::EngineStatus) -> String {
+    let conflict = match &status.last_conflict {
+        Some((rel_path, at)) => format!("{rel_path} at {}", at.format("%H:%M:%S")),
+        None => "none".into(),
+    };
+    format!(
+        "[watch] {} repos watched, {} files mirrored, {} events pending, last conflict: {}",
+        status.repos_watched, status.files_mirrored, status.pending_events, conflict
+    )
+}
+
+fn new_engine(
+    cfg: config::Config,
+    dry_run: bool,
+    stats_interval: Option<Duration>,
+) -> engine::MirrorEngine {
+    let mut engine = if dry_run {
+        engine::MirrorEngine::new_dry_run(cfg)
+    } else {
+        engine::MirrorEngine::new(cfg)
+    };
+    if let Some(interval) = stats_interval {
+        engine.override_stats_interval(interval);
+    }
+    engine
+}
+
+/// Load the config for a foreground engine and set up logging, exiting on error.
+fn load_engine_config(config_arg: Option<PathBuf>, log_level: Option<&str>) -> config::Config {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(config::ConfigError::NoConfigFound) => {
+            eprintln!(
+                "No config file found. Run 'ulysses-link sync <path> --output <output-dir>' to get started."
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    setup_config_logging(&cfg, log_level);
+    cfg
+}
+
+fn cmd_install(log_dir: Option<PathBuf>, config_arg: Option<PathBuf>, log_level: Option<&str>) {
+    if let Some(dir) = &log_dir {
+        let config_path = match config::find_config_path(config_arg.as_deref()) {
+            Ok(p) => p,
+            Err(config::ConfigError::NoConfigFound) => {
+                eprintln!(
+                    "No config file found. Run 'ulysses-link sync <path>' to add a repo first."
+                );
+                std::process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = config::set_log_dir(&config_path, dir) {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+
     let cfg = match config::load_config(config_arg.as_deref()) {
         Ok(c) => c,
         Err(config::ConfigError::NoConfigFound) => {
@@ -368,7 +1617,11 @@ fn cmd_install(config_arg: Option<PathBuf>) {
             std::process::exit(1);
         }
     };
-    setup_logging(&cfg.log_level);
+    setup_config_logging(&cfg, log_level);
+    if cfg.config_path.is_none() {
+        eprintln!("Error: the service reads its config from a file; pass a config file path.");
+        std::process::exit(1);
+    }
 
     if let Err(e) = service::install_service(&cfg) {
         eprintln!("Failed to install service: {e}");
@@ -420,49 +1673,483 @@ fn cmd_upgrade() {
     }
 }
 
-fn cmd_uninstall() {
-    let confirm = dialoguer::Confirm::new()
-        .with_prompt("Uninstall ulysses-link background service?")
-        .default(false)
-        .interact()
-        .unwrap_or(false);
-
-    if !confirm {
+fn cmd_uninstall(yes: bool, log_level: Option<&str>) {
+    if !confirm_or_exit("Uninstall ulysses-link background service?", false, yes) {
         println!("Cancelled.");
         return;
     }
 
-    setup_logging("INFO");
+    setup_logging("INFO", log_level);
     if let Err(e) = service::uninstall_service() {
         eprintln!("Failed to uninstall service: {e}");
         std::process::exit(1);
     }
 }
 
-fn cmd_status() {
-    if let Err(e) = service::print_status() {
+fn cmd_status(name: Option<String>) {
+    let cfg = config::load_config(None).ok();
+    if let (Some(cfg), Some(name)) = (&cfg, &name) {
+        if !cfg.repos.iter().any(|r| &r.name == name) {
+            eprintln!("No repo named '{name}' in config");
+            std::process::exit(1);
+        }
+    }
+    let log_dir = cfg.as_ref().and_then(|c| c.log_dir.as_deref());
+    if let Err(e) = service::print_status(log_dir, name.as_deref()) {
         eprintln!("Failed to get status: {e}");
         std::process::exit(1);
     }
+
+    // Without a config there are no repos to report on
+    let Some(cfg) = cfg else {
+        return;
+    };
+    println!();
+    println!("Last synced:");
+    for output_dir in cfg.active_output_dirs() {
+        let manifest = load_manifest_or_exit(&output_dir, &cfg.state);
+        for repo in cfg
+            .enabled_repos()
+            .filter(|r| r.output_dir == output_dir)
+            .filter(|r| name.as_ref().is_none_or(|n| &r.name == n))
+        {
+            let synced = match manifest.last_synced(&repo.name) {
+                Some(stamp) => stamp.to_string(),
+                None => paint(color::NOTICE, "never"),
+            };
+            anstream::println!("  {} {}", repo.name, synced);
+        }
+    }
+}
+
+fn cmd_version(json: bool) {
+    if !json {
+        println!("ulysses-link {VERSION}");
+        return;
+    }
+    let info = serde_json::json!({
+        "version": VERSION,
+        "git_sha": env!("ULYSSES_LINK_GIT_SHA"),
+        "rustc": env!("ULYSSES_LINK_RUSTC"),
+        "target": env!("ULYSSES_LINK_TARGET"),
+    });
+    println!("{}", serde_json::to_string_pretty(&info).unwrap());
+}
+
+fn cmd_verify(fix: bool, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let all = if fix {
+        while_service_stopped(|| verify::verify_all(&cfg, true))
+    } else {
+        verify::verify_all(&cfg, false)
+    };
+    let all = match all {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Failed to verify manifests: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut drifted = 0;
+    for output in &all {
+        if output.drifts.is_empty() {
+            println!("{}: ok", output.output_dir.display());
+            continue;
+        }
+        println!("{}:", output.output_dir.display());
+        for drift in &output.drifts {
+            println!("  {:<15} {}", drift.kind.label(), drift.rel_path);
+        }
+        drifted += output.drifts.len();
+    }
+    if drifted > 0 {
+        if !fix {
+            eprintln!("{drifted} drifted entries. Run with --fix to re-sync them.");
+        } else {
+            eprintln!(
+                "{drifted} drifted entries left; missing sources are pruned on the next sync, and conflicts are kept as .conflict_ files."
+            );
+        }
+        std::process::exit(1);
+    }
+}
+
+fn cmd_diff(path: Option<String>, name: Option<String>, stat: bool, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(ref name) = name {
+        if !cfg.repos.iter().any(|r| &r.name == name) {
+            eprintln!("No repo named '{name}' in config");
+            std::process::exit(1);
+        }
+    }
+    let manifests = load_manifests(&cfg);
+
+    let mut diffs = Vec::new();
+    for repo in cfg
+        .repos
+        .iter()
+        .filter(|r| name.as_ref().is_none_or(|n| &r.name == n))
+    {
+        let Some(manifest) = manifests.get(&repo.output_dir) else {
+            continue;
+        };
+        match diff::diff_repo(repo, manifest, path.as_deref()) {
+            Ok(d) => diffs.extend(d),
+            Err(e) => {
+                eprintln!("Failed to diff '{}': {e:#}", repo.name);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if diffs.is_empty() {
+        println!("Source and mirror match");
+        return;
+    }
+    if stat {
+        let width = diffs.iter().map(|d| d.rel_path.len()).max().unwrap_or(0);
+        for d in &diffs {
+            match d.patch {
+                Some(_) => println!(
+                    " {:<width$} | +{} -{}",
+                    d.rel_path, d.insertions, d.deletions
+                ),
+                None => println!(" {:<width$} | binary", d.rel_path),
+            }
+        }
+        let (insertions, deletions) = diffs
+            .iter()
+            .fold((0, 0), |(i, d), f| (i + f.insertions, d + f.deletions));
+        println!(
+            " {} files differ, {insertions} insertions(+), {deletions} deletions(-)",
+            diffs.len()
+        );
+        return;
+    }
+    for d in &diffs {
+        match d.patch {
+            Some(ref patch) => print!("{patch}"),
+            None => println!(
+                "Binary files {} and {} differ",
+                d.source.display(),
+                d.mirror.display()
+            ),
+        }
+    }
+}
+
+fn cmd_reindex(name: Option<String>, config_arg: Option<PathBuf>, quiet: bool) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+    if let Some(ref name) = name {
+        if !cfg.repos.iter().any(|r| &r.name == name) {
+            eprintln!("No repo named '{name}' in config");
+            std::process::exit(1);
+        }
+    }
+    let manifests = load_manifests(&cfg);
+
+    let mut touched = 0;
+    for repo in cfg
+        .repos
+        .iter()
+        .filter(|r| name.as_ref().is_none_or(|n| &r.name == n))
+    {
+        let Some(manifest) = manifests.get(&repo.output_dir) else {
+            continue;
+        };
+        match linker::touch_repo_mirror(manifest, &repo.output_dir, &repo.mirror_subdir) {
+            Ok(n) => {
+                if !quiet {
+                    println!("{}: {n} files", repo.name);
+                }
+                touched += n;
+            }
+            Err(e) => {
+                eprintln!("Failed to reindex '{}': {e:#}", repo.name);
+                std::process::exit(1);
+            }
+        }
+    }
+    if !quiet {
+        println!("Reindexed {touched} files");
+    }
+}
+
+fn cmd_stats(json: bool, config_arg: Option<PathBuf>) {
+    let cfg = match config::load_config(config_arg.as_deref()) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let all = match stats::collect_all(&cfg) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to collect stats: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&all) {
+            Ok(s) => println!("{s}"),
+            Err(e) => {
+                eprintln!("Failed to serialize stats: {e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    for (i, s) in all.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", s.output_dir.display());
+        println!(
+            "  Tracked files:  {} ({})",
+            s.tracked_files,
+            stats::format_bytes(s.total_bytes)
+        );
+        let repos: Vec<String> = s
+            .files_by_repo
+            .iter()
+            .map(|(name, n)| format!("{name} {n}"))
+            .collect();
+        println!("  Repos:          {}", repos.join(", "));
+        let mut extensions: Vec<(&String, &u64)> = s.files_by_extension.iter().collect();
+        extensions.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let extensions: Vec<String> = extensions
+            .iter()
+            .map(|(ext, n)| {
+                let ext = if ext.is_empty() {
+                    "(none)"
+                } else {
+                    ext.as_str()
+                };
+                format!("{ext} {n}")
+            })
+            .collect();
+        println!("  Extensions:     {}", extensions.join(", "));
+        println!("  Conflict files: {}", s.conflict_files);
+        println!(
+            "  Base cache:     {}",
+            stats::format_bytes(s.base_cache_bytes)
+        );
+    }
 }
 
-fn cmd_logs() {
-    if let Err(e) = service::print_logs() {
+fn cmd_logs(log_dir: Option<PathBuf>, name: Option<String>, config_arg: Option<PathBuf>) {
+    // Without a config the service logs to the platform default
+    let log_dir = log_dir.or_else(|| {
+        config::load_config(config_arg.as_deref())
+            .ok()
+            .and_then(|c| c.log_dir)
+    });
+    if let Err(e) = service::print_logs(log_dir.as_deref(), name.as_deref()) {
         eprintln!("Failed to get logs: {e}");
         std::process::exit(1);
     }
 }
 
-/// After a sync, notify the running service or warn the user to install.
-fn notify_or_warn_service() {
+fn cmd_rescan() {
+    if !service::is_running() {
+        eprintln!("Service is not running. Use 'ulysses-link sync' to scan once.");
+        std::process::exit(1);
+    }
+    if let Err(e) = service::send_rescan_signal() {
+        eprintln!("Failed to request rescan: {e}");
+        std::process::exit(1);
+    }
+    println!("Rescan requested");
+}
+
+/// Ask `prompt` on the terminal, or take yes for an answer with `--yes`.
+/// Without a terminal there is nobody to ask, so that exits with an error
+/// instead of silently picking the default.
+fn confirm_or_exit(prompt: &str, default: bool, yes: bool) -> bool {
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    match confirm(prompt, default, yes, interactive) {
+        Ok(answer) => answer,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn confirm(prompt: &str, default: bool, yes: bool, interactive: bool) -> Result<bool, String> {
+    if yes {
+        return Ok(true);
+    }
+    if !interactive {
+        return Err(format!(
+            "\"{prompt}\" needs confirmation, but this isn't a terminal; pass --yes to proceed"
+        ));
+    }
+    dialoguer::Confirm::new()
+        .with_prompt(prompt)
+        .default(default)
+        .interact()
+        .map_err(|e| format!("Failed to read confirmation: {e}"))
+}
+
+/// Run `work` with a running service stopped, starting it again afterwards
+/// even when `work` failed. The service holds manifests in memory and would
+/// write its own back over ones changed under it.
+fn while_service_stopped<T>(work: impl FnOnce() -> T) -> T {
+    around_service(
+        service::is_running(),
+        service::stop_service,
+        service::start_service,
+        work,
+    )
+}
+
+fn around_service<T>(
+    running: bool,
+    stop: impl FnOnce() -> anyhow::Result<()>,
+    start: impl FnOnce() -> anyhow::Result<()>,
+    work: impl FnOnce() -> T,
+) -> T {
+    if running {
+        if let Err(e) = stop() {
+            eprintln!("Error: failed to stop service: {e}");
+            std::process::exit(1);
+        }
+    }
+    let result = work();
+    if running {
+        match start() {
+            Ok(()) => println!("Service restarted"),
+            Err(e) => eprintln!("Warning: failed to start service again: {e}"),
+        }
+    }
+    result
+}
+
+/// Whether to print hints around a command's result: not under `--quiet`,
+/// and only to a terminal, so scripts capturing stdout get just the result.
+fn show_hints(quiet: bool) -> bool {
+    !quiet && std::io::stdout().is_terminal()
+}
+
+/// After a sync, notify the running service or, with `hints`, suggest
+/// installing it.
+fn notify_or_warn_service(hints: bool) {
     if service::is_running() {
         match service::send_reload_signal() {
-            Ok(()) => println!("Service reloaded with updated config"),
+            Ok(()) if hints => println!("Service reloaded with updated config"),
+            Ok(()) => {}
             Err(e) => eprintln!("Warning: failed to reload service: {e}"),
         }
-    } else {
+    } else if hints {
         println!();
         println!("Service is not running. To keep repos synced in the background:");
         println!("  ulysses-link install");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_since("2h"), Ok(Duration::from_secs(2 * 60 * 60)));
+        assert_eq!(parse_since("1d"), Ok(Duration::from_secs(24 * 60 * 60)));
+        assert!(parse_since("2").is_err());
+        assert!(parse_since("h").is_err());
+        assert!(parse_since("2w").is_err());
+        assert!(parse_since("18446744073709551615d").is_err());
+        assert!(parse_since("18446744073709551615s").is_err());
+    }
+
+    #[test]
+    fn test_parse_interval_rejects_zero() {
+        assert_eq!(parse_interval("10m"), Ok(Duration::from_secs(10 * 60)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("0h").is_err());
+    }
+
+    #[test]
+    fn test_level_override_wins_over_config() {
+        assert_eq!(resolve_log_level("WARNING", None), "WARNING");
+        assert_eq!(
+            resolve_log_level("WARNING", level_override(1, false)),
+            "DEBUG"
+        );
+        assert_eq!(
+            resolve_log_level("WARNING", level_override(2, false)),
+            "TRACE"
+        );
+        assert_eq!(resolve_log_level("DEBUG", level_override(0, true)), "ERROR");
+    }
+
+    #[test]
+    fn test_confirm_without_terminal_needs_yes() {
+        let err = confirm("Remove repo?", true, false, false).unwrap_err();
+        assert!(err.contains("--yes"));
+        assert_eq!(confirm("Remove repo?", false, true, false), Ok(true));
+    }
+
+    #[test]
+    fn test_service_stopped_around_work() {
+        use std::cell::RefCell;
+        let calls = RefCell::new(Vec::new());
+        let result = around_service(
+            true,
+            || {
+                calls.borrow_mut().push("stop");
+                Ok(())
+            },
+            || {
+                calls.borrow_mut().push("start");
+                Ok(())
+            },
+            || {
+                calls.borrow_mut().push("work");
+                Err::<(), _>("failed")
+            },
+        );
+        assert_eq!(result, Err("failed"));
+        assert_eq!(*calls.borrow(), ["stop", "work", "start"]);
+
+        calls.borrow_mut().clear();
+        around_service(
+            false,
+            || unreachable!(),
+            || unreachable!(),
+            || calls.borrow_mut().push("work"),
+        );
+        assert_eq!(*calls.borrow(), ["work"]);
+    }
+
+    #[test]
+    fn test_quiet_skips_hints() {
+        assert!(!show_hints(true));
+    }
+}
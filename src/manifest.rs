@@ -1,13 +1,28 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::matcher::ProtectSet;
 
 const MANIFEST_FILENAME: &str = ".ulysses-link";
+/// Manifest kept as an append log (`ManifestBackend::Log`)
+pub(crate) const MANIFEST_LOG_FILENAME: &str = ".ulysses-link.log";
+const LOG_VERSION: u64 = 1;
+/// Records a log may hold beyond its live state before a save compacts it:
+/// this many plus as many again as the live state has.
+const LOG_COMPACT_SLACK: usize = 1000;
+
+/// Shortest time between two writes from `Manifest::save_coalesced`, so a
+/// burst of small watcher batches doesn't rewrite the whole file for each.
+pub const SAVE_INTERVAL: Duration = Duration::from_millis(500);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestEntry {
@@ -20,26 +35,286 @@ struct ManifestFile {
     version: u64,
     #[serde(default)]
     files: HashMap<String, ManifestEntry>,
+    /// Content-addressed base cache index: rel_path -> blob hash
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    bases: HashMap<String, String>,
+    /// RFC 3339 time each repo (by name) last synced changes
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    last_synced: HashMap<String, String>,
+}
+
+/// One change in a manifest log. A log is a `Version` record followed by
+/// changes applied in order; compaction rewrites it as the live state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum LogRecord {
+    Version {
+        version: u64,
+    },
+    Put {
+        key: String,
+        source: PathBuf,
+        hash: String,
+    },
+    Remove {
+        key: String,
+    },
+    Base {
+        key: String,
+        blob: String,
+    },
+    ClearBase {
+        key: String,
+    },
+    Synced {
+        repo: String,
+        at: String,
+    },
+    Unsynced {
+        repo: String,
+    },
+}
+
+/// How a manifest is stored on disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ManifestBackend {
+    /// One TOML file, rewritten whole on every save
+    #[default]
+    Toml,
+    /// A line per change appended on save, compacted once mostly stale, so
+    /// a save costs the changes since the last one rather than every entry
+    Log,
+}
+
+/// How last-synced content is kept for three-way merges.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BaseCacheMode {
+    /// One copy per mirrored file, stored at its rel_path
+    #[default]
+    Path,
+    /// One blob per distinct content, shared by every file with that content
+    Content,
+    /// No base cache; edits on both sides always resolve as conflicts
+    Off,
+}
+
+/// Where and how a manifest keeps its state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateOptions {
+    /// Keep manifests and base caches here instead of in each output dir
+    pub state_dir: Option<PathBuf>,
+    pub manifest_backend: ManifestBackend,
+    pub base_cache: BaseCacheMode,
+    /// Gzip base cache files as they're written
+    pub base_cache_compress: bool,
+    /// Re-hash each file sync copies or merges after writing it
+    pub verify_after_write: bool,
+    /// Mirror files that pruning and delete propagation leave in place
+    pub protect: ProtectSet,
+    /// Cosmetic differences ignored when comparing source and mirror
+    pub normalize: Normalization,
+    /// Mirror roots of `flat_inbox` repos, whose files carry a source header
+    pub inboxes: Vec<Inbox>,
+    /// Mirror roots of `output_format = "ulysses"` repos, whose files carry
+    /// keywords named after their folders
+    pub keyword_trees: Vec<KeywordTree>,
+    /// Mirror roots of `read_only_source` repos, whose sources are never
+    /// written or deleted
+    pub read_only_roots: Vec<PathBuf>,
+}
+
+/// A repo mirrored as a flat inbox: each file directly under `mirror_root`
+/// starts with a header naming its source path relative to `repo_path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inbox {
+    pub mirror_root: PathBuf,
+    pub repo_path: PathBuf,
+}
+
+/// A repo mirrored with `output_format = "ulysses"`: each file under
+/// `mirror_root`, inside `output_dir`, starts with a line of Ulysses keywords
+/// named after the folders it sits in, e.g. `#docs #api` for
+/// `docs/api/auth.md`. Its source is at the same relative path under
+/// `source_root`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeywordTree {
+    pub output_dir: PathBuf,
+    pub mirror_root: PathBuf,
+    pub source_root: PathBuf,
+}
+
+/// Cosmetic rewrites, like the trailing newline Ulysses adds on save, that
+/// should not count as edits. Both sides are normalized before hashing; bases
+/// are cached and merged as written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Normalization {
+    /// End every non-empty file with exactly one newline
+    pub trailing_newline: bool,
+    /// `(from, to)` replacements applied in order, e.g. `“` to `"`
+    pub chars: Vec<(String, String)>,
+}
+
+impl Normalization {
+    pub fn is_none(&self) -> bool {
+        !self.trailing_newline && self.chars.is_empty()
+    }
+
+    pub fn apply<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        let mut content = Cow::Borrowed(content);
+        for (from, to) in &self.chars {
+            if content.contains(from.as_str()) {
+                content = Cow::Owned(content.replace(from.as_str(), to));
+            }
+        }
+        if self.trailing_newline && !content.is_empty() {
+            let trimmed = content.trim_end_matches(['\r', '\n']);
+            if trimmed.len() + 1 != content.len() || !content.ends_with('\n') {
+                content = Cow::Owned(format!("{trimmed}\n"));
+            }
+        }
+        content
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Manifest {
     files: HashMap<String, ManifestEntry>,
+    /// Reverse of `files`: which key each source file is mirrored at
+    keys_by_source: HashMap<PathBuf, String>,
+    /// Directory holding the manifest file and base cache when they are kept
+    /// outside the output directory (`state_dir`)
+    state_root: Option<PathBuf>,
+    backend: ManifestBackend,
+    /// Changes not yet appended to the log (`ManifestBackend::Log` only)
+    journal: Vec<LogRecord>,
+    /// Size of the log as last written or read, when it can be appended to.
+    /// `None`, or a different size on disk, means the next save compacts.
+    log_len: Option<u64>,
+    /// Records in the log as last written or read
+    log_records: usize,
+    /// Loaded from the other backend's file, removed on the next save
+    migrate_from: Option<PathBuf>,
+    base_cache: BaseCacheMode,
+    base_cache_compress: bool,
+    verify_after_write: bool,
+    protect: ProtectSet,
+    normalize: Normalization,
+    inboxes: Vec<Inbox>,
+    keyword_trees: Vec<KeywordTree>,
+    read_only_roots: Vec<PathBuf>,
+    /// Blob hash each rel_path's base points at (`BaseCacheMode::Content`)
+    bases: HashMap<String, String>,
+    /// Number of rel_paths referencing each blob, derived from `bases`
+    blob_refs: HashMap<String, u32>,
+    /// RFC 3339 time each repo (by name) last synced changes
+    last_synced: HashMap<String, String>,
+    /// The most recent conflict since this manifest was loaded, and when it
+    /// was resolved; not persisted
+    last_conflict: Option<(ConflictInfo, chrono::DateTime<chrono::Local>)>,
+    /// Conflicts resolved since this manifest was loaded; not persisted
+    conflicts: usize,
+    /// A `save_coalesced` call was deferred and nothing has been written since
+    dirty: bool,
+    /// When `save_coalesced` or `save_pending` last wrote the manifest
+    last_saved: Option<Instant>,
+    /// Writes made by `save_coalesced` and `save_pending`
+    #[cfg(test)]
+    saves: usize,
+}
+
+/// A conflict resolved by keeping one side and backing up the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictInfo {
+    /// Manifest key of the conflicting file
+    pub rel_path: String,
+    pub winner: ConflictWinner,
+    /// `.conflict_<timestamp>` file holding the other side's content
+    pub conflict_file: PathBuf,
+}
+
+/// Which side's content a resolved conflict kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictWinner {
+    Source,
+    Mirror,
 }
 
 impl Manifest {
     pub fn empty() -> Self {
+        Self::with_state(None, &StateOptions::default())
+    }
+
+    fn with_state(state_root: Option<PathBuf>, options: &StateOptions) -> Self {
         Self {
             files: HashMap::new(),
+            keys_by_source: HashMap::new(),
+            state_root,
+            backend: options.manifest_backend,
+            journal: Vec::new(),
+            log_len: None,
+            log_records: 0,
+            migrate_from: None,
+            base_cache: options.base_cache,
+            base_cache_compress: options.base_cache_compress,
+            verify_after_write: options.verify_after_write,
+            protect: options.protect.clone(),
+            normalize: options.normalize.clone(),
+            inboxes: options.inboxes.clone(),
+            keyword_trees: options.keyword_trees.clone(),
+            read_only_roots: options.read_only_roots.clone(),
+            bases: HashMap::new(),
+            blob_refs: HashMap::new(),
+            last_synced: HashMap::new(),
+            last_conflict: None,
+            conflicts: 0,
+            dirty: false,
+            last_saved: None,
+            #[cfg(test)]
+            saves: 0,
         }
     }
 
     pub fn load(output_dir: &Path) -> Result<Self> {
-        let path = output_dir.join(MANIFEST_FILENAME);
-        if !path.exists() {
-            return Ok(Self {
-                files: HashMap::new(),
-            });
+        Self::load_in(output_dir, &StateOptions::default())
+    }
+
+    /// Load the manifest for `output_dir`, from its subdirectory of
+    /// `state_dir` when one is configured. A manifest kept by the other
+    /// backend is read and converted on the next save.
+    pub fn load_in(output_dir: &Path, options: &StateOptions) -> Result<Self> {
+        let state_root = options
+            .state_dir
+            .as_deref()
+            .map(|d| state_root_for(d, output_dir));
+        let root = state_root.as_deref().unwrap_or(output_dir);
+        let toml_path = root.join(MANIFEST_FILENAME);
+        let log_path = root.join(MANIFEST_LOG_FILENAME);
+        let (path, other) = match options.manifest_backend {
+            ManifestBackend::Toml => (toml_path, log_path),
+            ManifestBackend::Log => (log_path, toml_path),
+        };
+        let mut manifest = Self::with_state(state_root, options);
+        let (path, from_other) = match (path.exists(), other.exists()) {
+            (true, true) => {
+                // A conversion stopped before removing the old file
+                manifest.migrate_from = Some(other);
+                (path, false)
+            }
+            (true, false) => (path, false),
+            (false, true) => (other, true),
+            (false, false) => return Ok(manifest),
+        };
+        if from_other {
+            manifest.migrate_from = Some(path.clone());
+        }
+
+        if path.file_name() == Some(MANIFEST_LOG_FILENAME.as_ref()) {
+            manifest.read_log(&path)?;
+            if from_other {
+                manifest.log_len = None;
+            }
+            manifest.journal.clear();
+            return Ok(manifest);
         }
 
         let contents = fs::read_to_string(&path)
@@ -47,16 +322,118 @@ impl Manifest {
         let manifest_file: ManifestFile = toml::from_str(&contents)
             .with_context(|| format!("Failed to parse manifest at {}", path.display()))?;
 
-        Ok(Self {
-            files: manifest_file.files,
-        })
+        for (key, entry) in manifest_file.files {
+            manifest.insert(key, entry);
+        }
+        manifest.last_synced = manifest_file.last_synced;
+        // An index left behind by another mode is dropped; its blobs are
+        // unreachable and bases are rebuilt as files sync
+        if options.base_cache == BaseCacheMode::Content {
+            for (rel_path, hash) in manifest_file.bases {
+                manifest.set_base_blob(&rel_path, hash);
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// Apply the records of the log at `path`. A torn last line, left by a
+    /// write cut short, is dropped and the log compacted on the next save.
+    fn read_log(&mut self, path: &Path) -> Result<()> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest at {}", path.display()))?;
+        let mut lines = contents.lines().enumerate().peekable();
+        let mut records = 0;
+        let mut torn = false;
+        while let Some((i, line)) = lines.next() {
+            let record: LogRecord = match serde_json::from_str(line) {
+                Ok(record) => record,
+                Err(_) if lines.peek().is_none() && !contents.ends_with('\n') => {
+                    warn!("Dropping torn last line of manifest at {}", path.display());
+                    torn = true;
+                    break;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| {
+                        format!(
+                            "Failed to parse manifest at {} line {}",
+                            path.display(),
+                            i + 1
+                        )
+                    })
+                }
+            };
+            match record {
+                LogRecord::Version { version } if version != LOG_VERSION => {
+                    anyhow::bail!(
+                        "Unsupported manifest version {version} at {}",
+                        path.display()
+                    );
+                }
+                LogRecord::Version { .. } => {}
+                LogRecord::Put { key, source, hash } => {
+                    self.insert(key, ManifestEntry { source, hash });
+                }
+                LogRecord::Remove { key } => {
+                    self.remove(&key);
+                }
+                // As with the TOML index, bases from another mode are dropped
+                LogRecord::Base { key, blob } => {
+                    if self.base_cache == BaseCacheMode::Content {
+                        self.set_base_blob(&key, blob);
+                    }
+                }
+                LogRecord::ClearBase { key } => {
+                    self.clear_base_blob(&key);
+                }
+                LogRecord::Synced { repo, at } => {
+                    self.last_synced.insert(repo, at);
+                }
+                LogRecord::Unsynced { repo } => {
+                    self.last_synced.remove(&repo);
+                }
+            }
+            records += 1;
+        }
+        self.log_len = (!torn).then_some(contents.len() as u64);
+        self.log_records = records;
+        Ok(())
+    }
+
+    /// Queue a change for the next log append.
+    fn record(&mut self, record: LogRecord) {
+        if self.backend == ManifestBackend::Log {
+            self.journal.push(record);
+        }
+    }
+
+    /// Write the manifest. A save deferred by `save_coalesced` is covered,
+    /// so its `save_pending` writes nothing, even after the manifest was
+    /// swapped for one reloaded from disk.
+    pub fn save(&mut self, output_dir: &Path) -> Result<()> {
+        let root = self.state_root(output_dir).to_path_buf();
+        if self.state_root.is_some() {
+            fs::create_dir_all(&root)
+                .with_context(|| format!("Failed to create state dir {}", root.display()))?;
+        }
+        match self.backend {
+            ManifestBackend::Toml => self.write_toml(&root)?,
+            ManifestBackend::Log => self.append_log(&root)?,
+        }
+        if let Some(old) = self.migrate_from.take() {
+            fs::remove_file(&old)
+                .with_context(|| format!("Failed to remove old manifest {}", old.display()))?;
+        }
+        self.dirty = false;
+        Ok(())
     }
 
-    pub fn save(&self, output_dir: &Path) -> Result<()> {
-        let path = output_dir.join(MANIFEST_FILENAME);
+    fn write_toml(&self, root: &Path) -> Result<()> {
+        let path = root.join(MANIFEST_FILENAME);
         let manifest_file = ManifestFile {
             version: 1,
             files: self.files.clone(),
+            bases: self.bases.clone(),
+            last_synced: self.last_synced.clone(),
         };
         let contents = toml::to_string(&manifest_file).context("Failed to serialize manifest")?;
         fs::write(&path, contents)
@@ -64,20 +441,308 @@ impl Manifest {
         Ok(())
     }
 
+    /// Append the changes since the last save, or compact the log when it
+    /// has grown mostly stale or was changed by someone else.
+    fn append_log(&mut self, root: &Path) -> Result<()> {
+        let path = root.join(MANIFEST_LOG_FILENAME);
+        let on_disk = fs::metadata(&path).map(|m| m.len()).ok();
+        let live = 1 + self.files.len() + self.bases.len() + self.last_synced.len();
+        let stale = self.log_records + self.journal.len() > 2 * live + LOG_COMPACT_SLACK;
+        let Some(len) = self.log_len.filter(|&len| on_disk == Some(len) && !stale) else {
+            return self.compact_log(root);
+        };
+        if self.journal.is_empty() {
+            return Ok(());
+        }
+
+        let contents = log_lines(&self.journal)?;
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open manifest at {}", path.display()))?;
+        if let Err(e) = file.write_all(contents.as_bytes()) {
+            // Part of it may be on disk; rewrite the whole log next time
+            self.log_len = None;
+            return Err(e)
+                .with_context(|| format!("Failed to write manifest to {}", path.display()));
+        }
+        self.log_len = Some(len + contents.len() as u64);
+        self.log_records += self.journal.len();
+        self.journal.clear();
+        Ok(())
+    }
+
+    /// Rewrite the log as just the live state, replacing it atomically.
+    fn compact_log(&mut self, root: &Path) -> Result<()> {
+        let path = root.join(MANIFEST_LOG_FILENAME);
+        let mut records = vec![LogRecord::Version {
+            version: LOG_VERSION,
+        }];
+        records.extend(self.files.iter().map(|(key, entry)| LogRecord::Put {
+            key: key.clone(),
+            source: entry.source.clone(),
+            hash: entry.hash.clone(),
+        }));
+        records.extend(self.bases.iter().map(|(key, blob)| LogRecord::Base {
+            key: key.clone(),
+            blob: blob.clone(),
+        }));
+        records.extend(self.last_synced.iter().map(|(repo, at)| LogRecord::Synced {
+            repo: repo.clone(),
+            at: at.clone(),
+        }));
+        let contents = log_lines(&records)?;
+        let tmp = root.join(format!("{MANIFEST_LOG_FILENAME}.tmp"));
+        fs::write(&tmp, &contents)
+            .with_context(|| format!("Failed to write manifest to {}", tmp.display()))?;
+        fs::rename(&tmp, &path)
+            .with_context(|| format!("Failed to write manifest to {}", path.display()))?;
+        self.log_len = Some(contents.len() as u64);
+        self.log_records = records.len();
+        self.journal.clear();
+        Ok(())
+    }
+
+    /// Save at the end of a batch, unless the manifest was written less than
+    /// `SAVE_INTERVAL` ago; then it's only marked dirty. Returns the delay
+    /// after which `save_pending` should run when this call deferred a save
+    /// and no earlier deferred save is still waiting.
+    pub fn save_coalesced(&mut self, output_dir: &Path) -> Result<Option<Duration>> {
+        match self.last_saved.map(|at| at.elapsed()) {
+            Some(since) if since < SAVE_INTERVAL => {
+                let waiting = self.dirty;
+                self.dirty = true;
+                Ok((!waiting).then(|| SAVE_INTERVAL - since))
+            }
+            _ => {
+                self.write_coalesced(output_dir)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Write a save deferred by `save_coalesced`, if one is still waiting.
+    pub fn save_pending(&mut self, output_dir: &Path) -> Result<()> {
+        if self.dirty {
+            self.write_coalesced(output_dir)?;
+        }
+        Ok(())
+    }
+
+    fn write_coalesced(&mut self, output_dir: &Path) -> Result<()> {
+        // A failed write is retried by the next batch, not by a timer
+        self.dirty = false;
+        self.save(output_dir)?;
+        self.last_saved = Some(Instant::now());
+        #[cfg(test)]
+        {
+            self.saves += 1;
+        }
+        Ok(())
+    }
+
+    /// Writes made by `save_coalesced` and `save_pending`.
+    #[cfg(test)]
+    pub(crate) fn save_count(&self) -> usize {
+        self.saves
+    }
+
+    /// Where this manifest and its base cache live: the output directory
+    /// itself unless a `state_dir` is configured.
+    pub fn state_root<'a>(&'a self, output_dir: &'a Path) -> &'a Path {
+        self.state_root.as_deref().unwrap_or(output_dir)
+    }
+
+    /// Record that the repo named `repo_name` just synced changes.
+    pub fn mark_synced(&mut self, repo_name: &str) {
+        self.set_last_synced(repo_name, chrono::Local::now().to_rfc3339());
+    }
+
+    /// Carry a repo's last sync time over to its new name.
+    pub fn rename_repo(&mut self, old_name: &str, new_name: &str) {
+        if let Some(stamp) = self.last_synced.remove(old_name) {
+            self.record(LogRecord::Unsynced {
+                repo: old_name.to_string(),
+            });
+            self.set_last_synced(new_name, stamp);
+        }
+    }
+
+    /// RFC 3339 time the repo named `repo_name` last synced changes.
+    pub fn last_synced(&self, repo_name: &str) -> Option<&str> {
+        self.last_synced.get(repo_name).map(String::as_str)
+    }
+
+    /// Each repo's last sync time, by repo name.
+    pub fn all_last_synced(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.last_synced.iter()
+    }
+
+    /// Record an RFC 3339 `stamp` carried over from elsewhere as the time
+    /// the repo named `repo_name` last synced changes.
+    pub fn set_last_synced(&mut self, repo_name: &str, stamp: String) {
+        self.record(LogRecord::Synced {
+            repo: repo_name.to_string(),
+            at: stamp.clone(),
+        });
+        self.last_synced.insert(repo_name.to_string(), stamp);
+    }
+
+    /// Record a conflict that was just resolved.
+    pub fn note_conflict(&mut self, conflict: ConflictInfo) {
+        self.last_conflict = Some((conflict, chrono::Local::now()));
+        self.conflicts += 1;
+    }
+
+    /// How many conflicts were resolved since the manifest was loaded.
+    pub fn conflict_count(&self) -> usize {
+        self.conflicts
+    }
+
+    /// Key and time of the most recent conflict since the manifest was loaded.
+    pub fn last_conflict(&self) -> Option<(&str, chrono::DateTime<chrono::Local>)> {
+        self.last_conflict
+            .as_ref()
+            .map(|(conflict, at)| (conflict.rel_path.as_str(), *at))
+    }
+
+    /// Details of the most recent conflict since the manifest was loaded.
+    pub fn last_conflict_info(&self) -> Option<&ConflictInfo> {
+        self.last_conflict.as_ref().map(|(conflict, _)| conflict)
+    }
+
+    pub fn base_cache(&self) -> BaseCacheMode {
+        self.base_cache
+    }
+
+    /// Whether new base cache files are gzipped.
+    pub fn base_cache_compress(&self) -> bool {
+        self.base_cache_compress
+    }
+
+    /// Whether sync re-hashes what it writes; see `linker::verify_written`.
+    pub fn verify_after_write(&self) -> bool {
+        self.verify_after_write
+    }
+
+    pub fn normalization(&self) -> &Normalization {
+        &self.normalize
+    }
+
+    /// The flat inbox holding the mirror file at `mirror`, if any.
+    pub fn inbox_for(&self, mirror: &Path) -> Option<&Inbox> {
+        let parent = mirror.parent()?;
+        self.inboxes
+            .iter()
+            .find(|inbox| inbox.mirror_root == parent)
+    }
+
+    /// The keyword tree holding the mirror file at `mirror`, if any.
+    pub fn keyword_tree_for(&self, mirror: &Path) -> Option<&KeywordTree> {
+        self.keyword_trees
+            .iter()
+            .find(|tree| mirror.starts_with(&tree.mirror_root))
+    }
+
+    /// Whether the mirror file at `mirror` belongs to a `read_only_source`
+    /// repo, so its source must never be written.
+    pub fn is_read_only(&self, mirror: &Path) -> bool {
+        self.read_only_roots
+            .iter()
+            .any(|root| mirror.starts_with(root))
+    }
+
+    /// Whether the mirror file at `rel_path` is protected from deletion.
+    pub fn is_protected(&self, rel_path: &str) -> bool {
+        self.protect.is_match(rel_path)
+    }
+
+    /// Blob hash holding the base for `rel_path` in content mode.
+    pub fn base_blob(&self, rel_path: &str) -> Option<&str> {
+        self.bases.get(rel_path).map(String::as_str)
+    }
+
+    /// Point `rel_path`'s base at blob `hash`. Returns the previously
+    /// referenced blob if nothing references it any more.
+    pub fn set_base_blob(&mut self, rel_path: &str, hash: String) -> Option<String> {
+        self.record(LogRecord::Base {
+            key: rel_path.to_string(),
+            blob: hash.clone(),
+        });
+        *self.blob_refs.entry(hash.clone()).or_default() += 1;
+        let old = self.bases.insert(rel_path.to_string(), hash)?;
+        self.release_blob(old)
+    }
+
+    /// Drop `rel_path`'s base reference. Returns the blob if nothing
+    /// references it any more.
+    pub fn clear_base_blob(&mut self, rel_path: &str) -> Option<String> {
+        let old = self.bases.remove(rel_path)?;
+        self.record(LogRecord::ClearBase {
+            key: rel_path.to_string(),
+        });
+        self.release_blob(old)
+    }
+
+    fn release_blob(&mut self, hash: String) -> Option<String> {
+        let refs = self.blob_refs.get_mut(&hash)?;
+        *refs -= 1;
+        if *refs > 0 {
+            return None;
+        }
+        self.blob_refs.remove(&hash);
+        Some(hash)
+    }
+
     pub fn get(&self, rel_path: &str) -> Option<&ManifestEntry> {
         self.files.get(rel_path)
     }
 
     pub fn insert(&mut self, rel_path: String, entry: ManifestEntry) {
-        self.files.insert(rel_path, entry);
+        self.record(LogRecord::Put {
+            key: rel_path.clone(),
+            source: entry.source.clone(),
+            hash: entry.hash.clone(),
+        });
+        self.keys_by_source
+            .insert(entry.source.clone(), rel_path.clone());
+        if let Some(old) = self.files.insert(rel_path, entry) {
+            self.forget_source(&old);
+        }
     }
 
     pub fn remove(&mut self, rel_path: &str) -> Option<ManifestEntry> {
-        self.files.remove(rel_path)
+        let removed = self.files.remove(rel_path)?;
+        self.record(LogRecord::Remove {
+            key: rel_path.to_string(),
+        });
+        self.forget_source(&removed);
+        Some(removed)
+    }
+
+    /// Drop the reverse mapping for a replaced or removed entry, unless its
+    /// source has since been mapped to a live key.
+    fn forget_source(&mut self, old: &ManifestEntry) {
+        if let Some(key) = self.keys_by_source.get(&old.source) {
+            if self.files.get(key).is_none_or(|e| e.source != old.source) {
+                self.keys_by_source.remove(&old.source);
+            }
+        }
+    }
+
+    /// Key of the entry mirroring `source`, if any.
+    pub fn key_for_source(&self, source: &Path) -> Option<&str> {
+        self.keys_by_source.get(source).map(String::as_str)
+    }
+
+    /// Every tracked entry, keyed by path relative to the output dir.
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &ManifestEntry)> {
+        self.files.iter()
     }
 
-    pub fn entries_for_repo(&self, repo_name: &str) -> Vec<(&String, &ManifestEntry)> {
-        let prefix = format!("{repo_name}/");
+    /// Entries owned by the repo mirrored at `mirror_subdir`.
+    pub fn entries_for_repo(&self, mirror_subdir: &str) -> Vec<(&String, &ManifestEntry)> {
+        let prefix = format!("{mirror_subdir}/");
         self.files
             .iter()
             .filter(|(k, _)| k.starts_with(&prefix))
@@ -87,6 +752,33 @@ impl Manifest {
     pub fn is_empty(&self) -> bool {
         self.files.is_empty()
     }
+
+    /// Number of tracked files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+}
+
+/// `records` as JSON lines.
+fn log_lines(records: &[LogRecord]) -> Result<String> {
+    let mut contents = String::new();
+    for record in records {
+        contents += &serde_json::to_string(record).context("Failed to serialize manifest")?;
+        contents.push('\n');
+    }
+    Ok(contents)
+}
+
+/// Subdirectory of `state_dir` holding the state for one output directory,
+/// named after the output dir plus a hash of its full path so several output
+/// dirs can share one `state_dir`.
+pub fn state_root_for(state_dir: &Path, output_dir: &Path) -> PathBuf {
+    let name = output_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "root".into());
+    let hash = hash_bytes(output_dir.to_string_lossy().as_bytes());
+    state_dir.join(format!("{name}-{}", &hash[..12]))
 }
 
 /// Compute SHA-256 hex digest of a file's contents.
@@ -119,10 +811,33 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    const BACKENDS: [ManifestBackend; 2] = [ManifestBackend::Toml, ManifestBackend::Log];
+
+    fn load_with(output_dir: &Path, backend: ManifestBackend) -> Manifest {
+        let options = StateOptions {
+            manifest_backend: backend,
+            ..Default::default()
+        };
+        Manifest::load_in(output_dir, &options).unwrap()
+    }
+
+    fn entry(source: &str, hash: &str) -> ManifestEntry {
+        ManifestEntry {
+            source: PathBuf::from(source),
+            hash: hash.into(),
+        }
+    }
+
     #[test]
     fn test_manifest_load_save_roundtrip() {
+        for backend in BACKENDS {
+            manifest_load_save_roundtrip(backend);
+        }
+    }
+
+    fn manifest_load_save_roundtrip(backend: ManifestBackend) {
         let tmp = TempDir::new().unwrap();
-        let mut manifest = Manifest::load(tmp.path()).unwrap();
+        let mut manifest = load_with(tmp.path(), backend);
         assert!(manifest.is_empty());
 
         manifest.insert(
@@ -142,7 +857,7 @@ mod tests {
 
         manifest.save(tmp.path()).unwrap();
 
-        let loaded = Manifest::load(tmp.path()).unwrap();
+        let loaded = load_with(tmp.path(), backend);
         assert_eq!(loaded.get("repo/README.md").unwrap().hash, "abc123");
         assert_eq!(loaded.get("repo/docs/guide.md").unwrap().hash, "def456");
         assert_eq!(
@@ -151,11 +866,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_last_synced_persists() {
+        for backend in BACKENDS {
+            last_synced_persists(backend);
+        }
+    }
+
+    fn last_synced_persists(backend: ManifestBackend) {
+        let tmp = TempDir::new().unwrap();
+        let mut manifest = load_with(tmp.path(), backend);
+        assert!(manifest.last_synced("repo").is_none());
+
+        manifest.mark_synced("repo");
+        let stamp = manifest.last_synced("repo").unwrap().to_string();
+        assert!(chrono::DateTime::parse_from_rfc3339(&stamp).is_ok());
+        manifest.save(tmp.path()).unwrap();
+
+        let mut loaded = load_with(tmp.path(), backend);
+        assert_eq!(loaded.last_synced("repo"), Some(stamp.as_str()));
+        assert!(loaded.last_synced("other").is_none());
+
+        loaded.rename_repo("repo", "renamed");
+        loaded.save(tmp.path()).unwrap();
+        let loaded = load_with(tmp.path(), backend);
+        assert!(loaded.last_synced("repo").is_none());
+        assert_eq!(loaded.last_synced("renamed"), Some(stamp.as_str()));
+    }
+
+    #[test]
+    fn test_save_coalesced_defers_quick_saves() {
+        for backend in BACKENDS {
+            save_coalesced_defers_quick_saves(backend);
+        }
+    }
+
+    fn save_coalesced_defers_quick_saves(backend: ManifestBackend) {
+        let tmp = TempDir::new().unwrap();
+        let mut manifest = load_with(tmp.path(), backend);
+
+        assert_eq!(manifest.save_coalesced(tmp.path()).unwrap(), None);
+        assert_eq!(manifest.save_count(), 1);
+
+        manifest.mark_synced("repo");
+        let delay = manifest.save_coalesced(tmp.path()).unwrap().unwrap();
+        assert!(delay <= SAVE_INTERVAL);
+        // Already waiting on the first deferred save
+        assert_eq!(manifest.save_coalesced(tmp.path()).unwrap(), None);
+        assert_eq!(manifest.save_count(), 1);
+        assert!(load_with(tmp.path(), backend).last_synced("repo").is_none());
+
+        manifest.save_pending(tmp.path()).unwrap();
+        assert_eq!(manifest.save_count(), 2);
+        assert!(load_with(tmp.path(), backend).last_synced("repo").is_some());
+
+        // Nothing left to write
+        manifest.save_pending(tmp.path()).unwrap();
+        assert_eq!(manifest.save_count(), 2);
+
+        // A full save covers a deferred one, which must not later write
+        // this manifest over one reloaded in its place
+        manifest.mark_synced("other");
+        assert!(manifest.save_coalesced(tmp.path()).unwrap().is_some());
+        manifest.save(tmp.path()).unwrap();
+        manifest.save_pending(tmp.path()).unwrap();
+        assert_eq!(manifest.save_count(), 2);
+    }
+
     #[test]
     fn test_manifest_get_insert_remove() {
-        let mut manifest = Manifest {
-            files: HashMap::new(),
-        };
+        let mut manifest = Manifest::empty();
 
         assert!(manifest.get("foo").is_none());
 
@@ -175,9 +955,7 @@ mod tests {
 
     #[test]
     fn test_entries_for_repo() {
-        let mut manifest = Manifest {
-            files: HashMap::new(),
-        };
+        let mut manifest = Manifest::empty();
 
         manifest.insert(
             "repo1/a.md".into(),
@@ -232,6 +1010,22 @@ mod tests {
         assert_ne!(hash_file(&file_path3).unwrap(), hash);
     }
 
+    #[test]
+    fn test_normalization_apply() {
+        let none = Normalization::default();
+        assert!(none.is_none());
+        assert_eq!(none.apply("a\n\n"), "a\n\n");
+
+        let normalize = Normalization {
+            trailing_newline: true,
+            chars: vec![("“".into(), "\"".into()), ("”".into(), "\"".into())],
+        };
+        assert_eq!(normalize.apply("say “hi”"), "say \"hi\"\n");
+        assert_eq!(normalize.apply("a\n\n\n"), "a\n");
+        assert!(matches!(normalize.apply("a\n"), Cow::Borrowed(_)));
+        assert_eq!(normalize.apply(""), "");
+    }
+
     #[test]
     fn test_hash_bytes() {
         let hash1 = hash_bytes(b"hello world");
@@ -246,7 +1040,155 @@ mod tests {
     #[test]
     fn test_manifest_load_missing_file() {
         let tmp = TempDir::new().unwrap();
-        let manifest = Manifest::load(tmp.path()).unwrap();
-        assert!(manifest.is_empty());
+        for backend in BACKENDS {
+            assert!(load_with(tmp.path(), backend).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_log_appends_changes() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(MANIFEST_LOG_FILENAME);
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Log);
+        manifest.insert("repo/a.md".into(), entry("/r/a.md", "a"));
+        manifest.insert("repo/b.md".into(), entry("/r/b.md", "b"));
+        manifest.save(tmp.path()).unwrap();
+        let first = fs::read_to_string(&path).unwrap();
+        assert_eq!(first.lines().count(), 3);
+        assert!(!tmp.path().join(MANIFEST_FILENAME).exists());
+
+        // Only the change is written, after what was there
+        manifest.insert("repo/a.md".into(), entry("/r/a.md", "a2"));
+        manifest.remove("repo/b.md");
+        manifest.save(tmp.path()).unwrap();
+        let second = fs::read_to_string(&path).unwrap();
+        assert!(second.starts_with(&first));
+        assert_eq!(second.lines().count(), 5);
+
+        // Nothing changed, nothing written
+        manifest.save(tmp.path()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), second);
+
+        let loaded = load_with(tmp.path(), ManifestBackend::Log);
+        assert_eq!(loaded.get("repo/a.md").unwrap().hash, "a2");
+        assert!(loaded.get("repo/b.md").is_none());
+        assert_eq!(
+            loaded.key_for_source(Path::new("/r/a.md")),
+            Some("repo/a.md")
+        );
+        assert_eq!(loaded.len(), 1);
+    }
+
+    #[test]
+    fn test_log_compacts_when_stale() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(MANIFEST_LOG_FILENAME);
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Log);
+        manifest.insert("repo/a.md".into(), entry("/r/a.md", "a"));
+        manifest.save(tmp.path()).unwrap();
+
+        // Rewriting one entry over and over leaves the log mostly stale
+        let saves = 2 * LOG_COMPACT_SLACK;
+        for i in 0..saves {
+            manifest.insert("repo/a.md".into(), entry("/r/a.md", &i.to_string()));
+            manifest.save(tmp.path()).unwrap();
+        }
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines <= LOG_COMPACT_SLACK + 4, "{lines} lines");
+        let last = (saves - 1).to_string();
+        let loaded = load_with(tmp.path(), ManifestBackend::Log);
+        assert_eq!(loaded.get("repo/a.md").unwrap().hash, last);
+    }
+
+    #[test]
+    fn test_log_rewritten_when_changed_elsewhere() {
+        let tmp = TempDir::new().unwrap();
+        let mut ours = load_with(tmp.path(), ManifestBackend::Log);
+        ours.insert("repo/a.md".into(), entry("/r/a.md", "a"));
+        ours.save(tmp.path()).unwrap();
+
+        let mut theirs = load_with(tmp.path(), ManifestBackend::Log);
+        theirs.insert("repo/b.md".into(), entry("/r/b.md", "b"));
+        theirs.save(tmp.path()).unwrap();
+
+        // Appending to a log that's no longer the one we wrote would mix
+        // two histories; the whole state is written instead, as with TOML
+        ours.insert("repo/c.md".into(), entry("/r/c.md", "c"));
+        ours.save(tmp.path()).unwrap();
+        let loaded = load_with(tmp.path(), ManifestBackend::Log);
+        assert!(loaded.get("repo/b.md").is_none());
+        assert_eq!(loaded.len(), 2);
+    }
+
+    #[test]
+    fn test_log_drops_torn_last_line() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join(MANIFEST_LOG_FILENAME);
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Log);
+        manifest.insert("repo/a.md".into(), entry("/r/a.md", "a"));
+        manifest.save(tmp.path()).unwrap();
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"op":"put","key":"repo/b"#).unwrap();
+
+        let mut loaded = load_with(tmp.path(), ManifestBackend::Log);
+        assert_eq!(loaded.len(), 1);
+        loaded.insert("repo/c.md".into(), entry("/r/c.md", "c"));
+        loaded.save(tmp.path()).unwrap();
+        assert_eq!(load_with(tmp.path(), ManifestBackend::Log).len(), 2);
+
+        // A bad line anywhere else is an error, not data to drop
+        fs::write(&path, "garbage\n").unwrap();
+        let err = Manifest::load_in(
+            tmp.path(),
+            &StateOptions {
+                manifest_backend: ManifestBackend::Log,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("line 1"), "{err:#}");
+    }
+
+    #[test]
+    fn test_backend_switch_converts_manifest() {
+        let tmp = TempDir::new().unwrap();
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Toml);
+        manifest.insert("repo/a.md".into(), entry("/r/a.md", "a"));
+        manifest.mark_synced("repo");
+        manifest.save(tmp.path()).unwrap();
+
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Log);
+        assert_eq!(manifest.len(), 1);
+        manifest.save(tmp.path()).unwrap();
+        assert!(tmp.path().join(MANIFEST_LOG_FILENAME).is_file());
+        assert!(!tmp.path().join(MANIFEST_FILENAME).exists());
+
+        let mut manifest = load_with(tmp.path(), ManifestBackend::Toml);
+        assert_eq!(manifest.len(), 1);
+        assert!(manifest.last_synced("repo").is_some());
+        manifest.save(tmp.path()).unwrap();
+        assert!(tmp.path().join(MANIFEST_FILENAME).is_file());
+        assert!(!tmp.path().join(MANIFEST_LOG_FILENAME).exists());
+    }
+
+    #[test]
+    fn test_log_keeps_content_bases() {
+        let tmp = TempDir::new().unwrap();
+        let options = StateOptions {
+            manifest_backend: ManifestBackend::Log,
+            base_cache: BaseCacheMode::Content,
+            ..Default::default()
+        };
+        let mut manifest = Manifest::load_in(tmp.path(), &options).unwrap();
+        manifest.set_base_blob("repo/a.md", "blob1".into());
+        manifest.set_base_blob("repo/b.md", "blob1".into());
+        manifest.save(tmp.path()).unwrap();
+        assert_eq!(manifest.clear_base_blob("repo/a.md"), None);
+        manifest.save(tmp.path()).unwrap();
+
+        let mut loaded = Manifest::load_in(tmp.path(), &options).unwrap();
+        assert_eq!(loaded.base_blob("repo/a.md"), None);
+        assert_eq!(loaded.base_blob("repo/b.md"), Some("blob1"));
+        assert_eq!(loaded.clear_base_blob("repo/b.md"), Some("blob1".into()));
     }
 }
@@ -1,20 +1,210 @@
-use std::path::Path;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use globset::GlobSet;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use ignore::gitignore::Gitignore;
+use regex::Regex;
+use tracing::warn;
+
+/// How much of a file is read when checking it against `skip_if_matches`
+/// or for binary content.
+pub const CONTENT_MATCH_BYTES: u64 = 8192;
+
+/// Share of control bytes in a file's head above which it counts as binary.
+const BINARY_CONTROL_RATIO: f64 = 0.3;
+
+/// File name prefix of the metadata Ulysses keeps in each group folder, such
+/// as `.Ulysses-Group.plist` with the group's sheet order.
+const ULYSSES_METADATA_PREFIX: &str = ".Ulysses-";
+
+/// Files editors and sync tools leave briefly beside the ones they work on:
+/// LibreOffice and Office lock files, GTK save streams, swap, backup and
+/// partial-download files. The mirror watcher always ignores them.
+pub const TRANSIENT_FILES: &[&str] = &[
+    ".~lock.*",
+    "~$*",
+    ".goutputstream-*",
+    "*.tmp",
+    "*.temp",
+    "*.swp",
+    "*.swx",
+    ".#*",
+    "*~",
+    "*.crdownload",
+];
+
+/// Binary files already warned about, so each is reported once per process.
+static WARNED_BINARY: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Include patterns compiled in declaration order.
+///
+/// Patterns prefixed with `!` are negations. Like gitignore, the last pattern
+/// that matches a path decides: `*.md` then `!CHANGELOG.md` includes every
+/// markdown file except changelogs, and a later `CHANGELOG.md` would bring
+/// them back.
+///
+/// Exact relative paths (a repo's `files`) are included outright, before any
+/// glob is consulted.
+///
+/// Unless `include_hidden` is set, a path with a segment starting with `.`
+/// (e.g. `.github/CONTRIBUTING.md`) is only included by an exact file or by a
+/// pattern that itself names a hidden segment, like `.github/*.md`.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeSet {
+    globs: GlobSet,
+    /// Parallel to the glob indices in `globs`: true when that pattern is a negation
+    negated: Vec<bool>,
+    /// Parallel to the glob indices in `globs`: true when that pattern names
+    /// a hidden segment
+    names_hidden: Vec<bool>,
+    files: HashSet<String>,
+    include_hidden: bool,
+}
+
+impl IncludeSet {
+    pub fn new(globs: GlobSet, negated: Vec<bool>, names_hidden: Vec<bool>) -> Self {
+        Self {
+            globs,
+            negated,
+            names_hidden,
+            files: HashSet::new(),
+            include_hidden: false,
+        }
+    }
+
+    /// Also include these exact `/`-separated relative paths.
+    pub fn with_files(mut self, files: &[String]) -> Self {
+        self.files.extend(files.iter().cloned());
+        self
+    }
+
+    /// Let any pattern include hidden paths, not just ones naming them.
+    pub fn with_include_hidden(mut self, include_hidden: bool) -> Self {
+        self.include_hidden = include_hidden;
+        self
+    }
+
+    pub fn is_match(&self, path: &Path) -> bool {
+        let path_str = path.to_string_lossy();
+        if !self.files.is_empty() && self.files.contains(path_str.as_ref()) {
+            return true;
+        }
+        // `matches` returns glob indices in ascending (declaration) order
+        self.globs.matches(path).last().is_some_and(|&i| {
+            !self.negated[i]
+                && (self.include_hidden || self.names_hidden[i] || !has_hidden_segment(&path_str))
+        })
+    }
+
+    /// Whether anything under a hidden directory could be included, so the
+    /// scanner knows whether such directories are worth walking.
+    pub fn may_include_hidden(&self) -> bool {
+        self.include_hidden
+            || self.names_hidden.iter().any(|&hidden| hidden)
+            || self.files.iter().any(|f| has_hidden_segment(f))
+    }
+}
+
+/// Mirror-side patterns for files sync must never delete, such as Ulysses
+/// group metadata. Matched against paths relative to the output directory.
+///
+/// Compares equal by its source patterns, so a config reload can tell
+/// whether protection changed.
+#[derive(Debug, Clone, Default)]
+pub struct ProtectSet {
+    patterns: Vec<String>,
+    globs: GlobSet,
+}
+
+impl ProtectSet {
+    pub fn new(patterns: Vec<String>, globs: GlobSet) -> Self {
+        Self { patterns, globs }
+    }
+
+    pub fn is_match(&self, rel_path: &str) -> bool {
+        self.globs.is_match(normalize_path(rel_path))
+    }
+}
+
+impl PartialEq for ProtectSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.patterns == other.patterns
+    }
+}
+
+impl Eq for ProtectSet {}
+
+/// Mirror files whose events the mirror watcher drops, so they're never
+/// synced to source or taken for edits: `TRANSIENT_FILES` plus the
+/// configured `mirror_ignore` patterns. Matched against paths relative to
+/// the output directory; a pattern without `/` matches at any depth.
+///
+/// Compares equal by its configured patterns, so a config reload can tell
+/// whether they changed.
+#[derive(Debug, Clone)]
+pub struct MirrorIgnore {
+    patterns: Vec<String>,
+    globs: GlobSet,
+}
+
+impl MirrorIgnore {
+    pub fn new(patterns: Vec<String>) -> Result<Self, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in TRANSIENT_FILES
+            .iter()
+            .copied()
+            .chain(patterns.iter().map(String::as_str))
+        {
+            let glob_pattern = if !pattern.contains('/') && !pattern.starts_with("**/") {
+                format!("**/{pattern}")
+            } else {
+                pattern.to_string()
+            };
+            builder.add(Glob::new(&glob_pattern)?);
+        }
+        Ok(Self {
+            patterns,
+            globs: builder.build()?,
+        })
+    }
+
+    pub fn is_match(&self, rel_path: &str) -> bool {
+        self.globs.is_match(normalize_path(rel_path))
+    }
+}
+
+impl Default for MirrorIgnore {
+    /// Only the built-in `TRANSIENT_FILES`.
+    fn default() -> Self {
+        Self::new(Vec::new()).expect("built-in transient patterns are valid globs")
+    }
+}
+
+impl PartialEq for MirrorIgnore {
+    fn eq(&self, other: &Self) -> bool {
+        self.patterns == other.patterns
+    }
+}
+
+impl Eq for MirrorIgnore {}
 
 /// Check if a file should be mirrored based on exclude/include patterns.
 ///
 /// Algorithm:
 /// 1. Normalize path to forward slashes, strip leading `./`
 /// 2. Check excludes first — if excluded, return false
-/// 3. Check includes — if included, return true
+/// 3. Check includes (exact files, then globs) — if included, return true
 /// 4. Otherwise return false
 ///
 /// Exclude is checked FIRST so that e.g. node_modules/*.md stays excluded.
-pub fn should_mirror(file_rel_path: &str, exclude: &Gitignore, include: &GlobSet) -> bool {
+/// Ulysses group metadata is never mirrored, whatever the patterns, so a
+/// copy committed to a repo can't overwrite the mirror's own.
+pub fn should_mirror(file_rel_path: &str, exclude: &Gitignore, include: &IncludeSet) -> bool {
     let normalized = normalize_path(file_rel_path);
-    if normalized.is_empty() {
+    if normalized.is_empty() || is_ulysses_metadata(&normalized) {
         return false;
     }
 
@@ -27,24 +217,116 @@ pub fn should_mirror(file_rel_path: &str, exclude: &Gitignore, include: &GlobSet
         return false;
     }
 
-    // Check include patterns (glob semantics)
+    // Check include patterns (glob semantics, last match wins)
     include.is_match(path)
 }
 
 /// Check if the scanner should descend into a directory.
 ///
-/// Returns false if the directory matches an exclude pattern.
-pub fn should_descend(dir_rel_path: &str, exclude: &Gitignore) -> bool {
+/// Returns false if the directory matches an exclude pattern, or is hidden
+/// and nothing in `include` could match inside it.
+pub fn should_descend(dir_rel_path: &str, exclude: &Gitignore, include: &IncludeSet) -> bool {
     let normalized = normalize_path(dir_rel_path);
     if normalized.is_empty() {
         return true;
     }
+    if has_hidden_segment(&normalized) && !include.may_include_hidden() {
+        return false;
+    }
 
     let path = Path::new(&normalized);
     // The `true` flag indicates this is a directory
     !exclude.matched(path, true).is_ignore()
 }
 
+/// Whether a relative path names a metadata file Ulysses writes into its
+/// group folders, like `.Ulysses-Group.plist`. Sync leaves these alone.
+pub fn is_ulysses_metadata(path: &str) -> bool {
+    path.rsplit(['/', '\\'])
+        .next()
+        .is_some_and(|name| name.starts_with(ULYSSES_METADATA_PREFIX))
+}
+
+/// Whether any `/`-separated segment of a path or pattern starts with `.`,
+/// ignoring `.` and `..` themselves.
+pub fn has_hidden_segment(path: &str) -> bool {
+    path.split('/')
+        .any(|segment| segment.starts_with('.') && segment != "." && segment != "..")
+}
+
+/// Check whether the start of a file matches a `skip_if_matches` pattern,
+/// or looks binary while `mirror_binary` is off.
+///
+/// Runs after `should_mirror`, so only included files are read. Unreadable
+/// files are not skipped; the sync that follows reports the real error.
+pub fn content_excluded(path: &Path, pattern: Option<&Regex>, mirror_binary: bool) -> bool {
+    if pattern.is_none() && mirror_binary {
+        return false;
+    }
+    let mut head = Vec::new();
+    match File::open(path) {
+        Ok(file) => {
+            if file
+                .take(CONTENT_MATCH_BYTES)
+                .read_to_end(&mut head)
+                .is_err()
+            {
+                return false;
+            }
+        }
+        Err(_) => return false,
+    }
+    if !mirror_binary && looks_binary(&head) {
+        if WARNED_BINARY.lock().unwrap().insert(path.to_path_buf()) {
+            warn!(
+                "Not mirroring binary file {} (set mirror_binary = true to mirror it)",
+                path.display()
+            );
+        }
+        return true;
+    }
+    pattern.is_some_and(|p| p.is_match(&String::from_utf8_lossy(&head)))
+}
+
+/// Whether the start of a file looks binary rather than text: it has a NUL
+/// byte, or too many control bytes other than whitespace. Bytes of non-ASCII
+/// UTF-8 and legacy 8-bit encodings count as text, and so does UTF-16 or
+/// UTF-32, whose NUL bytes would otherwise give it away as binary.
+pub fn looks_binary(head: &[u8]) -> bool {
+    if looks_wide_text(head) {
+        return false;
+    }
+    if head.contains(&0) {
+        return true;
+    }
+    if head.is_empty() {
+        return false;
+    }
+    let control = head
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)) || b == 0x7f)
+        .count();
+    control as f64 / head.len() as f64 > BINARY_CONTROL_RATIO
+}
+
+/// Whether the start of a file is UTF-16 or UTF-32 text: it starts with a
+/// byte order mark, or is mostly ASCII in UTF-16 without one, so every other
+/// byte is NUL and the bytes between never are.
+fn looks_wide_text(head: &[u8]) -> bool {
+    const BOMS: [&[u8]; 3] = [&[0xff, 0xfe], &[0xfe, 0xff], &[0x00, 0x00, 0xfe, 0xff]];
+    if BOMS.iter().any(|bom| head.starts_with(bom)) {
+        return true;
+    }
+    if head.len() < 2 {
+        return false;
+    }
+    let half = |parity: usize| head.iter().skip(parity).step_by(2);
+    (0..2).any(|parity| {
+        let nul = half(parity).filter(|&&b| b == 0).count();
+        nul * 10 >= half(parity).count() * 9 && !half(1 - parity).any(|&b| b == 0)
+    })
+}
+
 /// Normalize a relative path: forward slashes, strip leading `./`
 fn normalize_path(rel_path: &str) -> String {
     let normalized = rel_path.replace('\\', "/");
@@ -71,25 +353,33 @@ mod tests {
         builder.build().unwrap()
     }
 
-    fn build_include(patterns: &[&str]) -> GlobSet {
+    fn build_include(patterns: &[&str]) -> IncludeSet {
         let mut builder = GlobSetBuilder::new();
+        let mut negated = Vec::new();
+        let mut names_hidden = Vec::new();
         for p in patterns {
+            let (p, is_negated) = match p.strip_prefix('!') {
+                Some(rest) => (rest, true),
+                None => (*p, false),
+            };
             let glob_pattern = if !p.contains('/') && !p.starts_with("**/") {
                 format!("**/{p}")
             } else {
                 p.to_string()
             };
             builder.add(Glob::new(&glob_pattern).unwrap());
+            negated.push(is_negated);
+            names_hidden.push(has_hidden_segment(p));
         }
-        builder.build().unwrap()
+        IncludeSet::new(builder.build().unwrap(), negated, names_hidden)
     }
 
     fn default_exclude() -> Gitignore {
-        build_exclude(&DEFAULT_GLOBAL_EXCLUDE.iter().copied().collect::<Vec<_>>())
+        build_exclude(DEFAULT_GLOBAL_EXCLUDE)
     }
 
-    fn default_include() -> GlobSet {
-        build_include(&DEFAULT_GLOBAL_INCLUDE.iter().copied().collect::<Vec<_>>())
+    fn default_include() -> IncludeSet {
+        build_include(DEFAULT_GLOBAL_INCLUDE)
     }
 
     #[test]
@@ -164,19 +454,81 @@ mod tests {
     #[test]
     fn test_should_descend_excludes_dirs() {
         let exc = default_exclude();
-        assert!(!should_descend("node_modules", &exc));
-        assert!(!should_descend(".git", &exc));
-        assert!(!should_descend("__pycache__", &exc));
-        assert!(!should_descend("dist", &exc));
-        assert!(!should_descend(".venv", &exc));
+        // Hidden dirs are walkable, so only the excludes keep these out
+        let inc = default_include().with_include_hidden(true);
+        assert!(!should_descend("node_modules", &exc, &inc));
+        assert!(!should_descend(".git", &exc, &inc));
+        assert!(!should_descend("__pycache__", &exc, &inc));
+        assert!(!should_descend("dist", &exc, &inc));
+        assert!(!should_descend(".venv", &exc, &inc));
     }
 
     #[test]
     fn test_should_descend_allows_normal_dirs() {
         let exc = default_exclude();
-        assert!(should_descend("src", &exc));
-        assert!(should_descend("docs", &exc));
-        assert!(should_descend("lib", &exc));
+        let inc = default_include();
+        assert!(should_descend("src", &exc, &inc));
+        assert!(should_descend("docs", &exc, &inc));
+        assert!(should_descend("lib", &exc, &inc));
+    }
+
+    #[test]
+    fn test_hidden_paths_skipped_by_default() {
+        let exc = default_exclude();
+        let inc = default_include();
+        assert!(!should_mirror(".github/CONTRIBUTING.md", &exc, &inc));
+        assert!(!should_mirror("docs/.drafts/idea.md", &exc, &inc));
+        assert!(!should_mirror(".notes.md", &exc, &inc));
+        assert!(!should_descend(".github", &exc, &inc));
+
+        // A pattern or exact file naming the hidden path brings it back
+        let inc = build_include(&["*.md", ".github/*.md"]);
+        assert!(should_mirror(".github/CONTRIBUTING.md", &exc, &inc));
+        assert!(!should_mirror("docs/.drafts/idea.md", &exc, &inc));
+        assert!(should_descend(".github", &exc, &inc));
+        let inc = default_include().with_files(&[".github/CONTRIBUTING.md".to_string()]);
+        assert!(should_mirror(".github/CONTRIBUTING.md", &exc, &inc));
+    }
+
+    #[test]
+    fn test_include_hidden_relies_on_patterns() {
+        let exc = default_exclude();
+        let inc = default_include().with_include_hidden(true);
+        assert!(should_mirror(".github/CONTRIBUTING.md", &exc, &inc));
+        assert!(should_mirror(".well-known/security.txt", &exc, &inc));
+        assert!(should_descend(".github", &exc, &inc));
+        // Excludes still apply
+        assert!(!should_mirror(".git/description.md", &exc, &inc));
+        assert!(!should_mirror(".github/workflow.yml", &exc, &inc));
+    }
+
+    #[test]
+    fn test_mirror_ignore() {
+        let builtin = MirrorIgnore::default();
+        assert!(builtin.is_match("repo/.~lock.notes.md#"));
+        assert!(builtin.is_match("repo/docs/.goutputstream-4XQ2B1"));
+        assert!(builtin.is_match("repo/guide.md~"));
+        assert!(builtin.is_match("repo/~$report.docx"));
+        assert!(!builtin.is_match("repo/notes.md"));
+
+        let ignore = MirrorIgnore::new(vec!["*.bak".into(), "repo/scratch/**".into()]).unwrap();
+        assert!(ignore.is_match("repo/docs/old.bak"));
+        assert!(ignore.is_match("repo/scratch/a.md"));
+        assert!(ignore.is_match("repo/x.tmp"));
+        assert!(!ignore.is_match("other/scratch/a.md"));
+        assert_ne!(ignore, builtin);
+        assert!(MirrorIgnore::new(vec!["a[".into()]).is_err());
+    }
+
+    #[test]
+    fn test_ulysses_metadata_never_mirrored() {
+        let exc = default_exclude();
+        let inc = build_include(&["*.md", "*.plist"]).with_include_hidden(true);
+        assert!(should_mirror("docs/Info.plist", &exc, &inc));
+        assert!(!should_mirror(".Ulysses-Group.plist", &exc, &inc));
+        assert!(!should_mirror("docs/.Ulysses-Group.plist", &exc, &inc));
+        assert!(is_ulysses_metadata("repo/docs/.Ulysses-Settings.plist"));
+        assert!(!is_ulysses_metadata("repo/.Ulysses-docs/guide.md"));
     }
 
     #[test]
@@ -197,6 +549,55 @@ mod tests {
         assert!(!should_mirror("main.rs", &exc, &inc));
     }
 
+    #[test]
+    fn test_negated_include_pattern() {
+        let exc = build_exclude(&[]);
+        let inc = build_include(&["*.md", "!CHANGELOG.md"]);
+        assert!(should_mirror("README.md", &exc, &inc));
+        assert!(should_mirror("docs/guide.md", &exc, &inc));
+        assert!(!should_mirror("CHANGELOG.md", &exc, &inc));
+        assert!(!should_mirror("sub/CHANGELOG.md", &exc, &inc));
+    }
+
+    #[test]
+    fn test_negated_include_last_match_wins() {
+        let exc = build_exclude(&[]);
+        let inc = build_include(&["*.md", "!CHANGELOG.md", "docs/CHANGELOG.md"]);
+        assert!(!should_mirror("CHANGELOG.md", &exc, &inc));
+        assert!(should_mirror("docs/CHANGELOG.md", &exc, &inc));
+    }
+
+    #[test]
+    fn test_negation_alone_includes_nothing() {
+        let exc = build_exclude(&[]);
+        let inc = build_include(&["!CHANGELOG.md"]);
+        assert!(!should_mirror("CHANGELOG.md", &exc, &inc));
+        assert!(!should_mirror("README.md", &exc, &inc));
+    }
+
+    #[test]
+    fn test_exact_file_name_included() {
+        let exc = default_exclude();
+        let inc = default_include().with_files(&["ARCHITECTURE".to_string()]);
+        assert!(should_mirror("ARCHITECTURE", &exc, &inc));
+        assert!(should_mirror("./ARCHITECTURE", &exc, &inc));
+        // Exact paths are not globs: only the file at the repo root matches
+        assert!(!should_mirror("src/ARCHITECTURE", &exc, &inc));
+        assert!(!should_mirror("ARCHITECTURE/overview", &exc, &inc));
+    }
+
+    #[test]
+    fn test_exact_file_path_included() {
+        let exc = default_exclude();
+        let inc = default_include().with_files(&["docs/NOTES".to_string()]);
+        assert!(should_mirror("docs/NOTES", &exc, &inc));
+        assert!(!should_mirror("NOTES", &exc, &inc));
+        assert!(should_mirror("docs/guide.md", &exc, &inc));
+        // Excludes still win
+        let inc = default_include().with_files(&["node_modules/NOTES".to_string()]);
+        assert!(!should_mirror("node_modules/NOTES", &exc, &inc));
+    }
+
     #[test]
     fn test_normalize_path() {
         assert_eq!(normalize_path("./foo/bar.md"), "foo/bar.md");
@@ -219,4 +620,55 @@ mod tests {
         assert!(!should_mirror(".DS_Store", &exc, &inc));
         assert!(!should_mirror("Thumbs.db", &exc, &inc));
     }
+
+    #[test]
+    fn test_content_excluded() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let file = tmp.path().join("post.md");
+        std::fs::write(&file, "---\ntitle: Hi\ndraft: true\n---\nbody").unwrap();
+        let draft = Regex::new(r"(?m)^draft:\s*true").unwrap();
+
+        assert!(content_excluded(&file, Some(&draft), false));
+        assert!(!content_excluded(&file, None, false));
+        assert!(!content_excluded(
+            &tmp.path().join("missing.md"),
+            Some(&draft),
+            false
+        ));
+
+        // Only the head of the file is checked
+        let padded = format!("{}\ndraft: true", "x".repeat(CONTENT_MATCH_BYTES as usize));
+        std::fs::write(&file, padded).unwrap();
+        assert!(!content_excluded(&file, Some(&draft), false));
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary(b""));
+        assert!(!looks_binary(
+            "# Notes\n\tcaf\u{e9} \u{201c}quoted\u{201d}\r\n".as_bytes()
+        ));
+        assert!(looks_binary(b"PK\x03\x04\x00\x00"));
+        assert!(looks_binary(&[0x01, 0x02, 0x03, b'a', 0x04, 0x05]));
+    }
+
+    #[test]
+    fn test_wide_text_is_not_binary() {
+        let utf16le =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let utf16be =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_be_bytes).collect() };
+        assert!(!looks_binary(
+            &[&[0xff, 0xfe][..], &utf16le("# Notes\n")].concat()
+        ));
+        assert!(!looks_binary(
+            &[&[0xfe, 0xff][..], &utf16be("caf\u{e9}\n")].concat()
+        ));
+        assert!(!looks_binary(&utf16le("# Notes without a BOM\r\n")));
+        assert!(!looks_binary(&utf16be("# Notes without a BOM\r\n")));
+        let utf32le: Vec<u8> = [&[0xff, 0xfe, 0x00, 0x00][..], b"#\0\0\0"].concat();
+        assert!(!looks_binary(&utf32le));
+        // NULs on both halves are still binary
+        assert!(looks_binary(b"\x00\x00\x01\x00\x00\x02\x00\x00"));
+    }
 }
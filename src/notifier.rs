@@ -0,0 +1,143 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+use tracing::{debug, warn};
+
+/// How many conflicting paths a notification names before summarizing.
+const MAX_NAMED_PATHS: usize = 3;
+
+/// Something that can put a message in front of the user.
+pub trait Notifier: Send + Sync {
+    fn notify(&self, title: &str, body: &str);
+}
+
+/// OS desktop notifications: `notify-rust` on Linux, `osascript` on macOS.
+pub struct DesktopNotifier;
+
+impl Notifier for DesktopNotifier {
+    fn notify(&self, title: &str, body: &str) {
+        #[cfg(target_os = "linux")]
+        {
+            if let Err(e) = notify_rust::Notification::new()
+                .appname("ulysses-link")
+                .summary(title)
+                .body(body)
+                .show()
+            {
+                warn!("Failed to show notification: {}", e);
+            }
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            let script = format!(
+                "display notification {} with title {}",
+                applescript_string(body),
+                applescript_string(title)
+            );
+            match Command::new("osascript").args(["-e", &script]).output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => warn!(
+                    "Failed to show notification: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => warn!("Failed to run osascript: {}", e),
+            }
+        }
+
+        #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+        {
+            debug!("Notifications unsupported on this platform: {title}: {body}");
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Reports sync conflicts to the user when `notify_on_conflict` is set.
+///
+/// Watchers report once per debounced batch, so a burst of conflicts becomes
+/// a single notification. Shared across watchers; a config reload flips it
+/// on or off in place.
+pub struct ConflictNotifier {
+    enabled: AtomicBool,
+    notifier: Box<dyn Notifier>,
+}
+
+impl ConflictNotifier {
+    pub fn new(enabled: bool) -> Self {
+        Self::with_notifier(enabled, Box::new(DesktopNotifier))
+    }
+
+    pub fn with_notifier(enabled: bool, notifier: Box<dyn Notifier>) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+            notifier,
+        }
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Send one notification for the conflicts found in a batch, if any.
+    pub fn batch_conflicts(&self, rel_paths: &[String]) {
+        if rel_paths.is_empty() || !self.enabled.load(Ordering::Relaxed) {
+            return;
+        }
+        let (title, body) = conflict_message(rel_paths);
+        debug!("Notifying: {}: {}", title, body);
+        self.notifier.notify(&title, &body);
+    }
+}
+
+impl Default for ConflictNotifier {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+fn conflict_message(rel_paths: &[String]) -> (String, String) {
+    match rel_paths {
+        [only] => (
+            "ulysses-link conflict".to_string(),
+            format!(
+                "{only} was edited on both sides; the older version was kept as a conflict file"
+            ),
+        ),
+        _ => {
+            let mut body = rel_paths
+                .iter()
+                .take(MAX_NAMED_PATHS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ");
+            if rel_paths.len() > MAX_NAMED_PATHS {
+                body.push_str(&format!(" and {} more", rel_paths.len() - MAX_NAMED_PATHS));
+            }
+            (format!("ulysses-link: {} conflicts", rel_paths.len()), body)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conflict_message() {
+        let (title, body) = conflict_message(&["repo/doc.md".to_string()]);
+        assert_eq!(title, "ulysses-link conflict");
+        assert!(body.starts_with("repo/doc.md "));
+
+        let paths: Vec<String> = (1..=5).map(|i| format!("repo/{i}.md")).collect();
+        let (title, body) = conflict_message(&paths);
+        assert_eq!(title, "ulysses-link: 5 conflicts");
+        assert_eq!(body, "repo/1.md, repo/2.md, repo/3.md and 2 more");
+    }
+}
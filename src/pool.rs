@@ -0,0 +1,130 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::error;
+
+/// Most worker threads a default pool starts. Flushes into one output dir
+/// serialize on its manifest lock, so more rarely help.
+const MAX_DEFAULT_WORKERS: usize = 4;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A job waiting for its due time. Ordered by due time, then by the order
+/// it was scheduled in.
+struct Timer {
+    due: Instant,
+    seq: u64,
+    job: Job,
+}
+
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        (self.due, self.seq) == (other.due, other.seq)
+    }
+}
+
+impl Eq for Timer {}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.due, self.seq).cmp(&(other.due, other.seq))
+    }
+}
+
+/// Runs every watcher's debounced flushes: one timer thread holds the jobs
+/// until they're due and hands them to a fixed set of workers, so the thread
+/// count doesn't grow with the number of repos.
+///
+/// Clones share the same threads. The threads exit once every clone is
+/// dropped, after running the jobs still scheduled.
+#[derive(Clone)]
+pub struct SyncPool {
+    timers: Sender<(Duration, Job)>,
+}
+
+impl SyncPool {
+    /// Start a pool with `workers` worker threads (at least one).
+    pub fn new(workers: usize) -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+        for _ in 0..workers.max(1) {
+            let job_rx = Arc::clone(&job_rx);
+            thread::spawn(move || loop {
+                let Ok(job) = job_rx.lock().unwrap().recv() else {
+                    return;
+                };
+                if panic::catch_unwind(AssertUnwindSafe(job)).is_err() {
+                    error!("Sync job panicked");
+                }
+            });
+        }
+
+        let (timers, timer_rx) = mpsc::channel();
+        thread::spawn(move || run_timers(&timer_rx, &job_tx));
+        Self { timers }
+    }
+
+    /// Run `job` on a worker once `delay` has passed.
+    pub fn schedule(&self, delay: Duration, job: impl FnOnce() + Send + 'static) {
+        // Only fails once the timer thread is gone, which outlives every clone
+        let _ = self.timers.send((delay, Box::new(job)));
+    }
+}
+
+impl Default for SyncPool {
+    /// A pool sized to the machine, up to `MAX_DEFAULT_WORKERS` workers.
+    fn default() -> Self {
+        let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::new(cpus.min(MAX_DEFAULT_WORKERS))
+    }
+}
+
+/// Hand each scheduled job to the workers when it's due. Once every
+/// `SyncPool` is dropped, the jobs left are handed over at once.
+fn run_timers(timer_rx: &Receiver<(Duration, Job)>, job_tx: &Sender<Job>) {
+    let mut timers: BinaryHeap<Reverse<Timer>> = BinaryHeap::new();
+    let mut seq = 0;
+    loop {
+        let received = match timers.peek() {
+            Some(Reverse(next)) => {
+                timer_rx.recv_timeout(next.due.saturating_duration_since(Instant::now()))
+            }
+            None => timer_rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok((delay, job)) => {
+                seq += 1;
+                timers.push(Reverse(Timer {
+                    due: Instant::now() + delay,
+                    seq,
+                    job,
+                }));
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                for Reverse(timer) in timers.into_sorted_vec().into_iter().rev() {
+                    let _ = job_tx.send(timer.job);
+                }
+                return;
+            }
+        }
+        while timers
+            .peek()
+            .is_some_and(|Reverse(next)| next.due <= Instant::now())
+        {
+            let Reverse(timer) = timers.pop().unwrap();
+            let _ = job_tx.send(timer.job);
+        }
+    }
+}
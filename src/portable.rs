@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::linker;
+use crate::manifest::{Manifest, ManifestEntry};
+
+const STATE_VERSION: u64 = 1;
+
+/// The sync state of every output directory, as written by `export-state`.
+/// Base caches are left out: a file whose hashes match is in sync, and only
+/// edits made on both sides before the next sync lack a merge base.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortableState {
+    pub version: u64,
+    /// Keyed by output directory
+    pub output_dirs: BTreeMap<PathBuf, PortableManifest>,
+}
+
+/// One output directory's manifest.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PortableManifest {
+    /// Keyed by path relative to the output dir
+    pub files: BTreeMap<String, ManifestEntry>,
+    /// RFC 3339 time each repo (by name) last synced changes
+    #[serde(default)]
+    pub last_synced: BTreeMap<String, String>,
+}
+
+/// A path prefix rewritten on import, such as an old home directory to the
+/// new one, since manifests record absolute paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remap {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+impl Remap {
+    /// Parse `OLD=NEW`.
+    pub fn parse(arg: &str) -> Result<Self, String> {
+        match arg.split_once('=') {
+            Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok(Self {
+                from: PathBuf::from(from),
+                to: PathBuf::from(to),
+            }),
+            _ => Err(format!("expected OLD=NEW, got '{arg}'")),
+        }
+    }
+}
+
+/// `path` with the first remap whose `from` it starts with applied.
+fn remap(path: &Path, remaps: &[Remap]) -> PathBuf {
+    remaps
+        .iter()
+        .find_map(|r| path.strip_prefix(&r.from).ok().map(|rest| r.to.join(rest)))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Collect the manifest of every active output directory in the config.
+pub fn export_state(config: &Config) -> Result<PortableState> {
+    let mut output_dirs = BTreeMap::new();
+    for output_dir in config.active_output_dirs() {
+        let manifest = Manifest::load_in(&output_dir, &config.state)?;
+        let portable = PortableManifest {
+            files: manifest
+                .entries()
+                .map(|(k, e)| (k.clone(), e.clone()))
+                .collect(),
+            last_synced: manifest
+                .all_last_synced()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        };
+        output_dirs.insert(output_dir, portable);
+    }
+    Ok(PortableState {
+        version: STATE_VERSION,
+        output_dirs,
+    })
+}
+
+/// Merge `state` into the manifests of the config's output directories,
+/// with `remaps` applied to every output directory and source path. Entries
+/// already tracked under the same key are replaced. Every output directory
+/// must be in the config, or nothing is written. Returns how many entries
+/// each output directory received.
+pub fn import_state(
+    config: &Config,
+    state: PortableState,
+    remaps: &[Remap],
+) -> Result<Vec<(PathBuf, usize)>> {
+    if state.version != STATE_VERSION {
+        bail!(
+            "Unsupported state version {} (expected {STATE_VERSION})",
+            state.version
+        );
+    }
+    let active = config.active_output_dirs();
+    let targets: Vec<(PathBuf, PortableManifest)> = state
+        .output_dirs
+        .into_iter()
+        .map(|(dir, portable)| (remap(&dir, remaps), portable))
+        .collect();
+    if let Some((dir, _)) = targets.iter().find(|(dir, _)| !active.contains(dir)) {
+        bail!(
+            "{} is not an output directory in the config; use --remap OLD=NEW to map it",
+            dir.display()
+        );
+    }
+
+    let mut imported = Vec::new();
+    for (output_dir, portable) in targets {
+        let mut manifest = Manifest::load_in(&output_dir, &config.state)?;
+        let count = portable.files.len();
+        for (rel_path, mut entry) in portable.files {
+            entry.source = remap(&entry.source, remaps);
+            // The base of the entry being replaced describes the old pairing
+            if manifest.get(&rel_path).is_some() {
+                linker::remove_base(&mut manifest, &output_dir, &rel_path)?;
+            }
+            manifest.insert(rel_path, entry);
+        }
+        for (name, stamp) in portable.last_synced {
+            manifest.set_last_synced(&name, stamp);
+        }
+        manifest
+            .save(&output_dir)
+            .with_context(|| format!("Failed to save manifest in {}", output_dir.display()))?;
+        imported.push((output_dir, count));
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::linker::{SyncDirection, SyncOutcome};
+    use crate::manifest::BaseCacheMode;
+    use crate::scanner;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// A repo and output dir under `home`, with a config naming them.
+    fn machine(home: &Path) -> Config {
+        fs::create_dir_all(home.join("code/repo")).unwrap();
+        fs::create_dir_all(home.join("docs")).unwrap();
+        let config_file = home.join("config.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+                home.join("docs").display(),
+                home.join("code/repo").display()
+            ),
+        )
+        .unwrap();
+        config::load_config(Some(&config_file)).unwrap()
+    }
+
+    #[test]
+    fn test_remap_parse() {
+        let remap = Remap::parse("/Users/old=/Users/new").unwrap();
+        assert_eq!(remap.from, PathBuf::from("/Users/old"));
+        assert_eq!(remap.to, PathBuf::from("/Users/new"));
+        assert!(Remap::parse("/Users/old").is_err());
+        assert!(Remap::parse("=/Users/new").is_err());
+    }
+
+    #[test]
+    fn test_export_remap_import_roundtrip() {
+        let tmp = TempDir::new().unwrap();
+        let old_home = tmp.path().canonicalize().unwrap().join("old");
+        let new_home = tmp.path().canonicalize().unwrap().join("new");
+
+        let old = machine(&old_home);
+        fs::write(old.repos[0].path.join("guide.md"), "guide").unwrap();
+        let mut manifest = Manifest::load(&old.output_dir).unwrap();
+        scanner::scan_repo(&old.repos[0], &old.output_dir, &mut manifest);
+        let json = serde_json::to_string(&export_state(&old).unwrap()).unwrap();
+
+        // The new machine has the same files, e.g. from a backup or cloud sync
+        let new = machine(&new_home);
+        fs::write(new.repos[0].path.join("guide.md"), "guide").unwrap();
+        fs::create_dir_all(new.output_dir.join("repo")).unwrap();
+        fs::write(new.output_dir.join("repo/guide.md"), "guide").unwrap();
+
+        let state: PortableState = serde_json::from_str(&json).unwrap();
+        let imported = import_state(
+            &new,
+            state,
+            &[Remap {
+                from: old_home.clone(),
+                to: new_home.clone(),
+            }],
+        )
+        .unwrap();
+        assert_eq!(imported, vec![(new.output_dir.clone(), 1)]);
+
+        let mut manifest = Manifest::load(&new.output_dir).unwrap();
+        let source = new.repos[0].path.join("guide.md");
+        assert_eq!(manifest.get("repo/guide.md").unwrap().source, source);
+        assert!(manifest.last_synced("repo").is_some());
+        let outcome = linker::sync_file(
+            &source,
+            &new.output_dir.join("repo/guide.md"),
+            &mut manifest,
+            "repo/guide.md",
+            &new.output_dir,
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        assert_eq!(outcome, SyncOutcome::AlreadyInSync);
+
+        // Tracked, so a mirror edit flows back instead of being skipped as
+        // someone else's file
+        fs::write(new.output_dir.join("repo/guide.md"), "edited").unwrap();
+        let outcome = linker::sync_file(
+            &source,
+            &new.output_dir.join("repo/guide.md"),
+            &mut manifest,
+            "repo/guide.md",
+            &new.output_dir,
+            SyncDirection::Bidirectional,
+        )
+        .unwrap();
+        assert_eq!(outcome, SyncOutcome::Copied);
+        assert_eq!(fs::read_to_string(&source).unwrap(), "edited");
+    }
+
+    #[test]
+    fn test_import_drops_bases_of_replaced_entries() {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path().canonicalize().unwrap();
+        let mut config = machine(&home);
+        config.state.base_cache = BaseCacheMode::Content;
+        let source = config.repos[0].path.join("guide.md");
+        fs::write(&source, "local").unwrap();
+        let mut manifest = Manifest::load_in(&config.output_dir, &config.state).unwrap();
+        scanner::scan_repo(&config.repos[0], &config.output_dir, &mut manifest);
+        let base = |manifest: &Manifest| {
+            linker::read_base(manifest, &config.output_dir, "repo/guide.md").unwrap()
+        };
+        assert_eq!(base(&manifest), Some("local".to_string()));
+
+        let state = PortableState {
+            version: STATE_VERSION,
+            output_dirs: BTreeMap::from([(
+                config.output_dir.clone(),
+                PortableManifest {
+                    files: BTreeMap::from([(
+                        "repo/guide.md".to_string(),
+                        ManifestEntry {
+                            source: source.clone(),
+                            hash: "imported".to_string(),
+                        },
+                    )]),
+                    ..Default::default()
+                },
+            )]),
+        };
+        import_state(&config, state, &[]).unwrap();
+        let manifest = Manifest::load_in(&config.output_dir, &config.state).unwrap();
+        assert_eq!(manifest.get("repo/guide.md").unwrap().hash, "imported");
+        assert_eq!(base(&manifest), None);
+    }
+
+    #[test]
+    fn test_import_rejects_unknown_output_dir() {
+        let tmp = TempDir::new().unwrap();
+        let home = tmp.path().canonicalize().unwrap();
+        let config = machine(&home.join("a"));
+        let state = PortableState {
+            version: STATE_VERSION,
+            output_dirs: BTreeMap::from([(home.join("elsewhere"), PortableManifest::default())]),
+        };
+        let err = import_state(&config, state, &[]).unwrap_err();
+        assert!(err.to_string().contains("--remap"));
+    }
+}
@@ -1,13 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::DerefMut;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::SystemTime;
 
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 use walkdir::WalkDir;
 
 use crate::config::{Config, RepoConfig};
 use crate::linker::{self, SyncOutcome};
-use crate::manifest::Manifest;
-use crate::matcher;
+use crate::manifest::{BaseCacheMode, ConflictInfo, Manifest};
+use crate::{matcher, stats, syncer};
 
 #[derive(Debug, Default)]
 pub struct ScanResult {
@@ -17,33 +20,170 @@ pub struct ScanResult {
     pub pruned: u32,
     pub merged: u32,
     pub conflicts: u32,
+    /// Which files conflicted and where the other side was saved; conflicts
+    /// resolved without a backup file are only counted
+    pub conflict_details: Vec<ConflictInfo>,
+    /// New files left for a later scan by `initial_sync_limit`
+    pub deferred: u32,
     pub errors: u32,
+    /// Mirror files a dry run would delete; empty after a real scan
+    pub planned_deletes: Vec<PathBuf>,
 }
 
 impl ScanResult {
-    fn merge(&mut self, other: &ScanResult) {
+    /// Add `other`'s counts to these.
+    pub(crate) fn merge(&mut self, other: &ScanResult) {
         self.created += other.created;
         self.already_existed += other.already_existed;
         self.skipped += other.skipped;
         self.pruned += other.pruned;
         self.merged += other.merged;
         self.conflicts += other.conflicts;
+        self.conflict_details
+            .extend(other.conflict_details.iter().cloned());
+        self.deferred += other.deferred;
         self.errors += other.errors;
+        self.planned_deletes
+            .extend(other.planned_deletes.iter().cloned());
     }
 }
 
-/// Scan all repos and reconcile the mirror tree.
+/// Progress of a scan, reported once for each file that passes the patterns.
+#[derive(Debug)]
+pub struct ScanProgress<'a> {
+    pub repo: &'a str,
+    pub rel_path: &'a str,
+    /// Files handled so far in this repo, including this one
+    pub processed: u32,
+    /// What happened to the file; `None` if syncing it failed
+    pub outcome: Option<SyncOutcome>,
+}
+
+/// Which files a scan passes over for now, and how it hashes the rest.
+#[derive(Debug, Default)]
+struct ScanFilter {
+    /// Skip source files last modified before this
+    since: Option<SystemTime>,
+    /// New mirror files the scan may still create; once spent, the rest are
+    /// deferred
+    new_file_budget: Option<u32>,
+    /// Hash each repo's sources on several threads before syncing them
+    parallel_hash: bool,
+}
+
+impl ScanFilter {
+    /// The filter a full scan under `config` starts from.
+    fn for_config(config: &Config) -> Self {
+        Self {
+            since: None,
+            new_file_budget: config.initial_sync_limit,
+            parallel_hash: config.parallel_hash,
+        }
+    }
+}
+
+/// Scan all repos and reconcile the mirror tree. With `initial_sync_limit`
+/// set, new mirror files beyond the limit are left for later scans.
 pub fn full_scan(config: &Config, manifests: &mut HashMap<PathBuf, Manifest>) -> ScanResult {
+    full_scan_with_progress(config, manifests, &mut |_| {})
+}
+
+/// `full_scan`, calling `progress` after each file.
+pub fn full_scan_with_progress(
+    config: &Config,
+    manifests: &mut HashMap<PathBuf, Manifest>,
+    progress: &mut dyn FnMut(&ScanProgress),
+) -> ScanResult {
+    scan_repos(config, manifests, progress, None)
+}
+
+/// `full_scan` that only syncs source files modified at or after `since`.
+/// Older files are not read or hashed, so mirror edits to them wait for the
+/// next full scan; stale entries are still pruned.
+pub fn full_scan_since(
+    config: &Config,
+    manifests: &mut HashMap<PathBuf, Manifest>,
+    since: SystemTime,
+) -> ScanResult {
+    scan_repos(config, manifests, &mut |_| {}, Some(since))
+}
+
+fn scan_repos(
+    config: &Config,
+    manifests: &mut HashMap<PathBuf, Manifest>,
+    progress: &mut dyn FnMut(&ScanProgress),
+    since: Option<SystemTime>,
+) -> ScanResult {
     let mut result = ScanResult::default();
+    let mut filter = ScanFilter {
+        since,
+        ..ScanFilter::for_config(config)
+    };
+
+    if config.write_output_gitignore {
+        for output_dir in config.active_output_dirs() {
+            if let Err(e) = linker::write_output_gitignore(&output_dir) {
+                tracing::error!("{}", e);
+                result.errors += 1;
+            }
+        }
+    }
+
+    for repo_config in config.repos_by_priority() {
+        let extras = repo_config.extra_output_repos();
+        for target in std::iter::once(repo_config).chain(&extras) {
+            let manifest = match syncer::manifest_in(manifests, &target.output_dir, config) {
+                Ok(m) => m,
+                Err(e) => {
+                    tracing::error!("{:#}", e);
+                    result.errors += 1;
+                    continue;
+                }
+            };
+            let repo_result =
+                scan_repo_filtered(target, &target.output_dir, manifest, progress, &mut filter);
+            result.merge(&repo_result);
+        }
+    }
 
-    for repo_config in &config.repos {
-        let manifest = manifests
-            .entry(repo_config.output_dir.clone())
-            .or_insert_with(Manifest::empty);
-        let repo_result = scan_repo(repo_config, &repo_config.output_dir, manifest);
-        result.merge(&repo_result);
+    if result.deferred > 0 {
+        info!(
+            "Deferred {} new files to later scans (initial_sync_limit)",
+            result.deferred
+        );
     }
+    result
+}
 
+/// Scan one repo into its output dir and each of its extra outputs the way
+/// `full_scan` does, sharing one `initial_sync_limit` budget, e.g. for a repo
+/// added to a running service. `manifest_for` hands out each output dir's
+/// manifest; output dirs it has none for are skipped.
+pub fn scan_repo_outputs<M: DerefMut<Target = Manifest>>(
+    config: &Config,
+    repo_config: &RepoConfig,
+    mut manifest_for: impl FnMut(&Path) -> Option<M>,
+) -> ScanResult {
+    let mut result = ScanResult::default();
+    let mut filter = ScanFilter::for_config(config);
+    let extras = repo_config.extra_output_repos();
+    for target in std::iter::once(repo_config).chain(&extras) {
+        if let Some(mut manifest) = manifest_for(&target.output_dir) {
+            result.merge(&scan_repo_filtered(
+                target,
+                &target.output_dir,
+                &mut manifest,
+                &mut |_| {},
+                &mut filter,
+            ));
+        }
+    }
+    if result.deferred > 0 {
+        info!(
+            "Deferred {} new files of {} to later scans (initial_sync_limit)",
+            result.deferred, repo_config.name
+        );
+    }
     result
 }
 
@@ -53,42 +193,774 @@ pub fn scan_repo(
     output_dir: &Path,
     manifest: &mut Manifest,
 ) -> ScanResult {
+    scan_repo_with_progress(repo_config, output_dir, manifest, &mut |_| {})
+}
+
+/// `scan_repo`, calling `progress` after each file.
+pub fn scan_repo_with_progress(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    progress: &mut dyn FnMut(&ScanProgress),
+) -> ScanResult {
+    scan_repo_filtered(
+        repo_config,
+        output_dir,
+        manifest,
+        progress,
+        &mut ScanFilter::default(),
+    )
+}
+
+fn scan_repo_filtered(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    progress: &mut dyn FnMut(&ScanProgress),
+    filter: &mut ScanFilter,
+) -> ScanResult {
+    reconcile_repo(
+        repo_config,
+        output_dir,
+        manifest,
+        "Scan",
+        progress,
+        filter,
+        |source, mirror, manifest, rel_path, source_hash| {
+            linker::sync_file_with_source_hash(
+                source,
+                mirror,
+                manifest,
+                rel_path,
+                output_dir,
+                repo_config.direction,
+                source_hash,
+            )
+        },
+    )
+}
+
+/// Work out what a full scan would do without writing anything, logging each
+/// planned change at INFO. The counts are what `full_scan` would report.
+pub fn plan_scan(config: &Config, manifests: &HashMap<PathBuf, Manifest>) -> ScanResult {
+    let no_keys = Manifest::empty();
     let mut result = ScanResult::default();
-    let repo_path = &repo_config.path;
+    for repo_config in config.repos_by_priority() {
+        let extras = repo_config.extra_output_repos();
+        for target in std::iter::once(repo_config).chain(&extras) {
+            let manifest = manifests.get(&target.output_dir).unwrap_or(&no_keys);
+            result.merge(&plan_repo(target, &target.output_dir, manifest));
+        }
+    }
+    result
+}
 
-    if !repo_path.is_dir() {
+/// `plan_scan` for a single repo.
+pub fn plan_repo(repo_config: &RepoConfig, output_dir: &Path, manifest: &Manifest) -> ScanResult {
+    let mut result = ScanResult::default();
+    if !repo_config.source_root().is_dir() {
         warn!(
             "Repo path does not exist, skipping: {}",
-            repo_path.display()
+            repo_config.source_root().display()
         );
         return result;
     }
 
-    let walker = WalkDir::new(repo_path)
-        .follow_links(false)
-        .into_iter()
-        .filter_entry(|entry| {
-            if entry.path() == repo_path {
-                return true;
+    walk_mirrored(repo_config, output_dir, |rel_path| {
+        let source = repo_config.source_path(&rel_path);
+        if matcher::content_excluded(
+            &source,
+            repo_config.skip_if_matches.as_ref(),
+            repo_config.mirror_binary,
+        ) {
+            result.skipped += 1;
+            if let Some(key) = manifest.key_for_source(&source) {
+                debug!("Skipped by content: {}", key);
+                result.pruned += 1;
+                result
+                    .planned_deletes
+                    .extend(linker::log_planned_removal(key, manifest, output_dir));
+            }
+            return;
+        }
+        let key = repo_config.mirror_key(&rel_path, manifest);
+        match linker::plan_sync(
+            &source,
+            &output_dir.join(&key),
+            manifest,
+            &key,
+            output_dir,
+            repo_config.direction,
+        ) {
+            Ok(outcome) => {
+                linker::log_planned(&key, outcome);
+                match outcome {
+                    SyncOutcome::Copied => result.created += 1,
+                    SyncOutcome::AlreadyInSync | SyncOutcome::Claimed => {
+                        result.already_existed += 1
+                    }
+                    SyncOutcome::Skipped => result.skipped += 1,
+                    SyncOutcome::Merged => result.merged += 1,
+                    SyncOutcome::Conflict => result.conflicts += 1,
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to plan sync of {}: {}", rel_path, e);
+                result.errors += 1;
+            }
+        }
+    });
+
+    plan_prune_repo(repo_config, output_dir, manifest, &mut result);
+
+    info!(
+        "Dry run for {}: {} would be created, {} existed, {} skipped, {} merged, {} conflicts, {} pruned, {} errors",
+        repo_config.name,
+        result.created,
+        result.already_existed,
+        result.skipped,
+        result.merged,
+        result.conflicts,
+        result.pruned,
+        result.errors,
+    );
+    result
+}
+
+/// Work out what `full_prune` would remove without writing anything, logging
+/// each planned removal at INFO.
+pub fn plan_full_prune(config: &Config, manifests: &HashMap<PathBuf, Manifest>) -> ScanResult {
+    let no_keys = Manifest::empty();
+    let mut result = ScanResult::default();
+    for repo_config in config.enabled_repos() {
+        for output_dir in repo_config.output_dirs() {
+            let manifest = manifests.get(output_dir).unwrap_or(&no_keys);
+            plan_prune_repo(repo_config, output_dir, manifest, &mut result);
+        }
+    }
+    result
+}
+
+/// Count and log the stale entries `prune_stale` would remove for one repo.
+fn plan_prune_repo(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &Manifest,
+    result: &mut ScanResult,
+) {
+    let mut keys = linker::plan_prune_stale(&repo_config.mirror_subdir, manifest);
+    for (key, _) in out_of_scope(repo_config, manifest) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    for key in keys {
+        result.pruned += 1;
+        result
+            .planned_deletes
+            .extend(linker::log_planned_removal(&key, manifest, output_dir));
+    }
+}
+
+/// Estimate how many bytes a full scan would copy into each output dir: the
+/// sizes of mirrored source files whose mirror doesn't exist yet.
+pub fn estimate_scan_bytes(config: &Config) -> HashMap<PathBuf, u64> {
+    let no_keys = Manifest::empty();
+    let mut bytes: HashMap<PathBuf, u64> = HashMap::new();
+    for repo_config in config.enabled_repos() {
+        if !repo_config.source_root().is_dir() {
+            continue;
+        }
+        let output_dir = &repo_config.output_dir;
+        let total = bytes.entry(output_dir.clone()).or_default();
+        walk_mirrored(repo_config, output_dir, |rel_path| {
+            let mirror = output_dir.join(repo_config.mirror_key(&rel_path, &no_keys));
+            if mirror.exists() {
+                return;
+            }
+            if let Ok(meta) = repo_config.source_path(&rel_path).metadata() {
+                *total += meta.len();
+            }
+        });
+    }
+    bytes
+}
+
+/// Fail before a full scan if an output dir's filesystem can't hold what the
+/// scan would copy, including the base cache copy when it lives there too.
+pub fn check_disk_space(config: &Config) -> anyhow::Result<()> {
+    let copies =
+        if config.state.state_dir.is_none() && config.state.base_cache != BaseCacheMode::Off {
+            2
+        } else {
+            1
+        };
+    for (output_dir, bytes) in estimate_scan_bytes(config) {
+        let needed = bytes * copies;
+        if needed == 0 {
+            continue;
+        }
+        let available = linker::available_space(&output_dir)?;
+        if needed > available {
+            anyhow::bail!(
+                "Not enough space in {}: sync needs about {}, {} available",
+                output_dir.display(),
+                stats::format_bytes(needed),
+                stats::format_bytes(available)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Prune mirrors of deleted sources in every repo, extra outputs included,
+/// without walking the repos or syncing existing files.
+pub fn full_prune(config: &Config, manifests: &mut HashMap<PathBuf, Manifest>) -> ScanResult {
+    let mut result = ScanResult::default();
+    for repo_config in config.enabled_repos() {
+        for output_dir in repo_config.output_dirs() {
+            let manifest = match syncer::manifest_in(manifests, output_dir, config) {
+                Ok(m) => m,
+                Err(e) => {
+                    tracing::error!("{:#}", e);
+                    result.errors += 1;
+                    continue;
+                }
+            };
+            result.merge(&prune_repo(repo_config, output_dir, manifest));
+        }
+    }
+    result
+}
+
+/// Prune a single repo's stale manifest entries and save the manifest.
+pub fn prune_repo(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+) -> ScanResult {
+    // A repo that vanished entirely is left alone, as in a full scan
+    if !repo_config.source_root().is_dir() {
+        warn!(
+            "Repo path does not exist, skipping: {}",
+            repo_config.source_root().display()
+        );
+        return ScanResult::default();
+    }
+    if !output_dir.is_dir() {
+        warn!(
+            "Output directory does not exist, skipping {}: {}",
+            repo_config.name,
+            output_dir.display()
+        );
+        return ScanResult::default();
+    }
+    finish_repo(
+        repo_config,
+        output_dir,
+        manifest,
+        "Prune",
+        ScanResult::default(),
+    )
+}
+
+/// Reset a repo's mirror to match source, discarding mirror-side edits.
+/// With `keep_conflicts`, discarded edits are saved as conflict files.
+pub fn reset_repo(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    keep_conflicts: bool,
+) -> ScanResult {
+    reconcile_repo(
+        repo_config,
+        output_dir,
+        manifest,
+        "Reset",
+        &mut |_| {},
+        &mut ScanFilter::default(),
+        |source, mirror, manifest, rel_path, _| {
+            linker::force_source(
+                source,
+                mirror,
+                manifest,
+                rel_path,
+                output_dir,
+                keep_conflicts,
+            )
+        },
+    )
+}
+
+/// Move the mirror of a repo reached through a symlink from under its
+/// target's name, which repos without an explicit `name` used to get, to its
+/// mirror subdir. Only done while the subdir is empty and every entry under
+/// the old name belongs to this repo.
+fn adopt_target_named_mirror(repo_config: &RepoConfig, output_dir: &Path, manifest: &mut Manifest) {
+    let Some(old_subdir) = repo_config
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+    else {
+        return;
+    };
+    let new_subdir = &repo_config.mirror_subdir;
+    if old_subdir == *new_subdir
+        || output_dir.join(new_subdir).exists()
+        || !manifest.entries_for_repo(new_subdir).is_empty()
+    {
+        return;
+    }
+    let entries = manifest.entries_for_repo(&old_subdir);
+    if entries.is_empty()
+        || !entries
+            .iter()
+            .all(|(_, entry)| entry.source.starts_with(&repo_config.path))
+    {
+        return;
+    }
+    match linker::rename_repo_mirror(&old_subdir, new_subdir, output_dir, manifest) {
+        Ok(_) => manifest.rename_repo(&old_subdir, &repo_config.name),
+        Err(e) => warn!(
+            "Failed to move mirror of {} from {}: {}",
+            repo_config.name, old_subdir, e
+        ),
+    }
+}
+
+/// Walk a repo, run `sync` on every mirrored file, then prune stale entries
+/// and save the manifest. Files the `filter` passes over are left as they
+/// are: sources modified before `since`, and new mirror files once the
+/// budget is spent. With `parallel_hash`, `sync` is handed each source's
+/// hash worked out beforehand; otherwise it gets `None`.
+fn reconcile_repo(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    label: &str,
+    progress: &mut dyn FnMut(&ScanProgress),
+    filter: &mut ScanFilter,
+    mut sync: impl FnMut(
+        &Path,
+        &Path,
+        &mut Manifest,
+        &str,
+        Option<String>,
+    ) -> anyhow::Result<SyncOutcome>,
+) -> ScanResult {
+    let mut result = ScanResult::default();
+    let source_root = repo_config.source_root();
+
+    if !source_root.is_dir() {
+        warn!(
+            "Repo path does not exist, skipping: {}",
+            source_root.display()
+        );
+        return result;
+    }
+    // A vanished output dir (e.g. an unmounted drive) would otherwise be
+    // recreated empty, or read as every mirror file having been deleted
+    if !output_dir.is_dir() {
+        warn!(
+            "Output directory does not exist, skipping {}: {}",
+            repo_config.name,
+            output_dir.display()
+        );
+        return result;
+    }
+
+    adopt_target_named_mirror(repo_config, output_dir, manifest);
+
+    // Decorations left by an earlier config would read as mirror edits
+    if let Err(e) = linker::strip_stale_decorations(
+        manifest,
+        output_dir,
+        &repo_config.mirror_subdir,
+        &repo_config.path,
+    ) {
+        tracing::error!(
+            "Failed to strip stale decorations for {}: {}",
+            repo_config.name,
+            e
+        );
+        result.errors += 1;
+    }
+
+    let mut rel_paths = Vec::new();
+    let reached = walk_mirrored(repo_config, output_dir, |rel_path| {
+        if let Some(since) = filter.since {
+            let source = repo_config.source_path(&rel_path);
+            let modified = source.metadata().and_then(|m| m.modified());
+            if modified.is_ok_and(|modified| modified < since) {
+                return;
             }
+        }
+        rel_paths.push(rel_path);
+    });
+    let source_hashes = if filter.parallel_hash {
+        hash_sources(repo_config, output_dir, manifest, &rel_paths)
+    } else {
+        vec![None; rel_paths.len()]
+    };
 
-            let rel_path = entry.path().strip_prefix(repo_path).unwrap_or(entry.path());
-            let rel_str = rel_path.to_string_lossy();
+    let mut processed = 0u32;
+    for (rel_path, source_hash) in rel_paths.into_iter().zip(source_hashes) {
+        let source = repo_config.source_path(&rel_path);
+        // The source may have changed since it was hashed
+        let source_hash = source_hash.and_then(|hashed| hashed.current(&source));
+        if let Some(budget) = filter.new_file_budget.as_mut() {
+            if !output_dir
+                .join(repo_config.mirror_key(&rel_path, manifest))
+                .exists()
+            {
+                if *budget == 0 {
+                    result.deferred += 1;
+                    continue;
+                }
+                *budget -= 1;
+            }
+        }
+        processed += 1;
+        let outcome = if matcher::content_excluded(
+            &source,
+            repo_config.skip_if_matches.as_ref(),
+            repo_config.mirror_binary,
+        ) {
+            result.skipped += 1;
+            match linker::untrack_source(&repo_config.mirror_subdir, &source, manifest, output_dir)
+            {
+                Ok(true) => result.pruned += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::error!("Failed to remove skipped {}: {}", rel_path, e);
+                    result.errors += 1;
+                }
+            }
+            Some(SyncOutcome::Skipped)
+        } else {
+            let manifest_rel = repo_config.mirror_key(&rel_path, manifest);
+            let mirror = output_dir.join(&manifest_rel);
+            let noted_before = manifest.last_conflict().map(|(_, at)| at);
 
-            if entry.file_type().is_dir() {
-                matcher::should_descend(&rel_str, &repo_config.exclude)
-            } else {
-                true
+            match sync(&source, &mirror, manifest, &manifest_rel, source_hash) {
+                Ok(outcome) => {
+                    match outcome {
+                        SyncOutcome::Copied => result.created += 1,
+                        SyncOutcome::AlreadyInSync | SyncOutcome::Claimed => {
+                            result.already_existed += 1
+                        }
+                        SyncOutcome::Skipped => result.skipped += 1,
+                        SyncOutcome::Merged => result.merged += 1,
+                        SyncOutcome::Conflict => {
+                            result.conflicts += 1;
+                            // Only a conflict noted by this sync has a backup file
+                            if manifest.last_conflict().map(|(_, at)| at) != noted_before {
+                                if let Some(conflict) = manifest.last_conflict_info() {
+                                    result.conflict_details.push(conflict.clone());
+                                }
+                            }
+                        }
+                    }
+                    Some(outcome)
+                }
+                Err(e) => {
+                    tracing::error!("Failed to sync {}: {}", rel_path, e);
+                    result.errors += 1;
+                    None
+                }
             }
+        };
+
+        progress(&ScanProgress {
+            repo: &repo_config.name,
+            rel_path: &rel_path,
+            processed,
+            outcome,
         });
+    }
+
+    // A source outside the repo was reached through a followed symlink; once
+    // no followed symlink reaches it, its mirror goes
+    let unreached: Vec<PathBuf> = manifest
+        .entries_for_repo(&repo_config.mirror_subdir)
+        .into_iter()
+        .filter(|(_, e)| !e.source.starts_with(&repo_config.path) && !reached.contains(&e.source))
+        .map(|(_, e)| e.source.clone())
+        .collect();
+    for source in unreached {
+        match linker::untrack_source(&repo_config.mirror_subdir, &source, manifest, output_dir) {
+            Ok(true) => result.pruned += 1,
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to prune {}: {}", source.display(), e);
+                result.errors += 1;
+            }
+        }
+    }
+
+    let result = finish_repo(repo_config, output_dir, manifest, label, result);
+    if repo_config.mirror_empty_dirs {
+        sync_empty_dirs(repo_config, output_dir);
+    }
+    result
+}
+
+/// A source's `content_hash`, with the size and modification time it had
+/// when hashing started.
+#[derive(Debug, Clone)]
+struct SourceHash {
+    hash: String,
+    len: u64,
+    modified: SystemTime,
+}
+
+impl SourceHash {
+    fn new(manifest: &Manifest, source: &Path) -> Option<Self> {
+        let meta = source.metadata().ok()?;
+        Some(Self {
+            len: meta.len(),
+            modified: meta.modified().ok()?,
+            hash: linker::content_hash(manifest, source).ok()?,
+        })
+    }
+
+    /// The hash, if `source` still has the size and modification time it
+    /// had when hashed; `None` once it may have changed.
+    fn current(self, source: &Path) -> Option<String> {
+        let meta = source.metadata().ok()?;
+        (meta.len() == self.len && meta.modified().ok()? == self.modified).then_some(self.hash)
+    }
+}
+
+/// `content_hash` of each source whose mirror already exists, worked out on
+/// every core. New files are hashed as they're copied, and a hash that fails
+/// is left to `sync_file`, which hashes again and reports the error.
+fn hash_sources(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &Manifest,
+    rel_paths: &[String],
+) -> Vec<Option<SourceHash>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_len = rel_paths.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = rel_paths
+            .chunks(chunk_len)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|rel_path| {
+                            let source = repo_config.source_path(rel_path);
+                            let mirror =
+                                output_dir.join(repo_config.mirror_key(rel_path, manifest));
+                            // Only regular files: hashing a fifo blocks forever
+                            if !source.is_file() || !mirror.is_file() {
+                                return None;
+                            }
+                            SourceHash::new(manifest, &source)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("hashing thread panicked"))
+            .collect()
+    })
+}
+
+/// Match the folders of a `mirror_empty_dirs` repo: give every source
+/// directory the patterns descend into a mirror directory, even one holding
+/// no mirrored files, and remove empty mirror directories whose source
+/// directory is gone or excluded.
+pub fn sync_empty_dirs(repo_config: &RepoConfig, output_dir: &Path) {
+    sync_empty_dirs_under(repo_config, output_dir, &repo_config.source_root());
+}
+
+/// [`sync_empty_dirs`] for the source directory `dir` and what's below it
+/// only, e.g. one the watcher saw created or deleted.
+pub fn sync_empty_dirs_under(repo_config: &RepoConfig, output_dir: &Path, dir: &Path) {
+    let source_root = repo_config.source_root();
+    let Ok(top) = dir.strip_prefix(&source_root) else {
+        return;
+    };
+    let mirror_root = output_dir.join(&repo_config.mirror_subdir);
+    let source_dirs: HashSet<PathBuf> = if dir.is_dir() && is_mirrored_path(repo_config, dir) {
+        WalkDir::new(dir)
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.file_type().is_dir() && is_mirrored_dir(repo_config, entry.path())
+            })
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                e.path()
+                    .strip_prefix(&source_root)
+                    .ok()
+                    .filter(|rel| !rel.as_os_str().is_empty())
+                    .map(Path::to_path_buf)
+            })
+            .collect()
+    } else {
+        HashSet::new()
+    };
+
+    for rel in &source_dirs {
+        create_mirror_dir(&mirror_root.join(rel));
+    }
+
+    let stale: Vec<PathBuf> = WalkDir::new(mirror_root.join(top))
+        .contents_first(true)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path() != mirror_root && e.file_type().is_dir())
+        .filter(|e| {
+            e.path()
+                .strip_prefix(&mirror_root)
+                .is_ok_and(|rel| !source_dirs.contains(rel))
+        })
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    for dir in stale {
+        if linker::is_dir_empty(&dir) && std::fs::remove_dir(&dir).is_ok() {
+            debug!("Removed empty mirror dir: {}", dir.display());
+        }
+    }
+}
+
+/// Give the source directory `dir` of a `mirror_empty_dirs` repo back its
+/// mirror directory, which pruning the last file in it removed. Unlike
+/// [`sync_empty_dirs_under`] nothing below `dir` is visited.
+pub fn restore_empty_dir(repo_config: &RepoConfig, output_dir: &Path, dir: &Path) {
+    let Ok(rel) = dir.strip_prefix(repo_config.source_root()) else {
+        return;
+    };
+    if rel.as_os_str().is_empty() || !dir.is_dir() || !is_mirrored_path(repo_config, dir) {
+        return;
+    }
+    create_mirror_dir(&output_dir.join(&repo_config.mirror_subdir).join(rel));
+}
+
+fn create_mirror_dir(mirror_dir: &Path) {
+    if mirror_dir.is_dir() {
+        return;
+    }
+    match std::fs::create_dir_all(mirror_dir) {
+        Ok(()) => debug!("Mirrored empty dir: {}", mirror_dir.display()),
+        Err(e) => tracing::error!("Failed to create {}: {}", mirror_dir.display(), e),
+    }
+}
+
+/// [`is_mirrored_dir`] for `dir` and each of its parents below the source
+/// root, for a directory not reached by walking down from the root.
+fn is_mirrored_path(repo_config: &RepoConfig, dir: &Path) -> bool {
+    let source_root = repo_config.source_root();
+    dir.ancestors()
+        .take_while(|d| d.starts_with(&source_root) && *d != source_root)
+        .all(|d| is_mirrored_dir(repo_config, d))
+}
+
+/// Whether the scan descends into the source directory `dir`: it's within
+/// `max_depth`, outside output and state dirs, and not excluded.
+fn is_mirrored_dir(repo_config: &RepoConfig, dir: &Path) -> bool {
+    if repo_config.in_guarded_dir(dir) {
+        return false;
+    }
+    dir.strip_prefix(&repo_config.path).is_ok_and(|rel| {
+        let rel = rel.to_string_lossy();
+        repo_config.within_depth(&rel)
+            && matcher::should_descend(&rel, &repo_config.exclude, &repo_config.include)
+    })
+}
+
+/// Walk a repo's source root and call `visit` with the repo-relative path of
+/// every regular file the patterns mirror, and that git tracks when the repo
+/// sets `git_tracked_only`. Excluded directories and output or state dirs
+/// inside the repo are never entered, nor are symlinks unless the repo sets
+/// `follow_symlinks`.
+///
+/// With `follow_symlinks`, returns the canonical path of every file visited;
+/// a file reached through several paths is visited only once. Otherwise the
+/// set is empty.
+fn walk_mirrored(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    mut visit: impl FnMut(String),
+) -> HashSet<PathBuf> {
+    let repo_path = &repo_config.path;
+    let source_root = repo_config.source_root();
+    let follow = repo_config.follow_symlinks;
+    let tracked = repo_config.tracked_files();
+    let mut reached = HashSet::new();
+
+    // Output dirs and the state dir must never be walked when they sit
+    // inside the source tree, or the mirror would be mirrored into itself.
+    // Unless symlinked dirs are followed, a canonical repo root joined with a
+    // relative path is already canonical.
+    let canonical_repo = repo_path
+        .canonicalize()
+        .unwrap_or_else(|_| repo_path.clone());
+    let canonical_output = output_dir.canonicalize().ok();
+
+    // Rel paths stay relative to the repo itself so patterns keep matching
+    // the same paths whether or not a `root` is set. Walkdir reports a
+    // followed symlink loop as an error, which ends that branch.
+    let mut walk = WalkDir::new(&source_root).follow_links(follow);
+    if let Some(max_depth) = repo_config.max_depth {
+        walk = walk.max_depth(max_depth);
+    }
+
+    let walker = walk.into_iter().filter_entry(|entry| {
+        if entry.path() == source_root {
+            return true;
+        }
+
+        let rel_path = entry.path().strip_prefix(repo_path).unwrap_or(entry.path());
+
+        if !entry.file_type().is_dir() {
+            return true;
+        }
+
+        let canonical_dir = if follow {
+            entry
+                .path()
+                .canonicalize()
+                .unwrap_or_else(|_| canonical_repo.join(rel_path))
+        } else {
+            canonical_repo.join(rel_path)
+        };
+        if canonical_output
+            .as_ref()
+            .is_some_and(|output| canonical_dir.starts_with(output))
+            || repo_config.in_guarded_dir(&canonical_dir)
+        {
+            debug!(
+                "Skipping output or state dir inside repo: {}",
+                entry.path().display()
+            );
+            return false;
+        }
+
+        matcher::should_descend(
+            &rel_path.to_string_lossy(),
+            &repo_config.exclude,
+            &repo_config.include,
+        )
+    });
 
     for entry in walker.filter_map(|e| e.ok()) {
         if entry.file_type().is_dir() {
             continue;
         }
 
-        // Skip symlinks in the source repo
-        if entry.path_is_symlink() {
+        // Skip symlinks in the source repo unless following them
+        if entry.path_is_symlink() && !follow {
+            continue;
+        }
+
+        // Skip fifos, sockets, and device files: reading them can block forever
+        if !entry.file_type().is_file() {
             continue;
         }
 
@@ -100,27 +972,76 @@ pub fn scan_repo(
         if !matcher::should_mirror(&rel_path, &repo_config.exclude, &repo_config.include) {
             continue;
         }
+        if tracked.as_ref().is_some_and(|t| !t.contains(&rel_path)) {
+            debug!("Not tracked by git: {}", rel_path);
+            continue;
+        }
+
+        if follow {
+            let Ok(canonical) = entry.path().canonicalize() else {
+                continue;
+            };
+            if !reached.insert(canonical) {
+                debug!("Already mirrored through another path: {}", rel_path);
+                continue;
+            }
+        }
+
+        visit(rel_path);
+    }
+    reached
+}
 
-        let source = repo_path.join(&rel_path);
-        let manifest_rel = format!("{}/{}", repo_config.name, rel_path);
-        let mirror = output_dir.join(&manifest_rel);
+/// Keys and sources of a repo's entries whose source is still there but no
+/// longer mirrored: beyond `max_depth`, or left out by the patterns after an
+/// exclude, `.ulyssesignore`, or `include_hidden` change, or no longer
+/// tracked by git in a `git_tracked_only` repo.
+fn out_of_scope(repo_config: &RepoConfig, manifest: &Manifest) -> Vec<(String, PathBuf)> {
+    let tracked = repo_config.tracked_files();
+    manifest
+        .entries_for_repo(&repo_config.mirror_subdir)
+        .into_iter()
+        .filter(|(_, entry)| {
+            entry.source.is_file()
+                && entry
+                    .source
+                    .strip_prefix(&repo_config.path)
+                    .is_ok_and(|rel| {
+                        let rel = rel.to_string_lossy();
+                        !repo_config.within_depth(&rel)
+                            || !matcher::should_mirror(
+                                &rel,
+                                &repo_config.exclude,
+                                &repo_config.include,
+                            )
+                            || tracked.as_ref().is_some_and(|t| !t.contains(rel.as_ref()))
+                    })
+        })
+        .map(|(key, entry)| (key.clone(), entry.source.clone()))
+        .collect()
+}
 
-        match linker::sync_file(&source, &mirror, manifest, &manifest_rel, output_dir) {
-            Ok(SyncOutcome::Copied) => result.created += 1,
-            Ok(SyncOutcome::AlreadyInSync | SyncOutcome::Claimed) => result.already_existed += 1,
-            Ok(SyncOutcome::Skipped) => result.skipped += 1,
-            Ok(SyncOutcome::Merged) => result.merged += 1,
-            Ok(SyncOutcome::Conflict) => result.conflicts += 1,
+/// Prune stale entries using the manifest, then save it and log the totals.
+fn finish_repo(
+    repo_config: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    label: &str,
+    mut result: ScanResult,
+) -> ScanResult {
+    for (_, source) in out_of_scope(repo_config, manifest) {
+        match linker::untrack_source(&repo_config.mirror_subdir, &source, manifest, output_dir) {
+            Ok(true) => result.pruned += 1,
+            Ok(false) => {}
             Err(e) => {
-                tracing::error!("Failed to sync {}: {}", rel_path, e);
+                tracing::error!("Failed to prune {}: {}", source.display(), e);
                 result.errors += 1;
             }
         }
     }
 
-    // Prune stale entries using manifest
-    match linker::prune_stale(&repo_config.name, output_dir, manifest) {
-        Ok(pruned) => result.pruned = pruned,
+    match linker::prune_stale(&repo_config.mirror_subdir, output_dir, manifest) {
+        Ok(pruned) => result.pruned += pruned,
         Err(e) => {
             tracing::error!(
                 "Failed to prune stale entries for {}: {}",
@@ -131,13 +1052,18 @@ pub fn scan_repo(
         }
     }
 
+    if result.created + result.merged + result.conflicts + result.pruned > 0 {
+        manifest.mark_synced(&repo_config.name);
+    }
+
     if let Err(e) = manifest.save(output_dir) {
         tracing::error!("Failed to save manifest: {}", e);
         result.errors += 1;
     }
 
     info!(
-        "Scan complete for {}: {} created, {} existed, {} skipped, {} merged, {} conflicts, {} pruned, {} errors",
+        "{} complete for {}: {} created, {} existed, {} skipped, {} merged, {} conflicts, {} pruned, {} errors",
+        label,
         repo_config.name,
         result.created,
         result.already_existed,
@@ -180,19 +1106,390 @@ mod tests {
     }
 
     #[test]
-    fn test_full_scan_creates_copies() {
+    fn test_scan_loads_missing_manifest_with_state_options() {
         let tmp = TempDir::new().unwrap();
         let repo = tmp.path().join("my-repo");
         let output = tmp.path().join("output");
-        fs::create_dir(&repo).unwrap();
-
+        fs::create_dir_all(&repo).unwrap();
         fs::write(repo.join("README.md"), "hello").unwrap();
-        fs::create_dir(repo.join("docs")).unwrap();
-        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
-        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
 
-        let config = make_config(&repo, &output);
-        let mut manifests = make_manifests(&config);
+        let mut config = make_config(&repo, &output);
+        config.state.state_dir = Some(tmp.path().join("state"));
+        let mut manifests = HashMap::new();
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.created, 1);
+
+        // Saved under state_dir, not beside the mirror
+        let output = config.output_dir.clone();
+        assert_eq!(Manifest::load_in(&output, &config.state).unwrap().len(), 1);
+        assert_eq!(Manifest::load(&output).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_scan_strips_header_left_by_flat_inbox() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("doc.md"), "hello\n").unwrap();
+        let load = |repo_opts: &str| {
+            let toml = format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nflatten = true\n{repo_opts}",
+                output.display(),
+                repo.display()
+            );
+            let config_file = tmp.path().join("test-config.toml");
+            fs::write(&config_file, toml).unwrap();
+            config::load_config(Some(&config_file)).unwrap()
+        };
+
+        let manifests_for = |config: &Config| {
+            HashMap::from([(
+                output.clone(),
+                Manifest::load_in(&output, &config.state).unwrap(),
+            )])
+        };
+        let config = load("flat_inbox = true");
+        let mut manifests = manifests_for(&config);
+        full_scan(&config, &mut manifests);
+        let mirror = output.join("my-repo").join("doc.md");
+        assert!(fs::read_to_string(&mirror)
+            .unwrap()
+            .starts_with("<!-- source: doc.md -->"));
+
+        // Scanned without the service having stripped it on reload
+        let config = load("");
+        let mut manifests = manifests_for(&config);
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.errors, 0);
+        assert_eq!(fs::read_to_string(repo.join("doc.md")).unwrap(), "hello\n");
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "hello\n");
+    }
+
+    #[test]
+    fn test_scan_strips_keywords_left_by_output_format() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "# Guide\n").unwrap();
+        fs::write(repo.join("docs").join("notes.txt"), "notes\n").unwrap();
+        let load = |repo_opts: &str| {
+            let toml = format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n{repo_opts}",
+                output.display(),
+                repo.display()
+            );
+            let config_file = tmp.path().join("test-config.toml");
+            fs::write(&config_file, toml).unwrap();
+            config::load_config(Some(&config_file)).unwrap()
+        };
+        let manifests_for = |config: &Config| {
+            HashMap::from([(
+                output.clone(),
+                Manifest::load_in(&output, &config.state).unwrap(),
+            )])
+        };
+        let guide = output.join("my-repo").join("docs").join("guide.md");
+        let notes = output.join("my-repo").join("docs").join("notes.txt");
+
+        // Only Markdown gets keywords
+        let config = load("output_format = \"ulysses\"");
+        let mut manifests = manifests_for(&config);
+        full_scan(&config, &mut manifests);
+        assert_eq!(fs::read_to_string(&guide).unwrap(), "#docs\n# Guide\n");
+        assert_eq!(fs::read_to_string(&notes).unwrap(), "notes\n");
+
+        // Scanned without the service having stripped them on reload
+        let config = load("");
+        let mut manifests = manifests_for(&config);
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.errors, 0);
+        assert_eq!(
+            fs::read_to_string(repo.join("docs").join("guide.md")).unwrap(),
+            "# Guide\n"
+        );
+        assert_eq!(fs::read_to_string(&guide).unwrap(), "# Guide\n");
+    }
+
+    #[test]
+    fn test_source_hash_dropped_once_source_changes() {
+        let tmp = TempDir::new().unwrap();
+        let source = tmp.path().join("doc.md");
+        fs::write(&source, "hashed").unwrap();
+        let manifest = Manifest::empty();
+
+        let hashed = SourceHash::new(&manifest, &source).unwrap();
+        assert_eq!(
+            hashed.clone().current(&source),
+            Some(linker::content_hash(&manifest, &source).unwrap())
+        );
+
+        // Written between the parallel hash and the sync decision
+        fs::write(&source, "edited after hashing").unwrap();
+        assert_eq!(hashed.current(&source), None);
+    }
+
+    #[test]
+    fn test_scan_moves_mirror_named_after_symlink_target() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("target");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("doc.md"), "doc").unwrap();
+        let repo_link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&repo, &repo_link).unwrap();
+
+        // Mirrored under the target's name, as before repos were named
+        // after the link
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+        assert!(output.join("target").join("doc.md").exists());
+
+        let config = make_config(&repo_link, &output);
+        assert_eq!(config.repos[0].mirror_subdir, "link");
+        let result = full_scan(&config, &mut manifests);
+
+        assert_eq!(result.created, 0);
+        assert_eq!(result.already_existed, 1);
+        assert!(output.join("link").join("doc.md").exists());
+        assert!(!output.join("target").exists());
+        let manifest = &manifests[&config.output_dir];
+        assert_eq!(manifest.len(), 1);
+        assert!(manifest.get("link/doc.md").is_some());
+    }
+
+    #[test]
+    fn test_full_scan_follows_priority() {
+        let tmp = TempDir::new().unwrap();
+        let output = tmp.path().join("output");
+        let names = ["first", "second", "third"];
+        for name in names {
+            fs::create_dir(tmp.path().join(name)).unwrap();
+            fs::write(tmp.path().join(name).join("README.md"), name).unwrap();
+        }
+        let toml = format!(
+            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n\
+             [[repos]]\npath = \"{}\"\npriority = 5\n\n[[repos]]\npath = \"{}\"",
+            output.display(),
+            tmp.path().join(names[0]).display(),
+            tmp.path().join(names[1]).display(),
+            tmp.path().join(names[2]).display(),
+        );
+        let config_file = tmp.path().join("test-config.toml");
+        fs::write(&config_file, toml).unwrap();
+        let config = config::load_config(Some(&config_file)).unwrap();
+        let mut manifests = make_manifests(&config);
+
+        let mut order = Vec::new();
+        full_scan_with_progress(&config, &mut manifests, &mut |p| {
+            order.push(p.repo.to_string())
+        });
+
+        // The prioritized repo goes first; the rest keep config order
+        assert_eq!(order, vec!["second", "first", "third"]);
+    }
+
+    #[test]
+    fn test_initial_sync_limit_defers_new_files() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        for i in 0..5 {
+            fs::write(repo.join(format!("doc{i}.md")), format!("doc {i}")).unwrap();
+        }
+
+        let mut config = make_config(&repo, &output);
+        config.initial_sync_limit = Some(3);
+        let mut manifests = make_manifests(&config);
+
+        let first = full_scan(&config, &mut manifests);
+        assert_eq!(first.created, 3);
+        assert_eq!(first.deferred, 2);
+
+        // Files already mirrored don't count against the next scan's limit
+        let second = full_scan(&config, &mut manifests);
+        assert_eq!(second.created, 2);
+        assert_eq!(second.already_existed, 3);
+        assert_eq!(second.deferred, 0);
+        assert_eq!(fs::read_dir(output.join("my-repo")).unwrap().count(), 5);
+    }
+
+    #[test]
+    fn test_parallel_hash_matches_serial_scan() {
+        let tmp = TempDir::new().unwrap();
+        let mut runs = Vec::new();
+        for parallel_hash in [false, true] {
+            let root = tmp
+                .path()
+                .join(if parallel_hash { "parallel" } else { "serial" });
+            let repo = root.join("my-repo");
+            let output = root.join("output");
+            fs::create_dir_all(repo.join("docs")).unwrap();
+            for i in 0..200 {
+                fs::write(repo.join(format!("docs/doc{i}.md")), format!("doc {i}\n")).unwrap();
+            }
+            let mut config = make_config(&repo, &output);
+            config.parallel_hash = parallel_hash;
+            let mut manifests = make_manifests(&config);
+            let first = full_scan(&config, &mut manifests);
+
+            // Edits on the source side, the mirror side, and both
+            let mirror = output.join("my-repo/docs");
+            for i in 0..20 {
+                fs::write(
+                    repo.join(format!("docs/doc{i}.md")),
+                    format!("source {i}\n"),
+                )
+                .unwrap();
+            }
+            for i in 20..40 {
+                fs::write(mirror.join(format!("doc{i}.md")), format!("mirror {i}\n")).unwrap();
+            }
+            for i in 40..50 {
+                let path = format!("doc{i}.md");
+                fs::write(repo.join("docs").join(&path), format!("doc {i}\nsource\n")).unwrap();
+                fs::write(mirror.join(&path), format!("top\ndoc {i}\n")).unwrap();
+            }
+            fs::write(repo.join("docs/new.md"), "new\n").unwrap();
+            let second = full_scan(&config, &mut manifests);
+
+            let manifest = &manifests[&config.output_dir];
+            let mut hashes: Vec<(String, String)> = manifest
+                .entries_for_repo("my-repo")
+                .into_iter()
+                .map(|(key, entry)| (key.clone(), entry.hash.clone()))
+                .collect();
+            hashes.sort();
+            let contents: Vec<String> = hashes
+                .iter()
+                .map(|(key, _)| fs::read_to_string(output.join(key)).unwrap())
+                .collect();
+            runs.push((first, second, hashes, contents));
+        }
+
+        let (serial, parallel) = (&runs[0], &runs[1]);
+        for (s, p) in [(&serial.0, &parallel.0), (&serial.1, &parallel.1)] {
+            assert_eq!(
+                (
+                    s.created,
+                    s.already_existed,
+                    s.merged,
+                    s.conflicts,
+                    s.errors
+                ),
+                (
+                    p.created,
+                    p.already_existed,
+                    p.merged,
+                    p.conflicts,
+                    p.errors
+                )
+            );
+        }
+        assert_eq!(parallel.0.created, 200);
+        assert_eq!((parallel.1.created, parallel.1.merged), (41, 10));
+        assert_eq!(serial.2, parallel.2);
+        assert_eq!(serial.3, parallel.3);
+    }
+
+    #[test]
+    fn test_full_scan_since_skips_older_files() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("old.md"), "old").unwrap();
+        fs::write(repo.join("gone.md"), "gone").unwrap();
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+
+        // An edit dated a day ago, a new file, and a deletion
+        let day_ago = SystemTime::now() - std::time::Duration::from_secs(24 * 60 * 60);
+        fs::write(repo.join("old.md"), "edited").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(repo.join("old.md"))
+            .unwrap()
+            .set_modified(day_ago)
+            .unwrap();
+        fs::write(repo.join("new.md"), "new").unwrap();
+        fs::remove_file(repo.join("gone.md")).unwrap();
+
+        let since = SystemTime::now() - std::time::Duration::from_secs(60 * 60);
+        let result = full_scan_since(&config, &mut manifests, since);
+
+        // Only the recent file is synced, but deletions are still pruned
+        assert_eq!(result.created, 1);
+        assert_eq!(result.already_existed, 0);
+        assert_eq!(result.pruned, 1);
+        let mirror = output.join("my-repo");
+        assert!(mirror.join("new.md").exists());
+        assert!(!mirror.join("gone.md").exists());
+        assert_eq!(fs::read_to_string(mirror.join("old.md")).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_full_scan_reports_conflict_details() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("doc.md"), "original\n").unwrap();
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+
+        // The same line edited on both sides, the mirror more recently
+        let mirror = output.join("my-repo").join("doc.md");
+        fs::write(repo.join("doc.md"), "source edit\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(repo.join("doc.md"))
+            .unwrap()
+            .set_modified(SystemTime::now() - std::time::Duration::from_secs(60))
+            .unwrap();
+        fs::write(&mirror, "mirror edit\n").unwrap();
+
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.conflicts, 1);
+        assert_eq!(result.conflict_details.len(), 1);
+        let conflict = &result.conflict_details[0];
+        assert_eq!(conflict.rel_path, "my-repo/doc.md");
+        assert_eq!(conflict.winner, crate::manifest::ConflictWinner::Mirror);
+        assert_eq!(
+            conflict.conflict_file.parent(),
+            Some(config.repos[0].path.as_path())
+        );
+        assert_eq!(
+            fs::read_to_string(&conflict.conflict_file).unwrap(),
+            "source edit\n"
+        );
+
+        // Nothing new conflicts on the next scan
+        let result = full_scan(&config, &mut manifests);
+        assert!(result.conflict_details.is_empty());
+    }
+
+    #[test]
+    fn test_full_scan_creates_copies() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::create_dir(repo.join("docs")).unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
         let result = full_scan(&config, &mut manifests);
 
         assert_eq!(result.created, 2);
@@ -261,6 +1558,89 @@ mod tests {
         assert_eq!(result2.already_existed, 1);
     }
 
+    #[test]
+    fn test_estimate_scan_bytes() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide!").unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = make_config(&repo, &output);
+        let output_dir = config.output_dir.clone();
+        assert_eq!(estimate_scan_bytes(&config)[&output_dir], 5 + 6);
+
+        // Files already in the mirror don't count
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+        fs::write(repo.join("new.md"), "new").unwrap();
+        assert_eq!(estimate_scan_bytes(&config)[&output_dir], 3);
+
+        assert!(check_disk_space(&config).is_ok());
+    }
+
+    #[test]
+    fn test_scan_reports_progress() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        let mut events = Vec::new();
+        let result = full_scan_with_progress(&config, &mut manifests, &mut |p| {
+            events.push((p.repo.to_string(), p.processed, p.outcome));
+        });
+
+        assert_eq!(result.created, 2);
+        assert_eq!(events.len() as u32, result.created + result.already_existed);
+        assert!(events
+            .iter()
+            .all(|(repo, _, outcome)| repo == "my-repo" && *outcome == Some(SyncOutcome::Copied)));
+        let processed: Vec<u32> = events.iter().map(|(_, n, _)| *n).collect();
+        assert_eq!(processed, [1, 2]);
+
+        // A rescan reports the same files, now already in sync
+        events.clear();
+        full_scan_with_progress(&config, &mut manifests, &mut |p| {
+            events.push((p.repo.to_string(), p.processed, p.outcome));
+        });
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|(_, _, outcome)| *outcome == Some(SyncOutcome::AlreadyInSync)));
+    }
+
+    #[test]
+    fn test_scan_records_last_synced() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+
+        let config = make_config(&repo, &output);
+        let output = config.repos[0].output_dir.clone();
+        let mut manifests = make_manifests(&config);
+        assert!(manifests[&output].last_synced("my-repo").is_none());
+
+        full_scan(&config, &mut manifests);
+        let stamp = manifests[&output]
+            .last_synced("my-repo")
+            .unwrap()
+            .to_string();
+
+        // Saved by the scan, so it survives a reload
+        let loaded = Manifest::load(&output).unwrap();
+        assert_eq!(loaded.last_synced("my-repo"), Some(stamp.as_str()));
+    }
+
     #[test]
     fn test_scan_prunes_stale() {
         let tmp = TempDir::new().unwrap();
@@ -281,6 +1661,343 @@ mod tests {
         assert!(!output.join("my-repo").join("README.md").exists());
     }
 
+    #[test]
+    fn test_full_scan_writes_output_gitignore() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+
+        let mut config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+        assert!(!output.join(".gitignore").exists());
+
+        config.write_output_gitignore = true;
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.errors, 0);
+        let content = fs::read_to_string(output.join(".gitignore")).unwrap();
+        assert!(content.contains(".ulysses-link.d/"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_fifo() {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+
+        // A fifo whose name matches the include patterns; reading it would block
+        let fifo = repo.join("pipe.md");
+        let c_path = CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        let result = full_scan(&config, &mut manifests);
+
+        assert_eq!(result.created, 1);
+        assert_eq!(result.errors, 0);
+        assert!(!output.join("my-repo").join("pipe.md").exists());
+        let manifest = manifests.get(&config.repos[0].output_dir).unwrap();
+        assert!(manifest.get("my-repo/pipe.md").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_symlink_cycle_terminates() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+        std::os::unix::fs::symlink(&repo, repo.join("docs").join("loop")).unwrap();
+        std::os::unix::fs::symlink("..", repo.join("docs").join("up")).unwrap();
+
+        let config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        let result = full_scan(&config, &mut manifests);
+
+        assert_eq!(result.created, 2);
+        assert_eq!(result.errors, 0);
+        let manifest = manifests.get(&config.repos[0].output_dir).unwrap();
+        assert_eq!(manifest.entries_for_repo("my-repo").len(), 2);
+        assert!(!output.join("my-repo").join("docs").join("loop").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_mirrors_linked_docs() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let shared = tmp.path().join("shared");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::create_dir_all(&shared).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(shared.join("style.md"), "shared style guide").unwrap();
+        std::os::unix::fs::symlink(shared.join("style.md"), repo.join("docs").join("style.md"))
+            .unwrap();
+        std::os::unix::fs::symlink(&repo, repo.join("docs").join("loop")).unwrap();
+        let linked_mirror = output.join("my-repo").join("docs").join("style.md");
+
+        let mut config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.created, 1);
+        assert!(!linked_mirror.exists());
+
+        config.set_follow_symlinks();
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.created, 1);
+        assert_eq!(result.errors, 0);
+        assert_eq!(
+            fs::read_to_string(&linked_mirror).unwrap(),
+            "shared style guide"
+        );
+        let manifest = manifests.get(&config.repos[0].output_dir).unwrap();
+        assert_eq!(
+            manifest.get("my-repo/docs/style.md").unwrap().source,
+            shared.join("style.md").canonicalize().unwrap()
+        );
+        assert_eq!(manifest.entries_for_repo("my-repo").len(), 2);
+
+        // Removing the link prunes the mirror though its target remains
+        fs::remove_file(repo.join("docs").join("style.md")).unwrap();
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.pruned, 1);
+        assert!(!linked_mirror.exists());
+        assert!(shared.join("style.md").exists());
+    }
+
+    #[test]
+    fn test_git_tracked_only_skips_untracked_files() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("tracked.md"), "tracked").unwrap();
+        fs::write(repo.join("scratch.md"), "scratch").unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(args)
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["add", "tracked.md"]);
+
+        let mut config = make_config(&repo, &output);
+        config.set_git_tracked_only();
+        let mut manifests = make_manifests(&config);
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.created, 1);
+        assert!(output.join("my-repo/tracked.md").exists());
+        assert!(!output.join("my-repo/scratch.md").exists());
+
+        // Once tracked, the scratch file is mirrored by the next scan
+        git(&["add", "scratch.md"]);
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.created, 1);
+        assert!(output.join("my-repo/scratch.md").exists());
+
+        // Untracking it again prunes the mirror, even without a full scan
+        git(&["rm", "-q", "--cached", "scratch.md"]);
+        let result = full_prune(&config, &mut manifests);
+        assert_eq!(result.pruned, 1);
+        assert!(!output.join("my-repo/scratch.md").exists());
+        assert!(repo.join("scratch.md").exists());
+        assert!(output.join("my-repo/tracked.md").exists());
+    }
+
+    #[test]
+    fn test_mirror_empty_dirs() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs").join("drafts")).unwrap();
+        fs::create_dir_all(repo.join("node_modules").join("empty")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        let drafts_mirror = output.join("my-repo").join("docs").join("drafts");
+
+        let mut config = make_config(&repo, &output);
+        let mut manifests = make_manifests(&config);
+        full_scan(&config, &mut manifests);
+        assert!(!drafts_mirror.exists());
+
+        config.repos[0].mirror_empty_dirs = true;
+        full_scan(&config, &mut manifests);
+        assert!(drafts_mirror.is_dir());
+        assert!(linker::is_dir_empty(&drafts_mirror));
+        // Excluded dirs stay unmirrored
+        assert!(!output.join("my-repo").join("node_modules").exists());
+
+        // A file in the dir takes over, and its delete leaves the group
+        fs::write(repo.join("docs/drafts/idea.md"), "idea").unwrap();
+        full_scan(&config, &mut manifests);
+        assert!(drafts_mirror.join("idea.md").exists());
+        fs::remove_file(repo.join("docs/drafts/idea.md")).unwrap();
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!(result.pruned, 1);
+        assert!(drafts_mirror.is_dir());
+
+        // A dir holding only unmatched files is still a group
+        fs::create_dir_all(repo.join("docs/assets")).unwrap();
+        fs::write(repo.join("docs/assets/.gitkeep"), "").unwrap();
+        full_scan(&config, &mut manifests);
+        assert!(output.join("my-repo/docs/assets").is_dir());
+
+        // Deleting the source dir removes the mirror dir; its parent stays
+        fs::remove_dir(repo.join("docs/drafts")).unwrap();
+        full_scan(&config, &mut manifests);
+        assert!(!drafts_mirror.exists());
+        assert!(output.join("my-repo").join("docs").is_dir());
+
+        // The watcher's narrower passes only touch the given dir
+        let repo_config = &config.repos[0];
+        let repo_path = repo_config.path.clone();
+        fs::create_dir_all(repo_path.join("notes/old")).unwrap();
+        fs::create_dir_all(repo_path.join("other")).unwrap();
+        sync_empty_dirs_under(repo_config, &output, &repo_path.join("notes"));
+        assert!(output.join("my-repo/notes/old").is_dir());
+        assert!(!output.join("my-repo/other").exists());
+        fs::remove_dir_all(repo_path.join("notes")).unwrap();
+        sync_empty_dirs_under(repo_config, &output, &repo_path.join("notes"));
+        assert!(!output.join("my-repo/notes").exists());
+
+        fs::remove_dir(output.join("my-repo/docs/assets")).unwrap();
+        restore_empty_dir(repo_config, &output, &repo_path.join("docs/assets"));
+        assert!(output.join("my-repo/docs/assets").is_dir());
+        restore_empty_dir(repo_config, &output, &repo_path.join("node_modules/empty"));
+        assert!(!output.join("my-repo/node_modules").exists());
+    }
+
+    #[test]
+    fn test_group_plists_survive_sync_and_prune() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("docs/guide.md"), "guide").unwrap();
+        // A stray group plist committed to the repo, which the patterns match
+        fs::write(
+            repo.join("docs/.Ulysses-Group.plist"),
+            "<plist>repo</plist>",
+        )
+        .unwrap();
+        let config_file = tmp.path().join("test-config.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\ninclude_hidden = true\nprotect = []\n\n[[repos]]\npath = \"{}\"\ninclude = [\"*.plist\"]",
+                output.display(),
+                repo.display()
+            ),
+        )
+        .unwrap();
+        let config = config::load_config(Some(&config_file)).unwrap();
+        let mut manifests = make_manifests(&config);
+
+        full_scan(&config, &mut manifests);
+        let mirror_root = output.join("my-repo");
+        assert!(mirror_root.join("docs/guide.md").exists());
+        assert!(!mirror_root.join("docs/.Ulysses-Group.plist").exists());
+
+        // Ulysses writes its group metadata once the folder is added
+        let plists = [
+            mirror_root.join(".Ulysses-Group.plist"),
+            mirror_root.join("docs/.Ulysses-Group.plist"),
+        ];
+        for plist in &plists {
+            fs::write(plist, "<plist>order</plist>").unwrap();
+        }
+
+        // Deleting the group's only file prunes it, and pruning keeps the
+        // group folder holding the plist
+        fs::remove_file(repo.join("docs/guide.md")).unwrap();
+        fs::write(repo.join("NEW.md"), "new").unwrap();
+        let result = full_scan(&config, &mut manifests);
+        assert_eq!((result.created, result.pruned), (1, 1));
+        full_prune(&config, &mut manifests);
+        for plist in &plists {
+            assert_eq!(fs::read_to_string(plist).unwrap(), "<plist>order</plist>");
+        }
+        let manifest = &manifests[&config.output_dir];
+        assert!(manifest.get("my-repo/docs/.Ulysses-Group.plist").is_none());
+    }
+
+    #[test]
+    fn test_scan_skips_output_dir_inside_repo() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        fs::create_dir(&repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+
+        let config = make_config(&repo, &tmp.path().join("output"));
+        let nested_output = repo.join("mirror");
+        fs::create_dir_all(nested_output.join("my-repo")).unwrap();
+        fs::write(nested_output.join("my-repo").join("stale.md"), "old").unwrap();
+
+        let mut manifest = Manifest::empty();
+        let result = scan_repo(&config.repos[0], &nested_output, &mut manifest);
+
+        assert_eq!(result.created, 1);
+        assert!(manifest.get("my-repo/README.md").is_some());
+        assert!(manifest.get("my-repo/mirror/my-repo/stale.md").is_none());
+        assert!(!nested_output.join("my-repo").join("mirror").exists());
+    }
+
+    #[test]
+    fn test_scan_respects_max_depth() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("my-repo");
+        let output = tmp.path().join("output");
+        fs::create_dir_all(repo.join("docs").join("deep")).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+        fs::write(repo.join("docs").join("deep").join("notes.md"), "notes").unwrap();
+
+        let mut config = make_config(&repo, &output);
+        config.repos[0].max_depth = Some(2);
+        let mut manifests = make_manifests(&config);
+        let result = full_scan(&config, &mut manifests);
+
+        assert_eq!(result.created, 2);
+        assert!(output
+            .join("my-repo")
+            .join("docs")
+            .join("guide.md")
+            .exists());
+        assert!(!output.join("my-repo").join("docs").join("deep").exists());
+
+        // Lowering the limit prunes mirrors that are now too deep, in a
+        // prune-only pass as in a scan
+        config.repos[0].max_depth = Some(1);
+        let result = full_prune(&config, &mut manifests);
+        assert_eq!(result.pruned, 1);
+        assert!(!output
+            .join("my-repo")
+            .join("docs")
+            .join("guide.md")
+            .exists());
+        assert!(output.join("my-repo").join("README.md").exists());
+        assert!(repo.join("docs").join("guide.md").exists());
+        let manifest = &manifests[&config.output_dir];
+        assert!(manifest.get("my-repo/docs/guide.md").is_none());
+    }
+
     #[test]
     fn test_scan_missing_repo() {
         let tmp = TempDir::new().unwrap();
@@ -296,9 +2013,31 @@ mod tests {
                 let b = ignore::gitignore::GitignoreBuilder::new("/");
                 b.build().unwrap()
             },
-            include: globset::GlobSetBuilder::new().build().unwrap(),
+            include: matcher::IncludeSet::default(),
+            exclude_patterns: vec![],
             include_patterns: vec![],
+            files: vec![],
             output_dir: output.clone(),
+            extra_outputs: Vec::new(),
+            branch: None,
+            mirror_subdir: "deleted-repo".into(),
+            enabled: true,
+            priority: 0,
+            mirror_poll_interval: None,
+            follow_symlinks: false,
+            mirror_empty_dirs: false,
+            output_format: config::OutputFormat::Plain,
+            git_tracked_only: false,
+            guarded_dirs: Vec::new(),
+            direction: linker::SyncDirection::Bidirectional,
+            read_only_source: false,
+            max_depth: None,
+            include_hidden: false,
+            skip_if_matches: None,
+            mirror_binary: false,
+            flatten: None,
+            flat_inbox: false,
+            root: None,
         };
 
         let mut manifest = Manifest::load(&output).unwrap();
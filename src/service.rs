@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Context, Result};
@@ -16,17 +16,86 @@ fn binary_path() -> PathBuf {
     std::env::current_exe().expect("Failed to determine binary path")
 }
 
+/// Where the service writes its logs: the configured `log_dir`, or
+/// `~/Library/Logs/ulysses-link`.
 #[cfg(target_os = "macos")]
-fn log_dir() -> PathBuf {
-    let dir = dirs::home_dir()
-        .expect("Failed to determine home directory")
-        .join("Library")
-        .join("Logs")
-        .join("ulysses-link");
+fn service_log_dir(configured: Option<&Path>) -> PathBuf {
+    let dir = configured.map(Path::to_path_buf).unwrap_or_else(|| {
+        dirs::home_dir()
+            .expect("Failed to determine home directory")
+            .join("Library")
+            .join("Logs")
+            .join("ulysses-link")
+    });
     std::fs::create_dir_all(&dir).ok();
     dir
 }
 
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn stdout_log(log_dir: &Path) -> PathBuf {
+    log_dir.join("ulysses-link.stdout.log")
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn stderr_log(log_dir: &Path) -> PathBuf {
+    log_dir.join("ulysses-link.stderr.log")
+}
+
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn print_log_file(log_file: &Path, repo_name: Option<&str>) -> Result<()> {
+    if log_file.exists() {
+        let content = std::fs::read_to_string(log_file).context("Failed to read log file")?;
+        if content.is_empty() {
+            println!("Log file is empty: {}", log_file.display());
+        } else {
+            print_log_lines(&content, repo_name);
+        }
+    } else {
+        println!("No log file found at {}", log_file.display());
+    }
+    Ok(())
+}
+
+/// Print log text, only the lines naming `repo_name` when given.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn print_log_lines(content: &str, repo_name: Option<&str>) {
+    match repo_name {
+        Some(name) => content
+            .lines()
+            .filter(|line| mentions_repo(line, name))
+            .for_each(|line| println!("{line}")),
+        None => print!("{content}"),
+    }
+}
+
+/// Print the last 10 lines of log text (of those naming `repo_name`, when
+/// given), if it has any.
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+fn print_recent_log_lines(content: &str, repo_name: Option<&str>) {
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|line| repo_name.is_none_or(|name| mentions_repo(line, name)))
+        .collect();
+    if !lines.is_empty() {
+        let start = lines.len().saturating_sub(10);
+        println!("\nRecent logs:");
+        for line in &lines[start..] {
+            println!("  {line}");
+        }
+    }
+}
+
+/// Whether a log line names the repo `name` as a whole word, so `wiki`
+/// doesn't match lines about `wiki-archive`.
+pub fn mentions_repo(line: &str, name: &str) -> bool {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+    line.match_indices(name).any(|(at, _)| {
+        let before = line[..at].chars().next_back();
+        let after = line[at + name.len()..].chars().next();
+        !before.is_some_and(is_name_char) && !after.is_some_and(is_name_char)
+    })
+}
+
 pub fn install_service(config: &Config) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
@@ -75,59 +144,59 @@ pub fn uninstall_service() -> Result<()> {
     }
 }
 
-pub fn print_logs() -> Result<()> {
+/// Print the service logs. `log_dir` is the configured log directory; without
+/// one, logs come from the platform default (journald on Linux). With
+/// `repo_name`, only lines naming that repo are printed.
+pub fn print_logs(log_dir: Option<&Path>, repo_name: Option<&str>) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
-        let log_file = log_dir().join("ulysses-link.stdout.log");
-        if log_file.exists() {
-            let content = std::fs::read_to_string(&log_file).context("Failed to read log file")?;
-            if content.is_empty() {
-                println!("Log file is empty: {}", log_file.display());
-            } else {
-                print!("{content}");
-            }
-        } else {
-            println!("No log file found at {}", log_file.display());
-        }
-        Ok(())
+        print_log_file(&stdout_log(&service_log_dir(log_dir)), repo_name)
     }
 
     #[cfg(target_os = "linux")]
     {
+        if let Some(dir) = log_dir {
+            return print_log_file(&stdout_log(dir), repo_name);
+        }
         let output = Command::new("journalctl")
             .args(["--user", "-u", SYSTEMD_UNIT_NAME, "--no-pager"])
             .output()
             .context("Failed to run journalctl")?;
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+        print_log_lines(&String::from_utf8_lossy(&output.stdout), repo_name);
         Ok(())
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     {
+        let _ = (log_dir, repo_name);
         println!("Log viewing is not supported on this platform.");
         Ok(())
     }
 }
 
-pub fn print_status() -> Result<()> {
+/// Print whether the service is running, followed by its recent logs (only
+/// those naming `repo_name`, when given).
+pub fn print_status(log_dir: Option<&Path>, repo_name: Option<&str>) -> Result<()> {
     #[cfg(target_os = "macos")]
     {
-        status_launchd()
+        status_launchd(log_dir, repo_name)
     }
 
     #[cfg(target_os = "linux")]
     {
-        status_systemd()
+        status_systemd(log_dir, repo_name)
     }
 
     #[cfg(target_os = "windows")]
     {
+        let _ = (log_dir, repo_name);
         println!("Check Windows Task Scheduler for 'ulysses-link' task status.");
         Ok(())
     }
 
     #[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
     {
+        let _ = (log_dir, repo_name);
         println!("Unsupported platform");
         Ok(())
     }
@@ -185,6 +254,77 @@ pub fn restart_service() -> Result<()> {
     }
 }
 
+/// Stop the background service, leaving it installed, so state it holds in
+/// memory can't be written back while files are changed under it.
+pub fn stop_service() -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let uid = unsafe { libc::getuid() };
+        let status = Command::new("launchctl")
+            .args(["bootout", &format!("gui/{uid}/{LAUNCHD_LABEL}")])
+            .status()
+            .context("Failed to run launchctl bootout")?;
+        if !status.success() {
+            anyhow::bail!("launchctl bootout failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let status = Command::new("systemctl")
+            .args(["--user", "stop", SYSTEMD_UNIT_NAME])
+            .status()
+            .context("Failed to stop systemd unit")?;
+        if !status.success() {
+            anyhow::bail!("systemctl stop failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        anyhow::bail!("Service stop not supported on this platform")
+    }
+}
+
+/// Start the installed background service again after `stop_service`.
+pub fn start_service() -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let uid = unsafe { libc::getuid() };
+        let status = Command::new("launchctl")
+            .args([
+                "bootstrap",
+                &format!("gui/{uid}"),
+                &plist_path().to_string_lossy(),
+            ])
+            .status()
+            .context("Failed to run launchctl bootstrap")?;
+        if !status.success() {
+            anyhow::bail!("launchctl bootstrap failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let status = Command::new("systemctl")
+            .args(["--user", "start", SYSTEMD_UNIT_NAME])
+            .status()
+            .context("Failed to start systemd unit")?;
+        if !status.success() {
+            anyhow::bail!("systemctl start failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        anyhow::bail!("Service start not supported on this platform")
+    }
+}
+
 /// Send SIGHUP to the running service to trigger a config reload.
 pub fn send_reload_signal() -> Result<()> {
     #[cfg(target_os = "macos")]
@@ -218,6 +358,48 @@ pub fn send_reload_signal() -> Result<()> {
     }
 }
 
+/// Send SIGUSR2 to the running service to trigger a full rescan.
+pub fn send_rescan_signal() -> Result<()> {
+    #[cfg(target_os = "macos")]
+    {
+        let output = Command::new("launchctl")
+            .args([
+                "kill",
+                "SIGUSR2",
+                &format!("gui/{}/{LAUNCHD_LABEL}", unsafe { libc::getuid() }),
+            ])
+            .output()
+            .context("Failed to send SIGUSR2 via launchctl")?;
+        if !output.status.success() {
+            anyhow::bail!("launchctl kill SIGUSR2 failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let status = Command::new("systemctl")
+            .args([
+                "--user",
+                "kill",
+                "--signal=SIGUSR2",
+                "--kill-whom=main",
+                SYSTEMD_UNIT_NAME,
+            ])
+            .status()
+            .context("Failed to signal systemd unit")?;
+        if !status.success() {
+            anyhow::bail!("systemctl kill --signal=SIGUSR2 failed");
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        anyhow::bail!("Rescan signal not supported on this platform")
+    }
+}
+
 // --- macOS launchd ---
 
 #[cfg(target_os = "macos")]
@@ -232,7 +414,7 @@ fn plist_path() -> PathBuf {
 #[cfg(target_os = "macos")]
 fn build_plist(config: &Config) -> String {
     let binary = binary_path();
-    let log = log_dir();
+    let log = service_log_dir(config.log_dir.as_deref());
     let config_path = config
         .config_path
         .as_deref()
@@ -269,9 +451,9 @@ fn build_plist(config: &Config) -> String {
     <key>KeepAlive</key>
     <true/>
     <key>StandardOutPath</key>
-    <string>{log}/ulysses-link.stdout.log</string>
+    <string>{stdout}</string>
     <key>StandardErrorPath</key>
-    <string>{log}/ulysses-link.stderr.log</string>
+    <string>{stderr}</string>
     <key>ProcessType</key>
     <string>Background</string>
     <key>Nice</key>
@@ -279,7 +461,8 @@ fn build_plist(config: &Config) -> String {
 </dict>
 </plist>
 "#,
-        log = log.display()
+        stdout = stdout_log(&log).display(),
+        stderr = stderr_log(&log).display(),
     )
 }
 
@@ -357,7 +540,9 @@ fn is_running_launchd() -> bool {
 }
 
 #[cfg(target_os = "macos")]
-fn status_launchd() -> Result<()> {
+fn status_launchd(log_dir: Option<&Path>, repo_name: Option<&str>) -> Result<()> {
+    use crate::color;
+
     if is_running_launchd() {
         let output = Command::new("launchctl")
             .args(["list"])
@@ -366,27 +551,23 @@ fn status_launchd() -> Result<()> {
         let stdout = String::from_utf8_lossy(&output.stdout);
         for line in stdout.lines() {
             if line.contains(LAUNCHD_LABEL) {
-                println!("Service is running: {}", line.trim());
+                anstream::println!(
+                    "{}: {}",
+                    color::paint(color::SUCCESS, "Service is running"),
+                    line.trim()
+                );
                 break;
             }
         }
     } else {
-        println!("Service is not running.");
-    }
-
-    let log_file = log_dir().join("ulysses-link.stdout.log");
-    if log_file.exists() {
-        let content = std::fs::read_to_string(&log_file).unwrap_or_default();
-        let lines: Vec<&str> = content.lines().collect();
-        if !lines.is_empty() {
-            let start = lines.len().saturating_sub(10);
-            println!("\nRecent logs:");
-            for line in &lines[start..] {
-                println!("  {line}");
-            }
-        }
+        anstream::println!("{}", color::paint(color::NOTICE, "Service is not running."));
     }
 
+    let log_file = stdout_log(&service_log_dir(log_dir));
+    print_recent_log_lines(
+        &std::fs::read_to_string(log_file).unwrap_or_default(),
+        repo_name,
+    );
     Ok(())
 }
 
@@ -410,6 +591,18 @@ fn build_unit(config: &Config) -> String {
         .as_deref()
         .map(|p| p.to_string_lossy().to_string())
         .unwrap_or_default();
+    // Without a configured log_dir, output goes to the journal
+    let log_output = config
+        .log_dir
+        .as_deref()
+        .map(|dir| {
+            format!(
+                "StandardOutput=append:{}\nStandardError=append:{}\n",
+                stdout_log(dir).display(),
+                stderr_log(dir).display()
+            )
+        })
+        .unwrap_or_default();
 
     format!(
         r#"[Unit]
@@ -421,7 +614,7 @@ Type=simple
 ExecStart={binary} run --config {config_path}
 Restart=always
 RestartSec=5
-
+{log_output}
 [Install]
 WantedBy=default.target
 "#,
@@ -437,6 +630,9 @@ fn install_systemd(config: &Config) -> Result<()> {
     if let Some(parent) = unit.parent() {
         std::fs::create_dir_all(parent)?;
     }
+    if let Some(dir) = &config.log_dir {
+        std::fs::create_dir_all(dir)?;
+    }
     std::fs::write(&unit, &content)?;
     info!("Wrote systemd unit to {}", unit.display());
 
@@ -484,7 +680,7 @@ fn is_running_systemd() -> bool {
 }
 
 #[cfg(target_os = "linux")]
-fn status_systemd() -> Result<()> {
+fn status_systemd(log_dir: Option<&Path>, repo_name: Option<&str>) -> Result<()> {
     let output = Command::new("systemctl")
         .args(["--user", "status", SYSTEMD_UNIT_NAME])
         .output()
@@ -498,18 +694,22 @@ fn status_systemd() -> Result<()> {
         }
     }
 
-    let log_output = Command::new("journalctl")
-        .args(["--user", "-u", SYSTEMD_UNIT_NAME, "-n", "10", "--no-pager"])
-        .output();
-    if let Ok(log_output) = log_output {
-        let log_text = String::from_utf8_lossy(&log_output.stdout);
-        if !log_text.trim().is_empty() {
-            println!("\nRecent logs:");
-            for line in log_text.lines() {
-                println!("  {line}");
+    let log_text = match log_dir {
+        Some(dir) => std::fs::read_to_string(stdout_log(dir)).unwrap_or_default(),
+        // A repo's recent lines may be further back than the last 10
+        None => {
+            let mut args = vec!["--user", "-u", SYSTEMD_UNIT_NAME, "--no-pager"];
+            if repo_name.is_none() {
+                args.extend(["-n", "10"]);
             }
+            Command::new("journalctl")
+                .args(args)
+                .output()
+                .map(|out| String::from_utf8_lossy(&out.stdout).into_owned())
+                .unwrap_or_default()
         }
-    }
+    };
+    print_recent_log_lines(&log_text, repo_name);
 
     Ok(())
 }
@@ -553,6 +753,19 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_mentions_repo() {
+        assert!(mentions_repo(
+            "INFO Debounced batch for wiki: 2 events",
+            "wiki"
+        ));
+        assert!(mentions_repo("Started watcher for wiki", "wiki"));
+        assert!(mentions_repo("Conflict detected for wiki/notes.md", "wiki"));
+        assert!(!mentions_repo("Started watcher for wiki-archive", "wiki"));
+        assert!(!mentions_repo("Started watcher for mywiki", "wiki"));
+        assert!(!mentions_repo("Periodic rescan", "wiki"));
+    }
+
     #[test]
     fn test_binary_path_resolves() {
         let path = binary_path();
@@ -560,19 +773,42 @@ mod tests {
         assert!(!path.as_os_str().is_empty());
     }
 
-    #[cfg(target_os = "macos")]
-    #[test]
-    fn test_plist_content_generation() {
-        let tmp = TempDir::new().unwrap();
-        let config = Config {
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    fn test_config(tmp: &TempDir) -> Config {
+        Config {
             output_dir: tmp.path().join("output"),
             repos: vec![],
+            missing_repos: vec![],
             debounce_seconds: 0.5,
+            max_batch: 10_000,
             log_level: "INFO".into(),
             rescan_interval: crate::config::RescanInterval::Auto,
+            rescan_jitter: 0.0,
             auto_upgrade: true,
+            write_output_gitignore: false,
+            bidirectional: true,
+            max_depth: None,
+            initial_sync_limit: None,
+            parallel_hash: false,
+            mirror_poll_interval: None,
+            include_hidden: false,
+            notify_on_conflict: false,
+            skip_if_matches: None,
+            mirror_binary: false,
+            mirror_ignore: Default::default(),
+            state: Default::default(),
+            log_dir: None,
+            pattern_warnings: vec![],
+            stats_interval: None,
             config_path: Some(tmp.path().join("config.yaml")),
-        };
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_plist_content_generation() {
+        let tmp = TempDir::new().unwrap();
+        let config = test_config(&tmp);
 
         let plist = build_plist(&config);
         assert!(plist.contains(LAUNCHD_LABEL));
@@ -582,23 +818,47 @@ mod tests {
         assert!(plist.contains("ulysses-link.stderr.log"));
     }
 
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn test_plist_uses_configured_log_dir() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = test_config(&tmp);
+        config.log_dir = Some(tmp.path().join("logs"));
+
+        let plist = build_plist(&config);
+        let expected = tmp.path().join("logs").join("ulysses-link.stdout.log");
+        assert!(plist.contains(&format!("<string>{}</string>", expected.display())));
+    }
+
     #[cfg(target_os = "linux")]
     #[test]
     fn test_unit_content_generation() {
         let tmp = TempDir::new().unwrap();
-        let config = Config {
-            output_dir: tmp.path().join("output"),
-            repos: vec![],
-            debounce_seconds: 0.5,
-            log_level: "INFO".into(),
-            rescan_interval: crate::config::RescanInterval::Auto,
-            auto_upgrade: true,
-            config_path: Some(tmp.path().join("config.yaml")),
-        };
+        let config = test_config(&tmp);
 
         let unit = build_unit(&config);
         assert!(unit.contains("ulysses-link"));
         assert!(unit.contains("[Service]"));
         assert!(unit.contains("Restart=always"));
+        assert!(!unit.contains("StandardOutput="));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_unit_uses_configured_log_dir() {
+        let tmp = TempDir::new().unwrap();
+        let mut config = test_config(&tmp);
+        config.log_dir = Some(tmp.path().join("logs"));
+
+        let unit = build_unit(&config);
+        let logs = tmp.path().join("logs");
+        assert!(unit.contains(&format!(
+            "StandardOutput=append:{}",
+            logs.join("ulysses-link.stdout.log").display()
+        )));
+        assert!(unit.contains(&format!(
+            "StandardError=append:{}",
+            logs.join("ulysses-link.stderr.log").display()
+        )));
     }
 }
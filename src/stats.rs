@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::config::Config;
+use crate::linker;
+use crate::manifest::Manifest;
+
+/// Summary of one output directory's mirror tree.
+#[derive(Debug, Default, Serialize)]
+pub struct OutputStats {
+    pub output_dir: PathBuf,
+    /// Files recorded in the manifest
+    pub tracked_files: u64,
+    /// Total size of tracked mirror files present on disk
+    pub total_bytes: u64,
+    /// Tracked file counts keyed by repo name
+    pub files_by_repo: BTreeMap<String, u64>,
+    /// Tracked file counts keyed by lowercase extension, `""` for none
+    pub files_by_extension: BTreeMap<String, u64>,
+    /// `*.conflict_*` files present in the mirror tree
+    pub conflict_files: u64,
+    /// Total size of the base cache
+    pub base_cache_bytes: u64,
+}
+
+/// Collect stats for every active output directory in the config.
+pub fn collect_all(config: &Config) -> anyhow::Result<Vec<OutputStats>> {
+    let mut all = Vec::new();
+    for output_dir in config.active_output_dirs() {
+        let manifest = Manifest::load_in(&output_dir, &config.state)?;
+        let repos: Vec<(&str, &str)> = config
+            .enabled_repos()
+            .filter(|r| r.output_dir == output_dir)
+            .map(|r| (r.name.as_str(), r.mirror_subdir.as_str()))
+            .collect();
+        all.push(collect(&output_dir, &manifest, &repos));
+    }
+    Ok(all)
+}
+
+/// Collect stats for one output directory. `repos` pairs each repo name with
+/// its mirror subdir.
+pub fn collect(output_dir: &Path, manifest: &Manifest, repos: &[(&str, &str)]) -> OutputStats {
+    let mut stats = OutputStats {
+        output_dir: output_dir.to_path_buf(),
+        ..Default::default()
+    };
+
+    for (name, mirror_subdir) in repos {
+        let entries = manifest.entries_for_repo(mirror_subdir);
+        stats
+            .files_by_repo
+            .insert(name.to_string(), entries.len() as u64);
+
+        for (rel_path, _) in entries {
+            stats.tracked_files += 1;
+            let extension = Path::new(rel_path.as_str())
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            *stats.files_by_extension.entry(extension).or_default() += 1;
+            if let Ok(meta) = output_dir.join(rel_path).symlink_metadata() {
+                stats.total_bytes += meta.len();
+            }
+        }
+    }
+
+    let base_cache = linker::base_cache_dir(manifest.state_root(output_dir));
+    for entry in WalkDir::new(output_dir)
+        .into_iter()
+        .filter_entry(|e| e.path() != base_cache)
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_type().is_file() && entry.file_name().to_string_lossy().contains(".conflict_")
+        {
+            stats.conflict_files += 1;
+        }
+    }
+
+    stats.base_cache_bytes = WalkDir::new(&base_cache)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum();
+
+    stats
+}
+
+/// Format a byte count for humans, e.g. `1.5 KB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linker::{sync_file, SyncDirection};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_counts() {
+        let repo = TempDir::new().unwrap();
+        let output = TempDir::new().unwrap();
+        fs::create_dir(repo.path().join("docs")).unwrap();
+        fs::write(repo.path().join("README.md"), "hello").unwrap();
+        fs::write(repo.path().join("docs").join("guide.MD"), "guide!").unwrap();
+        fs::write(repo.path().join("LICENSE"), "mit").unwrap();
+
+        let mut manifest = Manifest::empty();
+        for rel in ["README.md", "docs/guide.MD", "LICENSE"] {
+            let key = format!("my-repo/{rel}");
+            sync_file(
+                &repo.path().join(rel),
+                &output.path().join(&key),
+                &mut manifest,
+                &key,
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap();
+        }
+        fs::write(
+            output
+                .path()
+                .join("my-repo")
+                .join("README.md.conflict_20240101_000000"),
+            "old",
+        )
+        .unwrap();
+
+        let stats = collect(output.path(), &manifest, &[("my-repo", "my-repo")]);
+
+        assert_eq!(stats.tracked_files, 3);
+        assert_eq!(stats.total_bytes, 5 + 6 + 3);
+        assert_eq!(stats.files_by_repo["my-repo"], 3);
+        assert_eq!(stats.files_by_extension["md"], 2);
+        assert_eq!(stats.files_by_extension[""], 1);
+        assert_eq!(stats.conflict_files, 1);
+        assert_eq!(stats.base_cache_bytes, 5 + 6 + 3);
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+}
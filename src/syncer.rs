@@ -0,0 +1,144 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::{Context, Result};
+
+use crate::config::{Config, RepoConfig};
+use crate::linker::{self, SyncOutcome};
+use crate::manifest::Manifest;
+use crate::scanner::{self, ScanResult};
+
+/// Load the manifest of every active output dir.
+pub fn load_manifests(config: &Config) -> Result<HashMap<PathBuf, Manifest>> {
+    let mut manifests = HashMap::new();
+    for output_dir in config.active_output_dirs() {
+        let manifest = Manifest::load_in(&output_dir, &config.state)
+            .with_context(|| format!("Failed to load manifest from {}", output_dir.display()))?;
+        manifests.insert(output_dir, manifest);
+    }
+    Ok(manifests)
+}
+
+/// The manifest of `output_dir` in `manifests`, loaded with the config's
+/// state options the first time it's needed.
+pub fn manifest_in<'a>(
+    manifests: &'a mut HashMap<PathBuf, Manifest>,
+    output_dir: &Path,
+    config: &Config,
+) -> Result<&'a mut Manifest> {
+    match manifests.entry(output_dir.to_path_buf()) {
+        Entry::Occupied(entry) => Ok(entry.into_mut()),
+        Entry::Vacant(entry) => {
+            let manifest = Manifest::load_in(output_dir, &config.state).with_context(|| {
+                format!("Failed to load manifest from {}", output_dir.display())
+            })?;
+            Ok(entry.insert(manifest))
+        }
+    }
+}
+
+/// A config together with the manifests of its output dirs: everything
+/// needed to sync repos without running the service.
+///
+/// Scans save the manifests of the repos they touch as they go; single-file
+/// syncs only change the manifests in memory until `save` is called.
+pub struct Syncer {
+    config: Config,
+    manifests: HashMap<PathBuf, Manifest>,
+}
+
+impl Syncer {
+    /// Load the manifest of every output dir the config's enabled repos use.
+    pub fn new(config: Config) -> Result<Self> {
+        let manifests = load_manifests(&config)?;
+        Ok(Self { config, manifests })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn manifests(&self) -> &HashMap<PathBuf, Manifest> {
+        &self.manifests
+    }
+
+    /// The manifest for `output_dir`, if an enabled repo mirrors into it.
+    pub fn manifest(&self, output_dir: &Path) -> Option<&Manifest> {
+        self.manifests.get(output_dir)
+    }
+
+    /// Sync every enabled repo.
+    pub fn scan_all(&mut self) -> ScanResult {
+        scanner::full_scan(&self.config, &mut self.manifests)
+    }
+
+    /// Sync every enabled repo, skipping source files last modified before
+    /// `since`.
+    pub fn scan_since(&mut self, since: SystemTime) -> ScanResult {
+        scanner::full_scan_since(&self.config, &mut self.manifests, since)
+    }
+
+    /// Prune mirrors of deleted sources in every enabled repo.
+    pub fn prune_all(&mut self) -> ScanResult {
+        scanner::full_prune(&self.config, &mut self.manifests)
+    }
+
+    /// What `scan_all` would do, without writing anything.
+    pub fn plan_all(&self) -> ScanResult {
+        scanner::plan_scan(&self.config, &self.manifests)
+    }
+
+    /// What `prune_all` would remove, without writing anything.
+    pub fn plan_prune_all(&self) -> ScanResult {
+        scanner::plan_full_prune(&self.config, &self.manifests)
+    }
+
+    /// Sync the enabled repo called `name` into its output dir and extra
+    /// outputs.
+    pub fn scan_repo(&mut self, name: &str) -> Result<ScanResult> {
+        let repo = find_repo(&self.config, name)?;
+        let extras = repo.extra_output_repos();
+        let mut result = ScanResult::default();
+        for target in std::iter::once(repo).chain(&extras) {
+            let manifest = manifest_in(&mut self.manifests, &target.output_dir, &self.config)?;
+            result.merge(&scanner::scan_repo(target, &target.output_dir, manifest));
+        }
+        Ok(result)
+    }
+
+    /// Sync one file of the enabled repo called `repo_name` into its output
+    /// dir; extra outputs catch up on the next scan. `rel_path` is relative
+    /// to the repo path, as in the repo's patterns.
+    pub fn sync_file(&mut self, repo_name: &str, rel_path: &str) -> Result<SyncOutcome> {
+        let repo = find_repo(&self.config, repo_name)?;
+        let manifest = manifest_in(&mut self.manifests, &repo.output_dir, &self.config)?;
+        let key = repo.mirror_key(rel_path, manifest);
+        linker::sync_file(
+            &repo.source_path(rel_path),
+            &repo.output_dir.join(&key),
+            manifest,
+            &key,
+            &repo.output_dir,
+            repo.direction,
+        )
+    }
+
+    /// Write every manifest to its output dir.
+    pub fn save(&mut self) -> Result<()> {
+        for (output_dir, manifest) in &mut self.manifests {
+            manifest
+                .save(output_dir)
+                .with_context(|| format!("Failed to save manifest in {}", output_dir.display()))?;
+        }
+        Ok(())
+    }
+}
+
+fn find_repo<'a>(config: &'a Config, name: &str) -> Result<&'a RepoConfig> {
+    config
+        .enabled_repos()
+        .find(|r| r.name == name)
+        .with_context(|| format!("No enabled repo named '{name}'"))
+}
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tracing::{error, warn};
+
+use crate::config::Config;
+use crate::linker::{self, SyncDirection, SyncOutcome};
+use crate::manifest::Manifest;
+
+/// How a manifest entry disagrees with the files on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriftKind {
+    /// The source file is gone; the next sync prunes the entry
+    SourceMissing,
+    /// The mirror file is gone while its source remains
+    MirrorMissing,
+    /// The source or mirror content no longer matches the recorded hash,
+    /// e.g. after edits made while the service wasn't running
+    HashDrift,
+}
+
+impl DriftKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            DriftKind::SourceMissing => "source-missing",
+            DriftKind::MirrorMissing => "mirror-missing",
+            DriftKind::HashDrift => "hash-drift",
+        }
+    }
+}
+
+/// A manifest entry that doesn't match disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    /// Manifest key, relative to the output dir
+    pub rel_path: String,
+    pub kind: DriftKind,
+}
+
+/// Drift found in one output directory.
+#[derive(Debug)]
+pub struct OutputDrift {
+    pub output_dir: PathBuf,
+    pub drifts: Vec<Drift>,
+}
+
+/// Verify every active output directory in the config. With `fix`, drifted
+/// entries of enabled repos are re-synced in each repo's direction and the
+/// manifest saved; only the drift left afterwards is returned.
+pub fn verify_all(config: &Config, fix_drift: bool) -> anyhow::Result<Vec<OutputDrift>> {
+    let mut all = Vec::new();
+    for output_dir in config.active_output_dirs() {
+        let mut manifest = Manifest::load_in(&output_dir, &config.state)?;
+        let mut drifts = verify(&output_dir, &manifest);
+        if fix_drift && !drifts.is_empty() {
+            for repo in config
+                .enabled_repos()
+                .filter(|r| r.output_dir == output_dir)
+            {
+                let prefix = format!("{}/", repo.mirror_subdir);
+                let (owned, rest): (Vec<Drift>, Vec<Drift>) = drifts
+                    .into_iter()
+                    .partition(|d| d.rel_path.starts_with(&prefix));
+                drifts = rest;
+                drifts.extend(fix(owned, &output_dir, &mut manifest, repo.direction));
+            }
+            drifts.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+            manifest
+                .save(&output_dir)
+                .with_context(|| format!("Failed to save manifest in {}", output_dir.display()))?;
+        }
+        all.push(OutputDrift { output_dir, drifts });
+    }
+    Ok(all)
+}
+
+/// Check every entry of `manifest` against the files on disk, sorted by
+/// manifest key. Only reads files.
+pub fn verify(output_dir: &Path, manifest: &Manifest) -> Vec<Drift> {
+    let mut drifts: Vec<Drift> = manifest
+        .entries()
+        .filter_map(|(rel_path, entry)| {
+            let mirror = output_dir.join(rel_path);
+            let kind = if !entry.source.is_file() {
+                DriftKind::SourceMissing
+            } else if !mirror.is_file() {
+                DriftKind::MirrorMissing
+            } else if [&entry.source, &mirror].into_iter().any(|path| {
+                linker::content_hash(manifest, path).map_or(true, |hash| hash != entry.hash)
+            }) {
+                DriftKind::HashDrift
+            } else {
+                return None;
+            };
+            Some(Drift {
+                rel_path: rel_path.clone(),
+                kind,
+            })
+        })
+        .collect();
+    drifts.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    drifts
+}
+
+/// Re-sync the drifted entries `sync_file` can repair: hash drift and
+/// missing mirrors. Source-missing entries are left for the next sync to
+/// prune. Returns the drifts that remain, including ones whose re-sync
+/// ended in a conflict or was skipped.
+pub fn fix(
+    drifts: Vec<Drift>,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    direction: SyncDirection,
+) -> Vec<Drift> {
+    drifts
+        .into_iter()
+        .filter(|drift| {
+            if drift.kind == DriftKind::SourceMissing {
+                return true;
+            }
+            let Some(source) = manifest.get(&drift.rel_path).map(|e| e.source.clone()) else {
+                return true;
+            };
+            match linker::sync_file(
+                &source,
+                &output_dir.join(&drift.rel_path),
+                manifest,
+                &drift.rel_path,
+                output_dir,
+                direction,
+            ) {
+                // In sync only once the stale hash was refreshed
+                Ok(SyncOutcome::Copied | SyncOutcome::Merged | SyncOutcome::AlreadyInSync) => false,
+                Ok(outcome) => {
+                    warn!("Re-sync of {} ended as {:?}", drift.rel_path, outcome);
+                    true
+                }
+                Err(e) => {
+                    error!("Failed to re-sync {}: {}", drift.rel_path, e);
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_classifies_drift() {
+        let repo = TempDir::new().unwrap();
+        let output = TempDir::new().unwrap();
+        let mut manifest = Manifest::load(output.path()).unwrap();
+        for name in ["ok", "gone", "unmirrored", "edited", "conflicted"] {
+            let source = repo.path().join(format!("{name}.md"));
+            fs::write(&source, name).unwrap();
+            let rel_path = format!("my-repo/{name}.md");
+            linker::sync_file(
+                &source,
+                &output.path().join(&rel_path),
+                &mut manifest,
+                &rel_path,
+                output.path(),
+                SyncDirection::Bidirectional,
+            )
+            .unwrap();
+        }
+        assert!(verify(output.path(), &manifest).is_empty());
+
+        fs::remove_file(repo.path().join("gone.md")).unwrap();
+        fs::remove_file(output.path().join("my-repo/unmirrored.md")).unwrap();
+        fs::write(repo.path().join("edited.md"), "edited offline").unwrap();
+        fs::write(repo.path().join("conflicted.md"), "source edit").unwrap();
+        fs::write(output.path().join("my-repo/conflicted.md"), "mirror edit").unwrap();
+
+        let drifts = verify(output.path(), &manifest);
+        let kinds: Vec<(&str, DriftKind)> = drifts
+            .iter()
+            .map(|d| (d.rel_path.as_str(), d.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("my-repo/conflicted.md", DriftKind::HashDrift),
+                ("my-repo/edited.md", DriftKind::HashDrift),
+                ("my-repo/gone.md", DriftKind::SourceMissing),
+                ("my-repo/unmirrored.md", DriftKind::MirrorMissing),
+            ]
+        );
+
+        // Fixing re-syncs what it can and leaves the missing source; a
+        // re-sync that conflicted is still reported
+        let remaining = fix(
+            drifts,
+            output.path(),
+            &mut manifest,
+            SyncDirection::Bidirectional,
+        );
+        let remaining_paths: Vec<&str> = remaining.iter().map(|d| d.rel_path.as_str()).collect();
+        assert_eq!(
+            remaining_paths,
+            ["my-repo/conflicted.md", "my-repo/gone.md"]
+        );
+        assert_eq!(
+            fs::read_to_string(output.path().join("my-repo/edited.md")).unwrap(),
+            "edited offline"
+        );
+        assert!(output.path().join("my-repo/unmirrored.md").exists());
+        assert_eq!(verify(output.path(), &manifest), remaining[1..]);
+    }
+}
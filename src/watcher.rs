@@ -1,22 +1,25 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use notify::{
-    Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    Config as NotifyConfig, Event, EventKind, PollWatcher, RecommendedWatcher, RecursiveMode,
+    Watcher,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use walkdir::WalkDir;
 
-use crate::config::RepoConfig;
-use crate::linker::{self, SyncOutcome};
-use crate::manifest::Manifest;
-use crate::matcher;
+use crate::config::{canonicalize_existing_prefix, RepoConfig, REPO_IGNORE_FILE};
+use crate::linker::{self, SyncDirection, SyncOutcome};
+use crate::manifest::{self, Manifest};
+use crate::matcher::{self, MirrorIgnore};
+use crate::notifier::ConflictNotifier;
+use crate::pool::SyncPool;
+use crate::scanner;
 
 #[derive(Debug, Clone, PartialEq)]
 enum EventType {
@@ -27,63 +30,324 @@ enum EventType {
     DirCreated,
 }
 
+/// How often the pending-event count is logged while a batch is open.
+const PENDING_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a file written by sync is remembered, so the watcher on the
+/// other side can drop the events that write produces.
+const SELF_WRITE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Files sync just wrote, keyed by absolute path with the raw hash of the
+/// bytes on disk. Shared by every watcher of an engine, so a source-to-mirror
+/// copy made by a repo watcher doesn't come back as a mirror edit, and vice
+/// versa.
+#[derive(Debug, Default)]
+pub struct SelfWrites {
+    writes: Mutex<HashMap<PathBuf, (String, Instant)>>,
+}
+
+impl SelfWrites {
+    /// Remember both sides of the synced manifest entry at `rel_path`. Each
+    /// side is hashed as written, since the manifest hash is normalized and
+    /// leaves out decorations like flat_inbox headers or Ulysses keywords.
+    fn record_synced(&self, manifest: &Manifest, rel_path: &str, output_dir: &Path) {
+        let Some(entry) = manifest.get(rel_path) else {
+            return;
+        };
+        let sides: Vec<(PathBuf, String)> = [entry.source.clone(), output_dir.join(rel_path)]
+            .into_iter()
+            .filter_map(|path| manifest::hash_file(&path).ok().map(|hash| (path, hash)))
+            .collect();
+        let mut writes = self.writes.lock().unwrap();
+        writes.retain(|_, (_, at)| at.elapsed() < SELF_WRITE_WINDOW);
+        let now = Instant::now();
+        for (path, hash) in sides {
+            writes.insert(path, (hash, now));
+        }
+    }
+
+    /// Whether an event for `path` comes from a recent sync write: the file
+    /// still holds exactly the content sync wrote. A real edit in the window
+    /// changes the hash and is not suppressed.
+    fn is_own_write(&self, path: &Path) -> bool {
+        let expected = match self.writes.lock().unwrap().get(path) {
+            Some((hash, at)) if at.elapsed() < SELF_WRITE_WINDOW => hash.clone(),
+            _ => return false,
+        };
+        manifest::hash_file(path).is_ok_and(|hash| hash == expected)
+    }
+}
+
+#[derive(Default)]
 struct PendingEvents {
     events: HashMap<String, EventType>,
+    /// A flush is scheduled on the pool for the open batch
+    scheduled: bool,
+    /// An early flush is scheduled because the batch reached `max_batch`
+    flush_early: bool,
+    /// The watcher was cancelled; nothing more is flushed
+    stopped: bool,
+    last_report: Option<Instant>,
+}
+
+impl PendingEvents {
+    /// Number of distinct paths buffered and waiting for the debounce window.
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Buffer a source event. A file deleted and then created again within
+    /// one batch, as editors do when saving by rename or rewrite, is one
+    /// modification, so its mirror and base cache are kept.
+    fn record(&mut self, rel_path: String, event_type: EventType) {
+        let event_type = match (self.events.get(&rel_path), event_type) {
+            (Some(EventType::Deleted), EventType::Created) => EventType::Modified,
+            (_, event_type) => event_type,
+        };
+        self.events.insert(rel_path, event_type);
+    }
+}
+
+/// Schedules a watcher's flushes on the sync pool. The first buffered event
+/// of a batch schedules a flush `debounce` later; events arriving before it
+/// runs join the batch. A batch reaching `max_batch` events is flushed right
+/// away, so huge operations are synced in chunks instead of all being held
+/// until the window closes.
+struct Debouncer {
+    pending: Arc<Mutex<PendingEvents>>,
+    pool: SyncPool,
+    debounce: Duration,
+    max_batch: usize,
+    label: String,
+    flush: Box<dyn Fn() + Send + Sync>,
+    /// Held while flushing, so `stop` waits for a flush already running
+    flushing: Mutex<()>,
+}
+
+impl Debouncer {
+    /// Call after the event handler buffers events.
+    fn events_recorded(self: &Arc<Self>) {
+        let mut p = self.pending.lock().unwrap();
+        if p.stopped || p.len() == 0 {
+            return;
+        }
+        let count = p.len();
+        report_pending(count, &self.label, &mut p.last_report);
+        if !p.scheduled {
+            p.scheduled = true;
+            let this = Arc::clone(self);
+            self.pool
+                .schedule(self.debounce, move || this.flush_scheduled(false));
+        }
+        if count >= self.max_batch && !p.flush_early {
+            debug!(
+                "{} events pending for {}, flushing early",
+                count, self.label
+            );
+            p.flush_early = true;
+            let this = Arc::clone(self);
+            self.pool
+                .schedule(Duration::ZERO, move || this.flush_scheduled(true));
+        }
+    }
+
+    /// Run a scheduled flush. The debounce flush scheduled alongside an early
+    /// one still runs, picking up whatever arrived in between.
+    fn flush_scheduled(&self, early: bool) {
+        let _flushing = self.flushing.lock().unwrap();
+        {
+            let mut p = self.pending.lock().unwrap();
+            if early {
+                p.flush_early = false;
+            } else {
+                p.scheduled = false;
+            }
+            p.last_report = None;
+            if p.stopped {
+                return;
+            }
+        }
+        (self.flush)();
+    }
+
+    /// Flush what's buffered and stop flushing. Waits for a flush in
+    /// progress; flushes still scheduled do nothing when they run.
+    fn stop(&self) {
+        let _flushing = self.flushing.lock().unwrap();
+        {
+            let mut p = self.pending.lock().unwrap();
+            if p.stopped {
+                return;
+            }
+            p.stopped = true;
+        }
+        (self.flush)();
+    }
+}
+
+/// An extra output a source watcher mirrors each batch into, after the
+/// repo's own output dir.
+pub struct ExtraOutput {
+    /// The repo as mirrored there, from `RepoConfig::extra_output_repos`
+    pub repo: RepoConfig,
+    pub manifest: Arc<Mutex<Manifest>>,
+}
+
+/// A repo mirrored into a mirror watcher's output dir. Edits made there
+/// reach its source, then each of `extra_outputs`.
+pub struct MirroredRepo {
+    pub repo: RepoConfig,
+    pub extra_outputs: Vec<ExtraOutput>,
+}
+
+impl MirroredRepo {
+    /// What a mirror watcher is restarted for when it changes: the repo's
+    /// name and path and its extra output dirs.
+    fn key(&self) -> (String, PathBuf, Vec<PathBuf>) {
+        (
+            self.repo.name.clone(),
+            self.repo.path.clone(),
+            self.extra_outputs
+                .iter()
+                .map(|e| e.repo.output_dir.clone())
+                .collect(),
+        )
+    }
+}
+
+/// The files git tracks in a `git_tracked_only` repo, listed on first use and
+/// again once the git index changes rather than on every flush. Listed each
+/// time when the index is outside the watched tree, as nothing would report
+/// its changes.
+#[derive(Default)]
+struct TrackedFiles {
+    index: Option<PathBuf>,
+    listed: Mutex<Option<Arc<Option<HashSet<String>>>>>,
+}
+
+impl TrackedFiles {
+    fn new(repo: &RepoConfig) -> Self {
+        let root = repo.source_root();
+        Self {
+            index: repo
+                .git_index_path()
+                .filter(|index| index.starts_with(&root)),
+            listed: Mutex::default(),
+        }
+    }
+
+    /// The tracked set, as `RepoConfig::tracked_files` returns it.
+    fn get(&self, repo: &RepoConfig) -> Arc<Option<HashSet<String>>> {
+        if self.index.is_none() {
+            return Arc::new(repo.tracked_files());
+        }
+        self.listed
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| Arc::new(repo.tracked_files()))
+            .clone()
+    }
+
+    /// Drop the cached set if `event` touched the git index.
+    fn invalidate_on(&self, event: &Event) {
+        if self
+            .index
+            .as_ref()
+            .is_some_and(|index| event.paths.iter().any(|p| p == index))
+        {
+            *self.listed.lock().unwrap() = None;
+        }
+    }
 }
 
 pub struct RepoWatcher {
     _watcher: RecommendedWatcher,
-    stop: Arc<Mutex<bool>>,
-    debounce_handle: Option<thread::JoinHandle<()>>,
+    debouncer: Arc<Debouncer>,
+    /// Where flushed events are mirrored; shared with the flush so the
+    /// watcher can follow an output_dir move without restarting
+    output_dir: Arc<Mutex<PathBuf>>,
+    #[cfg(test)]
+    id: usize,
 }
 
 impl RepoWatcher {
+    /// Events buffered and not yet flushed.
+    pub fn pending_count(&self) -> usize {
+        self.debouncer.pending.lock().unwrap().len()
+    }
+
+    /// Mirror future batches into `output_dir`. A flush in progress finishes
+    /// against the old directory; the next one reads the new value once it
+    /// holds the manifest lock.
+    pub fn set_output_dir(&self, output_dir: &Path) {
+        *self.output_dir.lock().unwrap() = output_dir.to_path_buf();
+    }
+
+    /// Distinct for every watcher created, to tell whether one was restarted.
+    #[cfg(test)]
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Flush buffered events and stop syncing.
     pub fn cancel(&mut self) {
-        {
-            let mut stop = self.stop.lock().unwrap();
-            *stop = true;
-        }
-        if let Some(handle) = self.debounce_handle.take() {
-            let _ = handle.join();
-        }
+        self.debouncer.stop();
     }
 }
 
 impl Drop for RepoWatcher {
     fn drop(&mut self) {
-        let mut stop = self.stop.lock().unwrap();
-        *stop = true;
+        self.debouncer.stop();
     }
 }
 
 pub struct MirrorWatcher {
-    _watcher: RecommendedWatcher,
-    stop: Arc<Mutex<bool>>,
-    debounce_handle: Option<thread::JoinHandle<()>>,
+    _watcher: Box<dyn Watcher + Send>,
+    poll_interval: Option<Duration>,
+    ignore: MirrorIgnore,
+    repos: Vec<(String, PathBuf, Vec<PathBuf>)>,
+    debouncer: Arc<Debouncer>,
 }
 
 impl MirrorWatcher {
+    /// Events buffered and not yet flushed.
+    pub fn pending_count(&self) -> usize {
+        self.debouncer.pending.lock().unwrap().len()
+    }
+
+    /// The interval the output dir is polled at, or `None` for native events.
+    pub fn poll_interval(&self) -> Option<Duration> {
+        self.poll_interval
+    }
+
+    /// Mirror files whose events are dropped.
+    pub fn ignore(&self) -> &MirrorIgnore {
+        &self.ignore
+    }
+
+    /// Whether the watcher was started for `repos`, with the same extra
+    /// outputs.
+    pub fn serves(&self, repos: &[MirroredRepo]) -> bool {
+        self.repos == repos.iter().map(MirroredRepo::key).collect::<Vec<_>>()
+    }
+
+    /// Flush buffered events and stop syncing.
     pub fn cancel(&mut self) {
-        {
-            let mut stop = self.stop.lock().unwrap();
-            *stop = true;
-        }
-        if let Some(handle) = self.debounce_handle.take() {
-            let _ = handle.join();
-        }
+        self.debouncer.stop();
     }
 }
 
 impl Drop for MirrorWatcher {
     fn drop(&mut self) {
-        let mut stop = self.stop.lock().unwrap();
-        *stop = true;
+        self.debouncer.stop();
     }
 }
 
 pub struct ConfigWatcher {
-    _watcher: RecommendedWatcher,
+    watcher: RecommendedWatcher,
+    config_dir: PathBuf,
     changed: Arc<AtomicBool>,
+    ignore_files: Arc<Mutex<HashSet<PathBuf>>>,
 }
 
 impl ConfigWatcher {
@@ -91,6 +355,26 @@ impl ConfigWatcher {
     pub fn has_changed(&self) -> bool {
         self.changed.swap(false, Ordering::SeqCst)
     }
+
+    /// Also count edits to each repo's `.ulyssesignore` as config changes,
+    /// replacing the repos watched before.
+    pub fn watch_ignore_files<'a>(&mut self, repo_paths: impl IntoIterator<Item = &'a Path>) {
+        let mut files = self.ignore_files.lock().unwrap();
+        for file in files.drain() {
+            if let Some(dir) = file.parent().filter(|d| *d != self.config_dir) {
+                let _ = self.watcher.unwatch(dir);
+            }
+        }
+        for repo_path in repo_paths {
+            if repo_path != self.config_dir {
+                if let Err(e) = self.watcher.watch(repo_path, RecursiveMode::NonRecursive) {
+                    warn!("Failed to watch {}: {}", repo_path.display(), e);
+                    continue;
+                }
+            }
+            files.insert(repo_path.join(REPO_IGNORE_FILE));
+        }
+    }
 }
 
 /// Watch the config file for changes. Watches the parent directory (non-recursive)
@@ -106,6 +390,8 @@ pub fn create_config_watcher(config_path: &Path) -> Result<ConfigWatcher> {
 
     let changed = Arc::new(AtomicBool::new(false));
     let changed_clone = Arc::clone(&changed);
+    let ignore_files: Arc<Mutex<HashSet<PathBuf>>> = Arc::default();
+    let ignore_files_clone = Arc::clone(&ignore_files);
 
     let watcher = RecommendedWatcher::new(
         move |result: Result<Event, notify::Error>| match result {
@@ -119,6 +405,13 @@ pub fn create_config_watcher(config_path: &Path) -> Result<ConfigWatcher> {
                         changed_clone.store(true, Ordering::SeqCst);
                     }
                 }
+                // A deleted ignore file drops its patterns too
+                if dominated || matches!(event.kind, EventKind::Remove(_)) {
+                    let ignore_files = ignore_files_clone.lock().unwrap();
+                    if event.paths.iter().any(|p| ignore_files.contains(p)) {
+                        changed_clone.store(true, Ordering::SeqCst);
+                    }
+                }
             }
             Err(e) => error!("Config watch error: {}", e),
         },
@@ -130,268 +423,316 @@ pub fn create_config_watcher(config_path: &Path) -> Result<ConfigWatcher> {
     watcher.watch(parent, RecursiveMode::NonRecursive)?;
 
     Ok(ConfigWatcher {
-        _watcher: watcher,
+        watcher,
+        config_dir: parent.to_path_buf(),
         changed,
+        ignore_files,
     })
 }
 
 /// Create a watcher for a single source repo with debounced event handling.
+/// Batches are flushed on `pool`. With `dry_run`, each batch is only logged
+/// as the changes it would make. Each batch is then mirrored into every
+/// one of `extra_outputs`.
+#[allow(clippy::too_many_arguments)]
 pub fn create_watcher(
     repo_config: &RepoConfig,
     output_dir: &Path,
     debounce_seconds: f64,
+    max_batch: usize,
     manifest: Arc<Mutex<Manifest>>,
+    extra_outputs: Vec<ExtraOutput>,
+    self_writes: Arc<SelfWrites>,
+    conflicts: Arc<ConflictNotifier>,
+    pool: &SyncPool,
+    dry_run: bool,
 ) -> Result<RepoWatcher> {
-    let pending = Arc::new(Mutex::new(PendingEvents {
-        events: HashMap::new(),
-    }));
-    let stop = Arc::new(Mutex::new(false));
+    let pending: Arc<Mutex<PendingEvents>> = Arc::default();
+    let shared_output_dir = Arc::new(Mutex::new(output_dir.to_path_buf()));
+
+    let pending_flush = Arc::clone(&pending);
+    let flush_repo = repo_config.clone();
+    let flush_output_dir = Arc::clone(&shared_output_dir);
+    let flush_self_writes = Arc::clone(&self_writes);
+    let flush_pool = pool.clone();
+    let tracked = Arc::new(TrackedFiles::new(repo_config));
+    let flush_tracked = Arc::clone(&tracked);
+    let debouncer = Arc::new(Debouncer {
+        pending: Arc::clone(&pending),
+        pool: pool.clone(),
+        debounce: Duration::from_millis((debounce_seconds * 1000.0) as u64),
+        max_batch,
+        label: repo_config.name.clone(),
+        flush: Box::new(move || {
+            flush_source_events(
+                &pending_flush,
+                &flush_repo,
+                &flush_output_dir,
+                &manifest,
+                &extra_outputs,
+                &flush_tracked,
+                &flush_self_writes,
+                &conflicts,
+                &flush_pool,
+                dry_run,
+            )
+        }),
+        flushing: Mutex::new(()),
+    });
 
     let repo_path = repo_config.path.clone();
-    let pending_clone = Arc::clone(&pending);
+    let guarded_dirs = repo_config.guarded_dirs.clone();
+    let event_debouncer = Arc::clone(&debouncer);
 
     let mut watcher = RecommendedWatcher::new(
         move |result: Result<Event, notify::Error>| match result {
-            Ok(event) => handle_raw_source_event(&event, &repo_path, &pending_clone),
+            Ok(event) => {
+                tracked.invalidate_on(&event);
+                handle_raw_source_event(&event, &repo_path, &guarded_dirs, &pending, &self_writes);
+                event_debouncer.events_recorded();
+            }
             Err(e) => error!("Watch error: {}", e),
         },
         NotifyConfig::default(),
     )?;
 
-    watcher.watch(&repo_config.path, RecursiveMode::Recursive)?;
-
-    let pending_flush = Arc::clone(&pending);
-    let stop_flush = Arc::clone(&stop);
-    let flush_repo_path = repo_config.path.clone();
-    let flush_repo_name = repo_config.name.clone();
-    let flush_output_dir = output_dir.to_path_buf();
-    let flush_exclude = repo_config.exclude.clone();
-    let flush_include = repo_config.include.clone();
-    let debounce_ms = (debounce_seconds * 1000.0) as u64;
-
-    let debounce_handle = thread::spawn(move || {
-        let check_interval = Duration::from_millis(100);
-        let debounce_duration = Duration::from_millis(debounce_ms);
-        let mut last_event_time: Option<std::time::Instant> = None;
-
-        loop {
-            if *stop_flush.lock().unwrap() {
-                flush_source_events(
-                    &pending_flush,
-                    &flush_repo_path,
-                    &flush_repo_name,
-                    &flush_output_dir,
-                    &flush_exclude,
-                    &flush_include,
-                    &manifest,
-                );
-                break;
-            }
-
-            let has_pending = {
-                let p = pending_flush.lock().unwrap();
-                !p.events.is_empty()
-            };
-
-            if has_pending {
-                if last_event_time.is_none() {
-                    last_event_time = Some(std::time::Instant::now());
-                }
-
-                if let Some(last) = last_event_time {
-                    if last.elapsed() >= debounce_duration {
-                        flush_source_events(
-                            &pending_flush,
-                            &flush_repo_path,
-                            &flush_repo_name,
-                            &flush_output_dir,
-                            &flush_exclude,
-                            &flush_include,
-                            &manifest,
-                        );
-                        last_event_time = None;
-                    }
-                }
-            } else {
-                last_event_time = None;
-            }
-
-            thread::sleep(check_interval);
-        }
-    });
+    watcher.watch(&repo_config.source_root(), RecursiveMode::Recursive)?;
 
     Ok(RepoWatcher {
         _watcher: watcher,
-        stop,
-        debounce_handle: Some(debounce_handle),
+        debouncer,
+        output_dir: shared_output_dir,
+        #[cfg(test)]
+        id: NEXT_WATCHER_ID.fetch_add(1, Ordering::Relaxed),
     })
 }
 
+#[cfg(test)]
+static NEXT_WATCHER_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
 /// Create a watcher on the output (mirror) directory for bidirectional sync.
+/// Batches are flushed on `pool`. Events for files matching `ignore` are
+/// dropped. Edits synced to the source of one of `repos` are passed on to
+/// its extra outputs.
+#[allow(clippy::too_many_arguments)]
 pub fn create_mirror_watcher(
     output_dir: &Path,
     debounce_seconds: f64,
+    max_batch: usize,
     manifest: Arc<Mutex<Manifest>>,
+    repos: Vec<MirroredRepo>,
+    self_writes: Arc<SelfWrites>,
+    conflicts: Arc<ConflictNotifier>,
+    pool: &SyncPool,
+    poll_interval: Option<Duration>,
+    ignore: MirrorIgnore,
 ) -> Result<MirrorWatcher> {
-    let pending = Arc::new(Mutex::new(PendingEvents {
-        events: HashMap::new(),
-    }));
-    let stop = Arc::new(Mutex::new(false));
-
-    let watch_dir = output_dir.to_path_buf();
-    let pending_clone = Arc::clone(&pending);
-
-    let mut watcher = RecommendedWatcher::new(
-        move |result: Result<Event, notify::Error>| match result {
-            Ok(event) => handle_raw_mirror_event(&event, &watch_dir, &pending_clone),
-            Err(e) => error!("Mirror watch error: {}", e),
-        },
-        NotifyConfig::default(),
-    )?;
-
-    watcher.watch(output_dir, RecursiveMode::Recursive)?;
+    let pending: Arc<Mutex<PendingEvents>> = Arc::default();
+    let repo_keys = repos.iter().map(MirroredRepo::key).collect();
 
     let pending_flush = Arc::clone(&pending);
-    let stop_flush = Arc::clone(&stop);
     let flush_output_dir = output_dir.to_path_buf();
-    let debounce_ms = (debounce_seconds * 1000.0) as u64;
-
-    let debounce_handle = thread::spawn(move || {
-        let check_interval = Duration::from_millis(100);
-        let debounce_duration = Duration::from_millis(debounce_ms);
-        let mut last_event_time: Option<std::time::Instant> = None;
-
-        loop {
-            if *stop_flush.lock().unwrap() {
-                flush_mirror_events(&pending_flush, &flush_output_dir, &manifest);
-                break;
-            }
-
-            let has_pending = {
-                let p = pending_flush.lock().unwrap();
-                !p.events.is_empty()
-            };
-
-            if has_pending {
-                if last_event_time.is_none() {
-                    last_event_time = Some(std::time::Instant::now());
-                }
+    let flush_self_writes = Arc::clone(&self_writes);
+    let flush_pool = pool.clone();
+    let debouncer = Arc::new(Debouncer {
+        pending: Arc::clone(&pending),
+        pool: pool.clone(),
+        debounce: Duration::from_millis((debounce_seconds * 1000.0) as u64),
+        max_batch,
+        label: format!("mirror {}", output_dir.display()),
+        flush: Box::new(move || {
+            flush_mirror_events(
+                &pending_flush,
+                &flush_output_dir,
+                &manifest,
+                &repos,
+                &flush_self_writes,
+                &conflicts,
+                &flush_pool,
+            )
+        }),
+        flushing: Mutex::new(()),
+    });
 
-                if let Some(last) = last_event_time {
-                    if last.elapsed() >= debounce_duration {
-                        flush_mirror_events(&pending_flush, &flush_output_dir, &manifest);
-                        last_event_time = None;
-                    }
-                }
-            } else {
-                last_event_time = None;
-            }
+    let watch_dir = output_dir.to_path_buf();
+    let event_debouncer = Arc::clone(&debouncer);
+    let event_ignore = ignore.clone();
 
-            thread::sleep(check_interval);
+    let handler = move |result: Result<Event, notify::Error>| match result {
+        Ok(event) => {
+            handle_raw_mirror_event(&event, &watch_dir, &event_ignore, &pending, &self_writes);
+            event_debouncer.events_recorded();
         }
-    });
+        Err(e) => error!("Mirror watch error: {}", e),
+    };
+    // Network and cloud volumes may not deliver native events. Polling
+    // hashes contents too, since mtimes are compared only to the second, and
+    // each poll's changes land in one debounce window.
+    let mut watcher: Box<dyn Watcher + Send> = match poll_interval {
+        Some(interval) => Box::new(PollWatcher::new(
+            handler,
+            NotifyConfig::default()
+                .with_poll_interval(interval)
+                .with_compare_contents(true),
+        )?),
+        None => Box::new(RecommendedWatcher::new(handler, NotifyConfig::default())?),
+    };
+
+    watcher.watch(output_dir, RecursiveMode::Recursive)?;
 
     Ok(MirrorWatcher {
         _watcher: watcher,
-        stop,
-        debounce_handle: Some(debounce_handle),
+        poll_interval,
+        ignore,
+        repos: repo_keys,
+        debouncer,
     })
 }
 
-fn handle_raw_source_event(event: &Event, repo_path: &Path, pending: &Arc<Mutex<PendingEvents>>) {
-    let mut p = pending.lock().unwrap();
+/// Log the pending-event count at most once per `PENDING_REPORT_INTERVAL`
+/// while a debounce window is open.
+fn report_pending(count: usize, label: &str, last_report: &mut Option<Instant>) {
+    if last_report.is_none_or(|t| t.elapsed() >= PENDING_REPORT_INTERVAL) {
+        debug!("{} events pending for {}", count, label);
+        *last_report = Some(Instant::now());
+    }
+}
 
-    for path in &event.paths {
-        let rel_path = match path.strip_prefix(repo_path) {
-            Ok(r) => r.to_string_lossy().to_string(),
-            Err(_) => continue,
-        };
+fn handle_raw_source_event(
+    event: &Event,
+    repo_path: &Path,
+    guarded_dirs: &[PathBuf],
+    pending: &Arc<Mutex<PendingEvents>>,
+    self_writes: &SelfWrites,
+) {
+    // Filtered before taking the lock, as the own-write check hashes files
+    let paths: Vec<(&PathBuf, String)> = event
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let rel_path = relative_event_path(path, repo_path)?;
+
+            // Merges write through a temp file renamed over the live one, and
+            // output or state dirs inside the repo are sync's own writes
+            let abs_path = repo_path.join(&rel_path);
+            if linker::is_temp_file(path)
+                || guarded_dirs.iter().any(|dir| abs_path.starts_with(dir))
+            {
+                return None;
+            }
+
+            // A mirror edit just propagated here; nothing to send back
+            if is_write_event(&event.kind) && self_writes.is_own_write(&abs_path) {
+                debug!("Ignoring source event from our own write: {}", rel_path);
+                return None;
+            }
+            Some((path, rel_path))
+        })
+        .collect();
 
+    let mut p = pending.lock().unwrap();
+    for (path, rel_path) in paths {
         match event.kind {
             EventKind::Create(_) => {
                 if path.is_dir() {
-                    p.events.insert(rel_path, EventType::DirCreated);
+                    p.record(rel_path, EventType::DirCreated);
                 } else {
-                    p.events.insert(rel_path, EventType::Created);
+                    p.record(rel_path, EventType::Created);
                 }
             }
             EventKind::Remove(notify::event::RemoveKind::Folder) => {
-                p.events.insert(rel_path, EventType::DirDeleted);
+                p.record(rel_path, EventType::DirDeleted);
             }
             EventKind::Remove(_) => {
-                p.events.insert(rel_path, EventType::Deleted);
+                p.record(rel_path, EventType::Deleted);
             }
             EventKind::Modify(notify::event::ModifyKind::Name(rename_mode)) => match rename_mode {
                 notify::event::RenameMode::From => {
-                    p.events.insert(rel_path, EventType::Deleted);
+                    p.record(rel_path, EventType::Deleted);
                 }
                 notify::event::RenameMode::To => {
                     if path.is_dir() {
-                        p.events.insert(rel_path, EventType::DirCreated);
+                        p.record(rel_path, EventType::DirCreated);
                     } else {
-                        p.events.insert(rel_path, EventType::Created);
+                        p.record(rel_path, EventType::Created);
                     }
                 }
                 notify::event::RenameMode::Both => {
                     if path == &event.paths[0] {
-                        p.events.insert(rel_path, EventType::Deleted);
+                        p.record(rel_path, EventType::Deleted);
                     } else if path.is_dir() {
-                        p.events.insert(rel_path, EventType::DirCreated);
+                        p.record(rel_path, EventType::DirCreated);
                     } else {
-                        p.events.insert(rel_path, EventType::Created);
+                        p.record(rel_path, EventType::Created);
                     }
                 }
                 _ => {
                     if path.is_dir() {
-                        p.events.insert(rel_path, EventType::DirCreated);
+                        p.record(rel_path, EventType::DirCreated);
                     } else {
-                        p.events.insert(rel_path, EventType::Created);
+                        p.record(rel_path, EventType::Created);
                     }
                 }
             },
-            EventKind::Modify(notify::event::ModifyKind::Data(_)) => {
-                if !path.is_dir() {
-                    p.events.insert(rel_path, EventType::Modified);
-                }
+            EventKind::Modify(notify::event::ModifyKind::Data(_)) if !path.is_dir() => {
+                p.record(rel_path, EventType::Modified);
             }
             _ => {}
         }
     }
 }
 
-fn handle_raw_mirror_event(event: &Event, output_dir: &Path, pending: &Arc<Mutex<PendingEvents>>) {
-    let mut p = pending.lock().unwrap();
-
-    for path in &event.paths {
-        let rel_path = match path.strip_prefix(output_dir) {
-            Ok(r) => r.to_string_lossy().to_string(),
-            Err(_) => continue,
-        };
+fn handle_raw_mirror_event(
+    event: &Event,
+    output_dir: &Path,
+    ignore: &MirrorIgnore,
+    pending: &Arc<Mutex<PendingEvents>>,
+    self_writes: &SelfWrites,
+) {
+    // Filtered before taking the lock, as the own-write check hashes files
+    let paths: Vec<(&PathBuf, String)> = event
+        .paths
+        .iter()
+        .filter_map(|path| {
+            let rel_path = relative_event_path(path, output_dir)?;
+
+            // Ignore manifest and base cache files, sync's temp files, the
+            // group metadata Ulysses keeps beside mirror files, and lock or
+            // temp files other tools leave there
+            if rel_path.starts_with(".ulysses-link")
+                || linker::is_temp_file(path)
+                || matcher::is_ulysses_metadata(&rel_path)
+                || ignore.is_match(&rel_path)
+            {
+                return None;
+            }
 
-        // Ignore manifest and base cache files
-        if rel_path.starts_with(".ulysses-link") {
-            continue;
-        }
+            // A source change was just copied here; re-syncing it is wasted work
+            if is_write_event(&event.kind) && self_writes.is_own_write(&output_dir.join(&rel_path))
+            {
+                debug!("Ignoring mirror event from our own write: {}", rel_path);
+                return None;
+            }
+            Some((path, rel_path))
+        })
+        .collect();
 
+    let mut p = pending.lock().unwrap();
+    for (path, rel_path) in paths {
         match event.kind {
-            EventKind::Modify(notify::event::ModifyKind::Data(_)) => {
-                if !path.is_dir() {
-                    p.events.insert(rel_path, EventType::Modified);
-                }
+            // Poll watchers report a content change as a new mtime
+            EventKind::Modify(notify::event::ModifyKind::Data(_))
+            | EventKind::Modify(notify::event::ModifyKind::Metadata(
+                notify::event::MetadataKind::WriteTime,
+            )) if !path.is_dir() => {
+                p.events.insert(rel_path, EventType::Modified);
             }
             EventKind::Modify(notify::event::ModifyKind::Name(rename_mode)) => match rename_mode {
                 notify::event::RenameMode::From => {
                     p.events.insert(rel_path, EventType::Deleted);
                 }
-                notify::event::RenameMode::To => {
-                    p.events.insert(rel_path, EventType::Modified);
-                }
-                notify::event::RenameMode::Both => {
-                    if path == &event.paths[0] {
-                        p.events.insert(rel_path, EventType::Deleted);
-                    } else {
-                        p.events.insert(rel_path, EventType::Modified);
-                    }
+                notify::event::RenameMode::Both if path == &event.paths[0] => {
+                    p.events.insert(rel_path, EventType::Deleted);
                 }
                 _ => {
                     p.events.insert(rel_path, EventType::Modified);
@@ -408,14 +749,74 @@ fn handle_raw_mirror_event(event: &Event, output_dir: &Path, pending: &Arc<Mutex
     }
 }
 
+/// Events that can follow sync writing a file: creation, a content change,
+/// or a rename into place. Removals are never suppressed.
+fn is_write_event(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_)
+            | EventKind::Modify(notify::event::ModifyKind::Data(_))
+            | EventKind::Modify(notify::event::ModifyKind::Metadata(
+                notify::event::MetadataKind::WriteTime
+            ))
+            | EventKind::Modify(notify::event::ModifyKind::Name(
+                notify::event::RenameMode::To
+            ))
+    )
+}
+
+/// Make an event path relative to `root`, the canonical watch root.
+///
+/// Events can name the root through an alias (macOS FSEvents may deliver
+/// `/var/...` for a root canonicalized to `/private/var/...`) or, on
+/// case-insensitive volumes, in a different case. When a plain prefix strip
+/// fails, the path's deepest existing ancestor is canonicalized (deleted
+/// files themselves can't be) and the strip is retried.
+fn relative_event_path(path: &Path, root: &Path) -> Option<String> {
+    if let Ok(rel) = path.strip_prefix(root) {
+        return Some(rel.to_string_lossy().to_string());
+    }
+
+    let resolved = canonicalize_existing_prefix(path)?;
+    if let Ok(rel) = resolved.strip_prefix(root) {
+        return Some(rel.to_string_lossy().to_string());
+    }
+
+    // APFS and HFS+ are case-insensitive by default
+    #[cfg(target_os = "macos")]
+    if let Some(rel) = strip_prefix_ignore_case(&resolved, root) {
+        return Some(rel);
+    }
+
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn strip_prefix_ignore_case(path: &Path, root: &Path) -> Option<String> {
+    let mut components = path.components();
+    for root_component in root.components() {
+        let component = components.next()?;
+        let a = component.as_os_str().to_string_lossy().to_lowercase();
+        let b = root_component.as_os_str().to_string_lossy().to_lowercase();
+        if a != b {
+            return None;
+        }
+    }
+    Some(components.as_path().to_string_lossy().to_string())
+}
+
+#[allow(clippy::too_many_arguments)]
 fn flush_source_events(
     pending: &Arc<Mutex<PendingEvents>>,
-    repo_path: &Path,
-    repo_name: &str,
-    output_dir: &Path,
-    exclude: &ignore::gitignore::Gitignore,
-    include: &globset::GlobSet,
-    manifest: &Arc<Mutex<Manifest>>,
+    repo: &RepoConfig,
+    output_dir: &Mutex<PathBuf>,
+    manifest_arc: &Arc<Mutex<Manifest>>,
+    extra_outputs: &[ExtraOutput],
+    tracked: &TrackedFiles,
+    self_writes: &SelfWrites,
+    conflicts: &ConflictNotifier,
+    pool: &SyncPool,
+    dry_run: bool,
 ) {
     let batch = {
         let mut p = pending.lock().unwrap();
@@ -426,61 +827,159 @@ fn flush_source_events(
         return;
     }
 
-    debug!("Debounced batch for {}: {} events", repo_name, batch.len());
+    debug!(
+        "Debounced batch for {}: {} events",
+        repo.mirror_subdir,
+        batch.len()
+    );
+
+    let tracked = tracked.get(repo);
+    let tracked = tracked.as_ref().as_ref();
+    let mut conflicted = {
+        let mut manifest = manifest_arc.lock().unwrap();
+        // Read under the manifest lock, which an output_dir move also holds
+        let output_dir = output_dir.lock().unwrap().clone();
+        sync_source_batch(
+            &batch,
+            repo,
+            &output_dir,
+            &mut manifest,
+            manifest_arc,
+            tracked,
+            self_writes,
+            pool,
+            dry_run,
+        )
+    };
+    for extra in extra_outputs {
+        let mut manifest = extra.manifest.lock().unwrap();
+        conflicted.extend(sync_source_batch(
+            &batch,
+            &extra.repo,
+            &extra.repo.output_dir,
+            &mut manifest,
+            &extra.manifest,
+            tracked,
+            self_writes,
+            pool,
+            dry_run,
+        ));
+    }
+    // Sent with no manifest locked: showing a notification can block
+    conflicts.batch_conflicts(&conflicted);
+}
 
-    let mut manifest = manifest.lock().unwrap();
+/// Mirror one debounced batch of source events into `output_dir`, whose
+/// manifest the caller has locked. Returns the keys that conflicted, for the
+/// caller to report once the lock is released.
+#[allow(clippy::too_many_arguments)]
+fn sync_source_batch(
+    batch: &HashMap<String, EventType>,
+    repo: &RepoConfig,
+    output_dir: &Path,
+    manifest: &mut Manifest,
+    manifest_arc: &Arc<Mutex<Manifest>>,
+    tracked: Option<&HashSet<String>>,
+    self_writes: &SelfWrites,
+    pool: &SyncPool,
+    dry_run: bool,
+) -> Vec<String> {
+    let repo_path = &repo.path;
+    let mirror_subdir = &repo.mirror_subdir;
+    if dry_run {
+        plan_source_events(batch, repo, tracked, output_dir, manifest);
+        return Vec::new();
+    }
+    // The engine rescans once a vanished output dir is back
+    if !output_dir.is_dir() {
+        warn!(
+            "Output directory {} is gone, dropping {} events for {}",
+            output_dir.display(),
+            batch.len(),
+            mirror_subdir
+        );
+        return Vec::new();
+    }
     let mut creates = 0u32;
     let mut deletes = 0u32;
+    let mut conflicted = Vec::new();
 
-    for (rel_path, event_type) in &batch {
+    for (rel_path, event_type) in batch {
         // On macOS, FSEvents may emit multiple flags for one operation (e.g.
         // Remove + Name + Data for a deletion). Later events overwrite earlier
         // ones in the HashMap, so a file can be marked Created/Modified even
         // though it was actually deleted. Re-check existence here.
+        // Likewise a delete can arrive after the create that replaced the
+        // file; a file that is still there was modified, not deleted.
         let effective_type = match event_type {
             EventType::Created | EventType::Modified if !repo_path.join(rel_path).exists() => {
                 &EventType::Deleted
             }
+            EventType::Deleted if repo_path.join(rel_path).is_file() => &EventType::Modified,
             other => other,
         };
 
         match effective_type {
             EventType::Deleted => {
-                let manifest_rel = format!("{repo_name}/{rel_path}");
-                match linker::propagate_delete(&manifest_rel, &mut manifest, output_dir) {
+                let manifest_rel = repo.mirror_key(rel_path, manifest);
+                match linker::propagate_delete(mirror_subdir, &manifest_rel, manifest, output_dir) {
                     Ok(true) => deletes += 1,
                     Ok(false) => {}
                     Err(e) => error!("Error propagating delete for {}: {}", rel_path, e),
                 }
             }
             EventType::Created | EventType::Modified => {
-                if matcher::should_mirror(rel_path, exclude, include) {
-                    let source = repo_path.join(rel_path);
-                    let manifest_rel = format!("{repo_name}/{rel_path}");
+                if repo.within_depth(rel_path)
+                    && matcher::should_mirror(rel_path, &repo.exclude, &repo.include)
+                    && tracked.is_none_or(|t| t.contains(rel_path))
+                {
+                    let source = repo.source_path(rel_path);
+                    if matcher::content_excluded(
+                        &source,
+                        repo.skip_if_matches.as_ref(),
+                        repo.mirror_binary,
+                    ) {
+                        match linker::untrack_source(mirror_subdir, &source, manifest, output_dir) {
+                            Ok(true) => deletes += 1,
+                            Ok(false) => {}
+                            Err(e) => error!("Error removing skipped {}: {}", rel_path, e),
+                        }
+                        continue;
+                    }
+                    let manifest_rel = repo.mirror_key(rel_path, manifest);
                     let mirror = output_dir.join(&manifest_rel);
                     match linker::sync_file(
                         &source,
                         &mirror,
-                        &mut manifest,
+                        manifest,
                         &manifest_rel,
                         output_dir,
+                        repo.direction,
                     ) {
-                        Ok(SyncOutcome::Copied) => creates += 1,
+                        Ok(SyncOutcome::Copied | SyncOutcome::Merged) => {
+                            self_writes.record_synced(manifest, &manifest_rel, output_dir);
+                            creates += 1;
+                        }
                         Ok(
                             SyncOutcome::AlreadyInSync
                             | SyncOutcome::Claimed
                             | SyncOutcome::Skipped,
                         ) => {}
-                        Ok(SyncOutcome::Merged) => creates += 1,
                         Ok(SyncOutcome::Conflict) => {
                             info!("Conflict detected for {}", rel_path);
+                            conflicted.push(manifest_rel);
                         }
                         Err(e) => error!("Error syncing {}: {}", rel_path, e),
                     }
                 }
             }
             EventType::DirDeleted => {
-                match linker::remove_dir_mirrors(repo_name, rel_path, output_dir, &mut manifest) {
+                match linker::remove_dir_mirrors(
+                    mirror_subdir,
+                    &repo_path.join(rel_path),
+                    output_dir,
+                    manifest,
+                ) {
                     Ok(n) => deletes += n,
                     Err(e) => error!("Error removing dir mirrors for {}: {}", rel_path, e),
                 }
@@ -490,12 +989,11 @@ fn flush_source_events(
                 if abs_dir.is_dir() {
                     scan_new_dir(
                         &abs_dir,
-                        repo_path,
-                        repo_name,
+                        repo,
+                        tracked,
                         output_dir,
-                        exclude,
-                        include,
-                        &mut manifest,
+                        manifest,
+                        self_writes,
                         &mut creates,
                     );
                 }
@@ -503,21 +1001,113 @@ fn flush_source_events(
         }
     }
 
-    if creates > 0 || deletes > 0 {
-        if let Err(e) = manifest.save(output_dir) {
-            error!("Failed to save manifest: {}", e);
+    if repo.mirror_empty_dirs {
+        // Only the directories the events touched: a file's parent may have
+        // lost its mirror dir when the file's mirror was pruned
+        for (rel_path, event_type) in batch {
+            let path = repo_path.join(rel_path);
+            match event_type {
+                EventType::DirCreated | EventType::DirDeleted => {
+                    scanner::sync_empty_dirs_under(repo, output_dir, &path);
+                }
+                _ => {
+                    if let Some(parent) = path.parent() {
+                        scanner::restore_empty_dir(repo, output_dir, parent);
+                    }
+                }
+            }
         }
+    }
+
+    if creates > 0 || deletes > 0 {
+        manifest.mark_synced(&repo.name);
+        save_batch(manifest, manifest_arc, output_dir, pool);
         info!(
             "Batch for {}: {} creates, {} deletes",
-            repo_name, creates, deletes
+            mirror_subdir, creates, deletes
         );
     }
+    conflicted
+}
+
+/// Log what `sync_source_batch` would do with `batch`, without writing.
+fn plan_source_events(
+    batch: &HashMap<String, EventType>,
+    repo: &RepoConfig,
+    tracked: Option<&HashSet<String>>,
+    output_dir: &Path,
+    manifest: &Manifest,
+) {
+    for (rel_path, event_type) in batch {
+        let source = repo.source_path(rel_path);
+        let effective_type = match event_type {
+            EventType::Created | EventType::Modified if !source.exists() => &EventType::Deleted,
+            EventType::Deleted if source.is_file() => &EventType::Modified,
+            other => other,
+        };
+        match effective_type {
+            EventType::Deleted => {
+                let key = repo.mirror_key(rel_path, manifest);
+                if manifest.get(&key).is_some() {
+                    linker::log_planned_removal(&key, manifest, output_dir);
+                }
+            }
+            EventType::Created | EventType::Modified => {
+                if !repo.within_depth(rel_path)
+                    || !matcher::should_mirror(rel_path, &repo.exclude, &repo.include)
+                    || tracked.is_some_and(|t| !t.contains(rel_path))
+                {
+                    debug!("Not mirrored: {}", rel_path);
+                    continue;
+                }
+                if matcher::content_excluded(
+                    &source,
+                    repo.skip_if_matches.as_ref(),
+                    repo.mirror_binary,
+                ) {
+                    if let Some(key) = manifest.key_for_source(&source) {
+                        debug!("Skipped by content: {}", key);
+                        linker::log_planned_removal(key, manifest, output_dir);
+                    }
+                    continue;
+                }
+                let key = repo.mirror_key(rel_path, manifest);
+                match linker::plan_sync(
+                    &source,
+                    &output_dir.join(&key),
+                    manifest,
+                    &key,
+                    output_dir,
+                    repo.direction,
+                ) {
+                    Ok(outcome) => linker::log_planned(&key, outcome),
+                    Err(e) => error!("Error planning sync of {}: {}", rel_path, e),
+                }
+            }
+            EventType::DirDeleted => {
+                for key in linker::plan_remove_dir_mirrors(
+                    &repo.mirror_subdir,
+                    &repo.path.join(rel_path),
+                    manifest,
+                ) {
+                    linker::log_planned_removal(&key, manifest, output_dir);
+                }
+            }
+            EventType::DirCreated => {
+                info!("Would scan new directory {}", rel_path);
+            }
+        }
+    }
 }
 
 fn flush_mirror_events(
     pending: &Arc<Mutex<PendingEvents>>,
     output_dir: &Path,
-    manifest: &Arc<Mutex<Manifest>>,
+    manifest_arc: &Arc<Mutex<Manifest>>,
+    repos: &[MirroredRepo],
+    self_writes: &SelfWrites,
+    conflicts: &ConflictNotifier,
+    pool: &SyncPool,
 ) {
     let batch = {
         let mut p = pending.lock().unwrap();
@@ -530,9 +1120,48 @@ fn flush_mirror_events(
 
     debug!("Mirror debounced batch: {} events", batch.len());
 
-    let mut manifest = manifest.lock().unwrap();
+    // Every file vanishes with the output dir itself; none of that is a
+    // deletion to propagate to source
+    if !output_dir.is_dir() {
+        warn!(
+            "Output directory {} is gone, dropping {} mirror events",
+            output_dir.display(),
+            batch.len()
+        );
+        return;
+    }
+
+    let mut manifest = manifest_arc.lock().unwrap();
     let mut syncs = 0u32;
     let mut deletes = 0u32;
+    let mut renames = 0u32;
+    let mut conflicted = Vec::new();
+    // Source changes by repo, to mark the repo synced and replay as source
+    // events into its extra outputs
+    let mut source_batches: HashMap<usize, HashMap<String, EventType>> = HashMap::new();
+    let mut record_source = |source: &Path, event_type: EventType| {
+        for (i, mirrored) in repos.iter().enumerate() {
+            if let Ok(rel) = source.strip_prefix(&mirrored.repo.path) {
+                source_batches
+                    .entry(i)
+                    .or_default()
+                    .insert(rel.to_string_lossy().to_string(), event_type);
+                return;
+            }
+        }
+    };
+
+    // Files renamed in a flat inbox are matched to their entries by source
+    // header first, so the old name's delete never reaches source
+    for (rel_path, event_type) in &batch {
+        if *event_type == EventType::Modified && manifest.get(rel_path).is_none() {
+            match linker::adopt_renamed_mirror(rel_path, &mut manifest, output_dir) {
+                Ok(true) => renames += 1,
+                Ok(false) => {}
+                Err(e) => error!("Error matching renamed mirror file {}: {}", rel_path, e),
+            }
+        }
+    }
 
     for (rel_path, event_type) in &batch {
         match event_type {
@@ -540,12 +1169,25 @@ fn flush_mirror_events(
                 if let Some(entry) = manifest.get(rel_path).cloned() {
                     let source = entry.source.clone();
                     let mirror = output_dir.join(rel_path);
-                    match linker::sync_file(&source, &mirror, &mut manifest, rel_path, output_dir) {
-                        Ok(SyncOutcome::Copied) => syncs += 1,
+                    // Mirror watchers only run when sync is bidirectional;
+                    // sync_file itself never writes a read-only source
+                    match linker::sync_file(
+                        &source,
+                        &mirror,
+                        &mut manifest,
+                        rel_path,
+                        output_dir,
+                        SyncDirection::Bidirectional,
+                    ) {
+                        Ok(SyncOutcome::Copied | SyncOutcome::Merged) => {
+                            self_writes.record_synced(&manifest, rel_path, output_dir);
+                            record_source(&source, EventType::Modified);
+                            syncs += 1;
+                        }
                         Ok(SyncOutcome::AlreadyInSync) => {}
-                        Ok(SyncOutcome::Merged) => syncs += 1,
                         Ok(SyncOutcome::Conflict) => {
                             info!("Conflict detected for mirror edit: {}", rel_path);
+                            conflicted.push(rel_path.clone());
                         }
                         Ok(_) => {}
                         Err(e) => error!("Error syncing mirror edit for {}: {}", rel_path, e),
@@ -553,8 +1195,14 @@ fn flush_mirror_events(
                 }
             }
             EventType::Deleted => {
+                let source = manifest.get(rel_path).map(|e| e.source.clone());
                 match linker::propagate_mirror_delete(rel_path, &mut manifest, output_dir) {
-                    Ok(true) => deletes += 1,
+                    Ok(true) => {
+                        if let Some(source) = source {
+                            record_source(&source, EventType::Deleted);
+                        }
+                        deletes += 1;
+                    }
                     Ok(false) => {}
                     Err(e) => error!("Error propagating mirror delete for {}: {}", rel_path, e),
                 }
@@ -563,31 +1211,82 @@ fn flush_mirror_events(
         }
     }
 
-    if syncs > 0 || deletes > 0 {
-        if let Err(e) = manifest.save(output_dir) {
-            error!("Failed to save manifest: {}", e);
+    for i in source_batches.keys() {
+        manifest.mark_synced(&repos[*i].repo.name);
+    }
+    if syncs > 0 || deletes > 0 || renames > 0 {
+        save_batch(&mut manifest, manifest_arc, output_dir, pool);
+        info!(
+            "Mirror batch: {} syncs, {} deletes, {} renames",
+            syncs, deletes, renames
+        );
+    }
+    drop(manifest);
+
+    // Writes to source are our own and never come back as source events,
+    // so the extra outputs are brought up to date here
+    for (i, batch) in &source_batches {
+        for extra in &repos[*i].extra_outputs {
+            let mut manifest = extra.manifest.lock().unwrap();
+            conflicted.extend(sync_source_batch(
+                batch,
+                &extra.repo,
+                &extra.repo.output_dir,
+                &mut manifest,
+                &extra.manifest,
+                None,
+                self_writes,
+                pool,
+                false,
+            ));
+        }
+    }
+    // Sent with the manifest unlocked: showing a notification can block
+    conflicts.batch_conflicts(&conflicted);
+}
+
+/// Save the manifest after a batch. A save coming right after the previous
+/// one is deferred and written once `manifest::SAVE_INTERVAL` has passed,
+/// so a burst of batches during a big operation writes it a few times
+/// instead of once per batch.
+fn save_batch(
+    manifest: &mut Manifest,
+    manifest_arc: &Arc<Mutex<Manifest>>,
+    output_dir: &Path,
+    pool: &SyncPool,
+) {
+    match manifest.save_coalesced(output_dir) {
+        Ok(Some(delay)) => {
+            let manifest_arc = Arc::clone(manifest_arc);
+            let output_dir = output_dir.to_path_buf();
+            pool.schedule(delay, move || {
+                if let Err(e) = manifest_arc.lock().unwrap().save_pending(&output_dir) {
+                    error!("Failed to save manifest: {}", e);
+                }
+            });
         }
-        info!("Mirror batch: {} syncs, {} deletes", syncs, deletes);
+        Ok(None) => {}
+        Err(e) => error!("Failed to save manifest: {}", e),
     }
 }
 
-#[allow(clippy::too_many_arguments)]
 fn scan_new_dir(
     abs_dir: &Path,
-    repo_path: &Path,
-    repo_name: &str,
+    repo: &RepoConfig,
+    tracked: Option<&HashSet<String>>,
     output_dir: &Path,
-    exclude: &ignore::gitignore::Gitignore,
-    include: &globset::GlobSet,
     manifest: &mut Manifest,
+    self_writes: &SelfWrites,
     creates: &mut u32,
 ) {
+    let repo_path = &repo.path;
     for entry in WalkDir::new(abs_dir)
-        .follow_links(false)
+        .follow_links(repo.follow_symlinks)
         .into_iter()
+        .filter_entry(|e| !repo.in_guarded_dir(e.path()))
         .filter_map(|e| e.ok())
     {
-        if entry.file_type().is_dir() || entry.path_is_symlink() {
+        if !entry.file_type().is_file() || (entry.path_is_symlink() && !repo.follow_symlinks) {
             continue;
         }
 
@@ -596,14 +1295,31 @@ fn scan_new_dir(
             Err(_) => continue,
         };
 
-        if matcher::should_mirror(&file_rel, exclude, include) {
-            let source = repo_path.join(&file_rel);
-            let manifest_rel = format!("{repo_name}/{file_rel}");
+        if repo.within_depth(&file_rel)
+            && matcher::should_mirror(&file_rel, &repo.exclude, &repo.include)
+            && tracked.is_none_or(|t| t.contains(&file_rel))
+        {
+            let source = repo.source_path(&file_rel);
+            if matcher::content_excluded(&source, repo.skip_if_matches.as_ref(), repo.mirror_binary)
+            {
+                continue;
+            }
+            let manifest_rel = repo.mirror_key(&file_rel, manifest);
             let mirror = output_dir.join(&manifest_rel);
-            match linker::sync_file(&source, &mirror, manifest, &manifest_rel, output_dir) {
-                Ok(SyncOutcome::Copied) => *creates += 1,
+            match linker::sync_file(
+                &source,
+                &mirror,
+                manifest,
+                &manifest_rel,
+                output_dir,
+                repo.direction,
+            ) {
+                Ok(SyncOutcome::Copied | SyncOutcome::Merged) => {
+                    self_writes.record_synced(manifest, &manifest_rel, output_dir);
+                    *creates += 1;
+                }
                 Ok(SyncOutcome::AlreadyInSync | SyncOutcome::Claimed | SyncOutcome::Skipped) => {}
-                Ok(SyncOutcome::Merged | SyncOutcome::Conflict) => *creates += 1,
+                Ok(SyncOutcome::Conflict) => *creates += 1,
                 Err(e) => error!("Error syncing {}: {}", file_rel, e),
             }
         }
@@ -615,8 +1331,149 @@ mod tests {
     use super::*;
     use crate::config;
     use std::fs;
+    use std::thread;
     use tempfile::TempDir;
 
+    /// One repo at `repo` mirrored into `output` under a temp dir, loaded
+    /// from a config with `global` and `repo` lines added to the top level
+    /// and the repo's table, plus the state a watcher shares.
+    struct Fixture {
+        // Removed on drop
+        _tmp: TempDir,
+        cfg: config::Config,
+        output: PathBuf,
+        manifest: Arc<Mutex<Manifest>>,
+        self_writes: Arc<SelfWrites>,
+        conflicts: Arc<ConflictNotifier>,
+        pool: SyncPool,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Self::with_config("", "")
+        }
+
+        fn with_config(global: &str, repo: &str) -> Self {
+            let tmp = TempDir::new().unwrap();
+            let repo_dir = tmp.path().join("repo");
+            let output = tmp.path().join("output");
+            fs::create_dir_all(&repo_dir).unwrap();
+            fs::create_dir_all(&output).unwrap();
+
+            let toml = format!(
+                "version = 1\noutput_dir = \"{}\"\n{global}\n\n[[repos]]\npath = \"{}\"\n{repo}",
+                output.display(),
+                repo_dir.display()
+            );
+            let config_file = tmp.path().join("config.toml");
+            fs::write(&config_file, toml).unwrap();
+            let cfg = config::load_config(Some(&config_file)).unwrap();
+            let output = cfg.output_dir.clone();
+            let manifest = Arc::new(Mutex::new(Manifest::load_in(&output, &cfg.state).unwrap()));
+            Fixture {
+                _tmp: tmp,
+                cfg,
+                output,
+                manifest,
+                self_writes: Arc::default(),
+                conflicts: Arc::default(),
+                pool: SyncPool::new(1),
+            }
+        }
+
+        fn repo(&self) -> &RepoConfig {
+            &self.cfg.repos[0]
+        }
+
+        /// Source file at the repo-relative `rel_path`.
+        fn source(&self, rel_path: &str) -> PathBuf {
+            self.repo().path.join(rel_path)
+        }
+
+        /// Mirror file of the repo-relative `rel_path`.
+        fn mirror(&self, rel_path: &str) -> PathBuf {
+            self.output.join("repo").join(rel_path)
+        }
+
+        /// Write `content` to the source file at `rel_path` and copy it to
+        /// the mirror, as a scan would.
+        fn mirrored(&self, rel_path: &str, content: &str) {
+            fs::write(self.source(rel_path), content).unwrap();
+            linker::sync_file(
+                &self.source(rel_path),
+                &self.mirror(rel_path),
+                &mut self.manifest.lock().unwrap(),
+                &format!("repo/{rel_path}"),
+                &self.output,
+                SyncDirection::Bidirectional,
+            )
+            .unwrap();
+        }
+
+        fn watch(&self, debounce_seconds: f64, max_batch: usize) -> RepoWatcher {
+            create_watcher(
+                self.repo(),
+                &self.output,
+                debounce_seconds,
+                max_batch,
+                Arc::clone(&self.manifest),
+                Vec::new(),
+                Arc::clone(&self.self_writes),
+                Arc::clone(&self.conflicts),
+                &self.pool,
+                false,
+            )
+            .unwrap()
+        }
+
+        fn watch_mirror(&self, poll_interval: Option<Duration>) -> MirrorWatcher {
+            create_mirror_watcher(
+                &self.output,
+                0.1,
+                config::DEFAULT_MAX_BATCH,
+                Arc::clone(&self.manifest),
+                Vec::new(),
+                Arc::clone(&self.self_writes),
+                Arc::clone(&self.conflicts),
+                &self.pool,
+                poll_interval,
+                MirrorIgnore::default(),
+            )
+            .unwrap()
+        }
+
+        /// Flush `pending` as one debounced source batch.
+        fn flush_pending(&self, pending: &Arc<Mutex<PendingEvents>>) {
+            flush_source_events(
+                pending,
+                self.repo(),
+                &Mutex::new(self.output.clone()),
+                &self.manifest,
+                &[],
+                &TrackedFiles::default(),
+                &self.self_writes,
+                &self.conflicts,
+                &self.pool,
+                false,
+            );
+        }
+
+        /// Flush `events`, by repo-relative path, as one source batch.
+        fn flush_source(&self, events: &[(&str, EventType)]) {
+            self.flush_pending(&pending(events));
+        }
+    }
+
+    fn pending(events: &[(&str, EventType)]) -> Arc<Mutex<PendingEvents>> {
+        Arc::new(Mutex::new(PendingEvents {
+            events: events
+                .iter()
+                .map(|(path, event_type)| (path.to_string(), event_type.clone()))
+                .collect(),
+            ..Default::default()
+        }))
+    }
+
     #[test]
     fn test_event_type_mapping() {
         assert_ne!(EventType::Created, EventType::Deleted);
@@ -626,15 +1483,336 @@ mod tests {
 
     #[test]
     fn test_watcher_creates_and_cancels() {
+        let fx = Fixture::new();
+        let mut watcher = fx.watch(0.1, config::DEFAULT_MAX_BATCH);
+
+        thread::sleep(Duration::from_millis(50));
+        watcher.cancel();
+    }
+
+    #[test]
+    fn test_watcher_flushes_after_debounce_and_cancels_promptly() {
+        let fx = Fixture::new();
+        let mut watcher = fx.watch(0.3, config::DEFAULT_MAX_BATCH);
+        thread::sleep(Duration::from_millis(100));
+
+        let written = Instant::now();
+        fs::write(fx.source("doc.md"), "new doc").unwrap();
+        let mirror = fx.mirror("doc.md");
+        while !mirror.exists() {
+            assert!(
+                written.elapsed() < Duration::from_secs(3),
+                "edit never flushed"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(written.elapsed() >= Duration::from_millis(300));
+
+        // An idle watcher has nothing scheduled to wait for
+        thread::sleep(Duration::from_millis(200));
+        let cancelled = Instant::now();
+        watcher.cancel();
+        assert!(cancelled.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_watcher_flushes_early_past_max_batch() {
+        let fx = Fixture::new();
+        // A debounce window far longer than the test waits
+        let mut watcher = fx.watch(30.0, 5);
+        thread::sleep(Duration::from_millis(100));
+
+        let written = Instant::now();
+        for i in 0..12 {
+            fs::write(fx.source(&format!("doc{i}.md")), "content").unwrap();
+        }
+        while fx.manifest.lock().unwrap().len() < 5 {
+            assert!(
+                written.elapsed() < Duration::from_secs(5),
+                "batch past max_batch never flushed early"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        watcher.cancel();
+        assert_eq!(fx.manifest.lock().unwrap().len(), 12);
+    }
+
+    #[test]
+    fn test_many_watchers_flush_through_shared_pool() {
         let tmp = TempDir::new().unwrap();
-        let repo = tmp.path().join("repo");
         let output = tmp.path().join("output");
-        fs::create_dir_all(&repo).unwrap();
         fs::create_dir_all(&output).unwrap();
+        let names: Vec<String> = (0..8).map(|i| format!("repo{i}")).collect();
+        let mut toml = format!("version = 1\noutput_dir = \"{}\"\n", output.display());
+        for name in &names {
+            let repo = tmp.path().join(name);
+            fs::create_dir_all(&repo).unwrap();
+            toml.push_str(&format!("\n[[repos]]\npath = \"{}\"\n", repo.display()));
+        }
+        let config_file = tmp.path().join("config.toml");
+        fs::write(&config_file, toml).unwrap();
+        let cfg = config::load_config(Some(&config_file)).unwrap();
+
+        let pool = SyncPool::new(2);
+        let manifest = Arc::new(Mutex::new(Manifest::load_in(&output, &cfg.state).unwrap()));
+        let self_writes = Arc::new(SelfWrites::default());
+        let conflicts = Arc::new(ConflictNotifier::default());
+        let mut watchers: Vec<RepoWatcher> = cfg
+            .repos
+            .iter()
+            .map(|repo| {
+                create_watcher(
+                    repo,
+                    &output,
+                    0.1,
+                    config::DEFAULT_MAX_BATCH,
+                    Arc::clone(&manifest),
+                    Vec::new(),
+                    Arc::clone(&self_writes),
+                    Arc::clone(&conflicts),
+                    &pool,
+                    false,
+                )
+                .unwrap()
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(100));
+
+        for name in &names {
+            fs::write(tmp.path().join(name).join("doc.md"), name).unwrap();
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        for name in &names {
+            let mirror = output.join(name).join("doc.md");
+            while fs::read_to_string(&mirror).ok().as_deref() != Some(name.as_str()) {
+                assert!(Instant::now() < deadline, "{name} never flushed");
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+        assert_eq!(manifest.lock().unwrap().len(), names.len());
+        for watcher in &mut watchers {
+            watcher.cancel();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_event_path_through_alias_attributed_to_repo() {
+        let tmp = TempDir::new().unwrap();
+        let real = tmp.path().join("private").join("var");
+        let repo = real.join("repo");
+        fs::create_dir_all(repo.join("docs")).unwrap();
+        fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+        // `alias` plays the role of macOS `/var` -> `/private/var`
+        let alias = tmp.path().join("var");
+        std::os::unix::fs::symlink(&real, &alias).unwrap();
+        let repo_path = fs::canonicalize(&repo).unwrap();
+
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
+        let created = Event::new(EventKind::Create(notify::event::CreateKind::File))
+            .add_path(alias.join("repo").join("docs").join("guide.md"));
+        handle_raw_source_event(&created, &repo_path, &[], &pending, &SelfWrites::default());
+        // Deleted files can't be canonicalized themselves, only their parents
+        let removed = Event::new(EventKind::Remove(notify::event::RemoveKind::File))
+            .add_path(alias.join("repo").join("gone.md"));
+        handle_raw_source_event(&removed, &repo_path, &[], &pending, &SelfWrites::default());
+
+        let events = &pending.lock().unwrap().events;
+        assert_eq!(events.get("docs/guide.md"), Some(&EventType::Created));
+        assert_eq!(events.get("gone.md"), Some(&EventType::Deleted));
+    }
+
+    #[test]
+    fn test_pending_count() {
+        let tmp = TempDir::new().unwrap();
+        let repo = fs::canonicalize(tmp.path()).unwrap();
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
+        assert_eq!(pending.lock().unwrap().len(), 0);
+
+        for name in ["a.md", "b.md", "a.md"] {
+            let event = Event::new(EventKind::Modify(notify::event::ModifyKind::Data(
+                notify::event::DataChange::Content,
+            )))
+            .add_path(repo.join(name));
+            handle_raw_source_event(&event, &repo, &[], &pending, &SelfWrites::default());
+        }
+
+        // Repeated events for one path coalesce into a single pending entry
+        assert_eq!(pending.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_source_sync_does_not_echo_as_mirror_edit() {
+        let fx = Fixture::new();
+        fs::write(fx.source("doc.md"), "edited in source").unwrap();
+        fx.flush_source(&[("doc.md", EventType::Modified)]);
+        let mirror = fx.mirror("doc.md");
+        assert_eq!(fs::read_to_string(&mirror).unwrap(), "edited in source");
+
+        // The copy's own event is dropped by the mirror watcher
+        let mirror_pending = Arc::new(Mutex::new(PendingEvents::default()));
+        let event = Event::new(EventKind::Modify(notify::event::ModifyKind::Data(
+            notify::event::DataChange::Content,
+        )))
+        .add_path(mirror.clone());
+        handle_raw_mirror_event(
+            &event,
+            &fx.output,
+            &MirrorIgnore::default(),
+            &mirror_pending,
+            &fx.self_writes,
+        );
+        assert_eq!(mirror_pending.lock().unwrap().len(), 0);
+
+        // A real edit in the same window still goes through
+        fs::write(&mirror, "edited in Ulysses").unwrap();
+        handle_raw_mirror_event(
+            &event,
+            &fx.output,
+            &MirrorIgnore::default(),
+            &mirror_pending,
+            &fx.self_writes,
+        );
+        assert_eq!(
+            mirror_pending.lock().unwrap().events.get("repo/doc.md"),
+            Some(&EventType::Modified)
+        );
+    }
+
+    #[test]
+    fn test_flat_inbox_sync_does_not_echo_as_mirror_edit() {
+        let fx = Fixture::with_config("", "flat_inbox = true");
+        fs::create_dir_all(fx.source("notes")).unwrap();
+        fs::write(fx.source("notes/doc.md"), "edited in source").unwrap();
+        fx.flush_source(&[("notes/doc.md", EventType::Created)]);
+        let rel_path = fx
+            .manifest
+            .lock()
+            .unwrap()
+            .entries()
+            .next()
+            .unwrap()
+            .0
+            .clone();
+        let mirror = fx.output.join(&rel_path);
+        // The mirror carries a source header, so its bytes differ from source
+        assert_ne!(fs::read_to_string(&mirror).unwrap(), "edited in source");
+
+        let mirror_pending = Arc::new(Mutex::new(PendingEvents::default()));
+        let event = Event::new(EventKind::Modify(notify::event::ModifyKind::Data(
+            notify::event::DataChange::Content,
+        )))
+        .add_path(mirror);
+        handle_raw_mirror_event(
+            &event,
+            &fx.output,
+            &MirrorIgnore::default(),
+            &mirror_pending,
+            &fx.self_writes,
+        );
+        assert_eq!(mirror_pending.lock().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_delete_then_create_keeps_base_cache() {
+        let fx = Fixture::new();
+        fx.mirrored("doc.md", "original");
+        let repo_config = fx.repo();
+        let read_base =
+            || linker::read_base(&fx.manifest.lock().unwrap(), &fx.output, "repo/doc.md").unwrap();
+        assert_eq!(read_base(), Some("original".to_string()));
+
+        // An editor saves by removing the file and writing a new one
+        let doc = fx.source("doc.md");
+        fs::write(&doc, "saved").unwrap();
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
+        let removed =
+            Event::new(EventKind::Remove(notify::event::RemoveKind::File)).add_path(doc.clone());
+        let created =
+            Event::new(EventKind::Create(notify::event::CreateKind::File)).add_path(doc.clone());
+        for event in [&removed, &created] {
+            handle_raw_source_event(
+                event,
+                &repo_config.path,
+                &repo_config.guarded_dirs,
+                &pending,
+                &SelfWrites::default(),
+            );
+        }
+        assert_eq!(
+            pending.lock().unwrap().events.get("doc.md"),
+            Some(&EventType::Modified)
+        );
+
+        fx.flush_pending(&pending);
+        assert!(fx.manifest.lock().unwrap().get("repo/doc.md").is_some());
+        assert_eq!(read_base(), Some("saved".to_string()));
+        assert_eq!(fs::read_to_string(fx.mirror("doc.md")).unwrap(), "saved");
+
+        // The same save reported in the opposite order is still an edit
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
+        for event in [&created, &removed] {
+            handle_raw_source_event(
+                event,
+                &repo_config.path,
+                &repo_config.guarded_dirs,
+                &pending,
+                &SelfWrites::default(),
+            );
+        }
+        assert_eq!(
+            pending.lock().unwrap().events.get("doc.md"),
+            Some(&EventType::Deleted)
+        );
+        fs::write(&doc, "saved again").unwrap();
+        fx.flush_pending(&pending);
+        assert_eq!(read_base(), Some("saved again".to_string()));
+    }
+
+    #[test]
+    fn test_source_event_beyond_max_depth_not_mirrored() {
+        let fx = Fixture::with_config("max_depth = 1", "");
+        fs::create_dir_all(fx.source("docs")).unwrap();
+        fs::write(fx.source("top.md"), "top").unwrap();
+        fs::write(fx.source("docs/deep.md"), "deep").unwrap();
+        fx.flush_source(&[
+            ("top.md", EventType::Created),
+            ("docs/deep.md", EventType::Created),
+        ]);
+
+        assert!(fx.mirror("top.md").exists());
+        assert!(!fx.mirror("docs/deep.md").exists());
+        assert!(fx
+            .manifest
+            .lock()
+            .unwrap()
+            .get("repo/docs/deep.md")
+            .is_none());
+    }
+
+    #[test]
+    fn test_tracked_files_relisted_after_index_change() {
+        let tmp = TempDir::new().unwrap();
+        let repo = tmp.path().join("repo");
+        fs::create_dir_all(&repo).unwrap();
+        fs::write(repo.join("a.md"), "a").unwrap();
+        fs::write(repo.join("b.md"), "b").unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .arg("-C")
+                .arg(&repo)
+                .args(args)
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["add", "a.md"]);
 
         let toml = format!(
-            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
-            output.display(),
+            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\ngit_tracked_only = true",
+            tmp.path().join("output").display(),
             repo.display()
         );
         let config_file = tmp.path().join("config.toml");
@@ -642,11 +1820,104 @@ mod tests {
         let cfg = config::load_config(Some(&config_file)).unwrap();
         let repo_config = &cfg.repos[0];
 
-        let manifest = Arc::new(Mutex::new(Manifest::load(&output).unwrap()));
-        let mut watcher = create_watcher(repo_config, &output, 0.1, manifest).unwrap();
+        let tracked = TrackedFiles::new(repo_config);
+        let listed = tracked.get(repo_config);
+        assert!(listed.as_ref().as_ref().unwrap().contains("a.md"));
+
+        // Cached until the index changes
+        git(&["add", "b.md"]);
+        let listed = tracked.get(repo_config);
+        assert!(!listed.as_ref().as_ref().unwrap().contains("b.md"));
+        let other = Event::new(EventKind::Create(notify::event::CreateKind::File))
+            .add_path(repo_config.path.join("b.md"));
+        tracked.invalidate_on(&other);
+        assert!(!tracked
+            .get(repo_config)
+            .as_ref()
+            .as_ref()
+            .unwrap()
+            .contains("b.md"));
+
+        let index = Event::new(EventKind::Create(notify::event::CreateKind::File))
+            .add_path(repo_config.path.join(".git").join("index"));
+        tracked.invalidate_on(&index);
+        assert!(tracked
+            .get(repo_config)
+            .as_ref()
+            .as_ref()
+            .unwrap()
+            .contains("b.md"));
+    }
 
-        thread::sleep(Duration::from_millis(50));
-        watcher.cancel();
+    #[test]
+    fn test_quick_batches_coalesce_manifest_saves() {
+        let fx = Fixture::new();
+        let batches = 20;
+        for i in 0..batches {
+            let name = format!("doc{i}.md");
+            fs::write(fx.source(&name), "content").unwrap();
+            fx.flush_source(&[(&name, EventType::Created)]);
+        }
+        assert_eq!(fx.manifest.lock().unwrap().len(), batches);
+        assert!(fx.manifest.lock().unwrap().save_count() < batches / 4);
+
+        // The deferred save lands once the interval is up
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Manifest::load(&fx.output).unwrap().len() < batches {
+            assert!(Instant::now() < deadline, "deferred save never written");
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    /// Records notification titles, checking `manifest` isn't locked while
+    /// one is shown.
+    struct CountingNotifier(Arc<Mutex<Vec<String>>>, Arc<Mutex<Manifest>>);
+
+    impl crate::notifier::Notifier for CountingNotifier {
+        fn notify(&self, title: &str, _body: &str) {
+            assert!(
+                self.1.try_lock().is_ok(),
+                "notified under the manifest lock"
+            );
+            self.0.lock().unwrap().push(title.to_string());
+        }
+    }
+
+    #[test]
+    fn test_conflicting_batch_notifies_once() {
+        let mut fx = Fixture::new();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        fx.conflicts = Arc::new(ConflictNotifier::with_notifier(
+            true,
+            Box::new(CountingNotifier(
+                Arc::clone(&sent),
+                Arc::clone(&fx.manifest),
+            )),
+        ));
+        let modified = |names: &[&'static str]| -> Vec<(&'static str, EventType)> {
+            names.iter().map(|n| (*n, EventType::Modified)).collect()
+        };
+
+        for name in ["a.md", "b.md"] {
+            fs::write(fx.source(name), "original").unwrap();
+        }
+        fx.flush_source(&modified(&["a.md", "b.md"]));
+        assert!(sent.lock().unwrap().is_empty());
+
+        // The same line edited on both sides can't merge
+        for name in ["a.md", "b.md"] {
+            fs::write(fx.mirror(name), "edited in Ulysses").unwrap();
+            fs::write(fx.source(name), "edited in source").unwrap();
+        }
+        fx.flush_source(&modified(&["a.md", "b.md"]));
+        assert_eq!(*sent.lock().unwrap(), vec!["ulysses-link: 2 conflicts"]);
+
+        // Disabled by a reload: conflicts go unannounced
+        fx.conflicts.set_enabled(false);
+        fs::write(fx.mirror("a.md"), "Ulysses again").unwrap();
+        fs::write(fx.source("a.md"), "source again").unwrap();
+        fx.flush_source(&modified(&["a.md"]));
+        assert_eq!(sent.lock().unwrap().len(), 1);
     }
 
     #[test]
@@ -670,24 +1941,142 @@ mod tests {
 
     #[test]
     fn test_mirror_watcher_creates_and_cancels() {
-        let tmp = TempDir::new().unwrap();
-        let output = tmp.path().join("output");
-        fs::create_dir_all(&output).unwrap();
-
-        let manifest = Arc::new(Mutex::new(Manifest::load(&output).unwrap()));
-        let mut watcher = create_mirror_watcher(&output, 0.1, manifest).unwrap();
+        let fx = Fixture::new();
+        let mut watcher = fx.watch_mirror(None);
 
         thread::sleep(Duration::from_millis(50));
         watcher.cancel();
     }
 
+    #[test]
+    fn test_poll_mirror_watcher_syncs_edit_to_source() {
+        let fx = Fixture::new();
+        fx.mirrored("doc.md", "original");
+        let mut watcher = fx.watch_mirror(Some(Duration::from_millis(100)));
+        assert_eq!(watcher.poll_interval(), Some(Duration::from_millis(100)));
+
+        fs::write(fx.mirror("doc.md"), "edited in Ulysses").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fs::read_to_string(fx.source("doc.md")).unwrap() != "edited in Ulysses" {
+            assert!(
+                Instant::now() < deadline,
+                "mirror edit never reached source"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+        watcher.cancel();
+    }
+
+    #[test]
+    fn test_mirror_edit_reaches_extra_outputs() {
+        let fx = Fixture::with_config("", "extra_outputs = [\"backup\"]");
+        fs::write(fx.source("doc.md"), "original").unwrap();
+        fs::write(fx.source("old.md"), "old").unwrap();
+        crate::scanner::scan_repo(fx.repo(), &fx.output, &mut fx.manifest.lock().unwrap());
+        let extra = fx.repo().extra_output_repos().remove(0);
+        let backup = extra.output_dir.clone();
+        let mut extra_manifest = Manifest::load(&backup).unwrap();
+        crate::scanner::scan_repo(&extra, &backup, &mut extra_manifest);
+        assert_eq!(
+            fs::read_to_string(backup.join("repo/doc.md")).unwrap(),
+            "original"
+        );
+
+        let repos = vec![MirroredRepo {
+            repo: fx.repo().clone(),
+            extra_outputs: vec![ExtraOutput {
+                repo: extra,
+                manifest: Arc::new(Mutex::new(extra_manifest)),
+            }],
+        }];
+        let stale = "2026-01-01T00:00:00+00:00".to_string();
+        fx.manifest
+            .lock()
+            .unwrap()
+            .set_last_synced("repo", stale.clone());
+        fs::write(fx.mirror("doc.md"), "edited in Ulysses").unwrap();
+        fs::remove_file(fx.mirror("old.md")).unwrap();
+        flush_mirror_events(
+            &pending(&[
+                ("repo/doc.md", EventType::Modified),
+                ("repo/old.md", EventType::Deleted),
+            ]),
+            &fx.output,
+            &fx.manifest,
+            &repos,
+            &fx.self_writes,
+            &fx.conflicts,
+            &fx.pool,
+        );
+
+        assert_eq!(
+            fs::read_to_string(fx.source("doc.md")).unwrap(),
+            "edited in Ulysses"
+        );
+        assert_eq!(
+            fs::read_to_string(backup.join("repo/doc.md")).unwrap(),
+            "edited in Ulysses"
+        );
+        assert!(!backup.join("repo/old.md").exists());
+        // The edit counts as the repo syncing
+        assert_ne!(
+            fx.manifest.lock().unwrap().last_synced("repo"),
+            Some(stale.as_str())
+        );
+    }
+
+    #[test]
+    fn test_mirror_lock_file_never_reaches_source() {
+        let fx = Fixture::new();
+        fx.mirrored("notes.md", "original");
+
+        let lock = fx.mirror(".~lock.notes.md#");
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
+        let event =
+            Event::new(EventKind::Create(notify::event::CreateKind::File)).add_path(lock.clone());
+        handle_raw_mirror_event(
+            &event,
+            &fx.output,
+            &MirrorIgnore::default(),
+            &pending,
+            &SelfWrites::default(),
+        );
+        assert_eq!(pending.lock().unwrap().len(), 0);
+
+        let mut watcher = fx.watch_mirror(Some(Duration::from_millis(100)));
+        fs::write(&lock, "user,host,01.01.2026 12:00").unwrap();
+        thread::sleep(Duration::from_millis(300));
+        // A real edit after it still syncs, so the watcher was running
+        fs::write(fx.mirror("notes.md"), "edited in Ulysses").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fs::read_to_string(fx.source("notes.md")).unwrap() != "edited in Ulysses" {
+            assert!(
+                Instant::now() < deadline,
+                "mirror edit never reached source"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+        watcher.cancel();
+
+        let names: Vec<_> = fs::read_dir(&fx.repo().path)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["notes.md"]);
+        assert!(fx
+            .manifest
+            .lock()
+            .unwrap()
+            .get("repo/.~lock.notes.md#")
+            .is_none());
+        assert!(lock.exists());
+    }
+
     #[test]
     fn test_mirror_rename_to_produces_modified() {
         let tmp = TempDir::new().unwrap();
         let output = tmp.path();
-        let pending = Arc::new(Mutex::new(PendingEvents {
-            events: HashMap::new(),
-        }));
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
 
         let event = Event {
             kind: EventKind::Modify(notify::event::ModifyKind::Name(
@@ -697,7 +2086,13 @@ mod tests {
             attrs: Default::default(),
         };
 
-        handle_raw_mirror_event(&event, output, &pending);
+        handle_raw_mirror_event(
+            &event,
+            output,
+            &MirrorIgnore::default(),
+            &pending,
+            &SelfWrites::default(),
+        );
 
         let p = pending.lock().unwrap();
         assert_eq!(p.events.get("repo/doc.md"), Some(&EventType::Modified));
@@ -707,9 +2102,7 @@ mod tests {
     fn test_mirror_create_produces_modified() {
         let tmp = TempDir::new().unwrap();
         let output = tmp.path();
-        let pending = Arc::new(Mutex::new(PendingEvents {
-            events: HashMap::new(),
-        }));
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
 
         let event = Event {
             kind: EventKind::Create(notify::event::CreateKind::File),
@@ -717,7 +2110,13 @@ mod tests {
             attrs: Default::default(),
         };
 
-        handle_raw_mirror_event(&event, output, &pending);
+        handle_raw_mirror_event(
+            &event,
+            output,
+            &MirrorIgnore::default(),
+            &pending,
+            &SelfWrites::default(),
+        );
 
         let p = pending.lock().unwrap();
         assert_eq!(p.events.get("repo/doc.md"), Some(&EventType::Modified));
@@ -727,9 +2126,7 @@ mod tests {
     fn test_mirror_rename_from_produces_deleted() {
         let tmp = TempDir::new().unwrap();
         let output = tmp.path();
-        let pending = Arc::new(Mutex::new(PendingEvents {
-            events: HashMap::new(),
-        }));
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
 
         let event = Event {
             kind: EventKind::Modify(notify::event::ModifyKind::Name(
@@ -739,7 +2136,13 @@ mod tests {
             attrs: Default::default(),
         };
 
-        handle_raw_mirror_event(&event, output, &pending);
+        handle_raw_mirror_event(
+            &event,
+            output,
+            &MirrorIgnore::default(),
+            &pending,
+            &SelfWrites::default(),
+        );
 
         let p = pending.lock().unwrap();
         assert_eq!(p.events.get("repo/doc.md"), Some(&EventType::Deleted));
@@ -749,9 +2152,7 @@ mod tests {
     fn test_mirror_ignores_ulysses_link_files() {
         let tmp = TempDir::new().unwrap();
         let output = tmp.path();
-        let pending = Arc::new(Mutex::new(PendingEvents {
-            events: HashMap::new(),
-        }));
+        let pending = Arc::new(Mutex::new(PendingEvents::default()));
 
         let event = Event {
             kind: EventKind::Modify(notify::event::ModifyKind::Data(
@@ -761,7 +2162,13 @@ mod tests {
             attrs: Default::default(),
         };
 
-        handle_raw_mirror_event(&event, output, &pending);
+        handle_raw_mirror_event(
+            &event,
+            output,
+            &MirrorIgnore::default(),
+            &pending,
+            &SelfWrites::default(),
+        );
 
         let p = pending.lock().unwrap();
         assert!(p.events.is_empty());
@@ -26,13 +26,7 @@ fn create_test_config(repo_paths: &[&Path], output_dir: &Path, config_dir: &Path
 fn load_manifests(
     config: &ulysses_link::config::Config,
 ) -> HashMap<PathBuf, ulysses_link::manifest::Manifest> {
-    let mut manifests = HashMap::new();
-    for od in config.active_output_dirs() {
-        manifests
-            .entry(od.clone())
-            .or_insert_with(|| ulysses_link::manifest::Manifest::load(&od).unwrap());
-    }
-    manifests
+    ulysses_link::syncer::load_manifests(config).unwrap()
 }
 
 #[test]
@@ -227,6 +221,161 @@ fn test_mirror_edit_propagates_to_source() {
     );
 }
 
+#[test]
+fn test_one_way_mode_reverts_mirror_edit() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "original").unwrap();
+
+    let config_content = format!(
+        "version = 1\noutput_dir = \"{}\"\nbidirectional = false\n\n[[repos]]\npath = \"{}\"",
+        output.display(),
+        repo.display(),
+    );
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(&config_path, &config_content).unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    // Edit mirror: source must stay untouched and the mirror reverts
+    let mirror = output.join("repo").join("README.md");
+    fs::write(&mirror, "edited in ulysses").unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.created, 1);
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "original"
+    );
+    assert_eq!(fs::read_to_string(&mirror).unwrap(), "original");
+
+    // Both sides edited: still no merge, source wins
+    fs::write(repo.join("README.md"), "source edit").unwrap();
+    fs::write(&mirror, "mirror edit").unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.merged + result.conflicts, 0);
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "source edit"
+    );
+    assert_eq!(fs::read_to_string(&mirror).unwrap(), "source edit");
+}
+
+#[test]
+fn test_extra_outputs_mirror_one_way() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    let backup = tmp.path().join("backup");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "original").unwrap();
+    fs::write(repo.join("notes.md"), "notes").unwrap();
+
+    let config_content = format!(
+        "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nextra_outputs = [\"{}\"]",
+        output.display(),
+        repo.display(),
+        backup.display(),
+    );
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(&config_path, &config_content).unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    assert_eq!(manifests.len(), 2);
+
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    let mirror = output.join("repo").join("README.md");
+    let backup_mirror = backup.join("repo").join("README.md");
+    assert_eq!(fs::read_to_string(&mirror).unwrap(), "original");
+    assert_eq!(fs::read_to_string(&backup_mirror).unwrap(), "original");
+
+    // An edit in the extra output never reaches source; one in the primary does
+    fs::write(&backup_mirror, "edited in backup").unwrap();
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "original"
+    );
+    assert_eq!(fs::read_to_string(&backup_mirror).unwrap(), "original");
+
+    fs::write(&mirror, "edited in ulysses").unwrap();
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "edited in ulysses"
+    );
+    assert_eq!(
+        fs::read_to_string(&backup_mirror).unwrap(),
+        "edited in ulysses"
+    );
+
+    // Pruning covers both outputs
+    fs::remove_file(repo.join("notes.md")).unwrap();
+    let result = ulysses_link::scanner::full_prune(&config, &mut manifests);
+    assert_eq!(result.pruned, 2);
+    assert!(!output.join("repo").join("notes.md").exists());
+    assert!(!backup.join("repo").join("notes.md").exists());
+}
+
+#[test]
+fn test_reset_discards_mirror_edit_with_backup() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "original").unwrap();
+    fs::write(repo.join("NOTES.md"), "notes").unwrap();
+
+    let config_path_str = create_test_config(&[repo.as_path()], &output, tmp.path());
+    let config_path = std::path::PathBuf::from(&config_path_str);
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let mirror_dir = output.join("repo");
+    fs::write(mirror_dir.join("README.md"), "botched edit").unwrap();
+
+    let rc = &config.repos[0];
+    let manifest = manifests.get_mut(&rc.output_dir).unwrap();
+    let result = ulysses_link::scanner::reset_repo(rc, &rc.output_dir, manifest, true);
+    assert_eq!(result.conflicts, 1);
+    assert_eq!(result.already_existed, 1);
+    assert_eq!(result.errors, 0);
+
+    assert_eq!(
+        fs::read_to_string(mirror_dir.join("README.md")).unwrap(),
+        "original"
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "original"
+    );
+    let backups: Vec<_> = fs::read_dir(&mirror_dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name()
+                .to_string_lossy()
+                .starts_with("README.md.conflict_")
+        })
+        .collect();
+    assert_eq!(backups.len(), 1);
+    assert_eq!(
+        fs::read_to_string(backups[0].path()).unwrap(),
+        "botched edit"
+    );
+
+    // A normal sync afterwards sees everything in sync
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 0);
+    assert_eq!(result.merged, 0);
+}
+
 #[test]
 fn test_non_overlapping_edits_merge() {
     let tmp = TempDir::new().unwrap();
@@ -388,6 +537,110 @@ fn test_manifest_persisted_across_scans() {
     assert_eq!(result.already_existed, 1);
 }
 
+#[test]
+fn test_state_dir_keeps_bookkeeping_out_of_mirror() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    let state = tmp.path().join("state");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "line1\nline2\nline3\n").unwrap();
+
+    let config_path = PathBuf::from(create_test_config(&[repo.as_path()], &output, tmp.path()));
+    let contents = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        &config_path,
+        contents.replacen(
+            "\n\n",
+            &format!("\nstate_dir = \"{}\"\n\n", state.display()),
+            1,
+        ),
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    assert_eq!(config.state.state_dir.as_deref(), Some(state.as_path()));
+
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    // Three-way merge still works, reading the base from the state dir
+    fs::write(repo.join("README.md"), "LINE1\nline2\nline3\n").unwrap();
+    fs::write(
+        output.join("repo").join("README.md"),
+        "line1\nline2\nLINE3\n",
+    )
+    .unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.merged, 1);
+    assert_eq!(
+        fs::read_to_string(repo.join("README.md")).unwrap(),
+        "LINE1\nline2\nLINE3\n"
+    );
+
+    let mirror_entries: Vec<String> = walkdir::WalkDir::new(&output)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    assert!(
+        !mirror_entries
+            .iter()
+            .any(|n| n.starts_with(".ulysses-link")),
+        "{mirror_entries:?}"
+    );
+
+    let state_root = ulysses_link::manifest::state_root_for(&state, &config.output_dir);
+    assert!(state_root.join(".ulysses-link").is_file());
+    assert!(state_root
+        .join(".ulysses-link.d")
+        .join("repo")
+        .join("README.md")
+        .is_file());
+
+    // A fresh process finds the manifest in the state dir
+    let reloaded = load_manifests(&config);
+    assert!(reloaded[&config.output_dir].get("repo/README.md").is_some());
+}
+
+#[test]
+fn test_manifest_log_backend() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("docs")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("docs/guide.md"), "guide").unwrap();
+
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "version = 2\noutput_dir = \"{}\"\n\n[sync]\nmanifest_backend = \"log\"\n\n[[repos]]\npath = \"{}\"",
+            output.display(),
+            repo.display()
+        ),
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 2);
+    assert!(output.join(".ulysses-link.log").is_file());
+    assert!(!output.join(".ulysses-link").exists());
+
+    // Deleting a source prunes its mirror, and the log replays to match
+    fs::remove_file(repo.join("docs/guide.md")).unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.pruned, 1);
+    let reloaded = load_manifests(&config);
+    let manifest = &reloaded[&config.output_dir];
+    assert!(manifest.get("repo/README.md").is_some());
+    assert!(manifest.get("repo/docs/guide.md").is_none());
+
+    let removed = ulysses_link::linker::clean_state(&output, None, false, true).unwrap();
+    assert_eq!(removed, vec![output.join(".ulysses-link.log")]);
+}
+
 #[test]
 fn test_per_repo_output_dir() {
     let tmp = TempDir::new().unwrap();
@@ -446,3 +699,1206 @@ fn test_per_repo_output_dir() {
         "repo 2"
     );
 }
+
+#[test]
+fn test_syncer_keeps_one_manifest_per_output_dir() {
+    let tmp = TempDir::new().unwrap();
+    let repo1 = tmp.path().join("repo1");
+    let repo2 = tmp.path().join("repo2");
+    let output1 = tmp.path().join("output1");
+    let output2 = tmp.path().join("output2");
+    fs::create_dir_all(&repo1).unwrap();
+    fs::create_dir_all(&repo2).unwrap();
+    fs::write(repo1.join("README.md"), "repo 1").unwrap();
+    fs::write(repo2.join("README.md"), "repo 2").unwrap();
+
+    let config_content = format!(
+        "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\n\n[[repos]]\npath = \"{}\"\noutput_dir = \"{}\"",
+        output1.display(),
+        repo1.display(),
+        repo2.display(),
+        output2.display(),
+    );
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(&config_path, &config_content).unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+
+    let mut syncer = ulysses_link::syncer::Syncer::new(config).unwrap();
+    let result = syncer.scan_all();
+    assert_eq!(result.created, 2);
+
+    let canon_out1 = fs::canonicalize(&output1).unwrap();
+    let canon_out2 = fs::canonicalize(&output2).unwrap();
+    assert_eq!(syncer.manifests().len(), 2);
+    let manifest1 = syncer.manifest(&canon_out1).unwrap();
+    assert!(manifest1.get("repo1/README.md").is_some());
+    assert!(manifest1.get("repo2/README.md").is_none());
+    let manifest2 = syncer.manifest(&canon_out2).unwrap();
+    assert!(manifest2.get("repo2/README.md").is_some());
+
+    // A fresh syncer picks up what the scan saved
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut syncer = ulysses_link::syncer::Syncer::new(config).unwrap();
+    let result = syncer.scan_all();
+    assert_eq!(result.created, 0);
+    assert_eq!(result.already_existed, 2);
+}
+
+#[test]
+fn test_syncer_scan_repo_and_sync_file() {
+    let tmp = TempDir::new().unwrap();
+    let repo1 = tmp.path().join("repo1");
+    let repo2 = tmp.path().join("repo2");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo1.join("docs")).unwrap();
+    fs::create_dir_all(&repo2).unwrap();
+    fs::write(repo1.join("docs").join("guide.md"), "original").unwrap();
+    fs::write(repo2.join("README.md"), "repo 2").unwrap();
+
+    let config_path_str =
+        create_test_config(&[repo1.as_path(), repo2.as_path()], &output, tmp.path());
+    let config_path = PathBuf::from(&config_path_str);
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut syncer = ulysses_link::syncer::Syncer::new(config).unwrap();
+
+    // Scanning one repo leaves the other alone
+    let result = syncer.scan_repo("repo1").unwrap();
+    assert_eq!(result.created, 1);
+    assert!(output.join("repo1").join("docs").join("guide.md").exists());
+    assert!(!output.join("repo2").exists());
+    assert!(syncer.scan_repo("missing").is_err());
+
+    // A single file syncs in either direction
+    fs::write(repo1.join("docs").join("guide.md"), "from source").unwrap();
+    let outcome = syncer.sync_file("repo1", "docs/guide.md").unwrap();
+    assert_eq!(outcome, ulysses_link::linker::SyncOutcome::Copied);
+    assert_eq!(
+        fs::read_to_string(output.join("repo1").join("docs").join("guide.md")).unwrap(),
+        "from source"
+    );
+    fs::write(
+        output.join("repo1").join("docs").join("guide.md"),
+        "from mirror",
+    )
+    .unwrap();
+    syncer.sync_file("repo1", "docs/guide.md").unwrap();
+    assert_eq!(
+        fs::read_to_string(repo1.join("docs").join("guide.md")).unwrap(),
+        "from mirror"
+    );
+    syncer.save().unwrap();
+
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let syncer = ulysses_link::syncer::Syncer::new(config).unwrap();
+    let output_dir = syncer.config().repos[0].output_dir.clone();
+    let manifest = syncer.manifest(&output_dir).unwrap();
+    assert!(manifest.get("repo1/docs/guide.md").is_some());
+}
+
+#[test]
+fn test_exclude_from_file() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("drafts")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("drafts").join("idea.md"), "wip").unwrap();
+
+    let ignore_file = tmp.path().join("ulysses-ignore");
+    fs::write(&ignore_file, "# unpublished work\ndrafts/\n").unwrap();
+
+    let config_content = format!(
+        "version = 1\noutput_dir = \"{}\"\nglobal_exclude_from = [\"{}\"]\n\n[[repos]]\npath = \"{}\"",
+        output.display(),
+        ignore_file.display(),
+        repo.display(),
+    );
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(&config_path, &config_content).unwrap();
+
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.created, 1);
+    assert!(output.join("repo").join("README.md").exists());
+    assert!(!output.join("repo").join("drafts").exists());
+}
+
+#[test]
+fn test_repo_ulyssesignore_excludes() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("private")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("private").join("notes.md"), "secret").unwrap();
+    fs::write(repo.join(".ulyssesignore"), "# not for Ulysses\nprivate/\n").unwrap();
+
+    let config_path_str = create_test_config(&[repo.as_path()], &output, tmp.path());
+    let config_path = PathBuf::from(&config_path_str);
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.created, 1);
+    assert!(output.join("repo").join("README.md").exists());
+    assert!(!output.join("repo").join("private").exists());
+    assert!(!output.join("repo").join(".ulyssesignore").exists());
+
+    // Excluding an already mirrored file prunes its mirror on the next scan
+    fs::write(
+        repo.join(".ulyssesignore"),
+        "private/
+README.md
+",
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.pruned, 1);
+    assert!(!output.join("repo").join("README.md").exists());
+    assert!(repo.join("README.md").exists());
+}
+
+#[test]
+fn test_nested_mirror_subdir() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("api");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("docs")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+
+    let config_content = format!(
+        "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nmirror_subdir = \"work/api\"",
+        output.display(),
+        repo.display(),
+    );
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(&config_path, &config_content).unwrap();
+
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.created, 2);
+    let mirror_root = output.join("work").join("api");
+    assert!(mirror_root.join("README.md").exists());
+    assert!(mirror_root.join("docs").join("guide.md").exists());
+    assert!(!output.join("api").exists());
+
+    let manifest = manifests.get_mut(&output).unwrap();
+    assert!(manifest.get("work/api/README.md").is_some());
+
+    // Pruning stays inside the subdir
+    fs::remove_file(repo.join("docs").join("guide.md")).unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.pruned, 1);
+    assert!(!mirror_root.join("docs").exists());
+    assert!(mirror_root.join("README.md").exists());
+
+    // Removing the repo mirror cleans up the intermediate directory too
+    let manifest = manifests.get_mut(&output).unwrap();
+    ulysses_link::linker::remove_repo_mirror("work/api", &output, manifest).unwrap();
+    assert!(!output.join("work").exists());
+    assert!(output.exists());
+}
+
+#[test]
+fn test_flatten_repo_with_collision() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("api");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("docs").join("api")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("docs").join("api").join("auth.md"), "nested").unwrap();
+    // Flattens onto the same name as docs/api/auth.md
+    fs::write(repo.join("docs - api - auth.md"), "literal").unwrap();
+
+    let write_config = |flatten: bool| {
+        let config_path = tmp.path().join("ulysses-link.toml");
+        fs::write(
+            &config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nflatten = {flatten}",
+                output.display(),
+                repo.display(),
+            ),
+        )
+        .unwrap();
+        ulysses_link::config::load_config(Some(&config_path)).unwrap()
+    };
+
+    let config = write_config(true);
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 3);
+
+    let mirror_root = output.join("api");
+    assert!(mirror_root.join("README.md").exists());
+    assert!(!mirror_root.join("docs").exists());
+    let mut names: Vec<String> = fs::read_dir(&mirror_root)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        [
+            "README.md",
+            "docs - api - auth (2).md",
+            "docs - api - auth.md"
+        ]
+    );
+
+    // Each flattened name maps back to its own source
+    let manifest = &manifests[&output];
+    let sources: Vec<PathBuf> = ["api/docs - api - auth.md", "api/docs - api - auth (2).md"]
+        .iter()
+        .map(|k| manifest.get(k).unwrap().source.clone())
+        .collect();
+    assert_ne!(sources[0], sources[1]);
+
+    // Rescans keep the names, and mirror edits reach the right source
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 0);
+    assert_eq!(result.already_existed, 3);
+    fs::write(
+        mirror_root.join("docs - api - auth (2).md"),
+        "edited in Ulysses",
+    )
+    .unwrap();
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(
+        fs::read_to_string(&sources[1]).unwrap(),
+        "edited in Ulysses"
+    );
+
+    // Deleting a source prunes its flattened mirror
+    fs::remove_file(&sources[0]).unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.pruned, 1);
+    assert!(!mirror_root.join("docs - api - auth.md").exists());
+
+    // Turning flatten off restores the tree and drops the flat copies
+    let config = write_config(false);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    let manifest = &manifests[&output];
+    assert_eq!(manifest.entries_for_repo("api").len(), 2);
+    assert!(!mirror_root.join("docs - api - auth (2).md").exists());
+}
+
+#[test]
+fn test_move_one_repo_mirror() {
+    let tmp = TempDir::new().unwrap();
+    let repo1 = tmp.path().join("repo1");
+    let repo2 = tmp.path().join("repo2");
+    let output = tmp.path().join("mirror");
+    let work = tmp.path().join("work-mirror");
+    fs::create_dir_all(repo1.join("docs")).unwrap();
+    fs::create_dir_all(&repo2).unwrap();
+    fs::write(repo1.join("docs").join("guide.md"), "guide").unwrap();
+    fs::write(repo2.join("README.md"), "two").unwrap();
+    fs::create_dir_all(&work).unwrap();
+
+    let config_path = create_test_config(&[&repo1, &repo2], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let output = config.output_dir.clone();
+    let work = fs::canonicalize(&work).unwrap();
+    let mut old_manifest = manifests.remove(&output).unwrap();
+    let mut new_manifest = ulysses_link::manifest::Manifest::load(&work).unwrap();
+    let moved = ulysses_link::linker::move_repo_mirror(
+        "repo1",
+        &output,
+        &mut old_manifest,
+        &work,
+        &mut new_manifest,
+    )
+    .unwrap();
+
+    assert_eq!(moved, 1);
+    assert_eq!(
+        fs::read_to_string(work.join("repo1").join("docs").join("guide.md")).unwrap(),
+        "guide"
+    );
+    assert!(!output.join("repo1").exists());
+    assert!(!output.join(".ulysses-link.d").join("repo1").exists());
+    assert!(work
+        .join(".ulysses-link.d")
+        .join("repo1")
+        .join("docs")
+        .join("guide.md")
+        .exists());
+    assert!(new_manifest.get("repo1/docs/guide.md").is_some());
+    assert!(old_manifest.get("repo1/docs/guide.md").is_none());
+
+    // The other repo is untouched
+    assert_eq!(
+        fs::read_to_string(output.join("repo2").join("README.md")).unwrap(),
+        "two"
+    );
+    assert!(old_manifest.get("repo2/README.md").is_some());
+    assert!(new_manifest.entries_for_repo("repo2").is_empty());
+}
+
+#[test]
+fn test_sync_command_adds_multiple_repos() {
+    let tmp = TempDir::new().unwrap();
+    let home = tmp.path().join("home");
+    let output = tmp.path().join("mirror");
+    let repos: Vec<PathBuf> = ["a", "b", "c"]
+        .iter()
+        .map(|name| tmp.path().join(name))
+        .collect();
+    fs::create_dir_all(&home).unwrap();
+    for repo in &repos {
+        fs::create_dir_all(repo).unwrap();
+        fs::write(repo.join("README.md"), "hello").unwrap();
+    }
+
+    let run_sync = |extra: &[&Path]| {
+        let mut cmd = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"));
+        cmd.arg("sync")
+            .args(extra)
+            .arg("--output")
+            .arg(&output)
+            .env("HOME", &home)
+            .env("XDG_CONFIG_HOME", home.join(".config"))
+            .env_remove("ULYSSES_LINK_CONFIG");
+        cmd.output().unwrap()
+    };
+
+    let out = run_sync(&[&repos[0], &repos[1], &repos[2]]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    for repo in &repos {
+        let name = repo.file_name().unwrap();
+        assert!(output.join(name).join("README.md").exists());
+    }
+
+    let config_path = home
+        .join(".config")
+        .join("ulysses-link")
+        .join("config.toml");
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    assert_eq!(config.repos.len(), 3);
+
+    // Re-adding reports each already-configured repo
+    let out = run_sync(&[&repos[0], &repos[2]]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success());
+    assert_eq!(
+        stdout.matches("is already configured").count(),
+        2,
+        "{stdout}"
+    );
+
+    // Two bare paths are two repos, never a repo and an output dir
+    let more: Vec<PathBuf> = ["d", "e"].iter().map(|n| tmp.path().join(n)).collect();
+    for repo in &more {
+        fs::create_dir_all(repo).unwrap();
+    }
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .arg("sync")
+        .args(&more)
+        .env("HOME", &home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env_remove("ULYSSES_LINK_CONFIG")
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    assert_eq!(config.repos.len(), 5);
+    assert_eq!(config.output_dir, output.canonicalize().unwrap());
+
+    // Quiet prints only the summary line
+    fs::write(repos[1].join("NOTES.md"), "notes").unwrap();
+    let out = run_sync(&[Path::new("-q"), &repos[1]]);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success());
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["Sync complete: 1 created, 3 existed, 0 pruned"],
+        "{stdout}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch_prints_status_and_stops_on_ctrl_c() {
+    use std::io::Read;
+    use std::process::Stdio;
+
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "version = 1\noutput_dir = \"{}\"\nauto_upgrade = false\n\n[[repos]]\npath = \"{}\"",
+            tmp.path().join("mirror").display(),
+            repo.display()
+        ),
+    )
+    .unwrap();
+
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args(["watch", "--config"])
+        .arg(&config_path)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_secs(2));
+    std::process::Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    let status = child.wait().unwrap();
+
+    let mut stdout = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+    assert!(status.success(), "{stdout}");
+    assert!(
+        stdout.contains("[watch] 1 repos watched, 1 files mirrored, 0 events pending"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Engine stopped"), "{stdout}");
+}
+
+#[test]
+fn test_config_check_reports_invalid_config() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "version = 1\noutput_dir = \"{}\"\nlog_level = \"LOUD\"\n\n[[repos]]\npath = \"{}\"",
+            tmp.path().join("mirror").display(),
+            repo.display()
+        ),
+    )
+    .unwrap();
+
+    let check = || {
+        std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(["config", "--check", "--config"])
+            .arg(&config_path)
+            .output()
+            .unwrap()
+    };
+
+    let out = check();
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("'log_level' must be one of"), "{stderr}");
+
+    let contents = fs::read_to_string(&config_path).unwrap();
+    fs::write(&config_path, contents.replace("LOUD", "INFO")).unwrap();
+    let out = check();
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Config OK"), "{stdout}");
+    assert!(stdout.contains("  repo "), "{stdout}");
+}
+
+#[test]
+fn test_repo_root_mirrors_only_subdirectory() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("project");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("docs").join("sub")).unwrap();
+    fs::write(repo.join("README.md"), "top").unwrap();
+    fs::write(repo.join("LICENSE"), "mit").unwrap();
+    fs::write(repo.join("docs").join("guide.md"), "guide").unwrap();
+    fs::write(repo.join("docs").join("sub").join("x.md"), "nested").unwrap();
+
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"\nroot = \"docs\"",
+            output.display(),
+            repo.display(),
+        ),
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 2);
+
+    let mirror_root = output.join("project");
+    assert_eq!(
+        fs::read_to_string(mirror_root.join("guide.md")).unwrap(),
+        "guide"
+    );
+    assert_eq!(
+        fs::read_to_string(mirror_root.join("sub").join("x.md")).unwrap(),
+        "nested"
+    );
+    assert!(!mirror_root.join("README.md").exists());
+    assert!(!mirror_root.join("LICENSE").exists());
+    assert!(!mirror_root.join("docs").exists());
+
+    let manifest = &manifests[&output];
+    assert_eq!(
+        manifest.get("project/guide.md").unwrap().source,
+        repo.join("docs").join("guide.md")
+    );
+}
+
+#[test]
+fn test_skip_if_matches_excludes_drafts() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("notes");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("done.md"), "---\ndraft: false\n---\nfinished").unwrap();
+    let draft = repo.join("wip.md");
+    fs::write(&draft, "---\ndraft: true\n---\nunfinished").unwrap();
+
+    let config_path = tmp.path().join("ulysses-link.toml");
+    fs::write(
+        &config_path,
+        format!(
+            "version = 1\noutput_dir = \"{}\"\nskip_if_matches = '(?m)^draft:\\s*true'\n\n[[repos]]\npath = \"{}\"",
+            output.display(),
+            repo.display(),
+        ),
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 1);
+    assert_eq!(result.skipped, 1);
+    assert!(output.join("notes").join("done.md").exists());
+    assert!(!output.join("notes").join("wip.md").exists());
+
+    // Publishing the draft brings it into the mirror
+    fs::write(&draft, "---\ndraft: false\n---\nunfinished").unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 1);
+    assert!(output.join("notes").join("wip.md").exists());
+
+    // Marking it a draft again removes the mirrored copy
+    fs::write(&draft, "---\ndraft: true\n---\nback to work").unwrap();
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.pruned, 1);
+    assert!(!output.join("notes").join("wip.md").exists());
+    assert!(manifests[&output].get("notes/wip.md").is_none());
+    assert!(draft.exists());
+}
+
+#[test]
+fn test_binary_files_skipped_unless_mirror_binary() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("notes");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("notes.txt"), "plain text").unwrap();
+    fs::write(
+        repo.join("blob.txt"),
+        [0x89, b'P', b'N', b'G', 0x00, 0x1a, 0x00],
+    )
+    .unwrap();
+
+    let config_path = tmp.path().join("ulysses-link.toml");
+    let load = |mirror_binary: bool| {
+        fs::write(
+            &config_path,
+            format!(
+                "version = 1\noutput_dir = \"{}\"\nmirror_binary = {mirror_binary}\n\n[[repos]]\npath = \"{}\"",
+                output.display(),
+                repo.display(),
+            ),
+        )
+        .unwrap();
+        ulysses_link::config::load_config(Some(&config_path)).unwrap()
+    };
+
+    // Detection is opt-in
+    fs::write(
+        &config_path,
+        format!(
+            "version = 1\noutput_dir = \"{}\"\n\n[[repos]]\npath = \"{}\"",
+            output.display(),
+            repo.display(),
+        ),
+    )
+    .unwrap();
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    assert!(config.mirror_binary);
+
+    let config = load(false);
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 1);
+    assert_eq!(result.skipped, 1);
+    assert!(output.join("notes").join("notes.txt").exists());
+    assert!(!output.join("notes").join("blob.txt").exists());
+
+    let config = load(true);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 1);
+    assert_eq!(result.errors, 0);
+    assert!(output.join("notes").join("blob.txt").exists());
+}
+
+#[test]
+fn test_remove_repo_by_name_after_source_deleted() {
+    let tmp = TempDir::new().unwrap();
+    let first = tmp.path().join("a").join("docs");
+    let second = tmp.path().join("b").join("docs");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&first).unwrap();
+    fs::create_dir_all(&second).unwrap();
+    fs::write(first.join("one.md"), "one").unwrap();
+    fs::write(second.join("two.md"), "two").unwrap();
+
+    let config_path = create_test_config(&[&first, &second], &output, tmp.path());
+    let config_path = Path::new(&config_path);
+    let config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    // The second repo's directory is gone, so it can only be found by name
+    fs::remove_dir_all(tmp.path().join("b")).unwrap();
+    let config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    assert_eq!(config.repos.len(), 1);
+    let repo = config
+        .missing_repos
+        .iter()
+        .find(|r| r.name == "docs-2")
+        .unwrap();
+    assert!(fs::canonicalize(&repo.path).is_err());
+
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args([
+            "remove",
+            "--name",
+            "docs-2",
+            "--yes",
+            "--delete-mirrors",
+            "--config",
+        ])
+        .arg(config_path)
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    assert!(config.missing_repos.is_empty());
+    assert_eq!(config.repos.len(), 1);
+    assert_eq!(config.repos[0].name, "docs");
+    assert!(!config.output_dir.join("docs-2").exists());
+    assert!(config.output_dir.join("docs").join("one.md").exists());
+    let manifests = load_manifests(&config);
+    assert!(manifests[&config.output_dir]
+        .entries_for_repo("docs-2")
+        .is_empty());
+}
+
+#[test]
+fn test_remove_all_repos() {
+    let tmp = TempDir::new().unwrap();
+    let repo1 = tmp.path().join("repo1");
+    let repo2 = tmp.path().join("repo2");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo1).unwrap();
+    fs::create_dir_all(&repo2).unwrap();
+    fs::write(repo1.join("README.md"), "one").unwrap();
+    fs::write(repo2.join("README.md"), "two").unwrap();
+
+    let config_path = create_test_config(&[&repo1, &repo2], &output, tmp.path());
+    let config_path = Path::new(&config_path);
+    let config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args(["remove", "--all", "--yes", "--delete-mirrors", "--config"])
+        .arg(config_path)
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let after = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    assert!(after.repos.is_empty());
+    assert!(!config.output_dir.join("repo1").exists());
+    assert!(!config.output_dir.join("repo2").exists());
+    let manifests = load_manifests(&config);
+    assert!(manifests[&config.output_dir]
+        .entries_for_repo("repo1")
+        .is_empty());
+}
+
+#[test]
+fn test_extra_include_for_one_run() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("wiki");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("paper.tex"), "\\section{Intro}").unwrap();
+
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let config_path = Path::new(&config_path);
+    let before = fs::read_to_string(config_path).unwrap();
+
+    let mut config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    config.add_patterns(&[], &["*.tex".to_string()]).unwrap();
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    assert_eq!(result.created, 2);
+    assert!(config.output_dir.join("wiki").join("paper.tex").exists());
+    assert_eq!(fs::read_to_string(config_path).unwrap(), before);
+
+    // A fresh load only has the configured patterns
+    let config = ulysses_link::config::load_config(Some(config_path)).unwrap();
+    assert!(!config.repos[0]
+        .include_patterns
+        .contains(&"*.tex".to_string()));
+}
+
+#[test]
+fn test_prune_only_leaves_other_mirrors_untouched() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("wiki");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("keep.md"), "keep").unwrap();
+    fs::write(repo.join("gone.md"), "gone").unwrap();
+
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let mirror = config.output_dir.join("wiki");
+    let kept_mtime = fs::metadata(mirror.join("keep.md"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    fs::remove_file(repo.join("gone.md")).unwrap();
+    // A source edit is not picked up by a prune
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    fs::write(repo.join("keep.md"), "edited").unwrap();
+
+    let result = ulysses_link::scanner::full_prune(&config, &mut manifests);
+
+    assert_eq!(result.pruned, 1);
+    assert_eq!(result.created + result.already_existed, 0);
+    assert!(!mirror.join("gone.md").exists());
+    assert_eq!(fs::read_to_string(mirror.join("keep.md")).unwrap(), "keep");
+    assert_eq!(
+        fs::metadata(mirror.join("keep.md"))
+            .unwrap()
+            .modified()
+            .unwrap(),
+        kept_mtime
+    );
+    let manifest = ulysses_link::manifest::Manifest::load(&config.output_dir).unwrap();
+    assert!(manifest.get("wiki/gone.md").is_none());
+    assert!(manifest.get("wiki/keep.md").is_some());
+}
+
+#[test]
+fn test_sync_dry_run_reports_prunes_without_deleting() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("wiki");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("keep.md"), "keep").unwrap();
+    fs::write(repo.join("gone.md"), "gone").unwrap();
+
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let gone_mirror = config.output_dir.join("wiki").join("gone.md");
+    fs::remove_file(repo.join("gone.md")).unwrap();
+    fs::write(repo.join("new.md"), "new").unwrap();
+
+    let dry_run = |extra: &[&str]| {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(["-q", "sync", "--dry-run", "--config", &config_path])
+            .args(extra)
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    let stdout = dry_run(&[]);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            format!("Would delete {}", gone_mirror.display()),
+            "Dry run: 1 would be created, 1 existed, 1 would be pruned".to_string(),
+        ]
+    );
+    assert!(gone_mirror.exists());
+    assert!(!config.output_dir.join("wiki").join("new.md").exists());
+
+    let stdout = dry_run(&["--prune-only"]);
+    assert!(stdout.ends_with("Dry run: 1 would be pruned\n"));
+    assert!(gone_mirror.exists());
+    let manifest = ulysses_link::manifest::Manifest::load(&config.output_dir).unwrap();
+    assert!(manifest.get("wiki/gone.md").is_some());
+
+    // A missing output dir isn't created either
+    fs::remove_dir_all(&config.output_dir).unwrap();
+    dry_run(&[]);
+    assert!(!config.output_dir.exists());
+}
+
+#[test]
+fn test_summary_color_respects_no_color() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("project");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "readme").unwrap();
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+
+    let dry_run = |env: &[(&str, &str)], extra: &[&str]| {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(["sync", "--dry-run", "--config", &config_path])
+            .args(extra)
+            .env("HOME", tmp.path())
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR")
+            .env_remove("CLICOLOR_FORCE")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8(out.stdout).unwrap()
+    };
+
+    // Piped output is plain unless color is forced
+    assert!(!dry_run(&[], &[]).contains('\x1b'));
+    let forced = dry_run(&[("CLICOLOR_FORCE", "1")], &[]);
+    assert!(forced.contains("\x1b[32m1 would be created"), "{forced:?}");
+    assert!(!dry_run(&[("CLICOLOR_FORCE", "1")], &["--color", "never"]).contains('\x1b'));
+
+    // NO_COLOR wins over forcing
+    let plain = dry_run(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")], &[]);
+    assert!(!plain.contains('\x1b'), "{plain:?}");
+    assert!(plain.contains("Dry run: 1 would be created"), "{plain:?}");
+}
+
+#[test]
+fn test_remove_without_terminal_requires_yes() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("project");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "readme").unwrap();
+
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    let mirror = config.output_dir.join("project").join("README.md");
+
+    // Output captured and stdin closed: no terminal to prompt on
+    let remove = |extra: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(["remove", "--name", "project", "--config", &config_path])
+            .args(extra)
+            .env("HOME", tmp.path())
+            .output()
+            .unwrap()
+    };
+
+    let out = remove(&[]);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--yes"));
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    assert_eq!(config.repos.len(), 1);
+    assert!(mirror.exists());
+
+    let out = remove(&["--yes"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    assert!(config.repos.is_empty());
+    // --yes alone keeps the mirror, which may hold unsynced edits
+    assert!(mirror.exists());
+    assert!(repo.join("README.md").exists());
+
+    create_test_config(&[&repo], &output, tmp.path());
+    let out = remove(&["--yes", "--delete-mirrors"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(!mirror.exists());
+    assert!(repo.join("README.md").exists());
+
+    // reset and clean refuse to prompt without a terminal too
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let run = |args: &[&str]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(args)
+            .args(["--config", &config_path])
+            .env("HOME", tmp.path())
+            .output()
+            .unwrap()
+    };
+    for args in [&["reset"][..], &["clean", "--all"]] {
+        let out = run(args);
+        assert!(!out.status.success(), "{args:?}");
+        let err = String::from_utf8_lossy(&out.stderr);
+        assert!(err.contains("--yes"), "{args:?}: {err}");
+    }
+    let out = run(&["clean", "--all", "--yes"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+fn test_rename_repo_moves_mirror_and_manifest_keys() {
+    let tmp = TempDir::new().unwrap();
+    let repo1 = tmp.path().join("project");
+    let repo2 = tmp.path().join("other");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo1.join("docs")).unwrap();
+    fs::create_dir_all(&repo2).unwrap();
+    fs::write(repo1.join("docs").join("guide.md"), "guide").unwrap();
+    fs::write(repo2.join("README.md"), "two").unwrap();
+
+    let config_path = create_test_config(&[&repo1, &repo2], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    let output = config.output_dir.clone();
+
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args(["rename", "project", "handbook", "--config"])
+        .arg(&config_path)
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    assert_eq!(config.repos[0].name, "handbook");
+    assert_eq!(
+        fs::read_to_string(output.join("handbook").join("docs").join("guide.md")).unwrap(),
+        "guide"
+    );
+    assert!(!output.join("project").exists());
+    let manifest = ulysses_link::manifest::Manifest::load(&output).unwrap();
+    assert!(manifest.get("handbook/docs/guide.md").is_some());
+    assert!(manifest.get("project/docs/guide.md").is_none());
+
+    // The other repo is untouched
+    assert_eq!(
+        fs::read_to_string(output.join("other").join("README.md")).unwrap(),
+        "two"
+    );
+    assert!(manifest.get("other/README.md").is_some());
+
+    // Taking another repo's name is refused
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args(["rename", "handbook", "other", "--config"])
+        .arg(&config_path)
+        .env("HOME", tmp.path())
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(output
+        .join("handbook")
+        .join("docs")
+        .join("guide.md")
+        .exists());
+}
+
+#[test]
+fn test_symlinked_repo_root() {
+    let tmp = TempDir::new().unwrap();
+    let real = tmp.path().join("real-notes");
+    let link = tmp.path().join("notes");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(real.join("docs")).unwrap();
+    fs::write(real.join("docs").join("guide.md"), "original").unwrap();
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    let config_path = create_test_config(&[&link], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let repo = &config.repos[0];
+    assert_eq!(repo.name, "notes");
+    assert_eq!(repo.path, fs::canonicalize(&real).unwrap());
+
+    let mut manifests = load_manifests(&config);
+    let result = ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(result.created, 1);
+    let mirror = config
+        .output_dir
+        .join("notes")
+        .join("docs")
+        .join("guide.md");
+    assert_eq!(fs::read_to_string(&mirror).unwrap(), "original");
+    assert_eq!(
+        manifests[&config.output_dir]
+            .get("notes/docs/guide.md")
+            .unwrap()
+            .source,
+        repo.path.join("docs").join("guide.md")
+    );
+
+    // Mirror edits land in the real file, and the link still points at it
+    fs::write(&mirror, "edited in ulysses").unwrap();
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+    assert_eq!(
+        fs::read_to_string(real.join("docs").join("guide.md")).unwrap(),
+        "edited in ulysses"
+    );
+    assert!(fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+}
+
+#[test]
+fn test_version_json() {
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+        .args(["version", "--json"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+
+    let info: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    for key in ["git_sha", "rustc", "target"] {
+        assert!(info[key].as_str().is_some_and(|v| !v.is_empty()), "{key}");
+    }
+}
+
+#[test]
+fn test_clean_manifest_then_rescan_claims_mirror() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("repo");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(repo.join("docs")).unwrap();
+    fs::write(repo.join("README.md"), "hello").unwrap();
+    fs::write(repo.join("docs/guide.md"), "guide").unwrap();
+
+    let config_path_str = create_test_config(&[repo.as_path()], &output, tmp.path());
+    let config_path = std::path::PathBuf::from(&config_path_str);
+    let config = ulysses_link::config::load_config(Some(&config_path)).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let removed = ulysses_link::linker::clean_state(&output, None, true, true).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(!output.join(".ulysses-link").exists());
+    assert!(!output.join(".ulysses-link.d").exists());
+    assert!(output.join("repo/docs/guide.md").exists());
+
+    // A fresh scan claims every mirror file instead of copying over it
+    let mut manifests = load_manifests(&config);
+    let mut outcomes = Vec::new();
+    let result =
+        ulysses_link::scanner::full_scan_with_progress(&config, &mut manifests, &mut |p| {
+            outcomes.push(p.outcome)
+        });
+    assert_eq!(
+        outcomes,
+        vec![Some(ulysses_link::linker::SyncOutcome::Claimed); 2]
+    );
+    assert_eq!(result.created, 0);
+    assert_eq!(result.conflicts, 0);
+    assert_eq!(result.already_existed, 2);
+    let manifest = manifests.get(&output).unwrap();
+    assert!(manifest.get("repo/README.md").is_some());
+    assert!(manifest.get("repo/docs/guide.md").is_some());
+}
+
+#[test]
+fn test_reindex_quiet_prints_nothing() {
+    let tmp = TempDir::new().unwrap();
+    let repo = tmp.path().join("project");
+    let output = tmp.path().join("mirror");
+    fs::create_dir_all(&repo).unwrap();
+    fs::write(repo.join("README.md"), "readme").unwrap();
+
+    let config_path = create_test_config(&[&repo], &output, tmp.path());
+    let config = ulysses_link::config::load_config(Some(Path::new(&config_path))).unwrap();
+    let mut manifests = load_manifests(&config);
+    ulysses_link::scanner::full_scan(&config, &mut manifests);
+
+    let reindex = |global: &[&str]| {
+        let out = std::process::Command::new(env!("CARGO_BIN_EXE_ulysses-link"))
+            .args(global)
+            .args(["reindex", "--config", &config_path])
+            .output()
+            .unwrap();
+        assert!(
+            out.status.success(),
+            "{}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+        String::from_utf8_lossy(&out.stdout).to_string()
+    };
+    assert!(reindex(&[]).contains("Reindexed 1 files"));
+    assert_eq!(reindex(&["-q"]), "");
+}